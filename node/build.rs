@@ -0,0 +1,5 @@
+extern crate napi_build;
+
+fn main() {
+	napi_build::setup();
+}
@@ -0,0 +1,204 @@
+//! Node.js bindings of the converter and the reader, working on Buffers instead of files.
+//!
+//! `npm run build` builds the module with the napi-rs CLI, which also writes `index.js` and `index.d.ts`. The
+//! options and results are plain objects, with the names in camelCase; panics of the converter become exceptions.
+
+extern crate napi;
+#[macro_use]
+extern crate napi_derive;
+extern crate obj_to_mesh;
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use obj_to_mesh::chunk;
+use obj_to_mesh::codec::Encoding;
+use obj_to_mesh::reader::{read_mesh, Topology};
+
+/// The subset of `ConvertOptions` the web pipeline uses; the missing ones keep the defaults of the command line
+#[napi(object)]
+#[derive(Default)]
+pub struct ConvertOptions {
+	pub generate_tangents: Option<bool>,
+	pub emit_bitangent: Option<bool>,
+	pub flat: Option<bool>,
+	pub material_ids: Option<bool>,
+	/// `raw`, `meshopt`, `delta`... like `--encoding`
+	pub encoding: Option<String>,
+	/// With the progressive encoding, 0.1 like `--progressive-base` by default
+	pub progressive_base: Option<f64>,
+	pub quantize_positions: Option<bool>,
+	pub shadow_indices: Option<bool>,
+	pub max_vertices: Option<u32>,
+	pub split_by_material: Option<bool>,
+	pub merge_objects: Option<bool>,
+	pub symmetry: Option<bool>,
+	pub obb: Option<bool>,
+	pub format_version: Option<u32>,
+}
+
+impl ConvertOptions {
+	fn to_options(&self) -> Result<obj_to_mesh::ConvertOptions> {
+		let encoding = match self.encoding {
+			Some(ref name) => match Encoding::from_name(name) {
+				Some(encoding) => encoding,
+				None => return Err(Error::from_reason(format!("Unknown encoding {}", name))),
+			},
+			None => Encoding::default(),
+		};
+		Ok(obj_to_mesh::ConvertOptions {
+			generate_tangents: self.generate_tangents.unwrap_or(false),
+			emit_bitangent: self.emit_bitangent.unwrap_or(false),
+			flat: self.flat.unwrap_or(false),
+			material_ids: self.material_ids.unwrap_or(false),
+			encoding,
+			progressive_base: self.progressive_base.unwrap_or(0.1),
+			quantize_positions: self.quantize_positions.unwrap_or(false),
+			shadow_indices: self.shadow_indices.unwrap_or(false),
+			max_vertices: self.max_vertices.map(|count| count as usize),
+			split_by_material: self.split_by_material.unwrap_or(false),
+			merge_objects: self.merge_objects.unwrap_or(false),
+			symmetry: self.symmetry.unwrap_or(false),
+			obb: self.obb.unwrap_or(false),
+			format_version: self.format_version,
+			..Default::default()
+		})
+	}
+}
+
+/// A converted object, in one or more `.mesh` files
+#[napi(object)]
+pub struct ConvertedMesh {
+	pub name: String,
+	/// Empty unless the object was split, like the suffixes of the file names of the command line
+	pub part_names: Vec<String>,
+	pub parts: Vec<Buffer>,
+	pub warnings: Vec<String>,
+}
+
+/// Converts every object of the obj file
+#[napi(catch_unwind)]
+pub fn convert(obj: Buffer, options: Option<ConvertOptions>) -> Result<Vec<ConvertedMesh>> {
+	let options = options.unwrap_or_default().to_options()?;
+	let content = String::from_utf8(obj.to_vec()).map_err(|err| Error::from_reason(err.to_string()))?;
+	let set = obj_to_mesh::parse_obj(content).map_err(|err| {
+		Error::from_reason(format!("line {}: {}", err.line_number, err.message))
+	})?;
+
+	Ok(obj_to_mesh::convert_obj_set(set, &options).into_iter().map(|mesh| ConvertedMesh {
+		name: mesh.name,
+		part_names: mesh.part_names,
+		parts: mesh.parts.into_iter().map(Buffer::from).collect(),
+		warnings: mesh.warnings.iter().map(|w| w.to_string()).collect(),
+	}).collect())
+}
+
+#[napi(object)]
+pub struct AttributeInfo {
+	pub name: String,
+	pub format: String,
+	pub offset: u32,
+	pub location: u32,
+}
+
+#[napi(object)]
+pub struct SubmeshInfo {
+	pub material: Option<String>,
+	pub first_index: u32,
+	pub index_count: u32,
+}
+
+/// What `obj_to_mesh inspect` prints
+#[napi(object)]
+pub struct MeshInfo {
+	pub vertex_count: u32,
+	pub index_count: u32,
+	/// `triangles`, `lines` or `points`
+	pub topology: String,
+	pub min: Vec<f64>,
+	pub max: Vec<f64>,
+	pub version: u32,
+	pub index_size: u32,
+	pub encoding: String,
+	pub stride: u32,
+	pub attributes: Vec<AttributeInfo>,
+	pub submeshes: Vec<SubmeshInfo>,
+	pub checksum: Option<u32>,
+}
+
+fn read_error(err: obj_to_mesh::reader::ReadError) -> Error {
+	Error::from_reason(err.to_string())
+}
+
+/// Reads a `.mesh` file, checking its checksum
+#[napi]
+pub fn inspect(mesh: Buffer) -> Result<MeshInfo> {
+	let mesh = read_mesh(&mut &mesh[..]).map_err(read_error)?;
+	let layout = mesh.header.layout();
+
+	Ok(MeshInfo {
+		vertex_count: mesh.positions.len() as u32,
+		index_count: mesh.indices.len() as u32,
+		topology: match mesh.header.topology {
+			Topology::TriangleList => "triangles",
+			Topology::LineList => "lines",
+			Topology::PointList => "points",
+		}.to_owned(),
+		min: mesh.header.min.iter().map(|&c| c as f64).collect(),
+		max: mesh.header.max.iter().map(|&c| c as f64).collect(),
+		version: mesh.header.version,
+		index_size: mesh.header.index_size as u32,
+		encoding: mesh.header.encoding.name().to_owned(),
+		stride: layout.stride as u32,
+		attributes: layout.attributes.iter().map(|attr| AttributeInfo {
+			name: attr.name.clone(),
+			format: format!("{:?}", attr.format),
+			offset: attr.offset as u32,
+			location: attr.location,
+		}).collect(),
+		submeshes: mesh.submeshes.iter().map(|submesh| SubmeshInfo {
+			material: submesh.material.clone(),
+			first_index: submesh.first_index,
+			index_count: submesh.index_count,
+		}).collect(),
+		checksum: mesh.checksum,
+	})
+}
+
+#[napi(object)]
+pub struct ChunkStats {
+	/// The FourCC, like `VERT`
+	pub id: String,
+	/// Without the id and length
+	pub bytes: u32,
+}
+
+/// Where the bytes of a `.mesh` file go
+#[napi(object)]
+pub struct MeshStats {
+	pub bytes: u32,
+	pub chunks: Vec<ChunkStats>,
+}
+
+/// Lists the chunks of a `.mesh` file and their sizes, without decoding them
+#[napi]
+pub fn stats(mesh: Buffer) -> Result<MeshStats> {
+	let mut input = &mesh[..];
+	if !input.starts_with(&chunk::MAGIC) {
+		return Err(Error::from_reason("Not a mesh file".to_owned()));
+	}
+	input = &input[chunk::MAGIC.len()..];
+	chunk::read_version(&mut input).map_err(read_error)?;
+
+	let mut chunks = vec![];
+	while let Some(chunk) = chunk::read_chunk(&mut input).map_err(read_error)? {
+		chunks.push(ChunkStats {
+			id: chunk.name(),
+			bytes: chunk.data.len() as u32,
+		});
+	}
+
+	Ok(MeshStats {
+		bytes: mesh.len() as u32,
+		chunks,
+	})
+}
@@ -0,0 +1,184 @@
+//! Rectangle packing, and repacking of the existing UV islands of several objects into one shared atlas.
+
+use wavefront_obj::obj::{ObjSet, Shape, TVertex};
+use std::collections::HashMap;
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasSettings {
+	/// Side of the texture in texels, used to size the padding
+	pub resolution: u32,
+	/// Texels between the islands and around the border
+	pub padding: u32,
+}
+
+impl Default for AtlasSettings {
+	fn default() -> Self {
+		AtlasSettings {
+			resolution: 1024,
+			padding: 2,
+		}
+	}
+}
+
+pub(crate) fn find(parents: &mut [usize], mut i: usize) -> usize {
+	while parents[i] != i {
+		parents[i] = parents[parents[i]];
+		i = parents[i];
+	}
+	i
+}
+
+pub(crate) fn union(parents: &mut [usize], a: usize, b: usize) {
+	let (a, b) = (find(parents, a), find(parents, b));
+	parents[a] = b;
+}
+
+/// Packs the rectangles in rows no wider than `width`, returning the height used
+fn shelf_pack(sizes: &[(f64, f64)], order: &[usize], offsets: &mut [(f64, f64)], width: f64, padding: f64) -> f64 {
+	let mut x = padding;
+	let mut y = padding;
+	let mut row_height: f64 = 0.0;
+
+	for &i in order {
+		let size = sizes[i];
+		if x > padding && x + size.0 + padding > width {
+			x = padding;
+			y += row_height + padding;
+			row_height = 0.0;
+		}
+
+		offsets[i] = (x, y);
+		x += size.0 + padding;
+		row_height = f64::max(row_height, size.1);
+	}
+
+	y + row_height + padding
+}
+
+/// Packs the rectangles into a square without overlaps, returning their offsets and the side of the square.
+/// The padding is in texels, so it depends on the final size: the square grows until everything fits
+pub(crate) fn pack(sizes: &[(f64, f64)], resolution: u32, padding: u32) -> (Vec<(f64, f64)>, f64) {
+	assert!(padding * 2 < resolution, "The padding doesn't leave room for the charts");
+
+	let mut order: Vec<usize> = (0..sizes.len()).collect();
+	order.sort_by(|&a, &b| sizes[b].1.partial_cmp(&sizes[a].1).unwrap());
+
+	let area: f64 = sizes.iter().map(|s| s.0 * s.1).sum();
+	let widest = sizes.iter().map(|s| s.0).fold(0.0, f64::max);
+	let texels = resolution as f64;
+	let mut extent = f64::max(f64::sqrt(area) * 1.1, widest);
+	if extent <= 0.0 {
+		extent = 1.0;
+	}
+
+	let mut offsets = vec![(0.0, 0.0); sizes.len()];
+	loop {
+		let padding = padding as f64 * extent / texels;
+		if widest + 2.0 * padding <= extent && shelf_pack(sizes, &order, &mut offsets, extent, padding) <= extent {
+			return (offsets, extent);
+		}
+		extent *= 1.1;
+	}
+}
+
+struct Island {
+	min: TVertex,
+	max: TVertex,
+	world_area: f64,
+	uv_area: f64,
+}
+
+impl Island {
+	/// Scaled so that all the islands get the same texel density
+	fn scale(&self) -> f64 {
+		if self.uv_area > 0.0 {
+			f64::sqrt(self.world_area / self.uv_area)
+		}
+		else {
+			1.0
+		}
+	}
+}
+
+fn uv_sub(a: TVertex, b: TVertex) -> (f64, f64) {
+	(a.x - b.x, a.y - b.y)
+}
+
+/// Finds the UV islands of every object in the set and packs them together, rewriting the texture coordinates.
+/// Tiling UVs can't be repacked, as each island is moved into its own spot of the 0-1 range
+pub fn repack_uvs(set: &mut ObjSet, settings: &AtlasSettings) {
+	//(object, island root) of every island, in the order they're found
+	let mut islands: Vec<Island> = vec![];
+	let mut island_ids: HashMap<(usize, usize), usize> = HashMap::new();
+	let mut object_parents: Vec<Vec<usize>> = vec![];
+
+	for (o, obj) in set.objects.iter().enumerate() {
+		let mut parents: Vec<usize> = (0..obj.tex_vertices.len()).collect();
+		let mut faces = vec![];
+		for geo in &obj.geometry {
+			for shape in &geo.shapes {
+				//lines and points have no area, and aren't packed
+				if let Shape::Triangle((p1, Some(t1), _), (p2, Some(t2), _), (p3, Some(t3), _)) = *shape {
+					union(&mut parents, t1, t2);
+					union(&mut parents, t2, t3);
+					faces.push(([p1, p2, p3], [t1, t2, t3]));
+				}
+			}
+		}
+
+		for (p, t) in faces {
+			let root = find(&mut parents, t[0]);
+			let id = *island_ids.entry((o, root)).or_insert_with(|| {
+				islands.push(Island {
+					min: TVertex { x: f64::MAX, y: f64::MAX },
+					max: TVertex { x: f64::MIN, y: f64::MIN },
+					world_area: 0.0,
+					uv_area: 0.0,
+				});
+				islands.len() - 1
+			});
+
+			let island = &mut islands[id];
+			let v = |i: usize| obj.vertices[p[i]];
+			island.world_area += lenght(cross(sub(v(1), v(0)), sub(v(2), v(0)))) / 2.0;
+
+			let uv = |i: usize| obj.tex_vertices[t[i]];
+			let (e1, e2) = (uv_sub(uv(1), uv(0)), uv_sub(uv(2), uv(0)));
+			island.uv_area += (e1.0 * e2.1 - e1.1 * e2.0).abs() / 2.0;
+
+			for i in 0..3 {
+				island.min = TVertex { x: f64::min(island.min.x, uv(i).x), y: f64::min(island.min.y, uv(i).y) };
+				island.max = TVertex { x: f64::max(island.max.x, uv(i).x), y: f64::max(island.max.y, uv(i).y) };
+			}
+		}
+
+		object_parents.push(parents);
+	}
+
+	let sizes: Vec<(f64, f64)> = islands.iter()
+		.map(|island| {
+			let (w, h) = uv_sub(island.max, island.min);
+			(w * island.scale(), h * island.scale())
+		})
+		.collect();
+	let (offsets, extent) = pack(&sizes, settings.resolution, settings.padding);
+
+	for (o, obj) in set.objects.iter_mut().enumerate() {
+		let parents = &mut object_parents[o];
+		for t in 0..obj.tex_vertices.len() {
+			//texture coordinates that no triangle uses aren't in any island
+			let id = match island_ids.get(&(o, find(parents, t))) {
+				Some(&id) => id,
+				None => continue,
+			};
+
+			let island = &islands[id];
+			let (u, v) = uv_sub(obj.tex_vertices[t], island.min);
+			obj.tex_vertices[t] = TVertex {
+				x: (u * island.scale() + offsets[id].0) / extent,
+				y: (v * island.scale() + offsets[id].1) / extent,
+			};
+		}
+	}
+}
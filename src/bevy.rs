@@ -0,0 +1,174 @@
+//! Bevy asset loaders turning `.mesh` and `.obj` files into `Mesh` assets.
+//!
+//! `bevy_mesh::Mesh` is the same type that Bevy re-exports as `bevy::render::mesh::Mesh`,
+//! so adding `ObjToMeshPlugin` to an app is enough to `asset_server.load("model.mesh")`.
+//! Loading `.obj` files needs the `converter` feature as well.
+
+use bevy_app::{App, Plugin};
+use bevy_asset::io::Reader;
+use bevy_asset::{AssetApp, AssetLoader, LoadContext, RenderAssetUsages};
+use bevy_mesh::{Indices, Mesh, MeshVertexAttribute, PrimitiveTopology, VertexAttributeValues, VertexFormat};
+use bevy_reflect::TypePath;
+#[cfg(feature = "converter")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "converter")]
+use std::io;
+use crate::reader::{read_mesh, MeshData, ReadError, Topology};
+use crate::layout::{srgb_to_linear, ColorSpace};
+
+/// Bevy has no built-in curvature attribute; custom shaders can bind this one
+pub const ATTRIBUTE_CURVATURE: MeshVertexAttribute = MeshVertexAttribute::new("Vertex_Curvature", 0x6f626a5f63757276, VertexFormat::Float32);
+
+/// Bevy reconstructs the bitangent from the tangent, this is for custom shaders that don't
+pub const ATTRIBUTE_BITANGENT: MeshVertexAttribute = MeshVertexAttribute::new("Vertex_Bitangent", 0x6f626a5f62697461, VertexFormat::Float32x3);
+
+/// Index of the material of the triangle, for bindless material lookups
+pub const ATTRIBUTE_MATERIAL_ID: MeshVertexAttribute = MeshVertexAttribute::new("Vertex_MaterialId", 0x6f626a5f6d617469, VertexFormat::Uint32);
+
+fn to_bevy_mesh(mut data: MeshData) -> Mesh {
+	data.unfold_mirror();
+	let vertex_count = data.header.vertex_count;
+	let topology = match data.header.topology {
+		Topology::TriangleList => PrimitiveTopology::TriangleList,
+		Topology::LineList => PrimitiveTopology::LineList,
+		Topology::PointList => PrimitiveTopology::PointList,
+	};
+	let mut mesh = Mesh::new(topology, RenderAssetUsages::default());
+
+	mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, data.positions);
+
+	if let Some(mut colors) = data.colors {
+		//bevy takes linear colors
+		if data.header.color_space == ColorSpace::Srgb {
+			for color in &mut colors {
+				for c in &mut color[..3] {
+					*c = srgb_to_linear(*c as f64) as f32;
+				}
+			}
+		}
+		mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+	}
+
+	if let Some(normals) = data.normals {
+		mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+	}
+
+	if let Some(tangents) = data.tangents {
+		mesh.insert_attribute(Mesh::ATTRIBUTE_TANGENT, tangents);
+	}
+
+	if let Some(bitangents) = data.bitangents {
+		mesh.insert_attribute(ATTRIBUTE_BITANGENT, bitangents);
+	}
+
+	if let Some(tex0) = data.tex0 {
+		mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, tex0);
+	}
+
+	if let Some(tex1) = data.tex1 {
+		mesh.insert_attribute(Mesh::ATTRIBUTE_UV_1, tex1);
+	}
+
+	if let Some(bone_indices) = data.bone_indices {
+		let joints = bone_indices.iter().map(|b| b.map(|i| i as u16)).collect();
+		mesh.insert_attribute(Mesh::ATTRIBUTE_JOINT_INDEX, VertexAttributeValues::Uint16x4(joints));
+	}
+
+	if let Some(bone_weights) = data.bone_weights {
+		mesh.insert_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, bone_weights);
+	}
+
+	if let Some(curvature) = data.curvature {
+		mesh.insert_attribute(ATTRIBUTE_CURVATURE, curvature);
+	}
+
+	if let Some(material_ids) = data.material_ids {
+		mesh.insert_attribute(ATTRIBUTE_MATERIAL_ID, material_ids);
+	}
+
+	mesh.insert_indices(if vertex_count <= 0xffff {
+		Indices::U16(data.indices.iter().map(|&i| i as u16).collect())
+	}
+	else {
+		Indices::U32(data.indices)
+	});
+
+	mesh
+}
+
+/// Loads `.mesh` files produced by the converter
+#[derive(Default, TypePath)]
+pub struct MeshFileLoader;
+
+impl AssetLoader for MeshFileLoader {
+	type Asset = Mesh;
+	type Settings = ();
+	type Error = ReadError;
+
+	async fn load(&self, reader: &mut dyn Reader, _settings: &(), _load_context: &mut LoadContext<'_>) -> Result<Mesh, ReadError> {
+		let mut bytes = vec![];
+		reader.read_to_end(&mut bytes).await?;
+
+		Ok(to_bevy_mesh(read_mesh(&mut &bytes[..])?))
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["mesh"]
+	}
+}
+
+#[cfg(feature = "converter")]
+#[derive(Default, Serialize, Deserialize)]
+pub struct ObjLoaderSettings {
+	pub generate_tangents: bool,
+}
+
+/// Converts `.obj` files at load time, going through the same path as the command line tool
+#[cfg(feature = "converter")]
+#[derive(Default, TypePath)]
+pub struct ObjLoader;
+
+#[cfg(feature = "converter")]
+impl AssetLoader for ObjLoader {
+	type Asset = Mesh;
+	type Settings = ObjLoaderSettings;
+	type Error = io::Error;
+
+	async fn load(&self, reader: &mut dyn Reader, settings: &ObjLoaderSettings, _load_context: &mut LoadContext<'_>) -> io::Result<Mesh> {
+		let mut bytes = vec![];
+		reader.read_to_end(&mut bytes).await?;
+		let content = String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+		let set = crate::parse_obj(content).map_err(|err| {
+			io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", err.line_number, err.message))
+		})?;
+
+		let obj = match set.objects.into_iter().next() {
+			Some(obj) => obj,
+			None => return Err(io::Error::new(io::ErrorKind::InvalidData, "No objects found")),
+		};
+
+		let options = crate::ConvertOptions {
+			generate_tangents: settings.generate_tangents,
+			..Default::default()
+		};
+		let bytes = crate::convert_obj(obj, &options).parts.remove(0);
+		Ok(to_bevy_mesh(read_mesh(&mut &bytes[..]).map_err(io::Error::other)?))
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["obj"]
+	}
+}
+
+/// Registers the loaders
+pub struct ObjToMeshPlugin;
+
+impl Plugin for ObjToMeshPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_asset_loader::<MeshFileLoader>();
+
+		#[cfg(feature = "converter")]
+		app.init_asset_loader::<ObjLoader>();
+	}
+}
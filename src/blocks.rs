@@ -0,0 +1,209 @@
+//! The block encoding of the vertices, which compute shaders decompress with a thread for each vertex.
+//!
+//! The vertices are split into blocks of `BLOCK_VERTICES`, the last one padded with zeros, so that every block has
+//! the same size. Each block starts with the min and the step of each float component of the built-in attributes
+//! over its vertices, as f32, then has the vertices: those components quantized to 16 bits, two in each 32 bit
+//! word, followed by the other words of the vertex as they are. A component decodes as `min + q * step`.
+
+use crate::layout::{Attribute, Format, VertexLayout};
+
+/// The vertices of a block, one workgroup of the decompression shaders
+pub const BLOCK_VERTICES: usize = 64;
+
+/// Where a 32 bit word of the vertex comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Word {
+	/// The index of the component among the quantized ones
+	Quantized(usize),
+	/// The index of the word among the ones stored as they are
+	Raw(usize),
+}
+
+/// The sizes of the blocks for a layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockLayout {
+	/// For each word of the vertex
+	pub words: Vec<Word>,
+	pub quantized: usize,
+	/// Of each vertex in the block
+	pub record_words: usize,
+	pub block_words: usize,
+}
+
+impl BlockLayout {
+	/// None when the vertices aren't made of whole words
+	pub fn new(layout: &VertexLayout) -> Option<BlockLayout> {
+		if !layout.stride.is_multiple_of(4) {
+			return None;
+		}
+		let (mut quantized, mut raw) = (0, 0);
+		let words: Vec<Word> = (0..layout.stride / 4).map(|w| {
+			let attr = layout.attributes.iter().find(|a| (a.offset..a.offset + a.format.size()).contains(&(w * 4)));
+			//the user attributes can hold anything, and be smaller than a word
+			let float = attr.is_some_and(|a| !matches!(a.attribute, Attribute::User(_))
+				&& matches!(a.format, Format::Float32 | Format::Float32x2 | Format::Float32x3 | Format::Float32x4));
+			if float {
+				quantized += 1;
+				Word::Quantized(quantized - 1)
+			}
+			else {
+				raw += 1;
+				Word::Raw(raw - 1)
+			}
+		}).collect();
+		let record_words = quantized.div_ceil(2) + raw;
+		Some(BlockLayout {
+			words,
+			quantized,
+			record_words,
+			block_words: quantized * 2 + BLOCK_VERTICES * record_words,
+		})
+	}
+
+	pub fn block_size(&self) -> usize {
+		self.block_words * 4
+	}
+}
+
+fn word(vertex: &[u8], w: usize) -> [u8; 4] {
+	[vertex[w * 4], vertex[w * 4 + 1], vertex[w * 4 + 2], vertex[w * 4 + 3]]
+}
+
+fn dequantize(min: f32, step: f32, q: u16) -> f32 {
+	min + q as f32 * step
+}
+
+/// Compresses the vertices laid out as `blocks`
+pub fn encode(vertices: &[u8], blocks: &BlockLayout) -> Vec<u8> {
+	let stride = blocks.words.len() * 4;
+	let mut data = Vec::with_capacity(vertices.len().div_ceil(stride * BLOCK_VERTICES) * blocks.block_size());
+	for block in vertices.chunks(stride * BLOCK_VERTICES) {
+		let components: Vec<Vec<f32>> = (0..blocks.quantized).map(|q| {
+			let w = blocks.words.iter().position(|&w| w == Word::Quantized(q)).unwrap();
+			block.chunks_exact(stride).map(|vertex| f32::from_le_bytes(word(vertex, w))).collect()
+		}).collect();
+
+		let mut quantized = vec![vec![0u16; block.len() / stride]; blocks.quantized];
+		for (values, quantized) in components.iter().zip(&mut quantized) {
+			let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+			let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+			let step = if max > min { (max - min) / u16::MAX as f32 } else { 0.0 };
+			for (v, q) in values.iter().zip(quantized.iter_mut()) {
+				*q = if step > 0.0 { ((v - min) / step).round() as u16 } else { 0 };
+				//the rounding of the decoding shouldn't move it past the values of the block
+				while *q > 0 && dequantize(min, step, *q) > max {
+					*q -= 1;
+				}
+			}
+			data.extend_from_slice(&min.to_le_bytes());
+			data.extend_from_slice(&step.to_le_bytes());
+		}
+
+		let start = data.len();
+		for (i, vertex) in block.chunks_exact(stride).enumerate() {
+			for pair in quantized.chunks(2) {
+				data.extend_from_slice(&pair[0][i].to_le_bytes());
+				data.extend_from_slice(&pair.get(1).map_or(0, |q| q[i]).to_le_bytes());
+			}
+			for (w, _) in blocks.words.iter().enumerate().filter(|(_, w)| matches!(w, Word::Raw(_))) {
+				data.extend_from_slice(&word(vertex, w));
+			}
+		}
+		data.resize(start + BLOCK_VERTICES * blocks.record_words * 4, 0);
+	}
+	data
+}
+
+/// The `count` vertices of the blocks, as the shaders decompress them; None when `data` isn't the size of the
+/// blocks they need
+pub fn decode(data: &[u8], count: usize, blocks: &BlockLayout) -> Option<Vec<u8>> {
+	if data.len() != count.div_ceil(BLOCK_VERTICES) * blocks.block_size() {
+		return None;
+	}
+	let mut vertices = Vec::with_capacity(count * blocks.words.len() * 4);
+	for (b, block) in data.chunks_exact(blocks.block_size()).enumerate() {
+		let (ranges, records) = block.split_at(blocks.quantized * 8);
+		let vertices_in_block = usize::min(BLOCK_VERTICES, count - b * BLOCK_VERTICES);
+		for record in records.chunks_exact(blocks.record_words * 4).take(vertices_in_block) {
+			let (quantized, raw) = record.split_at(blocks.quantized.div_ceil(2) * 4);
+			for &w in &blocks.words {
+				match w {
+					Word::Quantized(q) => {
+						let min = f32::from_le_bytes(word(ranges, q * 2));
+						let step = f32::from_le_bytes(word(ranges, q * 2 + 1));
+						let value = u16::from_le_bytes([quantized[q * 2], quantized[q * 2 + 1]]);
+						vertices.extend_from_slice(&dequantize(min, step, value).to_le_bytes());
+					},
+					Word::Raw(r) => vertices.extend_from_slice(&word(raw, r)),
+				}
+			}
+		}
+	}
+	Some(vertices)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layout::VertexFieldOffsets;
+
+	#[test]
+	fn round_trip() {
+		//a float position and packed normals and colors, stored as they are
+		let layout = VertexFieldOffsets::new(&[Attribute::Position, Attribute::Normal, Attribute::Color]).layout();
+		let blocks = BlockLayout::new(&layout).unwrap();
+		assert_eq!(blocks.quantized, 3);
+		for count in [0, 1, BLOCK_VERTICES, BLOCK_VERTICES * 3 + 5] {
+			let vertices: Vec<u8> = (0..count).flat_map(|i| {
+				let mut vertex = vec![];
+				for c in 0..3 {
+					vertex.extend_from_slice(&((i * (c + 3) % 17) as f32 * 0.37 - 2.0).to_le_bytes());
+				}
+				vertex.extend((0..layout.stride - 12).map(|b| (i * 31 + b) as u8));
+				vertex
+			}).collect();
+
+			let data = encode(&vertices, &blocks);
+			assert_eq!(data.len(), count.div_ceil(BLOCK_VERTICES) * blocks.block_size());
+			let decoded = decode(&data, count, &blocks).unwrap();
+			assert_eq!(decoded.len(), vertices.len());
+			for (original, decoded) in vertices.chunks(layout.stride).zip(decoded.chunks(layout.stride)) {
+				for c in 0..3 {
+					let (a, b) = (f32::from_le_bytes(word(original, c)), f32::from_le_bytes(word(decoded, c)));
+					//a step of the 16 bits over the range of the block, at most 16 * 0.37
+					assert!((a - b).abs() <= 16.0 * 0.37 / u16::MAX as f32, "{} decoded as {}", a, b);
+				}
+				assert_eq!(original[12..], decoded[12..]);
+			}
+			assert_eq!(decode(&data[..data.len().saturating_sub(4)], count.max(1), &blocks), None);
+		}
+	}
+
+	/// Through the reader, against the same conversion stored raw
+	#[test]
+	#[cfg(feature = "converter")]
+	fn through_the_reader() {
+		use crate::codec::Encoding;
+		use crate::convert::ConvertOptions;
+		use crate::fixtures;
+
+		for obj in [fixtures::CUBE.to_owned(), fixtures::grid(20)] {
+			let raw = fixtures::read(&fixtures::convert_one(&obj, &ConvertOptions::default()));
+			let encoded = fixtures::read(&fixtures::convert_one(&obj, &ConvertOptions { encoding: Encoding::Blocks, ..Default::default() }));
+			assert_eq!(encoded.header.encoding, Encoding::Blocks);
+			assert_eq!(encoded.indices, raw.indices);
+			assert_eq!(encoded.submeshes.len(), raw.submeshes.len());
+			//only the float positions are quantized
+			assert_eq!(encoded.positions.len(), raw.positions.len());
+			let extent = (0..3).map(|c| raw.header.max[c] - raw.header.min[c]).fold(0.0, f32::max);
+			for (a, b) in encoded.positions.iter().zip(&raw.positions) {
+				for c in 0..3 {
+					assert!((a[c] - b[c]).abs() <= extent / u16::MAX as f32, "{:?} decoded as {:?}", b, a);
+				}
+			}
+			assert_eq!(encoded.normals, raw.normals);
+			assert_eq!(encoded.colors, raw.colors);
+			assert_eq!(encoded.tex0, raw.tex0);
+		}
+	}
+}
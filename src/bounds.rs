@@ -0,0 +1,205 @@
+//! Bounding volumes: spheres and boxes for each submesh, so that the parts of large combined meshes can be culled
+//! individually, and optional tighter volumes for the whole mesh.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::Vertex;
+use crate::math::*;
+use crate::chunk;
+use crate::mesh::{Mesh, Submesh};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+	pub min: Vertex,
+	pub max: Vertex,
+	/// The sphere is centered on the box, which is close enough to the smallest one for culling
+	pub center: Vertex,
+	pub radius: f64,
+}
+
+pub fn submesh_bounds(mesh: &Mesh, submesh: &Submesh) -> Bounds {
+	let indices = &mesh.indices[submesh.first_index..submesh.first_index + submesh.index_count];
+
+	let mut min = Vertex { x: f64::MAX, y: f64::MAX, z: f64::MAX };
+	let mut max = Vertex { x: f64::MIN, y: f64::MIN, z: f64::MIN };
+	for &idx in indices {
+		min = vert_min(min, mesh.vertices[idx].pos());
+		max = vert_max(max, mesh.vertices[idx].pos());
+	}
+
+	let center = mul(Vertex { x: min.x + max.x, y: min.y + max.y, z: min.z + max.z }, 0.5);
+	let radius = indices.iter().map(|&idx| lenght(sub(mesh.vertices[idx].pos(), center))).fold(0.0, flt_max);
+	Bounds {
+		min,
+		max,
+		center,
+		radius,
+	}
+}
+
+fn write_vec3(data: &mut Vec<u8>, v: Vertex) {
+	data.write_f32::<LittleEndian>(v.x as f32).unwrap();
+	data.write_f32::<LittleEndian>(v.y as f32).unwrap();
+	data.write_f32::<LittleEndian>(v.z as f32).unwrap();
+}
+
+/// Writes the index range, material name and bounds of every submesh
+pub fn write_submeshes(data: &mut Vec<u8>, mesh: &Mesh) {
+	data.write_u32::<LittleEndian>(mesh.submeshes.len() as u32).unwrap();
+
+	for submesh in &mesh.submeshes {
+		data.write_u32::<LittleEndian>(submesh.first_index as u32).unwrap();
+		data.write_u32::<LittleEndian>(submesh.index_count as u32).unwrap();
+
+		//empty for the faces without a material
+		let name = submesh.material.as_deref().unwrap_or("");
+		data.write_u16::<LittleEndian>(name.len() as u16).unwrap();
+		data.extend_from_slice(name.as_bytes());
+
+		let bounds = submesh_bounds(mesh, submesh);
+		write_vec3(data, bounds.min);
+		write_vec3(data, bounds.max);
+		write_vec3(data, bounds.center);
+		data.write_f32::<LittleEndian>(bounds.radius as f32).unwrap();
+	}
+}
+
+/// A box along the principal axes of the vertices, which fits long thin objects much better than the bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct OrientedBox {
+	pub center: Vertex,
+	pub half_extents: Vertex,
+	/// Orthonormal and right handed, from the direction with the most spread to the one with the least
+	pub axes: [Vertex; 3],
+}
+
+/// Eigenvectors of a symmetric matrix with the Jacobi method, sorted by decreasing eigenvalue
+fn eigenvectors(mut a: [[f64; 3]; 3]) -> [Vertex; 3] {
+	let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+	for _ in 0..50 {
+		//zero the largest off diagonal element
+		let (p, q) = [(0, 1), (0, 2), (1, 2)].into_iter()
+			.max_by(|&(i, j), &(k, l)| a[i][j].abs().partial_cmp(&a[k][l].abs()).unwrap())
+			.unwrap();
+		if a[p][q].abs() < 1e-12 {
+			break;
+		}
+
+		let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+		let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+		let c = 1.0 / (t * t + 1.0).sqrt();
+		let s = t * c;
+
+		for row in &mut a {
+			let (akp, akq) = (row[p], row[q]);
+			row[p] = c * akp - s * akq;
+			row[q] = s * akp + c * akq;
+		}
+		let (ap, aq) = (a[p], a[q]);
+		a[p] = std::array::from_fn(|k| c * ap[k] - s * aq[k]);
+		a[q] = std::array::from_fn(|k| s * ap[k] + c * aq[k]);
+		for row in &mut v {
+			let (vp, vq) = (row[p], row[q]);
+			row[p] = c * vp - s * vq;
+			row[q] = s * vp + c * vq;
+		}
+	}
+
+	let mut order = [0, 1, 2];
+	order.sort_by(|&i, &j| a[j][j].partial_cmp(&a[i][i]).unwrap());
+	let column = |i: usize| Vertex { x: v[0][i], y: v[1][i], z: v[2][i] };
+	let x = normalize(column(order[0]));
+	let y = normalize(column(order[1]));
+	[x, y, cross(x, y)]
+}
+
+/// Fits a box along the principal components of the vertex positions
+pub fn oriented_box(mesh: &Mesh) -> OrientedBox {
+	let count = mesh.vertices.len() as f64;
+	let mut mean = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+	for v in &mesh.vertices {
+		addmut(&mut mean, mul(v.pos(), 1.0 / count));
+	}
+
+	let mut covariance = [[0.0; 3]; 3];
+	for v in &mesh.vertices {
+		let d = sub(v.pos(), mean);
+		let d = [d.x, d.y, d.z];
+		for i in 0..3 {
+			for j in 0..3 {
+				covariance[i][j] += d[i] * d[j] / count;
+			}
+		}
+	}
+	let axes = eigenvectors(covariance);
+
+	let mut min = [f64::MAX; 3];
+	let mut max = [f64::MIN; 3];
+	for v in &mesh.vertices {
+		for i in 0..3 {
+			let d = dot(v.pos(), axes[i]);
+			min[i] = flt_min(min[i], d);
+			max[i] = flt_max(max[i], d);
+		}
+	}
+
+	let mut center = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+	for i in 0..3 {
+		addmut(&mut center, mul(axes[i], (min[i] + max[i]) * 0.5));
+	}
+	OrientedBox {
+		center,
+		half_extents: Vertex { x: (max[0] - min[0]) * 0.5, y: (max[1] - min[1]) * 0.5, z: (max[2] - min[2]) * 0.5 },
+		axes,
+	}
+}
+
+fn write_volume(data: &mut Vec<u8>, kind: u8, body: &[u8]) {
+	data.write_u8(kind).unwrap();
+	data.write_u32::<LittleEndian>(body.len() as u32).unwrap();
+	data.extend_from_slice(body);
+}
+
+/// The extents of the vertices along the fixed directions of a k-DOP
+#[derive(Debug, Clone)]
+pub struct Kdop {
+	pub k: u8,
+	/// Min and max along each of `chunk::kdop_axes(k)`
+	pub slabs: Vec<(f64, f64)>,
+}
+
+pub fn kdop(mesh: &Mesh, k: u8) -> Kdop {
+	let axes = chunk::kdop_axes(k).unwrap_or_else(|| panic!("k-DOPs have 14, 18 or 26 sides, not {}", k));
+	let slabs = axes.iter().map(|a| {
+		let axis = Vertex { x: a[0] as f64, y: a[1] as f64, z: a[2] as f64 };
+		mesh.vertices.iter().fold((f64::MAX, f64::MIN), |(min, max), v| {
+			let d = dot(v.pos(), axis);
+			(flt_min(min, d), flt_max(max, d))
+		})
+	}).collect();
+	Kdop {
+		k,
+		slabs,
+	}
+}
+
+/// Writes the optional bounding volumes, each prefixed by its kind and size
+pub fn write_bounds(data: &mut Vec<u8>, obb: Option<&OrientedBox>, kdop: Option<&Kdop>) {
+	if let Some(obb) = obb {
+		let mut body = vec![];
+		write_vec3(&mut body, obb.center);
+		write_vec3(&mut body, obb.half_extents);
+		for &axis in &obb.axes {
+			write_vec3(&mut body, axis);
+		}
+		write_volume(data, chunk::BOUNDS_OBB, &body);
+	}
+
+	if let Some(kdop) = kdop {
+		let mut body = vec![kdop.k];
+		for &(min, max) in &kdop.slabs {
+			body.write_f32::<LittleEndian>(min as f32).unwrap();
+			body.write_f32::<LittleEndian>(max as f32).unwrap();
+		}
+		write_volume(data, chunk::BOUNDS_KDOP, &body);
+	}
+}
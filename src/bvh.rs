@@ -0,0 +1,204 @@
+//! A bounding volume hierarchy over the triangles, baked for the raycasts of editor picking and decal projection.
+//!
+//! The nodes are stored depth first with a fixed size, so that the runtime can traverse the chunk in place.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::Vertex;
+use crate::math::*;
+use crate::mesh::Mesh;
+
+/// Triangles per leaf
+const MAX_LEAF_SIZE: usize = 4;
+
+/// The nodes start at a multiple of this in the file
+pub const NODE_ALIGNMENT: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhNode {
+	pub min: Vertex,
+	pub max: Vertex,
+	/// For leaves the first entry of the triangle list, for the others the index of the right child;
+	/// the left child always follows its parent
+	pub offset: u32,
+	/// 0 for the nodes that aren't leaves
+	pub count: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bvh {
+	pub nodes: Vec<BvhNode>,
+	/// Triangles in leaf order, as the index of their first index divided by 3
+	pub triangles: Vec<u32>,
+}
+
+struct Triangle {
+	min: Vertex,
+	max: Vertex,
+	centroid: Vertex,
+}
+
+fn axis(v: Vertex, i: usize) -> f64 {
+	match i {
+		0 => v.x,
+		1 => v.y,
+		_ => v.z,
+	}
+}
+
+/// Splits at the median along the longest axis of the centroids
+pub fn build(mesh: &Mesh) -> Bvh {
+	let triangles: Vec<Triangle> = mesh.indices.chunks(3).map(|t| {
+		let p = [mesh.vertices[t[0]].pos(), mesh.vertices[t[1]].pos(), mesh.vertices[t[2]].pos()];
+		Triangle {
+			min: vert_min(vert_min(p[0], p[1]), p[2]),
+			max: vert_max(vert_max(p[0], p[1]), p[2]),
+			centroid: mul(Vertex { x: p[0].x + p[1].x + p[2].x, y: p[0].y + p[1].y + p[2].y, z: p[0].z + p[1].z + p[2].z }, 1.0 / 3.0),
+		}
+	}).collect();
+
+	let mut bvh = Bvh {
+		nodes: vec![],
+		triangles: (0..triangles.len() as u32).collect(),
+	};
+	if !triangles.is_empty() {
+		let count = bvh.triangles.len();
+		build_node(&mut bvh, &triangles, 0, count);
+	}
+	bvh
+}
+
+fn build_node(bvh: &mut Bvh, triangles: &[Triangle], first: usize, count: usize) -> usize {
+	let range = first..first + count;
+	let mut min = Vertex { x: f64::MAX, y: f64::MAX, z: f64::MAX };
+	let mut max = Vertex { x: f64::MIN, y: f64::MIN, z: f64::MIN };
+	let mut centroid_min = min;
+	let mut centroid_max = max;
+	for &t in &bvh.triangles[range.clone()] {
+		let t = &triangles[t as usize];
+		min = vert_min(min, t.min);
+		max = vert_max(max, t.max);
+		centroid_min = vert_min(centroid_min, t.centroid);
+		centroid_max = vert_max(centroid_max, t.centroid);
+	}
+
+	let index = bvh.nodes.len();
+	bvh.nodes.push(BvhNode {
+		min,
+		max,
+		offset: first as u32,
+		count: count as u32,
+	});
+	if count <= MAX_LEAF_SIZE {
+		return index;
+	}
+
+	let extent = sub(centroid_max, centroid_min);
+	let split_axis = (0..3).max_by(|&a, &b| axis(extent, a).partial_cmp(&axis(extent, b)).unwrap()).unwrap();
+	bvh.triangles[range].sort_by(|&a, &b| {
+		axis(triangles[a as usize].centroid, split_axis).partial_cmp(&axis(triangles[b as usize].centroid, split_axis)).unwrap()
+	});
+
+	let half = count / 2;
+	build_node(bvh, triangles, first, half);
+	let right = build_node(bvh, triangles, first + half, count - half);
+	bvh.nodes[index].offset = right as u32;
+	bvh.nodes[index].count = 0;
+	index
+}
+
+/// Whether the ray enters the box before `max_t`
+fn hits_box(node: &BvhNode, origin: Vertex, inverse: Vertex, max_t: f64) -> bool {
+	let (mut near, mut far) = (0.0, max_t);
+	for i in 0..3 {
+		let a = (axis(node.min, i) - axis(origin, i)) * axis(inverse, i);
+		let b = (axis(node.max, i) - axis(origin, i)) * axis(inverse, i);
+		//NaN when the ray runs in the plane of a face of the box, which counts as inside
+		if a.is_nan() || b.is_nan() {
+			continue;
+		}
+		near = flt_max(near, flt_min(a, b));
+		far = flt_min(far, flt_max(a, b));
+	}
+	near <= far
+}
+
+/// Calls `hit` with the triangle, the distance along `dir` and the barycentric weights of the corners of every
+/// triangle of `mesh` that the ray crosses before `max_t`, from either side, in no particular order
+pub fn raycast(bvh: &Bvh, mesh: &Mesh, origin: Vertex, dir: Vertex, max_t: f64, mut hit: impl FnMut(usize, f64, [f64; 3])) {
+	if bvh.nodes.is_empty() {
+		return;
+	}
+	let inverse = Vertex { x: 1.0 / dir.x, y: 1.0 / dir.y, z: 1.0 / dir.z };
+	let mut stack = vec![0];
+	while let Some(i) = stack.pop() {
+		let node = &bvh.nodes[i];
+		if !hits_box(node, origin, inverse, max_t) {
+			continue;
+		}
+		if node.count == 0 {
+			//the left child follows its parent
+			stack.push(i + 1);
+			stack.push(node.offset as usize);
+			continue;
+		}
+
+		for &t in &bvh.triangles[node.offset as usize..(node.offset + node.count) as usize] {
+			let t = t as usize;
+			let [a, b, c] = [0, 1, 2].map(|k| mesh.vertices[mesh.indices[t * 3 + k]].pos());
+			//Möller-Trumbore
+			let (e1, e2) = (sub(b, a), sub(c, a));
+			let p = cross(dir, e2);
+			let det = dot(e1, p);
+			if det.abs() < 1e-12 {
+				continue;
+			}
+			let to_origin = sub(origin, a);
+			let u = dot(to_origin, p) / det;
+			let q = cross(to_origin, e1);
+			let v = dot(dir, q) / det;
+			let distance = dot(e2, q) / det;
+			if u >= 0.0 && v >= 0.0 && u + v <= 1.0 && distance >= 0.0 && distance <= max_t {
+				hit(t, distance, [1.0 - u - v, u, v]);
+			}
+		}
+	}
+}
+
+//rounded outwards, so that no ray misses the triangles by a rounding error
+fn round_down(v: f64) -> f32 {
+	let f = v as f32;
+	if f as f64 > v { f.next_down() } else { f }
+}
+
+fn round_up(v: f64) -> f32 {
+	let f = v as f32;
+	if (f as f64) < v { f.next_up() } else { f }
+}
+
+/// Writes the node and triangle counts and the offset of the nodes in the chunk, padding so that the nodes
+/// are aligned in the file when the chunk starts at `chunk_start`
+pub fn write_bvh(data: &mut Vec<u8>, bvh: &Bvh, chunk_start: usize) {
+	let header_size = 12;
+	let padding = (NODE_ALIGNMENT - (chunk_start + header_size) % NODE_ALIGNMENT) % NODE_ALIGNMENT;
+
+	data.write_u32::<LittleEndian>(bvh.nodes.len() as u32).unwrap();
+	data.write_u32::<LittleEndian>(bvh.triangles.len() as u32).unwrap();
+	data.write_u32::<LittleEndian>((header_size + padding) as u32).unwrap();
+	data.resize(data.len() + padding, 0);
+
+	//32 bytes per node
+	for node in &bvh.nodes {
+		for c in [node.min.x, node.min.y, node.min.z] {
+			data.write_f32::<LittleEndian>(round_down(c)).unwrap();
+		}
+		for c in [node.max.x, node.max.y, node.max.z] {
+			data.write_f32::<LittleEndian>(round_up(c)).unwrap();
+		}
+		data.write_u32::<LittleEndian>(node.offset).unwrap();
+		data.write_u32::<LittleEndian>(node.count).unwrap();
+	}
+
+	for &t in &bvh.triangles {
+		data.write_u32::<LittleEndian>(t).unwrap();
+	}
+}
@@ -0,0 +1,261 @@
+//! The container of `.mesh` files: a magic number and version, followed by chunks made of a FourCC and a length,
+//! so that loaders can skip the chunks they don't know about. Version 1 files predate it.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "std")]
+use crate::reader::ReadError;
+
+pub const MAGIC: [u8; 4] = *b"MESH";
+/// The latest version, written by default
+pub const VERSION: u32 = 2;
+/// The files written before the container, which have no magic number and store everything in sequence
+pub const LEGACY_VERSION: u32 = 1;
+
+/// Index size, topology, attribute flags, user attributes, bounds, counts, the `codec::Encoding` of the
+/// vertices and indices, the `layout::Palette`, the dequantization matrix of quantized positions and the u32
+/// count of shadow indices; always the first chunk
+pub const HEADER: [u8; 4] = *b"HEAD";
+/// With a palette, the tables of `palette::build`; before the vertices
+pub const PALETTE: [u8; 4] = *b"PALT";
+/// The interleaved vertices, or with the meshopt encoding the stream of `codec::encode_vertex_buffer`, and with the
+/// block encoding the blocks of `blocks::encode`
+pub const VERTICES: [u8; 4] = *b"VERT";
+/// The indices of the index size, or with the meshopt encoding the stream of `codec::encode_index_buffer` for
+/// triangle lists and `codec::encode_index_sequence` for the others, and with the delta encoding that of
+/// `codec::encode_index_deltas`
+pub const INDICES: [u8; 4] = *b"INDX";
+/// The triangles of `Mesh::shadow_indices`, each position indexed once, encoded like `INDX`; only present with
+/// a shadow index count
+pub const SHADOW_INDICES: [u8; 4] = *b"SHDW";
+/// Only present when the Morph flag is set
+pub const MORPH_TARGETS: [u8; 4] = *b"MRPH";
+/// The index range, material and bounds of each submesh
+pub const SUBMESHES: [u8; 4] = *b"SUBM";
+/// The materials of the submeshes found in the mtl files: a u16 count, then for each a u16 length and its name,
+/// and a u16 count of textures, each a u16 length and the mtl statement, like `map_Kd`, and a u8 source: a u16
+/// length and the path for `TEXTURE_PATH`, or the u32 index of its `TEXR` chunk for `TEXTURE_EMBEDDED`. Then a u8
+/// that is 1 when the metallic-roughness values follow: the base color with alpha, metallic, roughness and emissive
+/// color as f32, and the u16 indices in the material of the base color, metallic, roughness, normal and emissive
+/// textures, `NO_TEXTURE` when there's none
+pub const MATERIALS: [u8; 4] = *b"MATL";
+/// A texture file stored in the mesh, one chunk each: a u16 length and its path, then the content of the file
+pub const TEXTURE: [u8; 4] = *b"TEXR";
+/// Optional bounding volumes of the whole mesh, each starting with its kind and a u32 size
+pub const BOUNDS: [u8; 4] = *b"BNDS";
+
+/// A BVH over the triangles: node and triangle counts, the offset of the nodes in the chunk, then the nodes
+/// aligned to 16 bytes in the file and the triangle list
+pub const BVH: [u8; 4] = *b"BVH ";
+
+/// Surface area as f32, then a u8 that is 1 for closed meshes and their volume as f32
+pub const MEASUREMENTS: [u8; 4] = *b"MEAS";
+
+/// Key-value pairs of UTF-8 strings recording where the mesh comes from: a u16 count, then for each pair
+/// a u16 length and the key, and a u16 length and the value
+pub const METADATA: [u8; 4] = *b"META";
+
+/// The objects drawn with this geometry: a u32 count, then for each a u16 length and the object name, and its
+/// transform as a row major 3x4 matrix of f32
+pub const INSTANCES: [u8; 4] = *b"INST";
+
+/// The rooms of an interior and the openings connecting them: a u32 count, then for each cell a u16 length and
+/// its name, the min and max of its bounds as f32, and a u32 count of portals. Each portal has a u16 length and
+/// the name of the cell on the other side, its center, its normal pointing out of the cell, a u32 count and the
+/// corners of the opening
+pub const CELLS: [u8; 4] = *b"CELL";
+
+/// The mesh is the half on the positive side of a symmetry plane, drawn a second time mirrored through it: a u8
+/// axis, 0 to 2 for x to z, and where the plane crosses it as f32. Then a u8 that is 1 when indices for the
+/// mirrored copy follow, those of `INDX` with the triangles wound the other way, so that it keeps the same culling.
+/// The mirrored copy flips the handedness of the tangents; the bounds, submeshes and BVH are those of the half
+pub const MIRROR: [u8; 4] = *b"MIRR";
+
+/// With the progressive encoding, the vertex splits after the base mesh of `VERT` and `INDX` until the end of the
+/// chunk: each adds a vertex and has the index of the one it splits from, the vertex laid out like in `VERT`, a
+/// u16 count and the u32 positions in the index list of the corners that move to it, and a u16 count and the
+/// triangles it adds back at the end of the list. The indices are of the index size. Last before the checksum, so
+/// that loaders can draw the base while it arrives
+pub const SPLITS: [u8; 4] = *b"PROG";
+
+/// The CRC-32 of everything before it, always the last chunk
+pub const CHECKSUM: [u8; 4] = *b"CRC ";
+
+pub const TEXTURE_PATH: u8 = 0;
+pub const TEXTURE_EMBEDDED: u8 = 1;
+pub const NO_TEXTURE: u16 = u16::MAX;
+
+/// Center, half extents and the three axes of an oriented bounding box
+pub const BOUNDS_OBB: u8 = 1;
+/// k, then the min and max of the vertices along each of the `kdop_axes(k)`
+pub const BOUNDS_KDOP: u8 = 2;
+
+const FACE_AXES: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+const CORNER_AXES: [[f32; 3]; 4] = [[1.0, 1.0, 1.0], [1.0, 1.0, -1.0], [1.0, -1.0, 1.0], [1.0, -1.0, -1.0]];
+const EDGE_AXES: [[f32; 3]; 6] = [[1.0, 1.0, 0.0], [1.0, 0.0, 1.0], [0.0, 1.0, 1.0], [1.0, -1.0, 0.0], [1.0, 0.0, -1.0], [0.0, 1.0, -1.0]];
+
+/// The directions of the slabs of a k-DOP, not normalized: the faces of the cube, then its corners for k = 14,
+/// its edges for k = 18, or both for k = 26
+pub fn kdop_axes(k: u8) -> Option<Vec<[f32; 3]>> {
+	let (corners, edges) = match k {
+		14 => (true, false),
+		18 => (false, true),
+		26 => (true, true),
+		_ => return None,
+	};
+	let mut axes = FACE_AXES.to_vec();
+	if corners {
+		axes.extend_from_slice(&CORNER_AXES);
+	}
+	if edges {
+		axes.extend_from_slice(&EDGE_AXES);
+	}
+	Some(axes)
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+	pub id: [u8; 4],
+	pub data: Vec<u8>,
+}
+
+impl Chunk {
+	/// The FourCC as text, for error messages
+	pub fn name(&self) -> String {
+		String::from_utf8_lossy(&self.id).into_owned()
+	}
+}
+
+const fn crc_table() -> [u32; 256] {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut c = i as u32;
+		let mut k = 0;
+		while k < 8 {
+			c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+			k += 1;
+		}
+		table[i] = c;
+		i += 1;
+	}
+	table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// The CRC-32 used by zip and png, computed as the data comes in
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+	state: u32,
+}
+
+impl Default for Crc32 {
+	fn default() -> Self {
+		Crc32 { state: 0xffffffff }
+	}
+}
+
+impl Crc32 {
+	pub fn update(&mut self, data: &[u8]) {
+		for &b in data {
+			self.state = CRC_TABLE[((self.state ^ b as u32) & 0xff) as usize] ^ (self.state >> 8);
+		}
+	}
+
+	pub fn value(&self) -> u32 {
+		self.state ^ 0xffffffff
+	}
+}
+
+#[cfg(feature = "std")]
+/// Computes the checksum of everything read through it
+pub struct ChecksumReader<R> {
+	inner: R,
+	pub crc: Crc32,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> ChecksumReader<R> {
+	pub fn new(inner: R) -> Self {
+		ChecksumReader {
+			inner,
+			crc: Crc32::default(),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for ChecksumReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.crc.update(&buf[..n]);
+		Ok(n)
+	}
+}
+
+#[cfg(feature = "std")]
+/// Closes a file with the checksum of its content
+pub fn write_checksum(data: &mut Vec<u8>) {
+	let mut crc = Crc32::default();
+	crc.update(data);
+	let mut body = vec![];
+	body.write_u32::<LittleEndian>(crc.value()).unwrap();
+	write_chunk(data, CHECKSUM, &body);
+}
+
+#[cfg(feature = "std")]
+pub fn write_file_header(data: &mut Vec<u8>, version: u32) {
+	data.extend_from_slice(&MAGIC);
+	data.write_u32::<LittleEndian>(version).unwrap();
+}
+
+#[cfg(feature = "std")]
+pub fn write_chunk(data: &mut Vec<u8>, id: [u8; 4], body: &[u8]) {
+	if body.len() > u32::MAX as usize {
+		panic!("The {} chunk is {} bytes, more than its length can store", String::from_utf8_lossy(&id), body.len());
+	}
+	data.extend_from_slice(&id);
+	data.write_u32::<LittleEndian>(body.len() as u32).unwrap();
+	data.extend_from_slice(body);
+}
+
+#[cfg(feature = "std")]
+/// Reads the version that follows the magic number
+pub fn read_version<R: Read>(input: &mut R) -> Result<u32, ReadError> {
+	let version = input.read_u32::<LittleEndian>()?;
+	if version <= LEGACY_VERSION || version > VERSION {
+		return Err(ReadError::UnsupportedVersion(version));
+	}
+	Ok(version)
+}
+
+#[cfg(feature = "std")]
+/// Reads the next chunk, or None at the end of the file
+pub fn read_chunk<R: Read>(input: &mut R) -> Result<Option<Chunk>, ReadError> {
+	let mut id = [0; 4];
+	//a clean end of file can only come before a chunk
+	let mut read = 0;
+	while read < id.len() {
+		match input.read(&mut id[read..])? {
+			0 if read == 0 => return Ok(None),
+			0 => return Err(ReadError::TruncatedChunk(String::from_utf8_lossy(&id[..read]).into_owned())),
+			n => read += n,
+		}
+	}
+
+	let len = input.read_u32::<LittleEndian>()? as usize;
+	//the length comes from the file, so let the buffer grow as the data is actually read
+	let mut data = vec![];
+	input.take(len as u64).read_to_end(&mut data)?;
+
+	let chunk = Chunk { id, data };
+	if chunk.data.len() != len {
+		return Err(ReadError::TruncatedChunk(chunk.name()));
+	}
+	Ok(Some(chunk))
+}
@@ -0,0 +1,690 @@
+//! The vertex and index codecs of meshoptimizer, which the `VERT` and `INDX` chunks can be stored with instead of
+//! raw. They are much smaller and still leave the data compressible by general purpose compressors.
+//!
+//! The vertices are split into blocks, and each byte of the vertex stored for the whole block as the deltas from
+//! the vertices before, in groups of 16 packed with 0, 2, 4 or 8 bits each, like version 0 of
+//! `meshopt_encodeVertexBuffer`. The triangles are stored as codes referencing the edges and vertices of the
+//! triangles before them, like version 1 of `meshopt_encodeIndexBuffer`, and the other index lists as deltas like
+//! `meshopt_encodeIndexSequence`, so meshoptimizer can decode the chunks too.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// How the vertices and indices are stored, which the header records as 0 to 4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+	/// As they are laid out in the buffers
+	#[default]
+	Raw,
+	Meshopt,
+	/// The vertices raw, and the indices as the varints of their difference with the one before, which takes
+	/// little more than a byte for each in the meshes ordered for the vertex cache
+	Delta,
+	/// The base of a progressive mesh raw, and the vertex splits refining it into the whole mesh after the other
+	/// chunks. The header and the other chunks describe the whole mesh
+	Progressive,
+	/// The vertices in the fixed size blocks of `blocks`, for compute shaders to decompress, and the indices raw
+	Blocks,
+}
+
+impl Encoding {
+	pub fn from_name(name: &str) -> Option<Encoding> {
+		match name {
+			"raw" => Some(Encoding::Raw),
+			"meshopt" => Some(Encoding::Meshopt),
+			"delta" => Some(Encoding::Delta),
+			"progressive" => Some(Encoding::Progressive),
+			"blocks" => Some(Encoding::Blocks),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Encoding::Raw => "raw",
+			Encoding::Meshopt => "meshopt",
+			Encoding::Delta => "delta",
+			Encoding::Progressive => "progressive",
+			Encoding::Blocks => "blocks",
+		}
+	}
+
+	pub fn from_u8(val: u8) -> Option<Encoding> {
+		match val {
+			0 => Some(Encoding::Raw),
+			1 => Some(Encoding::Meshopt),
+			2 => Some(Encoding::Delta),
+			3 => Some(Encoding::Progressive),
+			4 => Some(Encoding::Blocks),
+			_ => None,
+		}
+	}
+
+	pub fn to_u8(self) -> u8 {
+		match self {
+			Encoding::Raw => 0,
+			Encoding::Meshopt => 1,
+			Encoding::Delta => 2,
+			Encoding::Progressive => 3,
+			Encoding::Blocks => 4,
+		}
+	}
+}
+
+/// The largest vertices the vertex codec can store
+pub const MAX_VERTEX_SIZE: usize = 256;
+
+const VERTEX_HEADER: u8 = 0xa0;
+const INDEX_HEADER: u8 = 0xe0;
+const SEQUENCE_HEADER: u8 = 0xd0;
+const INDEX_VERSION: u8 = 1;
+
+const BYTE_GROUP_SIZE: usize = 16;
+const VERTEX_BLOCK_MAX_SIZE: usize = 256;
+//the tail is padded so that decoders can read past the end of the groups
+const TAIL_MIN_SIZE: usize = 32;
+
+/// The order of the corners of a triangle starting at each of them
+const TRIANGLE_ORDER: [[usize; 3]; 3] = [[0, 1, 2], [1, 2, 0], [2, 0, 1]];
+
+/// The most common combinations of vertex FIFO codes, stored with each stream
+const CODE_AUX_TABLE: [u8; 16] = [0x00, 0x76, 0x87, 0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0, 0];
+
+/// Reads the next `count` bytes of `data`, if there are enough
+fn take<'a>(data: &mut &'a [u8], count: usize) -> Option<&'a [u8]> {
+	if data.len() < count {
+		return None;
+	}
+	let (taken, rest) = data.split_at(count);
+	*data = rest;
+	Some(taken)
+}
+
+fn zigzag8(v: u8) -> u8 {
+	(((v as i8) >> 7) as u8) ^ (v << 1)
+}
+
+fn unzigzag8(v: u8) -> u8 {
+	(v & 1).wrapping_neg() ^ (v >> 1)
+}
+
+/// The vertices in a block, so that each byte of the block fits 8KB
+fn vertex_block_size(vertex_size: usize) -> usize {
+	usize::min((8192 / vertex_size) & !(BYTE_GROUP_SIZE - 1), VERTEX_BLOCK_MAX_SIZE)
+}
+
+/// The size of a group of 16 bytes stored with `1 << bits_log2` bits each, with a whole byte for the ones that
+/// don't fit, or none for the groups of zeros
+fn group_size(group: &[u8], bits_log2: usize) -> Option<usize> {
+	match bits_log2 {
+		0 => if group.iter().all(|&b| b == 0) { Some(0) } else { None },
+		3 => Some(BYTE_GROUP_SIZE),
+		_ => {
+			let bits = 1 << bits_log2;
+			let sentinel = (1u8 << bits) - 1;
+			Some(BYTE_GROUP_SIZE * bits / 8 + group.iter().filter(|&&b| b >= sentinel).count())
+		},
+	}
+}
+
+fn encode_group(data: &mut Vec<u8>, group: &[u8], bits_log2: usize) {
+	match bits_log2 {
+		0 => {},
+		3 => data.extend_from_slice(group),
+		_ => {
+			//the first value in the high bits, and the sentinel for the ones stored after
+			let bits = 1 << bits_log2;
+			let sentinel = (1u8 << bits) - 1;
+			for values in group.chunks(8 / bits) {
+				data.push(values.iter().fold(0, |byte, &b| (byte << bits) | u8::min(b, sentinel)));
+			}
+			data.extend(group.iter().filter(|&&b| b >= sentinel));
+		},
+	}
+}
+
+fn decode_group(data: &mut &[u8], group: &mut [u8], bits_log2: usize) -> Option<()> {
+	match bits_log2 {
+		0 => group.fill(0),
+		3 => group.copy_from_slice(take(data, BYTE_GROUP_SIZE)?),
+		_ => {
+			let bits = 1 << bits_log2;
+			let sentinel = (1u8 << bits) - 1;
+			let packed = take(data, BYTE_GROUP_SIZE * bits / 8)?;
+			for (i, value) in group.iter_mut().enumerate() {
+				let shift = 8 - bits * (i % (8 / bits) + 1);
+				*value = (packed[i / (8 / bits)] >> shift) & sentinel;
+			}
+			for value in group.iter_mut().filter(|v| **v == sentinel) {
+				*value = take(data, 1)?[0];
+			}
+		},
+	}
+	Some(())
+}
+
+/// Stores `buffer`, whose length is a multiple of 16, as a header with 2 bits for each group followed by the groups
+fn encode_bytes(data: &mut Vec<u8>, buffer: &[u8]) {
+	let header = data.len();
+	data.resize(header + (buffer.len() / BYTE_GROUP_SIZE).div_ceil(4), 0);
+	for (i, group) in buffer.chunks(BYTE_GROUP_SIZE).enumerate() {
+		let mut best = (3, BYTE_GROUP_SIZE);
+		for bits_log2 in 0..3 {
+			match group_size(group, bits_log2) {
+				Some(size) if size < best.1 => best = (bits_log2, size),
+				_ => {},
+			}
+		}
+		data[header + i / 4] |= (best.0 << (i % 4 * 2)) as u8;
+		encode_group(data, group, best.0);
+	}
+}
+
+fn decode_bytes(data: &mut &[u8], buffer: &mut [u8]) -> Option<()> {
+	let header = take(data, (buffer.len() / BYTE_GROUP_SIZE).div_ceil(4))?;
+	for (i, group) in buffer.chunks_mut(BYTE_GROUP_SIZE).enumerate() {
+		decode_group(data, group, (header[i / 4] >> (i % 4 * 2)) as usize & 3)?;
+	}
+	Some(())
+}
+
+/// Encodes the interleaved `vertices` of `vertex_size` bytes each, a multiple of 4 up to `MAX_VERTEX_SIZE`
+pub fn encode_vertex_buffer(vertices: &[u8], vertex_size: usize) -> Vec<u8> {
+	assert!(vertex_size > 0 && vertex_size <= MAX_VERTEX_SIZE && vertex_size.is_multiple_of(4), "Can't encode vertices of {} bytes", vertex_size);
+	assert!(vertices.len().is_multiple_of(vertex_size));
+	let mut data = vec![VERTEX_HEADER];
+
+	//the first vertex is the reference for the deltas of the first block
+	let first = if vertices.is_empty() { vec![0; vertex_size] } else { vertices[..vertex_size].to_vec() };
+	let mut last = first.clone();
+	let mut buffer = vec![0; VERTEX_BLOCK_MAX_SIZE];
+	for block in vertices.chunks(vertex_block_size(vertex_size) * vertex_size) {
+		let count = block.len() / vertex_size;
+		let aligned = count.div_ceil(BYTE_GROUP_SIZE) * BYTE_GROUP_SIZE;
+		for k in 0..vertex_size {
+			let mut previous = last[k];
+			for i in 0..count {
+				let byte = block[i * vertex_size + k];
+				buffer[i] = zigzag8(byte.wrapping_sub(previous));
+				previous = byte;
+			}
+			encode_bytes(&mut data, &buffer[..aligned]);
+		}
+		last.copy_from_slice(&block[(count - 1) * vertex_size..]);
+	}
+
+	data.resize(data.len() + usize::max(vertex_size, TAIL_MIN_SIZE) - vertex_size, 0);
+	data.extend(first);
+	data
+}
+
+/// The interleaved vertices encoded by `encode_vertex_buffer`, or none when `data` isn't valid
+pub fn decode_vertex_buffer(data: &[u8], vertex_count: usize, vertex_size: usize) -> Option<Vec<u8>> {
+	if vertex_size == 0 || vertex_size > MAX_VERTEX_SIZE || data.len() < 1 + vertex_size {
+		return None;
+	}
+	if data[0] & 0xf0 != VERTEX_HEADER || data[0] & 0x0f > 0 {
+		return None;
+	}
+	//each byte of each block has at least its header, so the counts that don't fit aren't allocated
+	if data.len() < vertex_count / 64 * vertex_size {
+		return None;
+	}
+	let mut last = data[data.len() - vertex_size..].to_vec();
+	let mut data = &data[1..];
+
+	let mut vertices = vec![0; vertex_count.checked_mul(vertex_size)?];
+	let mut buffer = vec![0; VERTEX_BLOCK_MAX_SIZE];
+	for block in vertices.chunks_mut(vertex_block_size(vertex_size) * vertex_size) {
+		let count = block.len() / vertex_size;
+		let aligned = count.div_ceil(BYTE_GROUP_SIZE) * BYTE_GROUP_SIZE;
+		for k in 0..vertex_size {
+			decode_bytes(&mut data, &mut buffer[..aligned])?;
+			let mut previous = last[k];
+			for i in 0..count {
+				previous = unzigzag8(buffer[i]).wrapping_add(previous);
+				block[i * vertex_size + k] = previous;
+			}
+		}
+		last.copy_from_slice(&block[(count - 1) * vertex_size..]);
+	}
+
+	if data.len() != usize::max(vertex_size, TAIL_MIN_SIZE) {
+		return None;
+	}
+	Some(vertices)
+}
+
+/// In groups of 7 bits, with the high bit set on all but the last
+fn encode_vbyte(data: &mut Vec<u8>, mut v: u32) {
+	loop {
+		data.push((v & 127) as u8 | if v > 127 { 128 } else { 0 });
+		v >>= 7;
+		if v == 0 {
+			break;
+		}
+	}
+}
+
+fn decode_vbyte(data: &mut &[u8]) -> Option<u32> {
+	let mut result = 0;
+	//at most 5 bytes for 32 bits
+	for i in 0..5 {
+		let byte = take(data, 1)?[0];
+		result |= ((byte & 127) as u32) << (7 * i);
+		if byte < 128 {
+			break;
+		}
+	}
+	Some(result)
+}
+
+fn zigzag32(d: u32) -> u32 {
+	(d << 1) ^ (((d as i32) >> 31) as u32)
+}
+
+fn unzigzag32(v: u32) -> u32 {
+	(v >> 1) ^ (v & 1).wrapping_neg()
+}
+
+/// The delta from the last index stored in full
+fn encode_index(data: &mut Vec<u8>, index: u32, last: u32) {
+	encode_vbyte(data, zigzag32(index.wrapping_sub(last)));
+}
+
+fn decode_index(data: &mut &[u8], last: u32) -> Option<u32> {
+	Some(last.wrapping_add(unzigzag32(decode_vbyte(data)?)))
+}
+
+/// The last edges and vertices of the triangles, from which the next triangles pick theirs
+struct Fifos {
+	edges: [[u32; 2]; 16],
+	edge_offset: usize,
+	vertices: [u32; 16],
+	vertex_offset: usize,
+}
+
+impl Fifos {
+	fn new() -> Self {
+		Fifos {
+			edges: [[u32::MAX; 2]; 16],
+			edge_offset: 0,
+			vertices: [u32::MAX; 16],
+			vertex_offset: 0,
+		}
+	}
+
+	/// The position of the most recent edge of the triangle, times 4, plus the corner it starts at
+	fn find_edge(&self, [a, b, c]: [u32; 3]) -> Option<usize> {
+		(0..16).find_map(|i| {
+			let edge = self.edges[(self.edge_offset + 15 - i) & 15];
+			[[a, b], [b, c], [c, a]].iter().position(|&e| e == edge).map(|corner| (i << 2) | corner)
+		})
+	}
+
+	fn push_edge(&mut self, a: u32, b: u32) {
+		self.edges[self.edge_offset] = [a, b];
+		self.edge_offset = (self.edge_offset + 1) & 15;
+	}
+
+	/// The edge `i` edges before the last one
+	fn edge(&self, i: usize) -> [u32; 2] {
+		self.edges[(self.edge_offset + 15 - i) & 15]
+	}
+
+	fn find_vertex(&self, v: u32) -> Option<usize> {
+		(0..16).find(|&i| self.vertex(i) == v)
+	}
+
+	/// Only moves on when `advance`, so that the decoder can push without branching like meshoptimizer
+	fn push_vertex(&mut self, v: u32, advance: bool) {
+		self.vertices[self.vertex_offset] = v;
+		self.vertex_offset = (self.vertex_offset + advance as usize) & 15;
+	}
+
+	/// The vertex `i` vertices before the last one
+	fn vertex(&self, i: usize) -> u32 {
+		self.vertices[(self.vertex_offset + 15 - i) & 15]
+	}
+}
+
+/// Encodes the indices of a triangle list. The codes are smallest when the vertices are in the order the
+/// triangles first use them
+pub fn encode_index_buffer(indices: &[u32]) -> Vec<u8> {
+	assert!(indices.len().is_multiple_of(3), "{} indices don't make a triangle list", indices.len());
+	let mut codes = vec![INDEX_HEADER | INDEX_VERSION];
+	let mut data = vec![];
+	let mut fifos = Fifos::new();
+	//the vertex used for the first time in order, and the last index stored in full
+	let mut next = 0u32;
+	let mut last = 0u32;
+	const FEC_MAX: usize = 13;
+
+	for triangle in indices.chunks(3) {
+		let triangle = [triangle[0], triangle[1], triangle[2]];
+		match fifos.find_edge(triangle) {
+			Some(found) if found >> 2 < 15 => {
+				let order = TRIANGLE_ORDER[found & 3];
+				let [a, b, c] = order.map(|i| triangle[i]);
+				let fe = found >> 2;
+				let mut fec = match fifos.find_vertex(c) {
+					Some(fc) if (1..FEC_MAX).contains(&fc) => fc,
+					_ if c == next => {
+						next = next.wrapping_add(1);
+						0
+					},
+					_ => 15,
+				};
+				//the neighbors of the last index, as in strips
+				if fec == 15 && c.wrapping_add(1) == last {
+					fec = 13;
+					last = c;
+				}
+				if fec == 15 && c == last.wrapping_add(1) {
+					fec = 14;
+					last = c;
+				}
+				codes.push(((fe << 4) | fec) as u8);
+				if fec == 15 {
+					encode_index(&mut data, c, last);
+					last = c;
+				}
+				if fec == 0 || fec >= FEC_MAX {
+					fifos.push_vertex(c, true);
+				}
+				fifos.push_edge(c, b);
+				fifos.push_edge(a, c);
+			},
+			_ => {
+				let rotation = if triangle[1] == next { 1 } else if triangle[2] == next { 2 } else { 0 };
+				let [a, b, c] = TRIANGLE_ORDER[rotation].map(|i| triangle[i]);
+
+				//0, 1, 2 after other vertices starts over, for the meshes made of several lists
+				let reset = a == 0 && b == 1 && c == 2 && next > 0;
+				if reset {
+					next = 0;
+					fifos.vertices = [u32::MAX; 16];
+				}
+
+				let fb = fifos.find_vertex(b);
+				let fc = fifos.find_vertex(c);
+				let mut code = |v: u32, found: Option<usize>| match found {
+					Some(f) if f < 14 => f + 1,
+					_ if v == next => {
+						next = next.wrapping_add(1);
+						0
+					},
+					_ => 15,
+				};
+				let fea = code(a, None);
+				let feb = code(b, fb);
+				let fec = code(c, fc);
+
+				let aux = ((feb << 4) | fec) as u8;
+				match CODE_AUX_TABLE[..14].iter().position(|&entry| entry == aux) {
+					Some(i) if fea == 0 && !reset => codes.push(0xf0 | i as u8),
+					_ => {
+						codes.push(0xf0 | 14 | fea as u8);
+						data.push(aux);
+					},
+				}
+				for (v, fe) in [(a, fea), (b, feb), (c, fec)] {
+					if fe == 15 {
+						encode_index(&mut data, v, last);
+						last = v;
+					}
+				}
+				for (v, fe) in [(a, fea), (b, feb), (c, fec)] {
+					if fe == 0 || fe == 15 {
+						fifos.push_vertex(v, true);
+					}
+				}
+				fifos.push_edge(b, a);
+				fifos.push_edge(c, b);
+				fifos.push_edge(a, c);
+			},
+		}
+	}
+
+	//the table is also the padding decoders read past the end with
+	codes.extend(data);
+	codes.extend_from_slice(&CODE_AUX_TABLE);
+	codes
+}
+
+/// The `index_count` indices of a triangle list encoded by `encode_index_buffer`, or none when `data` isn't valid
+pub fn decode_index_buffer(data: &[u8], index_count: usize) -> Option<Vec<u32>> {
+	let triangle_count = index_count / 3;
+	if !index_count.is_multiple_of(3) || data.len() < 1 + triangle_count + 16 {
+		return None;
+	}
+	if data[0] & 0xf0 != INDEX_HEADER || data[0] & 0x0f > INDEX_VERSION {
+		return None;
+	}
+	let fec_max = if data[0] & 0x0f >= 1 { 13 } else { 15 };
+	let codes = &data[1..1 + triangle_count];
+	let (mut data, table) = data[1 + triangle_count..].split_at(data.len() - 17 - triangle_count);
+
+	let mut indices = Vec::with_capacity(index_count);
+	let mut fifos = Fifos::new();
+	let mut next = 0u32;
+	let mut last = 0u32;
+	for &code in codes {
+		let triangle = if code < 0xf0 {
+			let [a, b] = fifos.edge((code >> 4) as usize);
+			let fec = (code & 15) as usize;
+			let c = if fec == 0 {
+				next = next.wrapping_add(1);
+				next.wrapping_sub(1)
+			}
+			else if fec < fec_max {
+				fifos.vertex(fec)
+			}
+			else {
+				last = match fec {
+					13 => last.wrapping_sub(1),
+					14 => last.wrapping_add(1),
+					_ => decode_index(&mut data, last)?,
+				};
+				last
+			};
+			fifos.push_vertex(c, fec == 0 || fec >= fec_max);
+			fifos.push_edge(c, b);
+			fifos.push_edge(a, c);
+			[a, b, c]
+		}
+		else {
+			let (fea, aux) = if code < 0xfe {
+				(0, table[(code & 15) as usize])
+			}
+			else {
+				(if code == 0xfe { 0 } else { 15 }, take(&mut data, 1)?[0])
+			};
+			let (feb, fec) = ((aux >> 4) as usize, (aux & 15) as usize);
+			//a code that isn't in the table for 0, 1, 2 starts over
+			if code >= 0xfe && aux == 0 {
+				next = 0;
+			}
+			let mut corner = |fe: usize| -> Option<u32> {
+				Some(match fe {
+					0 => {
+						next = next.wrapping_add(1);
+						next.wrapping_sub(1)
+					},
+					15 => {
+						last = decode_index(&mut data, last)?;
+						last
+					},
+					_ => fifos.vertex(fe - 1),
+				})
+			};
+			let a = corner(fea)?;
+			let b = corner(feb)?;
+			let c = corner(fec)?;
+			fifos.push_vertex(a, true);
+			fifos.push_vertex(b, feb == 0 || feb == 15);
+			fifos.push_vertex(c, fec == 0 || fec == 15);
+			fifos.push_edge(b, a);
+			fifos.push_edge(c, b);
+			fifos.push_edge(a, c);
+			[a, b, c]
+		};
+		indices.extend_from_slice(&triangle);
+	}
+
+	if !data.is_empty() {
+		return None;
+	}
+	Some(indices)
+}
+
+/// Encodes a list of indices that aren't triangles, as deltas from one of the last two
+pub fn encode_index_sequence(indices: &[u32]) -> Vec<u8> {
+	let mut data = vec![SEQUENCE_HEADER | INDEX_VERSION];
+	let mut last = [0u32; 2];
+	let mut current = 0;
+	for &index in indices {
+		//switches to the other baseline when the delta gets too large for a byte
+		let delta = index.wrapping_sub(last[current]) as i32;
+		if delta.unsigned_abs() >= 30 {
+			current ^= 1;
+		}
+		let v = zigzag32(index.wrapping_sub(last[current]));
+		encode_vbyte(&mut data, (v << 1) | current as u32);
+		last[current] = index;
+	}
+	data.extend_from_slice(&[0; 4]);
+	data
+}
+
+/// The `index_count` indices encoded by `encode_index_sequence`, or none when `data` isn't valid
+pub fn decode_index_sequence(data: &[u8], index_count: usize) -> Option<Vec<u32>> {
+	if data.len() < 1 + index_count + 4 {
+		return None;
+	}
+	if data[0] & 0xf0 != SEQUENCE_HEADER || data[0] & 0x0f > INDEX_VERSION {
+		return None;
+	}
+	let mut data = &data[1..data.len() - 4];
+	let mut last = [0u32; 2];
+	let mut indices = Vec::with_capacity(index_count);
+	for _ in 0..index_count {
+		let v = decode_vbyte(&mut data)?;
+		let current = (v & 1) as usize;
+		last[current] = last[current].wrapping_add(unzigzag32(v >> 1));
+		indices.push(last[current]);
+	}
+	if !data.is_empty() {
+		return None;
+	}
+	Some(indices)
+}
+
+/// Encodes indices as the zigzag varints of their difference with the one before, starting from 0
+pub fn encode_index_deltas(indices: &[u32]) -> Vec<u8> {
+	let mut data = Vec::with_capacity(indices.len());
+	let mut last = 0;
+	for &index in indices {
+		encode_index(&mut data, index, last);
+		last = index;
+	}
+	data
+}
+
+/// The `index_count` indices encoded by `encode_index_deltas`, or none when `data` isn't valid
+pub fn decode_index_deltas(mut data: &[u8], index_count: usize) -> Option<Vec<u32>> {
+	//at least a byte each
+	if data.len() < index_count {
+		return None;
+	}
+	let mut indices = Vec::with_capacity(index_count);
+	let mut last = 0;
+	for _ in 0..index_count {
+		last = decode_index(&mut data, last)?;
+		indices.push(last);
+	}
+	if !data.is_empty() {
+		return None;
+	}
+	Some(indices)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Bytes that don't repeat, from a xorshift
+	fn noise(len: usize) -> Vec<u8> {
+		let mut state = 0x9E37_79B9u32;
+		(0..len).map(|_| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state as u8
+		}).collect()
+	}
+
+	#[test]
+	fn vertex_buffer() {
+		for (vertex_size, count) in [(4, 0), (4, 1), (12, 15), (16, 257), (32, 1000), (MAX_VERTEX_SIZE, 70)] {
+			//smooth, constant and random bytes, for each packing of the groups
+			let smooth: Vec<u8> = (0..vertex_size * count).map(|i| (i / vertex_size + i % vertex_size * 3) as u8).collect();
+			for vertices in [smooth, vec![7; vertex_size * count], noise(vertex_size * count)] {
+				let data = encode_vertex_buffer(&vertices, vertex_size);
+				assert_eq!(decode_vertex_buffer(&data, count, vertex_size), Some(vertices), "{} vertices of {} bytes", count, vertex_size);
+			}
+		}
+	}
+
+	#[test]
+	fn index_buffer() {
+		//a strip of quads reusing the edges, then triangles far apart
+		let mut indices = vec![];
+		for i in 0..1000 {
+			indices.extend_from_slice(&[i * 2, i * 2 + 1, i * 2 + 2, i * 2 + 2, i * 2 + 1, i * 2 + 3]);
+		}
+		indices.extend_from_slice(&[70000, 5, 65536, 123456, 0, 99999]);
+		let data = encode_index_buffer(&indices);
+		assert_eq!(decode_index_buffer(&data, indices.len()), Some(indices.clone()));
+		assert_eq!(decode_index_sequence(&encode_index_sequence(&indices), indices.len()), Some(indices.clone()));
+		assert_eq!(decode_index_deltas(&encode_index_deltas(&indices), indices.len()), Some(indices));
+	}
+
+	#[test]
+	fn invalid_data() {
+		let vertices = noise(16 * 100);
+		let data = encode_vertex_buffer(&vertices, 16);
+		assert_eq!(decode_vertex_buffer(&data[..data.len() - 1], 100, 16), None);
+		assert_eq!(decode_vertex_buffer(&[0; 1], 100, 16), None);
+		let data = encode_index_buffer(&[0, 1, 2, 2, 1, 3]);
+		assert_eq!(decode_index_buffer(&data[1..], 6), None);
+		assert_eq!(decode_index_buffer(&data, 9), None);
+	}
+
+	/// Through the reader, against the same conversion stored raw
+	#[cfg(feature = "converter")]
+	fn assert_round_trip(obj: &str, options: &crate::convert::ConvertOptions) -> crate::reader::MeshData {
+		use crate::fixtures;
+		let raw = fixtures::read(&fixtures::convert_one(obj, options));
+		let options = crate::convert::ConvertOptions { encoding: Encoding::Meshopt, ..options.clone() };
+		let encoded = fixtures::read(&fixtures::convert_one(obj, &options));
+		assert_eq!(encoded.header.encoding, Encoding::Meshopt);
+		fixtures::assert_same_geometry(&raw, &encoded);
+		encoded
+	}
+
+	#[test]
+	#[cfg(feature = "converter")]
+	fn cube_through_the_reader() {
+		let options = crate::convert::ConvertOptions { generate_tangents: true, shadow_indices: true, ..Default::default() };
+		assert_round_trip(crate::fixtures::CUBE, &options);
+	}
+
+	#[test]
+	#[cfg(feature = "converter")]
+	fn large_mesh_through_the_reader() {
+		let mesh = assert_round_trip(&crate::fixtures::grid(260), &Default::default());
+		assert!(mesh.positions.len() > 65535);
+		assert_eq!(mesh.header.index_size, 4);
+	}
+}
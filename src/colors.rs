@@ -0,0 +1,33 @@
+//! Vertex colors, from the `v x y z r g b` extension that many exporters write.
+
+/// The color of every position of the obj file, across all its objects
+#[derive(Debug, Clone, Default)]
+pub struct VertexColors {
+	pub colors: Vec<[f64; 4]>,
+}
+
+pub const WHITE: [f64; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Reads the colors following the positions, with an optional alpha; returns None if no position has one.
+/// `v x y z w` is a position with a weight, not a color
+pub fn parse_vertex_colors(content: &str) -> Option<VertexColors> {
+	let mut result = VertexColors::default();
+	let mut any = false;
+
+	for line in content.lines() {
+		let mut tokens = line.split_whitespace();
+		if tokens.next() != Some("v") {
+			continue;
+		}
+
+		let values: Vec<f64> = tokens.skip(3).map(|t| t.parse().unwrap_or(1.0)).collect();
+		result.colors.push(match values.len() {
+			3 => [values[0], values[1], values[2], 1.0],
+			4 => [values[0], values[1], values[2], values[3]],
+			_ => WHITE,
+		});
+		any |= values.len() >= 3;
+	}
+
+	if any { Some(result) } else { None }
+}
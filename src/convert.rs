@@ -0,0 +1,1405 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::{ObjSet, Object, Shape, VTNIndex, Vertex};
+use wavefront_obj::ParseError;
+use crate::math::*;
+use crate::mesh::{self, Mesh, MirrorPlane};
+use crate::colors::VertexColors;
+use crate::reader::Topology;
+use crate::layout::{Attribute, ColorSpace, Format};
+use crate::skin::SkinWeights;
+use crate::morph::{self, MorphTarget};
+use crate::curvature::Curvature;
+use crate::normals::{self, NormalWeighting, SeamSmoothing};
+use crate::lightmap::LightmapSettings;
+use crate::uvgen::{self, UvProjection};
+use crate::user_attributes::UserAttributes;
+use crate::preparse;
+use crate::filter::NameFilter;
+use crate::chunk;
+use crate::codec::{self, Encoding};
+use crate::blocks::{self, BlockLayout};
+use crate::palette;
+use crate::dequantization::{self, Dequantization};
+use crate::progressive::{self, Refinement};
+use crate::bounds;
+use crate::bvh;
+use crate::measure::{self, Measurements};
+use crate::fill;
+use crate::sanitize;
+use crate::manifold;
+use crate::simplify;
+use crate::remesh::{self, RemeshSettings};
+use crate::planar;
+use crate::material::{self, MaterialLibrary, TexturePaths};
+use crate::portals::{self, Cell};
+use crate::impostor::{self, ImpostorAtlas, ImpostorSettings};
+use crate::instances::{self, Transform};
+use crate::diagnostics::{Warning, WarningKind};
+use crate::quantization::QuantizationReport;
+
+/// What to do with the texture coordinates outside of [-1, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UvOverflow {
+	/// Moves them back in by whole units, which breaks tiling across more than one unit
+	#[default]
+	Wrap,
+	Clamp,
+	/// Stores all the UVs as Float32x2
+	Keep,
+	/// Reports the ones the faces use as errors, with the first face
+	Error,
+}
+
+impl UvOverflow {
+	pub fn from_name(name: &str) -> Option<UvOverflow> {
+		match name {
+			"wrap" => Some(UvOverflow::Wrap),
+			"clamp" => Some(UvOverflow::Clamp),
+			"keep" => Some(UvOverflow::Keep),
+			"error" => Some(UvOverflow::Error),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			UvOverflow::Wrap => "wrap",
+			UvOverflow::Clamp => "clamp",
+			UvOverflow::Keep => "keep",
+			UvOverflow::Error => "error",
+		}
+	}
+}
+
+/// What to do with the normals of the file once a pass moved the positions or rewound the triangles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StaleNormals {
+	/// Generates smooth ones instead
+	#[default]
+	Recompute,
+	/// Keeps them, with a warning
+	Warn,
+}
+
+impl StaleNormals {
+	pub fn from_name(name: &str) -> Option<StaleNormals> {
+		match name {
+			"recompute" => Some(StaleNormals::Recompute),
+			"warn" => Some(StaleNormals::Warn),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			StaleNormals::Recompute => "recompute",
+			StaleNormals::Warn => "warn",
+		}
+	}
+}
+
+/// The local origin of each object, that its positions are stored relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pivot {
+	/// The center of the bounding box
+	Center,
+	/// The center of the bottom of the bounding box, where props stand on the ground
+	Bottom,
+}
+
+impl Pivot {
+	pub fn from_name(name: &str) -> Option<Pivot> {
+		match name {
+			"center" => Some(Pivot::Center),
+			"bottom" => Some(Pivot::Bottom),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Pivot::Center => "center",
+			Pivot::Bottom => "bottom",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+	pub generate_tangents: bool,
+	/// Colors for every position of the obj file
+	pub vertex_colors: Option<VertexColors>,
+	/// The vertex colors are read as sRGB, and converted when they are stored as linear
+	pub color_space: ColorSpace,
+	/// Adds the bitangent as its own attribute, for shaders that don't reconstruct it
+	pub emit_bitangent: bool,
+	/// Bone influences for every position of the obj file
+	pub skin: Option<SkinWeights>,
+	pub morph_targets: Vec<MorphTarget>,
+	/// Replaces the normals of the triangles with ones generated from the positions
+	pub recompute_normals: Option<NormalWeighting>,
+	/// Generates the normals of the triangles with hard edges where the faces are at more than this many degrees,
+	/// area weighted unless `recompute_normals` says otherwise
+	pub crease_angle: Option<f64>,
+	/// Gives every triangle its face normal, for faceted low-poly styles
+	pub flat: bool,
+	/// Averages the normals, and the tangents, of the vertices at the same position that the UV seams split,
+	/// unless they are further apart than the crease angle, or 60 degrees, as those are hard edges
+	pub smooth_seams: Option<SeamSmoothing>,
+	/// For the normals of the file after the simplification of the proxies or the rewinding of triangles
+	pub stale_normals: StaleNormals,
+	/// Adds the curvature as a vertex attribute
+	pub curvature: Option<Curvature>,
+	/// Generates lightmap UVs into Tex1
+	pub lightmap: Option<LightmapSettings>,
+	/// Projects texture coordinates for the objects that have none
+	pub gen_uvs: Option<UvProjection>,
+	/// Adds the index of the material of each triangle, in order of first use in the object
+	pub material_ids: bool,
+	/// Appended after the built-in attributes
+	pub user_attributes: Option<UserAttributes>,
+	/// Keeps the normals and texture coordinates when only some faces have them, filling in defaults
+	pub fill_missing: bool,
+	/// Attributes every mesh must have; the conversion panics when one can't be produced, instead of leaving it out
+	pub required: Vec<Attribute>,
+	/// Drops or substitutes the non-finite values and broken normals, instead of just warning about them
+	pub repair: bool,
+	/// Normalizes the normals of the file, which `repair` does too, without the other repairs
+	pub renormalize: bool,
+	/// How far from unit length a normal of the file can be before it's reported; 0.001 by default
+	pub normal_tolerance: Option<f64>,
+	/// Splits the objects with more vertices into several meshes
+	pub max_vertices: Option<usize>,
+	/// Makes a mesh for each connected piece, for physics or destruction
+	pub split_components: bool,
+	/// Makes a mesh for each material, with only the faces using it
+	pub split_by_material: bool,
+	/// Groups the faces by material, so that each material is a single submesh
+	pub sort_by_material: bool,
+	/// Makes a mesh for each cell of a world space grid of this size, with the faces centered in it
+	pub grid_cell_size: Option<f64>,
+	pub uv_overflow: UvOverflow,
+	/// Warns about the triangle meshes that aren't closed
+	pub check_manifold: bool,
+	/// Closes the holes with up to this many edges
+	pub fill_holes: Option<usize>,
+	/// Only closes the holes up to this long around
+	pub max_hole_perimeter: Option<f64>,
+	/// Drops the triangles at the same positions as an earlier one
+	pub dedup_faces: bool,
+	/// Drops the connected pieces with fewer triangles or less area, like the debris of scans
+	pub remove_small_components: Option<manifold::ComponentThreshold>,
+	/// Rewinds the triangles facing the other way from their neighbors, and with `Outward` the pieces facing inward
+	pub fix_winding: Option<manifold::WindingFix>,
+	/// Finds the triangles facing into the mesh, by raycasts from their centers, and lists or flips them
+	pub inverted_faces: Option<manifold::InvertedFaces>,
+	/// Remeshes the triangles to edges of about the same length, dropping the texture coordinates
+	pub remesh: Option<RemeshSettings>,
+	/// Triangulates again the regions of triangles facing within this many degrees of each other, with fewer
+	pub merge_coplanar: Option<f64>,
+	/// Stores only the half on one side of a symmetry plane of the mesh, when it has one
+	pub symmetry: bool,
+	/// With `symmetry`, also stores the indices of the mirrored half, wound the other way
+	pub mirrored_indices: bool,
+	/// Returns a table of where each vertex comes from in the obj file with each part
+	pub emit_remap: bool,
+	/// Returns how far the stored attributes are from the computed ones with each mesh
+	pub measure_quantization: bool,
+	/// How the vertices and indices are stored
+	pub encoding: Encoding,
+	/// The attributes stored as indices into a palette of their values, among `Palette::ATTRIBUTES`
+	pub palette: Vec<Attribute>,
+	/// Stores the positions as 16 bit unorms, and in the header the matrix taking them back into the bounds
+	pub quantize_positions: bool,
+	/// Also stores the triangles indexing a single vertex per position, for shadow and depth passes
+	pub shadow_indices: bool,
+	/// With the progressive encoding, the fraction of the triangles kept in the base mesh; 0 collapses as many as
+	/// it can
+	pub progressive_base: f64,
+	/// Stores an oriented bounding box, which culls long thin objects better
+	pub obb: bool,
+	/// Stores a k-DOP with this many sides: 14, 18 or 26
+	pub kdop: Option<u8>,
+	/// Stores a BVH over the triangles, for runtime raycasts
+	pub bake_bvh: bool,
+	/// Writes an older version of the format, for runtimes that can't read the latest one
+	pub format_version: Option<u32>,
+	/// Moves the positions of each object relative to this point of its bounds, recorded in the metadata
+	pub pivot: Option<Pivot>,
+	/// Skips the objects left without elements by this filter, which must be the one the obj was parsed with
+	pub filter: NameFilter,
+	/// Converts all the objects as one mesh, welding the vertices they have in common
+	pub merge_objects: bool,
+	/// Converts the objects repeating the geometry of another one, up to a rotation and a translation, as a list of
+	/// instances of it. Objects with morph targets are never merged
+	pub detect_instances: bool,
+	/// Stores the objects as the cells of an interior, with the portals between the openings of two cells whose
+	/// centers are at most this far apart
+	pub portal_gap: Option<f64>,
+	/// The materials of the mtl files of the obj, stored as a material table for the ones the mesh uses
+	pub materials: Option<MaterialLibrary>,
+	pub texture_paths: TexturePaths,
+	/// The content of the texture files by their path in the mtl files, stored in the mesh instead of the paths
+	pub embedded_textures: Vec<(String, Vec<u8>)>,
+	/// Multiplies the vertex colors, or white, by the diffuse color of the material of each face
+	pub bake_material_color: bool,
+	/// Adds metallic-roughness values converted from the mtl ones to the material table
+	pub pbr: bool,
+	/// Leaves the material table out, for a material library written separately; the submeshes keep the names
+	pub external_materials: bool,
+	/// Key-value pairs added to the converter version and the options in the metadata, like the source file
+	pub metadata: Vec<(String, String)>,
+}
+
+impl ConvertOptions {
+	/// The settings that affect the mesh and differ from the defaults, as `key=value` pairs separated by spaces, for
+	/// the metadata. The keys are the names of the fields, and the values are written as on the command line, so that
+	/// the description doesn't change with the types of the converter. The inputs, like the vertex colors, the skin and
+	/// the materials, are left out
+	pub fn describe(&self) -> String {
+		let flag = |set: bool| set.then(|| "true".to_owned());
+		let number = |value: Option<f64>| value.map(|value| value.to_string());
+		let count = |value: Option<usize>| value.map(|value| value.to_string());
+		let list = |names: Vec<&str>| (!names.is_empty()).then(|| names.join(","));
+		let pairs = [
+			("generate_tangents", flag(self.generate_tangents)),
+			("emit_bitangent", flag(self.emit_bitangent)),
+			("color_space", (self.color_space != ColorSpace::default()).then(|| self.color_space.name().to_owned())),
+			("morph_targets", list(self.morph_targets.iter().map(|t| t.name.as_str()).collect())),
+			("recompute_normals", self.recompute_normals.map(|weighting| weighting.name().to_owned())),
+			("crease_angle", number(self.crease_angle)),
+			("flat", flag(self.flat)),
+			("smooth_seams", self.smooth_seams.map(|smoothing| smoothing.name().to_owned())),
+			("stale_normals", (self.stale_normals != StaleNormals::default()).then(|| self.stale_normals.name().to_owned())),
+			("curvature", self.curvature.map(|curvature| curvature.name().to_owned())),
+			("lightmap.resolution", self.lightmap.map(|lightmap| lightmap.resolution.to_string())),
+			("lightmap.padding", self.lightmap.map(|lightmap| lightmap.padding.to_string())),
+			("gen_uvs", self.gen_uvs.map(|projection| projection.name().to_owned())),
+			("material_ids", flag(self.material_ids)),
+			("user_attributes", self.user_attributes.as_ref().and_then(|user| list(user.attributes.iter().map(|a| a.name.as_str()).collect()))),
+			("fill_missing", flag(self.fill_missing)),
+			("required", list(self.required.iter().map(|a| a.name()).collect())),
+			("repair", flag(self.repair)),
+			("renormalize", flag(self.renormalize)),
+			("normal_tolerance", number(self.normal_tolerance)),
+			("max_vertices", count(self.max_vertices)),
+			("split_components", flag(self.split_components)),
+			("split_by_material", flag(self.split_by_material)),
+			("sort_by_material", flag(self.sort_by_material)),
+			("grid_cell_size", number(self.grid_cell_size)),
+			("uv_overflow", (self.uv_overflow != UvOverflow::default()).then(|| self.uv_overflow.name().to_owned())),
+			("check_manifold", flag(self.check_manifold)),
+			("fill_holes", count(self.fill_holes)),
+			("max_hole_perimeter", number(self.max_hole_perimeter)),
+			("dedup_faces", flag(self.dedup_faces)),
+			("remove_small_components", self.remove_small_components.map(|threshold| threshold.name())),
+			("fix_winding", self.fix_winding.map(|fix| fix.name().to_owned())),
+			("inverted_faces", self.inverted_faces.map(|action| action.name().to_owned())),
+			("remesh.edge_length", number(self.remesh.map(|remesh| remesh.edge_length))),
+			("remesh.iterations", count(self.remesh.map(|remesh| remesh.iterations))),
+			("merge_coplanar", number(self.merge_coplanar)),
+			("symmetry", flag(self.symmetry)),
+			("mirrored_indices", flag(self.mirrored_indices)),
+			("encoding", (self.encoding != Encoding::default()).then(|| self.encoding.name().to_owned())),
+			("palette", list(self.palette.iter().map(|a| a.name()).collect())),
+			("quantize_positions", flag(self.quantize_positions)),
+			("shadow_indices", flag(self.shadow_indices)),
+			("progressive_base", (self.encoding == Encoding::Progressive).then(|| self.progressive_base.to_string())),
+			("obb", flag(self.obb)),
+			("kdop", self.kdop.map(|k| k.to_string())),
+			("bake_bvh", flag(self.bake_bvh)),
+			("pivot", self.pivot.map(|pivot| pivot.name().to_owned())),
+			("filter.only", list(self.filter.only.iter().map(String::as_str).collect())),
+			("filter.exclude", list(self.filter.exclude.iter().map(String::as_str).collect())),
+			("merge_objects", flag(self.merge_objects)),
+			("detect_instances", flag(self.detect_instances)),
+			("portal_gap", number(self.portal_gap)),
+			("texture_paths.strip_directories", flag(self.texture_paths.strip_directories)),
+			("texture_paths.prefix", self.texture_paths.prefix.clone()),
+			("embedded_textures", flag(!self.embedded_textures.is_empty())),
+			("pbr", flag(self.pbr)),
+			("bake_material_color", flag(self.bake_material_color)),
+			("external_materials", flag(self.external_materials)),
+			("format_version", Some(self.format_version.unwrap_or(chunk::VERSION).to_string())),
+		];
+		pairs.iter()
+			.filter_map(|(key, value)| value.as_ref().map(|value| match value.contains([' ', '"', '=']) {
+				//the names of the files and the patterns can have spaces
+				true => format!("{}={:?}", key, value),
+				false => format!("{}={}", key, value),
+			}))
+			.collect::<Vec<String>>()
+			.join(" ")
+	}
+}
+
+pub struct ConvertedMesh {
+	/// The content of a `.mesh` file for each part; there is only one unless `max_vertices`, `split_components`,
+	/// `split_by_material` or `grid_cell_size` split the object. The parts that can't be written are left out, with
+	/// an error in `warnings`
+	pub parts: Vec<Vec<u8>>,
+	/// A name for each part to tell them apart in file names: the grid cell, the material and/or the index
+	/// of the part, like `0_-1_2.wood.1`. Empty when the object isn't split
+	pub part_names: Vec<String>,
+	/// The name of the object
+	pub name: String,
+	/// Where the origin of the mesh is in the obj file, when it was moved to a pivot
+	pub pivot: Option<Vertex>,
+	/// The names and transforms of the objects drawn with this geometry, including itself; empty without
+	/// an instance list
+	pub instances: Vec<(String, Transform)>,
+	/// The remap table of each part with `emit_remap`, as CSV; none for the generated meshes
+	pub remaps: Vec<String>,
+	/// The errors of the vertex formats in all the parts, with `measure_quantization`
+	pub quantization: Option<QuantizationReport>,
+	pub warnings: Vec<Warning>,
+}
+
+/// Parses the content of an obj file, patching files that don't contain an object name
+/// and resolving relative indices
+pub fn parse_obj(content: String) -> Result<ObjSet, ParseError> {
+	parse_obj_filtered(content, &NameFilter::default())
+}
+
+/// Parses the content of an obj file, keeping only the elements of the objects and groups that pass `filter`.
+/// The objects left without elements are still in the set, with their vertices
+pub fn parse_obj_filtered(content: String, filter: &NameFilter) -> Result<ObjSet, ParseError> {
+	let preparsed = preparse::resolve_indices(&content, filter)?;
+	let mut content = preparsed.content;
+
+	let unnamed = !content.starts_with('o') && !content.contains("\no");
+	//after the material library, which has to come first
+	let library_lines = if content.starts_with("mtllib") { 1 } else { 0 };
+	if unnamed {
+		let mut at = 0;
+		if library_lines > 0 {
+			at = content.find('\n').map_or(content.len(), |i| i + 1);
+			if at == content.len() && !content.ends_with('\n') {
+				content.push('\n');
+				at += 1;
+			}
+		}
+		content.insert_str(at, "o unnamed_object \n");
+	}
+
+	wavefront_obj::obj::parse(content).map_err(|mut err| {
+		//point at the line of the original file
+		let line = if unnamed && err.line_number > library_lines { err.line_number - 1 } else { err.line_number };
+		if let Some(&original) = preparsed.line_numbers.get(line.wrapping_sub(1)) {
+			err.line_number = original;
+		}
+		err
+	})
+}
+
+/// Converts a single object; when skinning it, `options.skin` must start at the first position of the object,
+/// and the morph targets are taken from the first object of each target
+pub fn convert_obj(obj: Object, options: &ConvertOptions) -> ConvertedMesh {
+	let sources = [SourceObject { name: &obj.name, first: [0; 3], first_primitive: 0 }];
+	convert_obj_at(&obj, options, 0, &sources, &[], &[])
+}
+
+/// An object of the obj file, to tell in the remap tables where the vertices come from
+struct SourceObject<'a> {
+	name: &'a str,
+	/// The index of its first position, texture coordinate and normal in the file
+	first: [usize; 3],
+	/// The index of its first primitive among the ones of the objects converted together
+	first_primitive: usize,
+}
+
+/// `instances` are the names and transforms of the objects sharing the geometry of `obj`, when there are others,
+/// `cells` every cell of the set, of which the object stores its own, or all of them when merging, and `sources`
+/// the objects of the file it's made of, none when it's generated
+fn convert_obj_at(obj: &Object, options: &ConvertOptions, object_index: usize, sources: &[SourceObject],
+	instances: &[(String, Transform)], cells: &[Cell]) -> ConvertedMesh {
+	let mut warnings = vec![];
+	let source = obj;
+
+	let repaired;
+	let obj = match sanitize::sanitize(obj, options.repair, options.renormalize, options.normal_tolerance.unwrap_or(sanitize::NORMAL_TOLERANCE), &mut warnings) {
+		Some(result) => {
+			repaired = result;
+			&repaired
+		},
+		None => obj,
+	};
+
+	//the repeated triangles would make every edge of theirs non-manifold
+	let deduplicated;
+	let obj = if options.dedup_faces && mesh::object_topology(obj) == Topology::TriangleList {
+		let (result, removed) = manifold::remove_duplicate_triangles(obj);
+		if removed > 0 {
+			warnings.push(Warning::new(WarningKind::NonManifold, format!("{}: removed {} duplicate triangles", obj.name, removed)));
+		}
+		deduplicated = result;
+		&deduplicated
+	}
+	else {
+		obj
+	};
+
+	let cleaned;
+	let obj = match options.remove_small_components {
+		Some(threshold) if mesh::object_topology(obj) == Topology::TriangleList => {
+			let (result, removed) = manifold::remove_small_components(obj, threshold);
+			if removed > 0 {
+				warnings.push(Warning::new(WarningKind::NonManifold, format!("{}: removed {} small disconnected pieces", obj.name, removed)));
+			}
+			cleaned = result;
+			&cleaned
+		},
+		_ => obj,
+	};
+
+	//before the holes are found, which the badly wound triangles look like
+	let rewound;
+	let obj = match options.fix_winding {
+		Some(fix) if mesh::object_topology(obj) == Topology::TriangleList => {
+			let (result, flipped) = manifold::fix_winding(obj, fix);
+			rewound = if flipped > 0 {
+				warnings.push(Warning::new(WarningKind::NonManifold, format!("{}: flipped {} triangles", obj.name, flipped)));
+				refresh_normals(&result, options, "rewinding", &mut warnings).unwrap_or(result)
+			}
+			else {
+				result
+			};
+			&rewound
+		},
+		_ => obj,
+	};
+
+	let closed;
+	let obj = match options.fill_holes {
+		Some(max_edges) if mesh::object_topology(obj) == Topology::TriangleList => {
+			let (result, filled) = manifold::fill_holes(obj, max_edges, options.max_hole_perimeter);
+			if filled > 0 {
+				warnings.push(Warning::new(WarningKind::NonManifold, format!("{}: filled {} holes", obj.name, filled)));
+			}
+			closed = result;
+			&closed
+		},
+		_ => obj,
+	};
+
+	//once the holes are closed, as the rays escape through them
+	let reoriented;
+	let obj = match options.inverted_faces {
+		Some(action) if mesh::object_topology(obj) == Topology::TriangleList => {
+			let inverted = manifold::inverted_faces(obj);
+			match action {
+				_ if inverted.is_empty() => obj,
+				manifold::InvertedFaces::Warn => {
+					for &t in &inverted {
+						warnings.push(Warning::new(WarningKind::NonManifold, format!("{}: triangle {} faces into the mesh", obj.name, t)).at_face(t));
+					}
+					obj
+				},
+				manifold::InvertedFaces::Flip => {
+					warnings.push(Warning::new(WarningKind::NonManifold,
+						format!("{}: flipped {} triangles facing into the mesh", obj.name, inverted.len())));
+					let result = manifold::flip_triangles(obj, &inverted);
+					reoriented = refresh_normals(&result, options, "rewinding", &mut warnings).unwrap_or(result);
+					&reoriented
+				},
+			}
+		},
+		_ => obj,
+	};
+
+	//the attributes stored by position wouldn't follow the new positions
+	let remeshed;
+	let obj = match options.remesh {
+		Some(settings) if mesh::object_topology(obj) == Topology::TriangleList => {
+			if options.skin.is_some() || options.user_attributes.is_some() || options.vertex_colors.is_some() || !options.morph_targets.is_empty() {
+				warnings.push(Warning::new(WarningKind::Unsupported,
+					format!("{}: not remeshed, as the skin, vertex colors, user attributes and morph targets are stored by position", obj.name)));
+				obj
+			}
+			else {
+				if !obj.tex_vertices.is_empty() {
+					warnings.push(Warning::new(WarningKind::Unsupported, format!("{}: the texture coordinates are dropped by the remeshing", obj.name)));
+				}
+				let result = remesh::remesh(obj, &settings);
+				let regenerated = options.flat || options.recompute_normals.is_some() || options.crease_angle.is_some();
+				remeshed = if !obj.normals.is_empty() && !regenerated {
+					normals::recompute_normals(&result, NormalWeighting::Area, None)
+				}
+				else {
+					result
+				};
+				&remeshed
+			}
+		},
+		_ => obj,
+	};
+
+	let merged;
+	let obj = match options.merge_coplanar {
+		Some(angle) if mesh::object_topology(obj) == Topology::TriangleList => {
+			merged = planar::merge_coplanar(obj, angle).0;
+			&merged
+		},
+		_ => obj,
+	};
+
+	if (options.check_manifold || options.fill_holes.is_some()) && mesh::object_topology(obj) == Topology::TriangleList {
+		warn_manifold(obj, &mut warnings);
+	}
+
+	let generated;
+	let obj = match options.gen_uvs {
+		Some(projection) if obj.tex_vertices.is_empty() && mesh::object_topology(obj) == Topology::TriangleList => {
+			generated = uvgen::generate(obj, projection);
+			&generated
+		},
+		_ => obj,
+	};
+
+	let recomputed;
+	let obj = if options.flat && mesh::object_topology(obj) == Topology::TriangleList {
+		recomputed = normals::flat_normals(obj);
+		&recomputed
+	}
+	else if (options.recompute_normals.is_some() || options.crease_angle.is_some()) && mesh::object_topology(obj) == Topology::TriangleList {
+		recomputed = normals::recompute_normals(obj, options.recompute_normals.unwrap_or(NormalWeighting::Area), options.crease_angle);
+		&recomputed
+	}
+	else {
+		obj
+	};
+
+	//tangents are generated from the normals
+	let tangents = (options.generate_tangents || options.emit_bitangent) && mesh::object_topology(obj) == Topology::TriangleList;
+	let (primitives, missing_normals, missing_tex) = fill::count_missing(obj);
+	let partial = |missing| missing > 0 && missing < primitives;
+	let fill_normals = (tangents && missing_normals > 0) || (options.fill_missing && partial(missing_normals));
+	let fill_tex = options.fill_missing && partial(missing_tex);
+
+	let filled;
+	let obj = if fill_normals || fill_tex {
+		filled = fill::fill_missing(obj, fill_normals, fill_tex, &mut warnings);
+		&filled
+	}
+	else {
+		obj
+	};
+	fill::warn_dropped(obj, &mut warnings);
+
+	//build a VTNIndex => Vertex map and build actual vertices
+	let mut mesh = Mesh::from_object(obj, options, sources.first().map_or(0, |s| s.first[0]));
+	warnings.append(&mut mesh.warnings);
+	if !mesh.vertices.is_empty() {
+		check_required(obj, &mesh, &options.required);
+	}
+
+	for target in &options.morph_targets {
+		match target.set.objects.get(object_index) {
+			Some(target_obj) => mesh.add_morph_target(&target.name, obj, target_obj),
+			None => panic!("Morph target {} has no object {}", target.name, obj.name),
+		}
+	}
+
+	if options.merge_objects {
+		mesh.weld();
+	}
+	if options.sort_by_material {
+		mesh.sort_by_material();
+	}
+
+	let pivot = options.pivot.filter(|_| !mesh.vertices.is_empty()).map(|pivot| {
+		let mut point = mul(Vertex { x: mesh.min.x + mesh.max.x, y: mesh.min.y + mesh.max.y, z: mesh.min.z + mesh.max.z }, 0.5);
+		if pivot == Pivot::Bottom {
+			point.y = mesh.min.y;
+		}
+		point
+	});
+	//the instances place the geometry relative to its pivot
+	let instances: Vec<(String, Transform)> = instances.iter().map(|(name, transform)| {
+		let mut transform = *transform;
+		if let Some(pivot) = pivot {
+			let offset = transform.rotate(pivot);
+			addmut(&mut transform.translation, offset);
+		}
+		(name.clone(), transform)
+	}).collect();
+
+	let object = ObjectData {
+		name: &obj.name,
+		measurements: if mesh.topology == Topology::TriangleList { Some(measure::measure(obj)) } else { None },
+		instances: &instances,
+		cells,
+		stored_cells: (0..cells.len()).filter(|&c| options.merge_objects || cells[c].object == object_index).collect(),
+		pivot,
+	};
+
+	let size = lenght(sub(mesh.max, mesh.min));
+
+	//each split adds to the names of the parts
+	let mut pieces = vec![(String::new(), mesh)];
+	if options.split_components {
+		pieces = pieces.into_iter().flat_map(|(name, mesh)| {
+			mesh.split_by_component().into_iter().enumerate()
+				.map(|(i, part)| (join_names(&name, &format!("component{}", i)), part))
+				.collect::<Vec<_>>()
+		}).collect();
+	}
+	if let Some(cell_size) = options.grid_cell_size {
+		pieces = pieces.into_iter().flat_map(|(name, mesh)| {
+			mesh.split_by_cell(cell_size).into_iter()
+				.map(|(cell, part)| (join_names(&name, &format!("{}_{}_{}", cell[0], cell[1], cell[2])), part))
+				.collect::<Vec<_>>()
+		}).collect();
+	}
+	if options.split_by_material {
+		pieces = pieces.into_iter().flat_map(|(name, mesh)| {
+			mesh.split_by_material().into_iter().map(|part| {
+				let material = part.submeshes[0].material.as_deref().map_or("no_material".to_owned(), file_name);
+				(join_names(&name, &material), part)
+			}).collect::<Vec<_>>()
+		}).collect();
+	}
+
+	let max_vertices = options.max_vertices.unwrap_or(u32::MAX as usize);
+	//the generated objects don't come from the file
+	let remap_columns = if options.emit_remap && !sources.is_empty() { Some(RemapColumns::new(source, obj)) } else { None };
+	let mut parts = vec![];
+	let mut part_names = vec![];
+	let mut remaps = vec![];
+	let mut quantization = options.measure_quantization.then(QuantizationReport::default);
+	let mut rounding = 0.0;
+	for (name, mut mesh) in pieces {
+		//after the split, which can depend on where the positions are
+		if let Some(pivot) = pivot {
+			mesh.translate(mul(pivot, -1.0));
+		}
+		rounding = flt_max(rounding, mesh.f32_rounding());
+		let mut mirror = None;
+		if options.symmetry {
+			match mesh.mirror_half() {
+				Some((plane, half)) => {
+					mirror = Some(plane);
+					mesh = half;
+				},
+				None => {
+					let part = if name.is_empty() { obj.name.clone() } else { join_names(&obj.name, &name) };
+					warnings.push(Warning::new(WarningKind::Unsupported, format!("{}: no symmetry plane found, the whole mesh is stored", part)));
+				},
+			}
+		}
+		let written = if mesh.vertices.len() > max_vertices {
+			mesh.split(max_vertices).into_iter().enumerate().map(|(i, part)| (join_names(&name, &i.to_string()), part)).collect()
+		}
+		else {
+			vec![(name, mesh)]
+		};
+		for (name, part) in written {
+			let label = if name.is_empty() { obj.name.clone() } else { join_names(&obj.name, &name) };
+			//the base and the splits are of the whole part, which they reorder
+			let (part, refinement) = if options.encoding == Encoding::Progressive {
+				//the triangles of the splits come after the base, so the submeshes wouldn't be ranges of them
+				if part.submeshes.len() > 1 {
+					warnings.push(Warning::new(WarningKind::Unsupported, format!(
+						"{} has {} materials, a progressive mesh can only have one: --split-by material writes a mesh for each",
+						label, part.submeshes.len()
+					)).as_error());
+					continue;
+				}
+				let (part, refinement) = part.progressive(options.progressive_base);
+				(part, Some(refinement))
+			}
+			else {
+				(part, None)
+			};
+			parts.push(write_mesh(&object, &part, refinement.as_ref(), mirror, options));
+			if let Some(quantization) = &mut quantization {
+				measure_quantization(quantization, &label, &part, options);
+			}
+			part_names.push(name);
+			if let Some(columns) = &remap_columns {
+				remaps.push(remap_table(&part, sources, columns));
+			}
+		}
+	}
+
+	//a ten thousandth of the size of the object
+	if size > 0.0 && rounding > size * 1e-4 {
+		warnings.push(Warning::new(WarningKind::PrecisionLoss,
+			format!("{}: the positions move by up to {} as 32 bit floats, as they are far from the origin; --pivot stores them relative to the object", obj.name, rounding)));
+	}
+
+	ConvertedMesh {
+		parts,
+		part_names,
+		name: obj.name.clone(),
+		pivot,
+		instances,
+		remaps,
+		quantization,
+		warnings,
+	}
+}
+
+fn measure_quantization(report: &mut QuantizationReport, part: &str, mesh: &Mesh, options: &ConvertOptions) {
+	let dequantization = options.quantize_positions.then(|| position_dequantization(mesh));
+	for (i, v) in mesh.vertices.iter().enumerate() {
+		for (attribute, error) in v.quantization_errors(mesh.format.uv_format, dequantization.as_ref()) {
+			report.add(part, i, attribute, error);
+		}
+	}
+}
+
+/// Which columns of the remap tables still number the elements of the file after the passes
+struct RemapColumns {
+	faces: bool,
+	/// The positions, texture coordinates and normals
+	lists: [bool; 3],
+}
+
+impl RemapColumns {
+	fn new(source: &Object, obj: &Object) -> Self {
+		let bits = |list: &mut dyn Iterator<Item = [f64; 3]>| -> Vec<[u64; 3]> { list.map(|c| c.map(f64::to_bits)).collect() };
+		let lists = |obj: &Object| [
+			bits(&mut obj.vertices.iter().map(|v| [v.x, v.y, v.z])),
+			bits(&mut obj.tex_vertices.iter().map(|t| [t.x, t.y, 0.0])),
+			bits(&mut obj.normals.iter().map(|n| [n.x, n.y, n.z])),
+		];
+		let (before, after) = (lists(source), lists(obj));
+		//the passes adding texture coordinates or normals to the faces keep them
+		let positions = |obj: &Object| -> Vec<Vec<usize>> {
+			obj.geometry.iter().flat_map(|geo| &geo.shapes).map(|shape| match *shape {
+				Shape::Point(a) => vec![a.0],
+				Shape::Line(a, b) => vec![a.0, b.0],
+				Shape::Triangle(a, b, c) => vec![a.0, b.0, c.0],
+			}).collect()
+		};
+		RemapColumns {
+			faces: positions(source) == positions(obj),
+			lists: [0, 1, 2].map(|i| before[i] == after[i]),
+		}
+	}
+}
+
+/// The remap table of a part: for each vertex, the object and face it comes from, counting the faces from 0 like
+/// the warnings, and its v, vt and vn counting from 1 like the obj file. What the passes replaced is left empty
+fn remap_table(mesh: &Mesh, sources: &[SourceObject], columns: &RemapColumns) -> String {
+	//the indices in `obj` start at the first object
+	let (first, first_primitive) = (sources[0].first, sources[0].first_primitive);
+	let mut table = "vertex,object,face,v,vt,vn\n".to_owned();
+	for (i, v) in mesh.vertices.iter().enumerate() {
+		let origin = v.origin();
+		let primitive = first_primitive + origin.primitive;
+		let position = first[0] + origin.vtn.0;
+		let source = if columns.faces {
+			sources.iter().rev().find(|s| s.first_primitive <= primitive)
+		}
+		else if columns.lists[0] {
+			sources.iter().rev().find(|s| s.first[0] <= position)
+		}
+		else {
+			sources.first().filter(|_| sources.len() == 1)
+		};
+		let field = |known: bool, value: Option<usize>| value.filter(|_| known).map_or(String::new(), |value| value.to_string());
+		let name = source.map_or(String::new(), |s| {
+			if s.name.contains([',', '"']) { format!("\"{}\"", s.name.replace('"', "\"\"")) } else { s.name.to_owned() }
+		});
+		table += &format!("{},{},{},{},{},{}\n", i, name,
+			field(columns.faces && source.is_some(), source.map(|s| primitive - s.first_primitive)),
+			field(columns.lists[0], Some(position + 1)),
+			field(columns.lists[1], origin.vtn.1.map(|vt| first[1] + vt + 1)),
+			field(columns.lists[2], origin.vtn.2.map(|vn| first[2] + vn + 1)));
+	}
+	table
+}
+
+fn join_names(a: &str, b: &str) -> String {
+	if a.is_empty() { b.to_owned() } else { format!("{}.{}", a, b) }
+}
+
+/// Keeps the characters that are safe in file names
+pub(crate) fn file_name(name: &str) -> String {
+	name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+fn warn_manifold(obj: &Object, warnings: &mut Vec<Warning>) {
+	let report = manifold::analyze(obj);
+	if report.non_manifold_edges > 0 {
+		warnings.push(Warning::new(WarningKind::NonManifold,
+			format!("{}: {} edges are shared by more than two triangles", obj.name, report.non_manifold_edges)));
+	}
+	if report.non_manifold_vertices > 0 {
+		warnings.push(Warning::new(WarningKind::NonManifold,
+			format!("{}: {} vertices join separate fans of triangles", obj.name, report.non_manifold_vertices)));
+	}
+	if !report.boundary_loops.is_empty() {
+		let edges: usize = report.boundary_loops.iter().sum();
+		warnings.push(Warning::new(WarningKind::NonManifold,
+			format!("{}: {} open borders with {} edges in total", obj.name, report.boundary_loops.len(), edges)));
+	}
+}
+
+/// What the parts of an object share
+struct ObjectData<'a> {
+	name: &'a str,
+	/// Of the whole object, even when it's split
+	measurements: Option<Measurements>,
+	instances: &'a [(String, Transform)],
+	/// Every cell of the set, for the names of the other sides of the portals, and the indices of the stored ones
+	cells: &'a [Cell],
+	stored_cells: Vec<usize>,
+	/// Subtracted from the positions
+	pivot: Option<Vertex>,
+}
+
+/// Of the positions of a part quantized in its bounds
+fn position_dequantization(mesh: &Mesh) -> Dequantization {
+	Dequantization::from_bounds([mesh.min.x as f32, mesh.min.y as f32, mesh.min.z as f32], [mesh.max.x as f32, mesh.max.y as f32, mesh.max.z as f32])
+}
+
+fn write_mesh(object: &ObjectData, mesh: &Mesh, refinement: Option<&Refinement>, mirror: Option<MirrorPlane>, options: &ConvertOptions) -> Vec<u8> {
+	//the counts are stored as u32
+	if mesh.vertices.len() > u32::MAX as usize || mesh.indices.len() > u32::MAX as usize {
+		panic!("{} has {} vertices and {} indices, more than a .mesh file can store: --max-vertices splits it",
+			object.name, mesh.vertices.len(), mesh.indices.len());
+	}
+
+	let mut header = vec![];
+
+	//write the index size in bytes
+	let index_size = mesh.get_index_size() as u8;
+	header.write_u8(index_size).unwrap();
+
+	header.write_u8(mesh.topology.to_u8()).unwrap();
+
+	//write the vertex fields
+	header.write_u8(0).unwrap();  //Position2D
+	header.write_u8( if options.quantize_positions { 2 } else { 1 } ).unwrap();	//Position3D, 2 when quantized
+	header.write_u8( if mesh.format.color.is_some() { options.color_space.to_u8() } else { 0 } ).unwrap();	//Color
+	header.write_u8( if mesh.format.normal.is_some() { 1 } else { 0 } ).unwrap(); //Normal
+	header.write_u8( if mesh.format.tangent.is_some() { 1 } else { 0 } ).unwrap();	//Tangent
+	//2 for Float32x2 UVs
+	let uv_flag = if mesh.format.uv_format == Format::Float32x2 { 2 } else { 1 };
+	header.write_u8( if mesh.format.tex0.is_some() { uv_flag } else { 0 } ).unwrap();  //Tex0
+	header.write_u8( if mesh.format.tex1.is_some() { uv_flag } else { 0 } ).unwrap();	//Tex1
+	header.write_u8( if mesh.format.bone_indices.is_some() { 1 } else { 0 } ).unwrap();	//Skin
+	header.write_u8( if mesh.morphs.is_empty() { 0 } else { 1 } ).unwrap();	//Morph
+	header.write_u8( if mesh.format.curvature.is_some() { 1 } else { 0 } ).unwrap();	//Curvature
+	header.write_u8( if mesh.format.material_id.is_some() { 1 } else { 0 } ).unwrap();	//MaterialId
+	header.write_u8( if mesh.format.bitangent.is_some() { 1 } else { 0 } ).unwrap();	//Bitangent
+
+	//user attributes, with their format and name
+	let user_attributes = options.user_attributes.as_ref().map_or(&[][..], |user| &user.attributes[..]);
+	header.write_u8(user_attributes.len() as u8).unwrap();
+	for attr in user_attributes {
+		header.write_u8(attr.format.to_u8()).unwrap();
+		header.write_u8(attr.name.len() as u8).unwrap();
+		header.extend_from_slice(attr.name.as_bytes());
+	}
+
+	header.write_f32::<LittleEndian>(mesh.max.x as f32).unwrap();
+	header.write_f32::<LittleEndian>(mesh.max.y as f32).unwrap();
+	header.write_f32::<LittleEndian>(mesh.max.z as f32).unwrap();
+
+	header.write_f32::<LittleEndian>(mesh.min.x as f32).unwrap();
+	header.write_f32::<LittleEndian>(mesh.min.y as f32).unwrap();
+	header.write_f32::<LittleEndian>(mesh.min.z as f32).unwrap();
+
+	header.write_u32::<LittleEndian>(mesh.vertices.len() as u32).unwrap();
+	header.write_u32::<LittleEndian>(mesh.indices.len() as u32).unwrap();
+
+	let mut vertices = vec![];
+	for v in &mesh.vertices {
+		v.write_to(&mut vertices, mesh.format.uv_format);
+	}
+	//the indices replace the values in the vertices
+	let mut format = mesh.format;
+	let mut palette_tables = vec![];
+	if !options.palette.is_empty() {
+		(format, vertices, palette_tables) = palette::build(&vertices, &mesh.format, user_attributes, &options.palette);
+	}
+	let dequantization = options.quantize_positions.then(|| position_dequantization(mesh));
+	if let Some(dequantization) = &dequantization {
+		let stride = format.layout().with_user_attributes(user_attributes).stride;
+		vertices = dequantization::quantize_positions(&vertices, stride, dequantization);
+		format = format.with_position_format(dequantization::POSITION_FORMAT);
+	}
+
+	let mut indices = vec![];
+	let mut splits = vec![];
+	match refinement {
+		//the rest comes with the splits
+		Some(refinement) => {
+			let stride = format.layout().with_user_attributes(user_attributes).stride;
+			progressive::write_splits(&mut splits, refinement, &vertices, stride, index_size);
+			vertices.truncate(refinement.base_vertices * stride);
+			write_indices(&mut indices, refinement.base_triangles.iter().flatten().copied(), index_size);
+		},
+		None => write_indices(&mut indices, mesh.indices.iter().copied(), index_size),
+	}
+
+	let mut morphs = vec![];
+	if !mesh.morphs.is_empty() {
+		morph::write_targets(&mut morphs, &mesh.morphs, mesh.format.normal.is_some());
+	}
+
+	let version = options.format_version.unwrap_or(chunk::VERSION);
+	let mut file = vec![];
+	match version {
+		//the same data in sequence, without the submeshes, bounding volumes, measurements, BVH, metadata and checksum
+		chunk::LEGACY_VERSION => {
+			if !options.embedded_textures.is_empty() {
+				panic!("Embedded textures need format version {}", chunk::VERSION);
+			}
+			if !object.stored_cells.is_empty() {
+				panic!("The cells and portals of {} need format version {}", object.name, chunk::VERSION);
+			}
+			if object.pivot.is_some() {
+				panic!("The pivot of {} is stored in the metadata, which needs format version {}", object.name, chunk::VERSION);
+			}
+			if mirror.is_some() {
+				panic!("The symmetry plane of {} needs format version {}", object.name, chunk::VERSION);
+			}
+			if options.encoding != Encoding::Raw {
+				panic!("Encoded vertices and indices need format version {}", chunk::VERSION);
+			}
+			if !options.palette.is_empty() {
+				panic!("Palettes need format version {}", chunk::VERSION);
+			}
+			if options.quantize_positions {
+				panic!("Quantized positions need format version {}", chunk::VERSION);
+			}
+			if options.shadow_indices {
+				panic!("Shadow indices need format version {}", chunk::VERSION);
+			}
+			file.extend(header);
+			file.extend(vertices);
+			file.extend(indices);
+			file.extend(morphs);
+		},
+		chunk::VERSION => {
+			header.write_u8(options.encoding.to_u8()).unwrap();
+			header.write_u8(format.palette.to_u8()).unwrap();
+			if let Some(dequantization) = &dequantization {
+				header.extend_from_slice(&dequantization.to_bytes());
+			}
+			let shadow = options.shadow_indices.then(|| {
+				if mesh.topology != Topology::TriangleList {
+					panic!("{} isn't a triangle list, only those have shadow indices", object.name);
+				}
+				if options.encoding == Encoding::Progressive {
+					panic!("The shadow indices of {} would use the vertices of the splits, they can't be stored progressively", object.name);
+				}
+				mesh.shadow_indices()
+			});
+			header.write_u32::<LittleEndian>(shadow.as_ref().map_or(0, |s| s.len() as u32)).unwrap();
+			let indices32: Vec<u32> = mesh.indices.iter().map(|&i| i as u32).collect();
+			match options.encoding {
+				Encoding::Raw => {},
+				Encoding::Meshopt => {
+					let stride = format.layout().with_user_attributes(user_attributes).stride;
+					if stride > codec::MAX_VERTEX_SIZE {
+						panic!("{} has vertices of {} bytes, more than the {} meshopt can encode", object.name, stride, codec::MAX_VERTEX_SIZE);
+					}
+					vertices = codec::encode_vertex_buffer(&vertices, stride);
+					indices = match mesh.topology {
+						Topology::TriangleList => codec::encode_index_buffer(&indices32),
+						_ => codec::encode_index_sequence(&indices32),
+					};
+				},
+				Encoding::Delta => indices = codec::encode_index_deltas(&indices32),
+				Encoding::Progressive => {},
+				Encoding::Blocks => {
+					let layout = format.layout().with_user_attributes(user_attributes);
+					let Some(layout) = BlockLayout::new(&layout) else {
+						panic!("{} has vertices of {} bytes, the block encoding needs a multiple of 4", object.name, layout.stride);
+					};
+					vertices = blocks::encode(&vertices, &layout);
+				},
+			}
+			chunk::write_file_header(&mut file, version);
+			chunk::write_chunk(&mut file, chunk::HEADER, &header);
+			if !format.palette.is_empty() {
+				chunk::write_chunk(&mut file, chunk::PALETTE, &palette_tables);
+			}
+			chunk::write_chunk(&mut file, chunk::VERTICES, &vertices);
+			chunk::write_chunk(&mut file, chunk::INDICES, &indices);
+			if let Some(shadow) = shadow {
+				//encoded like the indices
+				let shadow32: Vec<u32> = shadow.iter().map(|&i| i as u32).collect();
+				let data = match options.encoding {
+					Encoding::Meshopt => codec::encode_index_buffer(&shadow32),
+					Encoding::Delta => codec::encode_index_deltas(&shadow32),
+					_ => {
+						let mut data = vec![];
+						write_indices(&mut data, shadow.into_iter(), index_size);
+						data
+					},
+				};
+				chunk::write_chunk(&mut file, chunk::SHADOW_INDICES, &data);
+			}
+			if !mesh.morphs.is_empty() {
+				chunk::write_chunk(&mut file, chunk::MORPH_TARGETS, &morphs);
+			}
+
+			let mut submeshes = vec![];
+			bounds::write_submeshes(&mut submeshes, mesh);
+			chunk::write_chunk(&mut file, chunk::SUBMESHES, &submeshes);
+
+			if let Some(library) = options.materials.as_ref().filter(|_| !options.external_materials) {
+				//in the order of the submeshes
+				let mut used: Vec<&material::Material> = vec![];
+				for found in mesh.submeshes.iter().filter_map(|s| s.material.as_deref()).filter_map(|name| library.find(name)) {
+					if !used.iter().any(|m| m.name == found.name) {
+						used.push(found);
+					}
+				}
+				if !used.is_empty() {
+					let mut data = vec![];
+					let embedded = material::write_materials(&mut data, &used, &options.texture_paths, &options.embedded_textures, options.pbr);
+					chunk::write_chunk(&mut file, chunk::MATERIALS, &data);
+					for i in embedded {
+						let (ref path, ref content) = options.embedded_textures[i];
+						let mut data = vec![];
+						material::write_texture(&mut data, &options.texture_paths.rewrite(path), content);
+						chunk::write_chunk(&mut file, chunk::TEXTURE, &data);
+					}
+				}
+			}
+
+			let obb = if options.obb && !mesh.vertices.is_empty() { Some(bounds::oriented_box(mesh)) } else { None };
+			let kdop = options.kdop.filter(|_| !mesh.vertices.is_empty()).map(|k| bounds::kdop(mesh, k));
+			if obb.is_some() || kdop.is_some() {
+				let mut volumes = vec![];
+				bounds::write_bounds(&mut volumes, obb.as_ref(), kdop.as_ref());
+				chunk::write_chunk(&mut file, chunk::BOUNDS, &volumes);
+			}
+
+			if let Some(ref measurements) = object.measurements {
+				let mut data = vec![];
+				measure::write_measurements(&mut data, measurements);
+				chunk::write_chunk(&mut file, chunk::MEASUREMENTS, &data);
+			}
+
+			if options.bake_bvh && mesh.topology == Topology::TriangleList {
+				let mut data = vec![];
+				//after the FourCC and the length
+				bvh::write_bvh(&mut data, &bvh::build(mesh), file.len() + 8);
+				chunk::write_chunk(&mut file, chunk::BVH, &data);
+			}
+
+			if !object.instances.is_empty() {
+				let mut data = vec![];
+				write_instances(&mut data, object.instances);
+				chunk::write_chunk(&mut file, chunk::INSTANCES, &data);
+			}
+			if !object.stored_cells.is_empty() {
+				let mut data = vec![];
+				write_cells(&mut data, object);
+				chunk::write_chunk(&mut file, chunk::CELLS, &data);
+			}
+			if let Some(plane) = mirror {
+				let mut data = vec![];
+				data.write_u8(plane.axis as u8).unwrap();
+				data.write_f32::<LittleEndian>(plane.offset as f32).unwrap();
+				data.write_u8(options.mirrored_indices as u8).unwrap();
+				if options.mirrored_indices {
+					let mirrored = mesh.indices.chunks(3).flat_map(|t| [t[0], t[2], t[1]]);
+					write_indices(&mut data, mirrored, index_size);
+				}
+				chunk::write_chunk(&mut file, chunk::MIRROR, &data);
+			}
+
+			let mut metadata = vec![
+				("converter".to_owned(), format!("obj_to_mesh {}", env!("CARGO_PKG_VERSION"))),
+				("options".to_owned(), options.describe()),
+			];
+			if let Some(pivot) = object.pivot {
+				metadata.push(("pivot".to_owned(), format!("{} {} {}", pivot.x, pivot.y, pivot.z)));
+			}
+			metadata.extend(options.metadata.iter().cloned());
+			let mut data = vec![];
+			write_metadata(&mut data, &metadata);
+			chunk::write_chunk(&mut file, chunk::METADATA, &data);
+
+			if refinement.is_some() {
+				chunk::write_chunk(&mut file, chunk::SPLITS, &splits);
+			}
+			chunk::write_checksum(&mut file);
+		},
+		_ => panic!("Unsupported format version {}, the supported ones are {} to {}", version, chunk::LEGACY_VERSION, chunk::VERSION),
+	}
+	file
+}
+
+fn write_indices(data: &mut Vec<u8>, indices: impl Iterator<Item = usize>, index_size: u8) {
+	for idx in indices {
+		match index_size {
+			1 => data.write_u8(idx as u8).unwrap(),
+			2 => data.write_u16::<LittleEndian>(idx as u16).unwrap(),
+			4 => data.write_u32::<LittleEndian>(idx as u32).unwrap(),
+			_ => panic!("Invalid index size"),
+		}
+	}
+}
+
+fn write_metadata(data: &mut Vec<u8>, pairs: &[(String, String)]) {
+	data.write_u16::<LittleEndian>(pairs.len() as u16).unwrap();
+	for (key, value) in pairs {
+		for text in [key, value] {
+			//cut at a character boundary
+			let mut len = usize::min(text.len(), u16::MAX as usize);
+			while !text.is_char_boundary(len) {
+				len -= 1;
+			}
+			data.write_u16::<LittleEndian>(len as u16).unwrap();
+			data.extend_from_slice(&text.as_bytes()[..len]);
+		}
+	}
+}
+
+/// Appends the positions, texture coordinates, normals and faces of all the objects into one, named after the first
+pub(crate) fn merge_objects(objects: &[Object]) -> Object {
+	let mut merged = Object {
+		name: objects.first().map_or("merged".to_owned(), |obj| obj.name.clone()),
+		vertices: vec![],
+		tex_vertices: vec![],
+		normals: vec![],
+		geometry: vec![],
+	};
+	for obj in objects {
+		let (v, t, n) = (merged.vertices.len(), merged.tex_vertices.len(), merged.normals.len());
+		let offset = |(vi, ti, ni): VTNIndex| (vi + v, ti.map(|ti| ti + t), ni.map(|ni| ni + n));
+		for geo in &obj.geometry {
+			let mut geo = geo.clone();
+			for shape in &mut geo.shapes {
+				*shape = match *shape {
+					Shape::Point(a) => Shape::Point(offset(a)),
+					Shape::Line(a, b) => Shape::Line(offset(a), offset(b)),
+					Shape::Triangle(a, b, c) => Shape::Triangle(offset(a), offset(b), offset(c)),
+				};
+			}
+			merged.geometry.push(geo);
+		}
+		merged.vertices.extend_from_slice(&obj.vertices);
+		merged.tex_vertices.extend_from_slice(&obj.tex_vertices);
+		merged.normals.extend_from_slice(&obj.normals);
+	}
+	merged
+}
+
+fn write_instances(data: &mut Vec<u8>, instances: &[(String, Transform)]) {
+	data.write_u32::<LittleEndian>(instances.len() as u32).unwrap();
+	for (name, transform) in instances {
+		write_name(data, name);
+
+		let t = transform.translation;
+		for (row, offset) in transform.rotation.iter().zip([t.x, t.y, t.z]) {
+			for c in [row.x, row.y, row.z, offset] {
+				data.write_f32::<LittleEndian>(c as f32).unwrap();
+			}
+		}
+	}
+}
+
+fn write_name(data: &mut Vec<u8>, name: &str) {
+	let len = usize::min(name.len(), u16::MAX as usize);
+	data.write_u16::<LittleEndian>(len as u16).unwrap();
+	data.extend_from_slice(&name.as_bytes()[..len]);
+}
+
+fn write_vertex(data: &mut Vec<u8>, v: Vertex) {
+	for c in [v.x, v.y, v.z] {
+		data.write_f32::<LittleEndian>(c as f32).unwrap();
+	}
+}
+
+/// In the space of the mesh, after moving it to its pivot
+fn write_cells(data: &mut Vec<u8>, object: &ObjectData) {
+	let origin = object.pivot.unwrap_or(Vertex { x: 0.0, y: 0.0, z: 0.0 });
+
+	data.write_u32::<LittleEndian>(object.stored_cells.len() as u32).unwrap();
+	for &c in &object.stored_cells {
+		let cell = &object.cells[c];
+		write_name(data, &cell.name);
+		write_vertex(data, sub(cell.min, origin));
+		write_vertex(data, sub(cell.max, origin));
+		data.write_u32::<LittleEndian>(cell.portals.len() as u32).unwrap();
+		for portal in &cell.portals {
+			write_name(data, &object.cells[portal.other].name);
+			write_vertex(data, sub(portal.center, origin));
+			write_vertex(data, portal.normal);
+			data.write_u32::<LittleEndian>(portal.corners.len() as u32).unwrap();
+			for &corner in &portal.corners {
+				write_vertex(data, sub(corner, origin));
+			}
+		}
+	}
+}
+
+/// Whether two objects have the same colors, weights and user attributes, which aren't part of the obj objects
+fn same_vertex_data(options: &ConvertOptions, sources: &[SourceObject], count: usize, a: usize, b: usize) -> bool {
+	let (a, b) = (sources[a].first[0], sources[b].first[0]);
+	let colors = options.vertex_colors.as_ref().is_none_or(|c| c.colors.get(a..a + count) == c.colors.get(b..b + count));
+	let skin = options.skin.as_ref().is_none_or(|s| s.vertices.get(a..a + count) == s.vertices.get(b..b + count));
+	let user = options.user_attributes.as_ref().is_none_or(|user| (0..count).all(|i| {
+		let (mut x, mut y) = (vec![], vec![]);
+		user.vertex_bytes(a + i, &mut x);
+		user.vertex_bytes(b + i, &mut y);
+		x == y
+	}));
+	colors && skin && user && options.morph_targets.is_empty()
+}
+
+/// Converts all the objects of the set merged and simplified with `settings`, as the proxy drawn in place of all
+/// of them at a distance. Morph targets are left out, and `members` is recorded in the metadata as the object
+/// names separated by spaces
+pub fn convert_hlod_proxy(set: &ObjSet, options: &ConvertOptions, settings: &simplify::SimplifySettings) -> ConvertedMesh {
+	let mut options = options.clone();
+	options.morph_targets.clear();
+	options.merge_objects = true;
+	let members: Vec<&str> = set.objects.iter()
+		.filter(|obj| obj.geometry.iter().any(|geo| !geo.shapes.is_empty()))
+		.map(|obj| obj.name.as_str())
+		.collect();
+	options.metadata.push(("hlod_members".to_owned(), members.join(" ")));
+
+	let mut proxy = simplify::cluster_vertices(&merge_objects(&set.objects), settings);
+	proxy.name = "hlod".to_owned();
+	let mut warnings = vec![];
+	if let Some(refreshed) = refresh_normals(&proxy, &options, "simplification", &mut warnings) {
+		proxy = refreshed;
+	}
+	let mut converted = convert_obj_at(&proxy, &options, 0, &[], &[], &[]);
+	warnings.append(&mut converted.warnings);
+	converted.warnings = warnings;
+	converted
+}
+
+fn check_required(obj: &Object, mesh: &Mesh, required: &[Attribute]) {
+	if let Some(&attr) = required.iter().find(|&&attr| !mesh.format.has(attr)) {
+		let hint = match attr {
+			Attribute::Normal => "not every face has normals; --recompute-normals generates them, and --fill-missing fills in the faces without",
+			Attribute::Tex0 => "not every face has texture coordinates; --gen-uvs projects them, and --fill-missing fills in the faces without",
+			Attribute::Tangent | Attribute::Bitangent => "it needs texture coordinates on every triangle",
+			Attribute::Color => "there are no vertex colors",
+			_ => "the options don't produce it",
+		};
+		panic!("{}: the required attribute {} can't be produced, as {}", obj.name, attr.name(), hint);
+	}
+}
+
+/// The normals of the file don't match `obj` anymore after `pass`: returns it with smooth normals, unless they
+/// are kept with a warning or the options generate new ones anyway
+fn refresh_normals(obj: &Object, options: &ConvertOptions, pass: &str, warnings: &mut Vec<Warning>) -> Option<Object> {
+	let regenerated = options.flat || options.recompute_normals.is_some() || options.crease_angle.is_some();
+	let has_normals = manifold::triangles(obj).iter().flatten().any(|corner| corner.2.is_some());
+	if regenerated || !has_normals || mesh::object_topology(obj) != Topology::TriangleList {
+		return None;
+	}
+	match options.stale_normals {
+		StaleNormals::Recompute => Some(normals::recompute_normals(obj, NormalWeighting::Area, None)),
+		StaleNormals::Warn => {
+			warnings.push(Warning::new(WarningKind::InvalidData, format!("{}: the normals are stale after the {}", obj.name, pass)));
+			None
+		},
+	}
+}
+
+/// Renders all the objects of the set into an octahedral impostor atlas, and converts the card it's drawn on
+pub fn convert_impostor(set: &ObjSet, options: &ConvertOptions, settings: &ImpostorSettings) -> (ConvertedMesh, ImpostorAtlas) {
+	let atlas = impostor::render_impostor(&merge_objects(&set.objects), options.vertex_colors.as_ref(), settings);
+
+	//only positions, normals and texture coordinates
+	let mut card_options = ConvertOptions {
+		format_version: options.format_version,
+		metadata: options.metadata.clone(),
+		..Default::default()
+	};
+	card_options.metadata.push(("impostor_frames".to_owned(), settings.frames.to_string()));
+	card_options.metadata.push(("impostor_resolution".to_owned(), settings.resolution.to_string()));
+	card_options.metadata.push(("impostor_radius".to_owned(), format!("{:?}", atlas.radius as f32)));
+	let card = convert_obj_at(&impostor::card_object(&atlas), &card_options, 0, &[], &[], &[]);
+	(card, atlas)
+}
+
+pub fn convert_obj_set(set: ObjSet, options: &ConvertOptions) -> Vec<ConvertedMesh> {
+	//the skin weights are indexed across all the objects, and the remap tables number the elements of the file
+	let mut first = [0; 3];
+	let mut first_primitive = 0;
+	let sources: Vec<SourceObject> = set.objects.iter().map(|obj| {
+		let source = SourceObject { name: &obj.name, first, first_primitive };
+		first[0] += obj.vertices.len();
+		first[1] += obj.tex_vertices.len();
+		first[2] += obj.normals.len();
+		first_primitive += obj.geometry.iter().map(|geo| geo.shapes.len()).sum::<usize>();
+		source
+	}).collect();
+	//the ones the filter took every element from
+	let skipped = |obj: &Object| !options.filter.is_empty() && obj.geometry.iter().all(|geo| geo.shapes.is_empty());
+	let cells = match options.portal_gap {
+		Some(_) if options.detect_instances => panic!("Cells and portals can't be stored for instanced objects"),
+		Some(max_gap) => portals::find_cells(&set, max_gap),
+		None => vec![],
+	};
+
+	if options.merge_objects {
+		let mut options = options.clone();
+		for target in &mut options.morph_targets {
+			target.set.objects = vec![merge_objects(&target.set.objects)];
+		}
+		return vec![convert_obj_at(&merge_objects(&set.objects), &options, 0, &sources, &[], &cells)];
+	}
+
+	if !options.detect_instances {
+		return set.objects.iter().enumerate()
+			.filter(|(_, obj)| !skipped(obj))
+			.map(|(i, obj)| convert_obj_at(obj, options, i, &sources[i..i + 1], &[], &cells))
+			.collect();
+	}
+
+	if options.format_version == Some(chunk::LEGACY_VERSION) {
+		panic!("Instance lists need format version {}", chunk::VERSION);
+	}
+	let groups = instances::find_instances(&set, |a, b| {
+		same_vertex_data(options, &sources, set.objects[a].vertices.len(), a, b)
+	});
+	groups.iter().filter(|group| !skipped(&set.objects[group.prototype])).map(|group| {
+		let placements: Vec<(String, Transform)> = if group.instances.len() > 1 {
+			group.instances.iter().map(|i| (set.objects[i.object].name.clone(), i.transform)).collect()
+		}
+		else {
+			vec![]
+		};
+		let i = group.prototype;
+		convert_obj_at(&set.objects[i], options, i, &sources[i..i + 1], &placements, &[])
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn describe_defaults() {
+		assert_eq!(ConvertOptions::default().describe(), format!("format_version={}", chunk::VERSION));
+	}
+
+	#[test]
+	fn describe_names_the_values() {
+		let options = ConvertOptions {
+			generate_tangents: true,
+			color_space: ColorSpace::Linear,
+			vertex_colors: Some(VertexColors::default()),
+			smooth_seams: Some(SeamSmoothing::NormalsAndTangents),
+			required: vec![Attribute::Normal, Attribute::Tex0],
+			remove_small_components: Some(manifold::ComponentThreshold::Area(2.0)),
+			encoding: Encoding::Meshopt,
+			progressive_base: 0.5,
+			filter: NameFilter { only: vec!["door frame".to_owned()], exclude: vec![] },
+			format_version: Some(1),
+			..Default::default()
+		};
+		assert_eq!(options.describe(), "generate_tangents=true color_space=linear smooth_seams=tangents required=normal,tex0 \
+			remove_small_components=2.0 encoding=meshopt filter.only=\"door frame\" format_version=1");
+	}
+}
@@ -0,0 +1,110 @@
+//! Discrete curvature of the triangle mesh, for shaders that need it for edge wear and the like.
+//!
+//! Computed per obj position rather than per output vertex, so that UV and normal seams don't cut the surface.
+
+use wavefront_obj::obj::{Object, Shape, Vertex};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curvature {
+	/// Cotangent Laplacian, positive on convex areas
+	Mean,
+	/// Angle deficit
+	Gaussian,
+}
+
+impl Curvature {
+	pub fn from_name(name: &str) -> Option<Curvature> {
+		match name {
+			"mean" => Some(Curvature::Mean),
+			"gaussian" => Some(Curvature::Gaussian),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Curvature::Mean => "mean",
+			Curvature::Gaussian => "gaussian",
+		}
+	}
+}
+
+fn angle(a: Vertex, b: Vertex) -> f64 {
+	f64::atan2(lenght(cross(a, b)), dot(a, b))
+}
+
+fn cotangent(a: Vertex, b: Vertex) -> f64 {
+	let sin = lenght(cross(a, b));
+	if sin == 0.0 {
+		return 0.0;
+	}
+	dot(a, b) / sin
+}
+
+/// Curvature of each position of `obj`; vertices on open borders get 0
+pub fn compute(obj: &Object, kind: Curvature) -> Vec<f64> {
+	let count = obj.vertices.len();
+	let zero = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+
+	let mut area = vec![0.0; count];
+	let mut angles = vec![0.0; count];
+	let mut laplacian = vec![zero; count];
+	let mut normals = vec![zero; count];
+	let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+
+	for geo in &obj.geometry {
+		for shape in &geo.shapes {
+			let (a, b, c) = match *shape {
+				Shape::Triangle(v1, v2, v3) => (v1.0, v2.0, v3.0),
+				//lines mixed with the faces aren't part of the surface
+				_ => continue,
+			};
+			let corners = [a, b, c];
+
+			let face_normal = cross(sub(obj.vertices[b], obj.vertices[a]), sub(obj.vertices[c], obj.vertices[a]));
+			let face_area = lenght(face_normal) / 2.0;
+
+			for i in 0..3 {
+				let v = corners[i];
+				let next = corners[(i + 1) % 3];
+				let prev = corners[(i + 2) % 3];
+				let to_next = sub(obj.vertices[next], obj.vertices[v]);
+				let to_prev = sub(obj.vertices[prev], obj.vertices[v]);
+
+				area[v] += face_area / 3.0;
+				angles[v] += angle(to_next, to_prev);
+				addmut(&mut normals[v], face_normal);
+
+				//the angle at v is opposite to the edge next-prev
+				let cot = cotangent(to_next, to_prev);
+				let edge = sub(obj.vertices[prev], obj.vertices[next]);
+				addmut(&mut laplacian[next], mul(edge, cot));
+				addmut(&mut laplacian[prev], mul(edge, -cot));
+
+				*edges.entry((usize::min(v, next), usize::max(v, next))).or_insert(0) += 1;
+			}
+		}
+	}
+
+	let mut border = vec![false; count];
+	for (&(a, b), &uses) in &edges {
+		if uses == 1 {
+			border[a] = true;
+			border[b] = true;
+		}
+	}
+
+	(0..count).map(|v| {
+		if border[v] || area[v] == 0.0 {
+			return 0.0;
+		}
+
+		match kind {
+			Curvature::Gaussian => (2.0 * PI - angles[v]) / area[v],
+			Curvature::Mean => -dot(laplacian[v], normalize(normals[v])) / (4.0 * area[v]),
+		}
+	}).collect()
+}
@@ -0,0 +1,81 @@
+//! The quantized positions of `--quantize-positions`: each component stored as a 16 bit unorm, and the header
+//! holding the matrix that takes them back to object space, so that every mesh and part is decoded by the same
+//! shader whatever its bounds.
+
+use alloc::vec::Vec;
+use crate::layout::Format;
+
+/// The format of the quantized positions; w is always 0
+pub const POSITION_FORMAT: Format = Format::Unorm16x4;
+
+/// Takes the positions read as unorms, with 1 as w, to object space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dequantization {
+	/// Column major, as shaders take their matrices
+	pub matrix: [[f32; 4]; 4],
+}
+
+impl Dequantization {
+	/// The scale and offset mapping the unit cube onto the bounds
+	pub fn from_bounds(min: [f32; 3], max: [f32; 3]) -> Dequantization {
+		let mut matrix = [[0.0; 4]; 4];
+		for i in 0..3 {
+			matrix[i][i] = max[i] - min[i];
+			matrix[3][i] = min[i];
+		}
+		matrix[3][3] = 1.0;
+		Dequantization { matrix }
+	}
+
+	/// The scale of each axis and the offset, for the matrices made by `from_bounds`
+	#[cfg(feature = "std")]
+	fn scale_offset(&self) -> ([f32; 3], [f32; 3]) {
+		([self.matrix[0][0], self.matrix[1][1], self.matrix[2][2]], [self.matrix[3][0], self.matrix[3][1], self.matrix[3][2]])
+	}
+
+	/// The unorms of a position inside the bounds of `from_bounds`
+	#[cfg(feature = "std")]
+	pub fn quantize(&self, pos: [f64; 3]) -> [u16; 4] {
+		let (scale, offset) = self.scale_offset();
+		let mut q = [0; 4];
+		for i in 0..3 {
+			if scale[i] > 0.0 {
+				q[i] = ((pos[i] - offset[i] as f64) / scale[i] as f64 * u16::MAX as f64).round().clamp(0.0, u16::MAX as f64) as u16;
+			}
+		}
+		q
+	}
+
+	/// As the shaders compute it
+	pub fn apply(&self, q: [u16; 4]) -> [f32; 3] {
+		let unorm = [q[0] as f32 / u16::MAX as f32, q[1] as f32 / u16::MAX as f32, q[2] as f32 / u16::MAX as f32, 1.0];
+		let mut pos = [0.0; 3];
+		for (i, p) in pos.iter_mut().enumerate() {
+			*p = (0..4).map(|c| self.matrix[c][i] * unorm[c]).sum();
+		}
+		pos
+	}
+
+	pub fn is_finite(&self) -> bool {
+		self.matrix.iter().flatten().all(|v| v.is_finite())
+	}
+
+	/// The 16 floats of the header, column after column
+	pub fn to_bytes(&self) -> Vec<u8> {
+		self.matrix.iter().flatten().flat_map(|v| v.to_le_bytes()).collect()
+	}
+}
+
+/// Replaces the f32 positions at the start of the vertices of `stride` bytes with their unorms
+#[cfg(feature = "std")]
+pub fn quantize_positions(vertices: &[u8], stride: usize, dequantization: &Dequantization) -> Vec<u8> {
+	let mut out = Vec::with_capacity(vertices.len() / stride * (stride - 4));
+	for vertex in vertices.chunks_exact(stride) {
+		let pos = [0, 4, 8].map(|o| f32::from_le_bytes([vertex[o], vertex[o + 1], vertex[o + 2], vertex[o + 3]]) as f64);
+		for c in dequantization.quantize(pos) {
+			out.extend_from_slice(&c.to_le_bytes());
+		}
+		out.extend_from_slice(&vertex[Format::Float32x3.size()..]);
+	}
+	out
+}
@@ -0,0 +1,118 @@
+//! Warnings about the source data, collected during the conversion instead of being printed. Some are errors: the
+//! conversion still returns a mesh, but it's not what the source and the options ask for, and the command line
+//! doesn't write it.
+
+use std::fmt;
+use crate::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+	/// Some faces lack an attribute that others have
+	MissingAttribute,
+	/// Triangles with zero area in UV space, which have no tangent direction
+	DegenerateUvs,
+	/// Non-finite values or broken normals in the source data
+	InvalidData,
+	/// Data or options that don't apply to the object
+	Unsupported,
+	/// Non-manifold edges or vertices, and open borders
+	NonManifold,
+	/// Files referenced by the source that can't be found, like textures
+	MissingFile,
+	/// Materials used by the faces that no mtl file has
+	MissingMaterial,
+	/// Texture coordinates outside of [-1, 1], moved back in
+	UvOverflow,
+	/// Triangles with zero area
+	DegenerateTriangles,
+	/// Values that change noticeably when they are stored, like positions far from the origin as 32 bit floats
+	PrecisionLoss,
+}
+
+impl WarningKind {
+	pub const ALL: [WarningKind; 10] = [
+		WarningKind::MissingAttribute,
+		WarningKind::DegenerateUvs,
+		WarningKind::InvalidData,
+		WarningKind::Unsupported,
+		WarningKind::NonManifold,
+		WarningKind::MissingFile,
+		WarningKind::MissingMaterial,
+		WarningKind::UvOverflow,
+		WarningKind::DegenerateTriangles,
+		WarningKind::PrecisionLoss,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			WarningKind::MissingAttribute => "missing-attribute",
+			WarningKind::DegenerateUvs => "degenerate-uvs",
+			WarningKind::InvalidData => "invalid-data",
+			WarningKind::Unsupported => "unsupported",
+			WarningKind::NonManifold => "non-manifold",
+			WarningKind::MissingFile => "missing-file",
+			WarningKind::MissingMaterial => "missing-material",
+			WarningKind::UvOverflow => "uv-overflow",
+			WarningKind::DegenerateTriangles => "degenerate-tris",
+			WarningKind::PrecisionLoss => "precision-loss",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<WarningKind> {
+		WarningKind::ALL.iter().copied().find(|kind| kind.name() == name)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+	pub kind: WarningKind,
+	pub message: String,
+	/// The triangle the warning is about, counting from 0 in the order of the object, when it's about one
+	pub face: Option<usize>,
+	/// Whatever the warnings denied
+	pub error: bool,
+}
+
+impl Warning {
+	pub fn new(kind: WarningKind, message: String) -> Self {
+		Warning {
+			kind,
+			message,
+			face: None,
+			error: false,
+		}
+	}
+
+	pub fn at_face(mut self, face: usize) -> Self {
+		self.face = Some(face);
+		self
+	}
+
+	pub fn as_error(mut self) -> Self {
+		self.error = true;
+		self
+	}
+
+	/// A line of `--message-format json`, for build systems
+	pub fn to_json(&self, severity: &str, file: &str, object: Option<&str>) -> String {
+		json_line(severity, self.kind.name(), file, object, self.face, &self.message)
+	}
+}
+
+/// A diagnostic as a single line JSON object, with null for the object and face it isn't about
+pub fn json_line(severity: &str, code: &str, file: &str, object: Option<&str>, face: Option<usize>, message: &str) -> String {
+	format!("{{\"severity\": {}, \"code\": {}, \"file\": {}, \"object\": {}, \"face\": {}, \"message\": {}}}",
+		json::string(severity),
+		json::string(code),
+		json::string(file),
+		object.map_or("null".to_owned(), json::string),
+		face.map_or("null".to_owned(), |face| face.to_string()),
+		json::string(message)
+	)
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} [{}]", self.message, self.kind.name())
+	}
+}
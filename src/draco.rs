@@ -0,0 +1,291 @@
+//! Draco files of the converted meshes, for the web viewers that load them with the Draco decoder.
+//!
+//! They use version 2.2 of the bitstream with the sequential method: the triangles are stored uncompressed, and
+//! the positions, normals and texture coordinates quantized to the bits of `DracoSettings`, with the colors as
+//! bytes. The other attributes have no Draco equivalent and are left out.
+
+use crate::reader::{MeshData, Topology};
+
+const MAGIC: &[u8; 5] = b"DRACO";
+const VERSION: [u8; 2] = [2, 2];
+const TRIANGULAR_MESH: u8 = 1;
+const SEQUENTIAL_ENCODING: u8 = 0;
+const UNCOMPRESSED_INDICES: u8 = 1;
+
+const POSITION: u8 = 0;
+const NORMAL: u8 = 1;
+const COLOR: u8 = 2;
+const TEX_COORD: u8 = 3;
+
+const DT_UINT8: u8 = 2;
+const DT_FLOAT32: u8 = 9;
+
+const GENERIC_ENCODER: u8 = 0;
+const QUANTIZATION_ENCODER: u8 = 2;
+const PREDICTION_NONE: u8 = -2i8 as u8;
+
+/// The bits each component is quantized to, from 1 to 30
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DracoSettings {
+	pub position_bits: u8,
+	pub normal_bits: u8,
+	pub tex_coord_bits: u8,
+}
+
+impl Default for DracoSettings {
+	/// Those of the Draco encoder
+	fn default() -> Self {
+		DracoSettings {
+			position_bits: 11,
+			normal_bits: 8,
+			tex_coord_bits: 10,
+		}
+	}
+}
+
+enum Values {
+	/// Floats quantized in the bounds of all the components
+	Quantized { components: usize, values: Vec<f32>, bits: u8 },
+	/// Normalized bytes
+	Unorm8 { components: usize, values: Vec<u8> },
+}
+
+struct Attribute {
+	kind: u8,
+	values: Values,
+}
+
+fn write_varint(data: &mut Vec<u8>, mut v: u64) {
+	loop {
+		data.push((v & 127) as u8 | if v > 127 { 128 } else { 0 });
+		v >>= 7;
+		if v == 0 {
+			break;
+		}
+	}
+}
+
+fn quantized<const N: usize>(kind: u8, values: &[[f32; N]], bits: u8) -> Attribute {
+	assert!((1..=30).contains(&bits), "Draco quantizes to 1 to 30 bits, not {}", bits);
+	Attribute {
+		kind,
+		values: Values::Quantized { components: N, values: values.iter().flatten().copied().collect(), bits },
+	}
+}
+
+/// The attributes of `mesh` that Draco has, and the names of the others
+fn attributes(mesh: &MeshData, settings: &DracoSettings) -> (Vec<Attribute>, Vec<&'static str>) {
+	let mut attributes = vec![quantized(POSITION, &mesh.positions, settings.position_bits)];
+	if let Some(normals) = &mesh.normals {
+		attributes.push(quantized(NORMAL, normals, settings.normal_bits));
+	}
+	if let Some(colors) = &mesh.colors {
+		let values = colors.iter().flatten().map(|&c| (c * 255.0).round() as u8).collect();
+		attributes.push(Attribute { kind: COLOR, values: Values::Unorm8 { components: 4, values } });
+	}
+	for uvs in [&mesh.tex0, &mesh.tex1].into_iter().flatten() {
+		attributes.push(quantized(TEX_COORD, uvs, settings.tex_coord_bits));
+	}
+
+	let mut dropped = vec![];
+	if mesh.tangents.is_some() {
+		dropped.push("tangents");
+	}
+	if mesh.bitangents.is_some() {
+		dropped.push("bitangents");
+	}
+	if mesh.bone_indices.is_some() {
+		dropped.push("skin weights");
+	}
+	if mesh.curvature.is_some() {
+		dropped.push("curvature");
+	}
+	if mesh.material_ids.is_some() {
+		dropped.push("material ids");
+	}
+	if !mesh.user_data.is_empty() {
+		dropped.push("user attributes");
+	}
+	if !mesh.morph_targets.is_empty() {
+		dropped.push("morph targets");
+	}
+	(attributes, dropped)
+}
+
+/// Encodes a triangle mesh as a Draco file, and returns it with the names of the attributes left out of it. A
+/// mirrored half is stored whole
+pub fn encode(mesh: &MeshData, settings: &DracoSettings) -> (Vec<u8>, Vec<&'static str>) {
+	assert!(mesh.header.topology == Topology::TriangleList, "Only triangle lists can be stored as Draco meshes");
+	let mut mesh = mesh.clone();
+	mesh.unfold_mirror();
+	let points = mesh.positions.len();
+	//the reader of the Draco decoder would need the 32 bit indices past that
+	assert!(points < 1 << 21, "A Draco mesh stores up to {} vertices, not {}", (1 << 21) - 1, points);
+
+	let mut data = MAGIC.to_vec();
+	data.extend_from_slice(&VERSION);
+	data.push(TRIANGULAR_MESH);
+	data.push(SEQUENTIAL_ENCODING);
+	//no metadata
+	data.extend_from_slice(&0u16.to_le_bytes());
+
+	write_varint(&mut data, mesh.indices.len() as u64 / 3);
+	write_varint(&mut data, points as u64);
+	data.push(UNCOMPRESSED_INDICES);
+	for &index in &mesh.indices {
+		match points {
+			0..=0xff => data.push(index as u8),
+			0x100..=0xffff => data.extend_from_slice(&(index as u16).to_le_bytes()),
+			_ => write_varint(&mut data, index as u64),
+		}
+	}
+
+	//a single decoder for all of them, in the order of the points
+	let (attributes, dropped) = attributes(&mesh, settings);
+	data.push(1);
+	write_varint(&mut data, attributes.len() as u64);
+	for (i, attribute) in attributes.iter().enumerate() {
+		let (data_type, components, normalized) = match attribute.values {
+			Values::Quantized { components, .. } => (DT_FLOAT32, components, 0),
+			Values::Unorm8 { components, .. } => (DT_UINT8, components, 1),
+		};
+		data.extend_from_slice(&[attribute.kind, data_type, components as u8, normalized]);
+		write_varint(&mut data, i as u64);
+	}
+	for attribute in &attributes {
+		data.push(match attribute.values {
+			Values::Quantized { .. } => QUANTIZATION_ENCODER,
+			Values::Unorm8 { .. } => GENERIC_ENCODER,
+		});
+	}
+
+	//the values of all the attributes, then how to dequantize them
+	let mut parameters = vec![];
+	for attribute in &attributes {
+		match attribute.values {
+			Values::Quantized { components, ref values, bits } => {
+				let mut min = vec![f32::INFINITY; components];
+				let mut max = vec![f32::NEG_INFINITY; components];
+				for (i, &v) in values.iter().enumerate() {
+					min[i % components] = f32::min(min[i % components], v);
+					max[i % components] = f32::max(max[i % components], v);
+				}
+				if values.is_empty() {
+					min.fill(0.0);
+					max.fill(0.0);
+				}
+				let range = (0..components).map(|c| max[c] - min[c]).fold(0.0, f32::max);
+				let range = if range > 0.0 { range } else { 1.0 };
+
+				let scale = ((1u32 << bits) - 1) as f32 / range;
+				//the values are stored signed, as twice the quantized ones
+				let bytes = (bits as usize + 1).div_ceil(8);
+				data.push(PREDICTION_NONE);
+				data.push(0);
+				data.push(bytes as u8);
+				for (i, &v) in values.iter().enumerate() {
+					let quantized = ((v - min[i % components]) * scale + 0.5).floor() as u32;
+					data.extend_from_slice(&(quantized << 1).to_le_bytes()[..bytes]);
+				}
+
+				for m in min {
+					parameters.extend_from_slice(&m.to_le_bytes());
+				}
+				parameters.extend_from_slice(&range.to_le_bytes());
+				parameters.push(bits);
+			},
+			Values::Unorm8 { ref values, .. } => data.extend_from_slice(values),
+		}
+	}
+	data.extend(parameters);
+	(data, dropped)
+}
+
+#[cfg(all(test, feature = "converter"))]
+mod tests {
+	use std::io::Write;
+	use std::process::{Command, Stdio};
+	use super::*;
+	use crate::convert::ConvertOptions;
+	use crate::fixtures;
+
+	/// Decodes the file on stdin with the Draco decoder of npm, and prints a line with the triangles, then one
+	/// with the name and the values of each attribute, as floats
+	const DECODE: &str = r#"
+		const data = require("fs").readFileSync(0);
+		require("draco3d").createDecoderModule({}).then(draco => {
+			const decoder = new draco.Decoder();
+			const buffer = new draco.DecoderBuffer();
+			buffer.Init(new Int8Array(data), data.length);
+			const mesh = new draco.Mesh();
+			const status = decoder.DecodeBufferToMesh(buffer, mesh);
+			if (!status.ok()) {
+				console.error(status.error_msg());
+				process.exit(1);
+			}
+			const indices = [];
+			const face = new draco.DracoInt32Array();
+			for (let i = 0; i < mesh.num_faces(); i++) {
+				decoder.GetFaceFromMesh(mesh, i, face);
+				indices.push(face.GetValue(0), face.GetValue(1), face.GetValue(2));
+			}
+			console.log("indices " + indices.join(" "));
+			for (const [name, kind] of [["position", draco.POSITION], ["normal", draco.NORMAL], ["color", draco.COLOR], ["uv", draco.TEX_COORD]]) {
+				const id = decoder.GetAttributeId(mesh, kind);
+				if (id < 0) {
+					continue;
+				}
+				const values = new draco.DracoFloat32Array();
+				decoder.GetAttributeFloatForAllPoints(mesh, decoder.GetAttribute(mesh, id), values);
+				console.log(name + " " + Array.from({ length: values.size() }, (_, i) => values.GetValue(i)).join(" "));
+			}
+		});
+	"#;
+
+	fn decode(file: &[u8]) -> Vec<(String, Vec<f32>)> {
+		let mut node = Command::new("node").arg("-e").arg(DECODE)
+			.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().expect("node isn't installed");
+		node.stdin.take().unwrap().write_all(file).unwrap();
+		let output = node.wait_with_output().unwrap();
+		assert!(output.status.success(), "The Draco decoder failed");
+		String::from_utf8(output.stdout).unwrap().lines().map(|line| {
+			let mut values = line.split_whitespace();
+			let name = values.next().unwrap().to_owned();
+			(name, values.map(|v| v.parse().unwrap()).collect())
+		}).collect()
+	}
+
+	fn assert_close(name: &str, decoded: &[f32], original: impl Iterator<Item = f32>, tolerance: f32) {
+		let original: Vec<f32> = original.collect();
+		assert_eq!(decoded.len(), original.len(), "{} values", name);
+		for (i, (a, b)) in decoded.iter().zip(&original).enumerate() {
+			assert!((a - b).abs() <= tolerance, "{} value {} is {}, not {}", name, i, a, b);
+		}
+	}
+
+	/// Half of a step of `bits` over `range`, with some room for the float maths of the decoder
+	fn quantization_error(bits: u8, range: f32) -> f32 {
+		range / ((1u32 << bits) - 1) as f32 * 0.5 + 1e-5
+	}
+
+	#[test]
+	#[ignore = "needs node and the draco3d package of npm"]
+	fn cube_with_the_draco_decoder() {
+		let part = fixtures::convert_one(fixtures::CUBE, &ConvertOptions::default());
+		let mesh = fixtures::read(&part);
+		let settings = DracoSettings::default();
+		let (file, dropped) = encode(&mesh, &settings);
+		assert!(dropped.is_empty(), "{:?}", dropped);
+
+		let decoded = decode(&file);
+		let names: Vec<&str> = decoded.iter().map(|(name, _)| name.as_str()).collect();
+		assert_eq!(names, ["indices", "position", "normal", "color", "uv"]);
+		let indices: Vec<u32> = decoded[0].1.iter().map(|&i| i as u32).collect();
+		assert_eq!(indices, mesh.indices);
+		//the cube is 1 wide and the normals 2, the UVs go from 0 to 1
+		assert_close("position", &decoded[1].1, mesh.positions.iter().flatten().copied(), quantization_error(settings.position_bits, 1.0));
+		assert_close("normal", &decoded[2].1, mesh.normals.as_ref().unwrap().iter().flatten().copied(), quantization_error(settings.normal_bits, 2.0));
+		assert_close("color", &decoded[3].1, mesh.colors.as_ref().unwrap().iter().flatten().copied(), 0.5 / 255.0 + 1e-5);
+		assert_close("uv", &decoded[4].1, mesh.tex0.as_ref().unwrap().iter().flatten().copied(), quantization_error(settings.tex_coord_bits, 1.0));
+	}
+}
@@ -0,0 +1,33 @@
+use std::fmt::Write;
+use crate::blocks::{BlockLayout, Word, BLOCK_VERTICES};
+use crate::layout::VertexLayout;
+
+/// Where each word of the decompressed vertex comes from in the blocks of `--encoding blocks`, as JSON, for the
+/// compute shaders decompressing them. Offsets and sizes are in 32 bit words
+pub fn json(layout: &VertexLayout, blocks: &BlockLayout) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "{{").unwrap();
+	writeln!(out, "\t\"blockVertices\": {},", BLOCK_VERTICES).unwrap();
+	writeln!(out, "\t\"blockWords\": {},", blocks.block_words).unwrap();
+	writeln!(out, "\t\"rangeWords\": {},", blocks.quantized * 2).unwrap();
+	writeln!(out, "\t\"recordWords\": {},", blocks.record_words).unwrap();
+	writeln!(out, "\t\"quantizedWords\": {},", blocks.quantized.div_ceil(2)).unwrap();
+	writeln!(out, "\t\"stride\": {},", layout.stride / 4).unwrap();
+	writeln!(out, "\t\"words\": [").unwrap();
+	for (w, word) in blocks.words.iter().enumerate() {
+		let name = layout.attributes.iter()
+			.find(|a| (a.offset..a.offset + a.format.size()).contains(&(w * 4)))
+			.map_or("padding", |a| a.name.as_str());
+		let source = match *word {
+			Word::Quantized(q) => format!("\"quantized\": {}", q),
+			Word::Raw(r) => format!("\"raw\": {}", r),
+		};
+		let separator = if w + 1 < blocks.words.len() { "," } else { "" };
+		writeln!(out, "\t\t{{ \"attribute\": \"{}\", {} }}{}", name, source, separator).unwrap();
+	}
+	writeln!(out, "\t]").unwrap();
+	writeln!(out, "}}").unwrap();
+
+	out
+}
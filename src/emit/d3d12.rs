@@ -0,0 +1,85 @@
+use std::fmt::Write;
+use crate::layout::{Attribute, Format, VertexLayout};
+
+/// Name of the format without the `DXGI_FORMAT_` prefix. DXGI has no signed 10-10-10-2 format, so packed normals and
+/// tangents are read as a u32 and unpacked in the shader, like `emit::shader` does
+pub fn format_name(format: Format) -> &'static str {
+	match format {
+		Format::Float32x3 => "R32G32B32_FLOAT",
+		Format::Snorm10_10_10_2 => "R32_UINT",
+		Format::Float16x2 => "R16G16_FLOAT",
+		Format::Uint8x4 => "R8G8B8A8_UINT",
+		Format::Unorm8x4 => "R8G8B8A8_UNORM",
+		Format::Float32 => "R32_FLOAT",
+		Format::Uint32 => "R32_UINT",
+		Format::Float32x2 => "R32G32_FLOAT",
+		Format::Float32x4 => "R32G32B32A32_FLOAT",
+		Format::Uint8 => "R8_UINT",
+		Format::Uint16 => "R16_UINT",
+		Format::Unorm16x4 => "R16G16B16A16_UNORM",
+	}
+}
+
+/// The semantic name and index of the HLSL inputs of `emit::shader`
+pub fn semantic(attr: Attribute) -> (&'static str, u32) {
+	match attr {
+		Attribute::Position => ("POSITION", 0),
+		Attribute::Color => ("COLOR", 0),
+		Attribute::Normal => ("NORMAL", 0),
+		Attribute::Tangent => ("TANGENT", 0),
+		Attribute::Bitangent => ("BINORMAL", 0),
+		Attribute::Tex0 => ("TEXCOORD", 0),
+		Attribute::Tex1 => ("TEXCOORD", 1),
+		Attribute::BoneIndices => ("BLENDINDICES", 0),
+		Attribute::BoneWeights => ("BLENDWEIGHT", 0),
+		Attribute::Curvature => ("CURVATURE", 0),
+		Attribute::MaterialId => ("MATERIALID", 0),
+		Attribute::User(i) => ("USER", i as u32),
+	}
+}
+
+/// C++ source of the `D3D12_INPUT_ELEMENT_DESC` array, reading the vertex buffer bound to `slot`
+pub fn source(layout: &VertexLayout, slot: u32) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "// the stride of the D3D12_VERTEX_BUFFER_VIEW is {} bytes", layout.stride).unwrap();
+	writeln!(out, "const D3D12_INPUT_ELEMENT_DESC input_elements[] = {{").unwrap();
+	for attr in &layout.attributes {
+		let (name, index) = semantic(attr.attribute);
+		writeln!(out, "\t// {}", attr.name).unwrap();
+		writeln!(out, "\t{{ \"{}\", {}, DXGI_FORMAT_{}, {}, {}, D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA, 0 }},",
+			name,
+			index,
+			format_name(attr.format),
+			slot,
+			attr.offset).unwrap();
+	}
+	writeln!(out, "}};").unwrap();
+
+	out
+}
+
+/// The same elements as JSON, using the D3D12 enum names
+pub fn json(layout: &VertexLayout, slot: u32) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "{{").unwrap();
+	writeln!(out, "\t\"stride\": {},", layout.stride).unwrap();
+	writeln!(out, "\t\"elements\": [").unwrap();
+	for (i, attr) in layout.attributes.iter().enumerate() {
+		let (name, index) = semantic(attr.attribute);
+		let separator = if i + 1 < layout.attributes.len() { "," } else { "" };
+		writeln!(out, "\t\t{{ \"name\": \"{}\", \"semanticName\": \"{}\", \"semanticIndex\": {}, \"format\": \"DXGI_FORMAT_{}\", \"inputSlot\": {}, \"alignedByteOffset\": {}, \"inputSlotClass\": \"D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA\", \"instanceDataStepRate\": 0 }}{}",
+			attr.name,
+			name,
+			index,
+			format_name(attr.format),
+			slot,
+			attr.offset,
+			separator).unwrap();
+	}
+	writeln!(out, "\t]").unwrap();
+	writeln!(out, "}}").unwrap();
+
+	out
+}
@@ -0,0 +1,113 @@
+use std::fmt::Write;
+use crate::layout::{Format, VertexLayout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+	C,
+	Rust,
+}
+
+impl Language {
+	pub fn from_name(name: &str) -> Option<Language> {
+		match name {
+			"c" => Some(Language::C),
+			"rust" => Some(Language::Rust),
+			_ => None,
+		}
+	}
+
+	pub fn extension(self) -> &'static str {
+		match self {
+			Language::C => "c",
+			Language::Rust => "rs",
+		}
+	}
+}
+
+/// How GL reads an attribute of a format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttribFormat {
+	pub size: u32,
+	/// Without the `GL_` prefix
+	pub ty: &'static str,
+	pub normalized: bool,
+	/// Read with `glVertexAttribIPointer` into an integer input
+	pub integer: bool,
+}
+
+/// GL reads the packed normals and tangents as GL_INT_2_10_10_10_REV, normalized, with x in the low bits
+pub fn attrib_format(format: Format) -> AttribFormat {
+	let (size, ty, normalized, integer) = match format {
+		Format::Float32x3 => (3, "FLOAT", false, false),
+		Format::Snorm10_10_10_2 => (4, "INT_2_10_10_10_REV", true, false),
+		Format::Float16x2 => (2, "HALF_FLOAT", false, false),
+		Format::Uint8x4 => (4, "UNSIGNED_BYTE", false, true),
+		Format::Unorm8x4 => (4, "UNSIGNED_BYTE", true, false),
+		Format::Float32 => (1, "FLOAT", false, false),
+		Format::Uint32 => (1, "UNSIGNED_INT", false, true),
+		Format::Float32x2 => (2, "FLOAT", false, false),
+		Format::Float32x4 => (4, "FLOAT", false, false),
+		Format::Uint8 => (1, "UNSIGNED_BYTE", false, true),
+		Format::Uint16 => (1, "UNSIGNED_SHORT", false, true),
+		Format::Unorm16x4 => (4, "UNSIGNED_SHORT", true, false),
+	};
+	AttribFormat { size, ty, normalized, integer }
+}
+
+/// C code creating a VAO with the attributes of the layout, reading `vertex_buffer`
+fn c(layout: &VertexLayout, out: &mut String) {
+	writeln!(out, "GLuint vao;").unwrap();
+	writeln!(out, "glGenVertexArrays(1, &vao);").unwrap();
+	writeln!(out, "glBindVertexArray(vao);").unwrap();
+	writeln!(out, "glBindBuffer(GL_ARRAY_BUFFER, vertex_buffer);").unwrap();
+	for attr in &layout.attributes {
+		let format = attrib_format(attr.format);
+		writeln!(out).unwrap();
+		writeln!(out, "// {}", attr.name).unwrap();
+		writeln!(out, "glEnableVertexAttribArray({});", attr.location).unwrap();
+		if format.integer {
+			writeln!(out, "glVertexAttribIPointer({}, {}, GL_{}, {}, (const void*){});",
+				attr.location, format.size, format.ty, layout.stride, attr.offset).unwrap();
+		}
+		else {
+			writeln!(out, "glVertexAttribPointer({}, {}, GL_{}, {}, {}, (const void*){});",
+				attr.location, format.size, format.ty, if format.normalized { "GL_TRUE" } else { "GL_FALSE" }, layout.stride, attr.offset).unwrap();
+		}
+	}
+}
+
+/// The same as `c`, with the bindings of the `gl` crate
+fn rust(layout: &VertexLayout, out: &mut String) {
+	writeln!(out, "let mut vao = 0;").unwrap();
+	writeln!(out, "unsafe {{").unwrap();
+	writeln!(out, "\tgl::GenVertexArrays(1, &mut vao);").unwrap();
+	writeln!(out, "\tgl::BindVertexArray(vao);").unwrap();
+	writeln!(out, "\tgl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);").unwrap();
+	for attr in &layout.attributes {
+		let format = attrib_format(attr.format);
+		writeln!(out).unwrap();
+		writeln!(out, "\t// {}", attr.name).unwrap();
+		writeln!(out, "\tgl::EnableVertexAttribArray({});", attr.location).unwrap();
+		if format.integer {
+			writeln!(out, "\tgl::VertexAttribIPointer({}, {}, gl::{}, {}, {} as *const _);",
+				attr.location, format.size, format.ty, layout.stride, attr.offset).unwrap();
+		}
+		else {
+			writeln!(out, "\tgl::VertexAttribPointer({}, {}, gl::{}, {}, {}, {} as *const _);",
+				attr.location, format.size, format.ty, if format.normalized { "gl::TRUE" } else { "gl::FALSE" }, layout.stride, attr.offset).unwrap();
+		}
+	}
+	writeln!(out, "}}").unwrap();
+}
+
+/// Vertex array setup matching the layout, for a buffer bound as `vertex_buffer`
+pub fn source(layout: &VertexLayout, language: Language) -> String {
+	let mut out = String::new();
+
+	match language {
+		Language::C => c(layout, &mut out),
+		Language::Rust => rust(layout, &mut out),
+	}
+
+	out
+}
@@ -0,0 +1,69 @@
+use std::fmt::Write;
+use crate::layout::{Format, VertexLayout};
+
+/// Name of the `MTLVertexFormat` case in Swift. Metal reads the packed normals and tangents in hardware, with x in
+/// the low bits
+pub fn format_name(format: Format) -> &'static str {
+	match format {
+		Format::Float32x3 => "float3",
+		Format::Snorm10_10_10_2 => "int1010102Normalized",
+		Format::Float16x2 => "half2",
+		Format::Uint8x4 => "uchar4",
+		Format::Unorm8x4 => "uchar4Normalized",
+		Format::Float32 => "float",
+		Format::Uint32 => "uint",
+		Format::Float32x2 => "float2",
+		Format::Float32x4 => "float4",
+		Format::Uint8 => "uchar",
+		Format::Uint16 => "ushort",
+		Format::Unorm16x4 => "ushort4Normalized",
+	}
+}
+
+/// The Objective-C name of the format, like `MTLVertexFormatFloat3`
+fn enum_name(format: Format) -> String {
+	let name = format_name(format);
+	format!("MTLVertexFormat{}{}", name[..1].to_uppercase(), &name[1..])
+}
+
+/// Swift source filling a `MTLVertexDescriptor`, with the vertices in the buffer at `buffer_index`
+pub fn source(layout: &VertexLayout, buffer_index: u32) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "let vertexDescriptor = MTLVertexDescriptor()").unwrap();
+	for attr in &layout.attributes {
+		writeln!(out, "// {}", attr.name).unwrap();
+		writeln!(out, "vertexDescriptor.attributes[{}].format = .{}", attr.location, format_name(attr.format)).unwrap();
+		writeln!(out, "vertexDescriptor.attributes[{}].offset = {}", attr.location, attr.offset).unwrap();
+		writeln!(out, "vertexDescriptor.attributes[{}].bufferIndex = {}", attr.location, buffer_index).unwrap();
+	}
+	writeln!(out, "vertexDescriptor.layouts[{}].stride = {}", buffer_index, layout.stride).unwrap();
+	writeln!(out, "vertexDescriptor.layouts[{}].stepFunction = .perVertex", buffer_index).unwrap();
+
+	out
+}
+
+/// The same descriptor as JSON, using the Metal enum names
+pub fn json(layout: &VertexLayout, buffer_index: u32) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "{{").unwrap();
+	writeln!(out, "\t\"layouts\": [").unwrap();
+	writeln!(out, "\t\t{{ \"bufferIndex\": {}, \"stride\": {}, \"stepFunction\": \"MTLVertexStepFunctionPerVertex\" }}", buffer_index, layout.stride).unwrap();
+	writeln!(out, "\t],").unwrap();
+	writeln!(out, "\t\"attributes\": [").unwrap();
+	for (i, attr) in layout.attributes.iter().enumerate() {
+		let separator = if i + 1 < layout.attributes.len() { "," } else { "" };
+		writeln!(out, "\t\t{{ \"name\": \"{}\", \"index\": {}, \"format\": \"{}\", \"offset\": {}, \"bufferIndex\": {} }}{}",
+			attr.name,
+			attr.location,
+			enum_name(attr.format),
+			attr.offset,
+			buffer_index,
+			separator).unwrap();
+	}
+	writeln!(out, "\t]").unwrap();
+	writeln!(out, "}}").unwrap();
+
+	out
+}
@@ -0,0 +1,10 @@
+//! Generates the vertex input descriptions of each graphics API from a `VertexLayout`,
+//! so that loaders don't need to be kept in sync with the converter by hand.
+
+pub mod blocks;
+pub mod d3d12;
+pub mod gl;
+pub mod metal;
+pub mod shader;
+pub mod vulkan;
+pub mod wgpu;
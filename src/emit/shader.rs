@@ -0,0 +1,168 @@
+use std::fmt::Write;
+use crate::layout::{Attribute, Format, VertexAttribute, VertexLayout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+	Glsl,
+	Hlsl,
+	Wgsl,
+}
+
+impl Language {
+	pub fn from_name(name: &str) -> Option<Language> {
+		match name {
+			"glsl" => Some(Language::Glsl),
+			"hlsl" => Some(Language::Hlsl),
+			"wgsl" => Some(Language::Wgsl),
+			_ => None,
+		}
+	}
+
+	pub fn extension(self) -> &'static str {
+		match self {
+			Language::Glsl => "glsl",
+			Language::Hlsl => "hlsl",
+			Language::Wgsl => "wgsl",
+		}
+	}
+}
+
+const HLSL_UNPACK: &str = "\
+float4 unpack_snorm_10_10_10_2(uint v) {
+	int4 i = int4(int(v << 22) >> 22, int(v << 12) >> 22, int(v << 2) >> 22, int(v) >> 30);
+	return max(float4(i) / float4(511.0, 511.0, 511.0, 1.0), -1.0);
+}
+";
+
+const WGSL_UNPACK: &str = "\
+fn unpack_snorm_10_10_10_2(v: u32) -> vec4<f32> {
+	let i = vec4<i32>(i32(v << 22u) >> 22u, i32(v << 12u) >> 22u, i32(v << 2u) >> 22u, i32(v) >> 30u);
+	return max(vec4<f32>(i) / vec4<f32>(511.0, 511.0, 511.0, 1.0), vec4<f32>(-1.0));
+}
+";
+
+fn comment(attr: &VertexAttribute, unpacked_in_shader: bool) -> String {
+	if matches!(attr.format, Format::Uint8 | Format::Uint16) {
+		return " // the index of the value in the palette".to_owned();
+	}
+	if attr.format == Format::Unorm16x4 {
+		return " // quantized: multiply vec4(xyz, 1) by the dequantization matrix of the header".to_owned();
+	}
+	let note = match attr.attribute {
+		Attribute::Normal | Attribute::Bitangent => "w is unused",
+		Attribute::Tangent => "w is the handedness of the bitangent",
+		_ => return String::new(),
+	};
+
+	if unpacked_in_shader {
+		format!(" // decode with unpack_snorm_10_10_10_2, {}", note)
+	}
+	else {
+		format!(" // {}", note)
+	}
+}
+
+fn hlsl_semantic(attr: Attribute) -> String {
+	let semantic = match attr {
+		Attribute::Position => "POSITION",
+		Attribute::Color => "COLOR",
+		Attribute::Normal => "NORMAL",
+		Attribute::Tangent => "TANGENT",
+		Attribute::Bitangent => "BINORMAL",
+		Attribute::Tex0 => "TEXCOORD0",
+		Attribute::Tex1 => "TEXCOORD1",
+		Attribute::BoneIndices => "BLENDINDICES",
+		Attribute::BoneWeights => "BLENDWEIGHT",
+		Attribute::Curvature => "CURVATURE",
+		Attribute::MaterialId => "MATERIALID",
+		Attribute::User(i) => return format!("USER{}", i),
+	};
+	semantic.to_owned()
+}
+
+/// GL and Vulkan both decode the packed formats in hardware, so the inputs are plain vectors
+fn glsl(layout: &VertexLayout, out: &mut String) {
+	for attr in &layout.attributes {
+		let ty = match attr.format {
+			Format::Float32x3 => "vec3",
+			Format::Snorm10_10_10_2 => "vec4",
+			Format::Float16x2 => "vec2",
+			Format::Uint8x4 => "uvec4",
+			Format::Unorm8x4 => "vec4",
+			Format::Float32 => "float",
+			Format::Uint32 => "uint",
+			Format::Float32x2 => "vec2",
+			Format::Float32x4 => "vec4",
+			Format::Uint8 | Format::Uint16 => "uint",
+			Format::Unorm16x4 => "vec4",
+		};
+		writeln!(out, "layout(location = {}) in {} in_{};{}", attr.location, ty, attr.name, comment(attr, false)).unwrap();
+	}
+}
+
+/// DXGI has no signed 10-10-10-2 format, so those attributes are bound as R32_UINT and unpacked
+fn hlsl(layout: &VertexLayout, out: &mut String) {
+	let packed = layout.attributes.iter().any(|a| a.format == Format::Snorm10_10_10_2);
+	if packed {
+		writeln!(out, "{}", HLSL_UNPACK).unwrap();
+	}
+
+	writeln!(out, "struct VertexInput {{").unwrap();
+	for attr in &layout.attributes {
+		let ty = match attr.format {
+			Format::Float32x3 => "float3",
+			Format::Snorm10_10_10_2 => "uint",
+			Format::Float16x2 => "float2",
+			Format::Uint8x4 => "uint4",
+			Format::Unorm8x4 => "float4",
+			Format::Float32 => "float",
+			Format::Uint32 => "uint",
+			Format::Float32x2 => "float2",
+			Format::Float32x4 => "float4",
+			Format::Uint8 | Format::Uint16 => "uint",
+			Format::Unorm16x4 => "float4",
+		};
+		writeln!(out, "\t[[vk::location({})]] {} {} : {};{}", attr.location, ty, attr.name, hlsl_semantic(attr.attribute), comment(attr, true)).unwrap();
+	}
+	writeln!(out, "}};").unwrap();
+}
+
+/// Matches `emit::wgpu`, which binds the packed attributes as Uint32
+fn wgsl(layout: &VertexLayout, out: &mut String) {
+	let packed = layout.attributes.iter().any(|a| a.format == Format::Snorm10_10_10_2);
+	if packed {
+		writeln!(out, "{}", WGSL_UNPACK).unwrap();
+	}
+
+	writeln!(out, "struct VertexInput {{").unwrap();
+	for attr in &layout.attributes {
+		let ty = match attr.format {
+			Format::Float32x3 => "vec3<f32>",
+			Format::Snorm10_10_10_2 => "u32",
+			Format::Float16x2 => "vec2<f32>",
+			Format::Uint8x4 => "vec4<u32>",
+			Format::Unorm8x4 => "vec4<f32>",
+			Format::Float32 => "f32",
+			Format::Uint32 => "u32",
+			Format::Float32x2 => "vec2<f32>",
+			Format::Float32x4 => "vec4<f32>",
+			Format::Uint8 | Format::Uint16 => "u32",
+			Format::Unorm16x4 => "vec4<f32>",
+		};
+		writeln!(out, "\t@location({}) {}: {},{}", attr.location, attr.name, ty, comment(attr, true)).unwrap();
+	}
+	writeln!(out, "}}").unwrap();
+}
+
+/// Vertex input declarations matching the layout, plus the helpers needed to decode packed attributes
+pub fn source(layout: &VertexLayout, language: Language) -> String {
+	let mut out = String::new();
+
+	match language {
+		Language::Glsl => glsl(layout, &mut out),
+		Language::Hlsl => hlsl(layout, &mut out),
+		Language::Wgsl => wgsl(layout, &mut out),
+	}
+
+	out
+}
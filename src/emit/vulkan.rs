@@ -0,0 +1,69 @@
+use std::fmt::Write;
+use crate::layout::{Format, VertexLayout};
+
+/// Name of the format without the `VK_FORMAT_` prefix, as used by ash's `vk::Format` constants
+pub fn format_name(format: Format) -> &'static str {
+	match format {
+		Format::Float32x3 => "R32G32B32_SFLOAT",
+		Format::Snorm10_10_10_2 => "A2B10G10R10_SNORM_PACK32",
+		Format::Float16x2 => "R16G16_SFLOAT",
+		Format::Uint8x4 => "R8G8B8A8_UINT",
+		Format::Unorm8x4 => "R8G8B8A8_UNORM",
+		Format::Float32 => "R32_SFLOAT",
+		Format::Uint32 => "R32_UINT",
+		Format::Float32x2 => "R32G32_SFLOAT",
+		Format::Float32x4 => "R32G32B32A32_SFLOAT",
+		Format::Uint8 => "R8_UINT",
+		Format::Uint16 => "R16_UINT",
+		Format::Unorm16x4 => "R16G16B16A16_UNORM",
+	}
+}
+
+/// Rust source of the binding and attribute descriptions, using ash types
+pub fn source(layout: &VertexLayout, binding: u32) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "let binding_description = vk::VertexInputBindingDescription {{").unwrap();
+	writeln!(out, "\tbinding: {},", binding).unwrap();
+	writeln!(out, "\tstride: {},", layout.stride).unwrap();
+	writeln!(out, "\tinput_rate: vk::VertexInputRate::VERTEX,").unwrap();
+	writeln!(out, "}};").unwrap();
+	writeln!(out).unwrap();
+	writeln!(out, "let attribute_descriptions = [").unwrap();
+	for attr in &layout.attributes {
+		writeln!(out, "\t// {}", attr.name).unwrap();
+		writeln!(out, "\tvk::VertexInputAttributeDescription {{ location: {}, binding: {}, format: vk::Format::{}, offset: {} }},",
+			attr.location,
+			binding,
+			format_name(attr.format),
+			attr.offset).unwrap();
+	}
+	writeln!(out, "];").unwrap();
+
+	out
+}
+
+/// The same descriptions as JSON, using the Vulkan enum names
+pub fn json(layout: &VertexLayout, binding: u32) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "{{").unwrap();
+	writeln!(out, "\t\"bindings\": [").unwrap();
+	writeln!(out, "\t\t{{ \"binding\": {}, \"stride\": {}, \"inputRate\": \"VK_VERTEX_INPUT_RATE_VERTEX\" }}", binding, layout.stride).unwrap();
+	writeln!(out, "\t],").unwrap();
+	writeln!(out, "\t\"attributes\": [").unwrap();
+	for (i, attr) in layout.attributes.iter().enumerate() {
+		let separator = if i + 1 < layout.attributes.len() { "," } else { "" };
+		writeln!(out, "\t\t{{ \"name\": \"{}\", \"location\": {}, \"binding\": {}, \"format\": \"VK_FORMAT_{}\", \"offset\": {} }}{}",
+			attr.name,
+			attr.location,
+			binding,
+			format_name(attr.format),
+			attr.offset,
+			separator).unwrap();
+	}
+	writeln!(out, "\t]").unwrap();
+	writeln!(out, "}}").unwrap();
+
+	out
+}
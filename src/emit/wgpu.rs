@@ -0,0 +1,78 @@
+use std::fmt::Write;
+use crate::layout::{Format, VertexLayout};
+
+/// WebGPU has no signed 10-10-10-2 format, so packed normals and tangents are read as a u32 and decoded in the shader
+pub fn vertex_format_name(format: Format) -> &'static str {
+	match format {
+		Format::Float32x3 => "Float32x3",
+		Format::Snorm10_10_10_2 => "Uint32",
+		Format::Float16x2 => "Float16x2",
+		Format::Uint8x4 => "Uint8x4",
+		Format::Unorm8x4 => "Unorm8x4",
+		Format::Float32 => "Float32",
+		Format::Uint32 => "Uint32",
+		Format::Float32x2 => "Float32x2",
+		Format::Float32x4 => "Float32x4",
+		Format::Uint8 => "Uint8",
+		Format::Uint16 => "Uint16",
+		Format::Unorm16x4 => "Unorm16x4",
+	}
+}
+
+/// Rust source of a `wgpu::VertexBufferLayout` matching the layout
+pub fn source(layout: &VertexLayout) -> String {
+	let mut out = String::new();
+
+	writeln!(out, "wgpu::VertexBufferLayout {{").unwrap();
+	writeln!(out, "\tarray_stride: {},", layout.stride).unwrap();
+	writeln!(out, "\tstep_mode: wgpu::VertexStepMode::Vertex,").unwrap();
+	writeln!(out, "\tattributes: &[").unwrap();
+	for attr in &layout.attributes {
+		writeln!(out, "\t\t// {}", attr.name).unwrap();
+		writeln!(out, "\t\twgpu::VertexAttribute {{ format: wgpu::VertexFormat::{}, offset: {}, shader_location: {} }},",
+			vertex_format_name(attr.format),
+			attr.offset,
+			attr.location).unwrap();
+	}
+	writeln!(out, "\t],").unwrap();
+	writeln!(out, "}}").unwrap();
+
+	out
+}
+
+#[cfg(feature = "wgpu")]
+pub fn vertex_format(format: Format) -> ::wgpu::VertexFormat {
+	match format {
+		Format::Float32x3 => ::wgpu::VertexFormat::Float32x3,
+		Format::Snorm10_10_10_2 => ::wgpu::VertexFormat::Uint32,
+		Format::Float16x2 => ::wgpu::VertexFormat::Float16x2,
+		Format::Uint8x4 => ::wgpu::VertexFormat::Uint8x4,
+		Format::Unorm8x4 => ::wgpu::VertexFormat::Unorm8x4,
+		Format::Float32 => ::wgpu::VertexFormat::Float32,
+		Format::Uint32 => ::wgpu::VertexFormat::Uint32,
+		Format::Float32x2 => ::wgpu::VertexFormat::Float32x2,
+		Format::Float32x4 => ::wgpu::VertexFormat::Float32x4,
+		Format::Uint8 => ::wgpu::VertexFormat::Uint8,
+		Format::Uint16 => ::wgpu::VertexFormat::Uint16,
+		Format::Unorm16x4 => ::wgpu::VertexFormat::Unorm16x4,
+	}
+}
+
+#[cfg(feature = "wgpu")]
+pub fn vertex_attributes(layout: &VertexLayout) -> Vec<::wgpu::VertexAttribute> {
+	layout.attributes.iter().map(|attr| ::wgpu::VertexAttribute {
+		format: vertex_format(attr.format),
+		offset: attr.offset as ::wgpu::BufferAddress,
+		shader_location: attr.location,
+	}).collect()
+}
+
+/// `attributes` should come from `vertex_attributes`, as the buffer layout can only borrow them
+#[cfg(feature = "wgpu")]
+pub fn buffer_layout<'a>(layout: &VertexLayout, attributes: &'a [::wgpu::VertexAttribute]) -> ::wgpu::VertexBufferLayout<'a> {
+	::wgpu::VertexBufferLayout {
+		array_stride: layout.stride as ::wgpu::BufferAddress,
+		step_mode: ::wgpu::VertexStepMode::Vertex,
+		attributes,
+	}
+}
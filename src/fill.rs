@@ -0,0 +1,101 @@
+//! Defaults for the faces that lack the normals or texture coordinates the rest of the object has.
+
+use wavefront_obj::obj::{Normal, Object, Shape, TVertex, VTNIndex};
+use crate::diagnostics::{Warning, WarningKind};
+use crate::normals::{self, NormalWeighting};
+use crate::mesh::{is_primitive_of, object_topology, shape_corners};
+use crate::reader::Topology;
+
+/// The number of primitives that will be converted, and of those missing a normal and a texture coordinate on any corner
+pub fn count_missing(obj: &Object) -> (usize, usize, usize) {
+	let topology = object_topology(obj);
+	let mut primitives = 0;
+	let mut normals = 0;
+	let mut tex = 0;
+	for geo in &obj.geometry {
+		for shape in geo.shapes.iter().filter(|shape| is_primitive_of(shape, topology)) {
+			let corners = shape_corners(shape);
+			primitives += 1;
+			if corners.iter().any(|c| c.2.is_none()) {
+				normals += 1;
+			}
+			if corners.iter().any(|c| c.1.is_none()) {
+				tex += 1;
+			}
+		}
+	}
+	(primitives, normals, tex)
+}
+
+fn primitive_noun(obj: &Object) -> &'static str {
+	match object_topology(obj) {
+		Topology::TriangleList => "triangles",
+		Topology::LineList => "lines",
+		Topology::PointList => "points",
+	}
+}
+
+/// Area weighted normals of every position, shared by the triangles around it
+pub fn smooth_normals(obj: &Object) -> Vec<Normal> {
+	normals::vertex_normals(obj, NormalWeighting::Area)
+}
+
+/// Returns a copy of `obj` where the corners without a normal get a generated one, and/or the corners
+/// without a texture coordinate get (0,0)
+pub fn fill_missing(obj: &Object, fill_normals: bool, fill_tex: bool, warnings: &mut Vec<Warning>) -> Object {
+	let (primitives, missing_normals, missing_tex) = count_missing(obj);
+	let fill_normals = fill_normals && missing_normals > 0;
+	let fill_tex = fill_tex && missing_tex > 0;
+	let noun = primitive_noun(obj);
+
+	let mut result = obj.clone();
+	if !fill_normals && !fill_tex {
+		return result;
+	}
+
+	//the generated normals go after the ones of the file, one per position
+	let first_normal = result.normals.len();
+	if fill_normals {
+		result.normals.extend(smooth_normals(obj));
+		warnings.push(Warning::new(WarningKind::MissingAttribute,
+			format!("{}: generated normals for {} of {} {}", obj.name, missing_normals, primitives, noun)));
+	}
+
+	let default_tex = result.tex_vertices.len();
+	if fill_tex {
+		result.tex_vertices.push(TVertex { x: 0.0, y: 0.0 });
+		warnings.push(Warning::new(WarningKind::MissingAttribute,
+			format!("{}: used (0,0) texture coordinates for {} of {} {}", obj.name, missing_tex, primitives, noun)));
+	}
+
+	let fill = |(v, t, n): VTNIndex| -> VTNIndex {
+		(
+			v,
+			if fill_tex { t.or(Some(default_tex)) } else { t },
+			if fill_normals { n.or(Some(first_normal + v)) } else { n },
+		)
+	};
+
+	for geo in &mut result.geometry {
+		for shape in &mut geo.shapes {
+			*shape = match *shape {
+				Shape::Point(v1) => Shape::Point(fill(v1)),
+				Shape::Line(v1, v2) => Shape::Line(fill(v1), fill(v2)),
+				Shape::Triangle(v1, v2, v3) => Shape::Triangle(fill(v1), fill(v2), fill(v3)),
+			};
+		}
+	}
+	result
+}
+
+/// Warns about the attributes that will be dropped because only some of the primitives have them
+pub fn warn_dropped(obj: &Object, warnings: &mut Vec<Warning>) {
+	let (primitives, missing_normals, missing_tex) = count_missing(obj);
+	let noun = primitive_noun(obj);
+	for (missing, attr) in [(missing_normals, "normals"), (missing_tex, "texture coordinates")] {
+		if missing > 0 && missing < primitives {
+			warnings.push(Warning::new(WarningKind::MissingAttribute,
+				format!("{}: dropped the {} because {} of {} {} lack them", obj.name, attr, missing, primitives, noun)));
+		}
+	}
+}
@@ -0,0 +1,54 @@
+//! Selection of the objects and groups to convert, to leave out the reference geometry and collision proxies
+//! that artists keep in the same file.
+
+/// Glob patterns matched against the object and group names; `*` matches any run of characters, `?` any one
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+	/// When not empty, only the elements whose object or group matches one of these are kept
+	pub only: Vec<String>,
+	/// The elements whose object or group matches one of these are dropped
+	pub exclude: Vec<String>,
+}
+
+impl NameFilter {
+	pub fn is_empty(&self) -> bool {
+		self.only.is_empty() && self.exclude.is_empty()
+	}
+
+	/// Whether to keep the elements of an object and its current groups
+	pub fn keeps(&self, names: &[&str]) -> bool {
+		let any = |patterns: &[String]| patterns.iter().any(|p| names.iter().any(|name| glob_match(p, name)));
+		(self.only.is_empty() || any(&self.only)) && !any(&self.exclude)
+	}
+}
+
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+
+	//where to resume after the last *, if the rest doesn't match
+	let (mut p, mut n) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+	while n < name.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				star = Some((p, n));
+				p += 1;
+			},
+			Some(&c) if c == '?' || c == name[n] => {
+				p += 1;
+				n += 1;
+			},
+			_ => match star {
+				//let the * take one more character
+				Some((sp, sn)) => {
+					p = sp + 1;
+					n = sn + 1;
+					star = Some((sp, sn + 1));
+				},
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,111 @@
+//! The obj files the tests convert, and the conversion they all go through.
+
+use std::fmt::Write;
+use crate::colors;
+use crate::convert::{self, ConvertOptions, ConvertedMesh};
+use crate::reader::{self, MeshData};
+
+/// A unit cube with a color on every position, and UVs and normals on every face
+pub const CUBE: &str = "o cube
+v 0 0 0 1 0 0
+v 1 0 0 0 1 0
+v 1 1 0 0 0 1
+v 0 1 0 1 1 0
+v 0 0 1 0 1 1
+v 1 0 1 1 0 1
+v 1 1 1 1 1 1
+v 0 1 1 0.5 0.5 0.5
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 -1
+vn 0 0 1
+vn 0 -1 0
+vn 0 1 0
+vn -1 0 0
+vn 1 0 0
+f 1/1/1 4/4/1 3/3/1 2/2/1
+f 5/1/2 6/2/2 7/3/2 8/4/2
+f 1/1/3 2/2/3 6/3/3 5/4/3
+f 4/1/4 8/2/4 7/3/4 3/4/4
+f 1/1/5 5/2/5 8/3/5 4/4/5
+f 2/1/6 3/2/6 7/3/6 6/4/6
+";
+
+/// A bumpy square of `size` by `size` positions, each with its own UV, in two halves with their own material
+pub fn grid(size: usize) -> String {
+	let mut obj = "o grid\n".to_owned();
+	for y in 0..size {
+		for x in 0..size {
+			let height = ((x * 7 + y * 13) % 5) as f64 * 0.1;
+			writeln!(obj, "v {} {} {}", x, height, y).unwrap();
+		}
+	}
+	for y in 0..size {
+		for x in 0..size {
+			writeln!(obj, "vt {} {}", x as f64 / size as f64, y as f64 / size as f64).unwrap();
+		}
+	}
+	for y in 0..size - 1 {
+		if y == 0 || y == size / 2 {
+			writeln!(obj, "usemtl {}", if y == 0 { "near" } else { "far" }).unwrap();
+		}
+		for x in 0..size - 1 {
+			let corner = y * size + x + 1;
+			writeln!(obj, "f {0}/{0} {1}/{1} {2}/{2} {3}/{3}", corner, corner + size, corner + size + 1, corner + 1).unwrap();
+		}
+	}
+	obj
+}
+
+/// The meshes of every object of `obj`, with the colors of its positions
+pub fn convert(obj: &str, options: &ConvertOptions) -> Vec<ConvertedMesh> {
+	let mut options = options.clone();
+	options.vertex_colors = colors::parse_vertex_colors(obj);
+	let set = convert::parse_obj(obj.to_owned()).unwrap();
+	convert::convert_obj_set(set, &options)
+}
+
+/// The only part of the only object of `obj`
+pub fn convert_one(obj: &str, options: &ConvertOptions) -> Vec<u8> {
+	let mut meshes = convert(obj, options);
+	assert_eq!((meshes.len(), meshes[0].parts.len()), (1, 1));
+	meshes.remove(0).parts.remove(0)
+}
+
+/// Where the chunks of a chunked file end, the only places it can be cut and still be read
+pub fn chunk_ends(file: &[u8]) -> Vec<usize> {
+	let mut ends = vec![];
+	let mut at = 8;
+	while at < file.len() {
+		at += 8 + u32::from_le_bytes([file[at + 4], file[at + 5], file[at + 6], file[at + 7]]) as usize;
+		ends.push(at);
+	}
+	ends
+}
+
+pub fn read(part: &[u8]) -> MeshData {
+	reader::read_mesh(&mut &part[..]).unwrap()
+}
+
+/// Checks that two readings of the same conversion have the same vertices and triangles
+pub fn assert_same_geometry(a: &MeshData, b: &MeshData) {
+	assert_eq!(a.positions, b.positions);
+	assert_eq!(a.normals, b.normals);
+	assert_eq!(a.tangents, b.tangents);
+	assert_eq!(a.colors, b.colors);
+	assert_eq!(a.tex0, b.tex0);
+	assert_eq!(a.tex1, b.tex1);
+	//the meshopt codec can rotate the corners of the triangles
+	let triangles = |indices: &[u32]| -> Vec<[u32; 3]> {
+		indices.chunks(3).map(|t| {
+			let first = (0..3).min_by_key(|&i| t[i]).unwrap();
+			[t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+		}).collect()
+	};
+	assert!(triangles(&a.indices) == triangles(&b.indices), "{} and {} indices differ", a.indices.len(), b.indices.len());
+	assert_eq!(a.shadow_indices.as_deref().map(triangles), b.shadow_indices.as_deref().map(triangles));
+	let ranges = |mesh: &MeshData| -> Vec<(u32, u32)> { mesh.submeshes.iter().map(|s| (s.first_index, s.index_count)).collect() };
+	assert_eq!(ranges(a), ranges(b));
+}
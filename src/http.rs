@@ -0,0 +1,321 @@
+//! The part of HTTP/1.1 `obj_to_mesh serve` needs: requests with a `Content-Length`, their query strings and
+//! `multipart/form-data` bodies, and responses closing the connection. Chunked bodies aren't supported.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+/// Longer request and header lines are refused
+const MAX_LINE: u64 = 8192;
+const MAX_HEADERS: usize = 100;
+
+#[derive(Debug)]
+pub enum HttpError {
+	Io(io::Error),
+	Malformed(String),
+	/// A body without a `Content-Length`
+	LengthRequired,
+	/// A body past the limit of the server
+	TooLarge(usize),
+}
+
+impl HttpError {
+	/// The status to answer with
+	pub fn status(&self) -> u16 {
+		match self {
+			HttpError::Io(_) | HttpError::Malformed(_) => 400,
+			HttpError::LengthRequired => 411,
+			HttpError::TooLarge(_) => 413,
+		}
+	}
+}
+
+impl fmt::Display for HttpError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			HttpError::Io(err) => write!(f, "{}", err),
+			HttpError::Malformed(what) => write!(f, "Malformed request: {}", what),
+			HttpError::LengthRequired => write!(f, "The body needs a Content-Length"),
+			HttpError::TooLarge(limit) => write!(f, "The body is larger than {} bytes", limit),
+		}
+	}
+}
+
+impl From<io::Error> for HttpError {
+	fn from(err: io::Error) -> Self {
+		HttpError::Io(err)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+	pub method: String,
+	/// Without the query
+	pub path: String,
+	/// Decoded, in order
+	pub query: Vec<(String, String)>,
+	/// With the names in lowercase
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+}
+
+impl Request {
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+	}
+
+	/// Whether the client waits for `100 Continue` before sending the body, like curl does for large uploads
+	pub fn expects_continue(&self) -> bool {
+		self.header("expect").is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"))
+	}
+}
+
+/// A line without its CRLF, or None at the end of the input
+fn read_line<R: BufRead>(input: &mut R) -> Result<Option<String>, HttpError> {
+	let mut line = vec![];
+	if input.take(MAX_LINE).read_until(b'\n', &mut line)? == 0 {
+		return Ok(None);
+	}
+	if line.last() != Some(&b'\n') {
+		return Err(HttpError::Malformed("line too long".to_owned()));
+	}
+	line.pop();
+	if line.last() == Some(&b'\r') {
+		line.pop();
+	}
+	String::from_utf8(line).map(Some).map_err(|_| HttpError::Malformed("the line isn't UTF-8".to_owned()))
+}
+
+/// Decodes the `%XX` escapes, and the `+` of the forms as spaces
+pub fn decode_component(text: &str) -> String {
+	let bytes = text.as_bytes();
+	let mut out = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let escaped = bytes.get(i + 1..i + 3)
+			.and_then(|hex| std::str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match (bytes[i], escaped) {
+			(b'+', _) => out.push(b' '),
+			(b'%', Some(byte)) => {
+				out.push(byte);
+				i += 2;
+			},
+			(b, _) => out.push(b),
+		}
+		i += 1;
+	}
+	String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The request line and the headers; the body is read by `read_body`. None when the connection closed first
+pub fn read_head<R: BufRead>(input: &mut R) -> Result<Option<Request>, HttpError> {
+	let line = match read_line(input)? {
+		Some(line) => line,
+		None => return Ok(None),
+	};
+	let mut parts = line.split(' ');
+	let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+		(Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => (method, target),
+		_ => return Err(HttpError::Malformed(format!("request line {}", line))),
+	};
+
+	let (path, query) = target.split_once('?').unwrap_or((target, ""));
+	let query = query.split('&').filter(|pair| !pair.is_empty()).map(|pair| {
+		let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+		(decode_component(name), decode_component(value))
+	}).collect();
+
+	let mut headers = vec![];
+	loop {
+		let line = match read_line(input)? {
+			Some(line) => line,
+			None => return Err(HttpError::Malformed("the headers end early".to_owned())),
+		};
+		if line.is_empty() {
+			break;
+		}
+		if headers.len() == MAX_HEADERS {
+			return Err(HttpError::Malformed("too many headers".to_owned()));
+		}
+		match line.split_once(':') {
+			Some((name, value)) => headers.push((name.trim().to_ascii_lowercase(), value.trim().to_owned())),
+			None => return Err(HttpError::Malformed(format!("header {}", line))),
+		}
+	}
+
+	Ok(Some(Request {
+		method: method.to_owned(),
+		path: decode_component(path),
+		query,
+		headers,
+		body: vec![],
+	}))
+}
+
+/// The length of the body announced by the headers, if it's at most `limit` bytes
+pub fn body_length(request: &Request, limit: usize) -> Result<usize, HttpError> {
+	if request.header("transfer-encoding").is_some() {
+		return Err(HttpError::LengthRequired);
+	}
+	let length = match request.header("content-length") {
+		Some(length) => length.parse::<usize>().map_err(|_| HttpError::Malformed(format!("Content-Length {}", length)))?,
+		None => return Ok(0),
+	};
+	if length > limit {
+		return Err(HttpError::TooLarge(limit));
+	}
+	Ok(length)
+}
+
+/// Reads the body announced by the headers, up to `limit` bytes
+pub fn read_body<R: BufRead>(input: &mut R, request: &mut Request, limit: usize) -> Result<(), HttpError> {
+	let length = body_length(request, limit)?;
+	request.body.clear();
+	input.take(length as u64).read_to_end(&mut request.body)?;
+	if request.body.len() != length {
+		return Err(HttpError::Malformed("the body ends early".to_owned()));
+	}
+	Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+	match status {
+		100 => "Continue",
+		200 => "OK",
+		400 => "Bad Request",
+		404 => "Not Found",
+		405 => "Method Not Allowed",
+		411 => "Length Required",
+		413 => "Payload Too Large",
+		422 => "Unprocessable Entity",
+		503 => "Service Unavailable",
+		_ => "Internal Server Error",
+	}
+}
+
+pub fn write_continue<W: Write>(out: &mut W) -> io::Result<()> {
+	out.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+	out.flush()
+}
+
+/// A response closing the connection
+pub fn write_response<W: Write>(out: &mut W, status: u16, headers: &[(&str, &str)], body: &[u8]) -> io::Result<()> {
+	let mut head = format!("HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, reason(status), body.len());
+	for (name, value) in headers {
+		head += &format!("{}: {}\r\n", name, value);
+	}
+	head += "\r\n";
+	out.write_all(head.as_bytes())?;
+	out.write_all(body)?;
+	out.flush()
+}
+
+/// The value of a `name=value` parameter of a header, like the boundary of a content type
+fn parameter<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+	header.split(';').skip(1).find_map(|param| {
+		let (key, value) = param.trim().split_once('=')?;
+		key.trim().eq_ignore_ascii_case(name).then(|| value.trim().trim_matches('"'))
+	})
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// An uploaded file of a `multipart/form-data` body: the first part with a file name, or else the one named
+/// `file`, with its file name
+pub fn multipart_file<'a>(body: &'a [u8], content_type: &str) -> Option<(Option<String>, &'a [u8])> {
+	let boundary = parameter(content_type, "boundary")?;
+	let delimiter = format!("\r\n--{}", boundary);
+
+	//the first delimiter has no CRLF before it, unless there is a preamble
+	let mut rest = if body.starts_with(&delimiter.as_bytes()[2..]) {
+		&body[delimiter.len() - 2..]
+	}
+	else {
+		&body[find(body, delimiter.as_bytes())? + delimiter.len()..]
+	};
+
+	let mut named_file = None;
+	//each part starts after the CRLF of the delimiter, and the last delimiter is followed by --
+	while let Some(part) = rest.strip_prefix(b"\r\n") {
+		let end = find(part, delimiter.as_bytes())?;
+		let (headers, content) = part[..end].split_at(find(&part[..end], b"\r\n\r\n")? + 4);
+		let headers = String::from_utf8_lossy(headers);
+		let disposition = headers.lines()
+			.filter_map(|line| line.split_once(':'))
+			.find(|(name, _)| name.trim().eq_ignore_ascii_case("content-disposition"))
+			.map(|(_, value)| value.trim());
+		if let Some(disposition) = disposition {
+			if let Some(file_name) = parameter(disposition, "filename") {
+				return Some((Some(file_name.to_owned()), content));
+			}
+			if parameter(disposition, "name") == Some("file") && named_file.is_none() {
+				named_file = Some((None, content));
+			}
+		}
+		rest = &part[end + delimiter.len()..];
+	}
+	named_file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn head(text: &str) -> Result<Option<Request>, HttpError> {
+		read_head(&mut text.as_bytes())
+	}
+
+	fn status(result: Result<impl fmt::Debug, HttpError>) -> u16 {
+		result.unwrap_err().status()
+	}
+
+	#[test]
+	fn request_line() {
+		let request = head("POST /convert?flat&crease-angle=30&only=a%20b+c HTTP/1.1\r\nContent-Length: 3\r\nX-Name:  value \r\n\r\n").unwrap().unwrap();
+		assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/convert"));
+		assert_eq!(request.query, [("flat".to_owned(), String::new()), ("crease-angle".to_owned(), "30".to_owned()), ("only".to_owned(), "a b c".to_owned())]);
+		assert_eq!((request.header("content-length"), request.header("x-name")), (Some("3"), Some("value")));
+		assert_eq!(head("").unwrap(), None);
+
+		for line in ["GET\r\n\r\n", "GET /\r\n\r\n", "GET / SPDY/3\r\n\r\n", "\r\n\r\n", "GET / HTTP/1.1\r\nno colon\r\n\r\n", "GET / HTTP/1.1\r\n"] {
+			assert_eq!(status(head(line)), 400, "{:?}", line);
+		}
+		assert_eq!(status(read_head(&mut &[b'G', b'E', b'T', b' ', 0xff, b'\n'][..])), 400);
+		let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE as usize));
+		assert_eq!(status(head(&long)), 400);
+		let many = format!("GET / HTTP/1.1\r\n{}\r\n", "A: b\r\n".repeat(MAX_HEADERS + 1));
+		assert_eq!(status(head(&many)), 400);
+	}
+
+	#[test]
+	fn content_length() {
+		let body = |headers: &str, rest: &str, limit: usize| {
+			let text = format!("POST /convert HTTP/1.1\r\n{}\r\n{}", headers, rest);
+			let mut input = text.as_bytes();
+			let mut request = read_head(&mut input).unwrap().unwrap();
+			read_body(&mut input, &mut request, limit).map(|()| (request.body, input.to_vec()))
+		};
+		//only the announced bytes are read
+		assert_eq!(body("Content-Length: 3\r\n", "abcdef", 10).unwrap(), (b"abc".to_vec(), b"def".to_vec()));
+		assert_eq!(body("Content-Length: 0\r\n", "", 10).unwrap().0, b"");
+		assert_eq!(body("", "", 10).unwrap().0, b"");
+		assert_eq!(status(body("Content-Length: 11\r\n", "", 10)), 413);
+		assert_eq!(status(body("Content-Length: 10\r\n", "abc", 10)), 400);
+		assert_eq!(status(body("Content-Length: -1\r\n", "", 10)), 400);
+		assert_eq!(status(body("Content-Length: 99999999999999999999999\r\n", "", 10)), 400);
+		assert_eq!(status(body("Transfer-Encoding: chunked\r\n", "3\r\nabc\r\n0\r\n\r\n", 10)), 411);
+	}
+
+	#[test]
+	fn multipart() {
+		let body = b"preamble\r\n--xyz\r\nContent-Disposition: form-data; name=\"flat\"\r\n\r\ntrue\r\n--xyz\r\n\
+			Content-Disposition: form-data; name=\"file\"; filename=\"crate.obj\"\r\nContent-Type: text/plain\r\n\r\nv 0 0 0\r\n--xyz--\r\n";
+		assert_eq!(multipart_file(body, "multipart/form-data; boundary=xyz"), Some((Some("crate.obj".to_owned()), &b"v 0 0 0"[..])));
+		let body = b"--xyz\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nv 1 1 1\r\n--xyz--\r\n";
+		assert_eq!(multipart_file(body, "multipart/form-data; boundary=\"xyz\""), Some((None, &b"v 1 1 1"[..])));
+		assert_eq!(multipart_file(body, "multipart/form-data"), None);
+		assert_eq!(multipart_file(b"--xyz\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nv 1", "multipart/form-data; boundary=xyz"), None);
+	}
+}
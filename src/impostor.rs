@@ -0,0 +1,223 @@
+//! Octahedral impostors: a mesh rendered from many directions into an atlas, drawn on a camera facing card in
+//! place of the mesh at the largest distances, like for vegetation.
+//!
+//! The directions are the centers of a grid of `frames` x `frames` laid on an octahedron unfolded into a square,
+//! covering the whole sphere, with Y up. Each frame is an orthographic view of the bounding sphere towards its
+//! center, and the runtime picks and blends the frames closest to the view direction.
+
+use wavefront_obj::obj::{Object, Shape, Vertex};
+use crate::colors::VertexColors;
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImpostorSettings {
+	/// Directions along each side of the atlas
+	pub frames: u32,
+	/// Side of each frame in texels
+	pub resolution: u32,
+}
+
+impl Default for ImpostorSettings {
+	fn default() -> Self {
+		ImpostorSettings {
+			frames: 8,
+			resolution: 64,
+		}
+	}
+}
+
+/// RGBA8 images of `side` x `side` texels, row by row from the top
+#[derive(Debug, Clone)]
+pub struct ImpostorAtlas {
+	pub side: u32,
+	/// The vertex colors, or white, with the coverage in alpha
+	pub albedo: Vec<u8>,
+	/// The object space normals mapped to 0..1, with the depth towards the camera in alpha
+	pub normal: Vec<u8>,
+	pub center: Vertex,
+	pub radius: f64,
+}
+
+/// The direction from the center towards the camera of a point of the unfolded octahedron, in -1..1
+pub fn octahedron_direction(u: f64, v: f64) -> Vertex {
+	let mut n = Vertex { x: u, y: v, z: 1.0 - u.abs() - v.abs() };
+	if n.z < 0.0 {
+		//the lower half is folded over the corners
+		let (x, y) = (n.x, n.y);
+		n.x = (1.0 - y.abs()) * x.signum();
+		n.y = (1.0 - x.abs()) * y.signum();
+	}
+	normalize(Vertex { x: n.x, y: n.z, z: n.y })
+}
+
+/// The right and up axes of the frame looking along `-direction`
+fn view_axes(direction: Vertex) -> (Vertex, Vertex) {
+	let up = if direction.y.abs() > 0.999 { Vertex { x: 0.0, y: 0.0, z: 1.0 } } else { Vertex { x: 0.0, y: 1.0, z: 0.0 } };
+	let right = normalize(cross(up, direction));
+	(right, cross(direction, right))
+}
+
+/// A corner of a triangle projected on a frame: texel coordinates, depth, normal and color
+#[derive(Clone, Copy)]
+struct Corner {
+	x: f64,
+	y: f64,
+	depth: f64,
+	normal: Vertex,
+	color: [f64; 4],
+}
+
+fn to_byte(value: f64) -> u8 {
+	(value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Renders every frame of the atlas of `obj`; `colors` are the vertex colors of the positions
+pub fn render_impostor(obj: &Object, colors: Option<&VertexColors>, settings: &ImpostorSettings) -> ImpostorAtlas {
+	assert!(settings.frames > 0 && settings.resolution > 0, "Impostors need at least one frame of one texel");
+	let side = settings.frames * settings.resolution;
+	let mut atlas = ImpostorAtlas {
+		side,
+		albedo: vec![0; (side * side * 4) as usize],
+		normal: vec![0; (side * side * 4) as usize],
+		center: Vertex { x: 0.0, y: 0.0, z: 0.0 },
+		radius: 0.0,
+	};
+	if obj.vertices.is_empty() {
+		return atlas;
+	}
+
+	let min = obj.vertices.iter().fold(obj.vertices[0], |m, &v| vert_min(m, v));
+	let max = obj.vertices.iter().fold(obj.vertices[0], |m, &v| vert_max(m, v));
+	atlas.center = mul(Vertex { x: min.x + max.x, y: min.y + max.y, z: min.z + max.z }, 0.5);
+	atlas.radius = obj.vertices.iter().fold(0.0, |r, &v| flt_max(r, lenght(sub(v, atlas.center))));
+	if atlas.radius == 0.0 {
+		return atlas;
+	}
+
+	let triangles: Vec<[(usize, Option<usize>); 3]> = obj.geometry.iter()
+		.flat_map(|geo| &geo.shapes)
+		.filter_map(|shape| match *shape {
+			Shape::Triangle(a, b, c) => Some([(a.0, a.2), (b.0, b.2), (c.0, c.2)]),
+			_ => None,
+		})
+		.collect();
+
+	let resolution = settings.resolution as f64;
+	for frame_y in 0..settings.frames {
+		for frame_x in 0..settings.frames {
+			let u = (frame_x as f64 + 0.5) / settings.frames as f64 * 2.0 - 1.0;
+			let v = (frame_y as f64 + 0.5) / settings.frames as f64 * 2.0 - 1.0;
+			let direction = octahedron_direction(u, v);
+			let (right, up) = view_axes(direction);
+
+			let mut depths = vec![f64::NEG_INFINITY; (settings.resolution * settings.resolution) as usize];
+			for triangle in &triangles {
+				let face_normal = {
+					let [a, b, c] = triangle.map(|(p, _)| obj.vertices[p]);
+					normalize(cross(sub(b, a), sub(c, a)))
+				};
+				let corners = triangle.map(|(p, n)| {
+					let d = sub(obj.vertices[p], atlas.center);
+					Corner {
+						x: (dot(d, right) / atlas.radius * 0.5 + 0.5) * resolution,
+						//rows go down
+						y: (0.5 - dot(d, up) / atlas.radius * 0.5) * resolution,
+						depth: dot(d, direction) / atlas.radius,
+						normal: n.map_or(face_normal, |n| obj.normals[n]),
+						color: colors.and_then(|c| c.colors.get(p)).copied().unwrap_or([1.0; 4]),
+					}
+				});
+				rasterize(&corners, settings.resolution, |x, y, w| {
+					let depth = w[0] * corners[0].depth + w[1] * corners[1].depth + w[2] * corners[2].depth;
+					let local = (y * settings.resolution + x) as usize;
+					if depth <= depths[local] {
+						return;
+					}
+					depths[local] = depth;
+
+					let mut normal = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+					let mut color = [0.0; 4];
+					for (corner, w) in corners.iter().zip(w) {
+						addmut(&mut normal, mul(corner.normal, w));
+						for (c, value) in color.iter_mut().zip(corner.color) {
+							*c += value * w;
+						}
+					}
+					let normal = if lenght(normal) > 0.0 { normalize(normal) } else { face_normal };
+
+					let texel = (((frame_y * settings.resolution + y) * side + frame_x * settings.resolution + x) * 4) as usize;
+					atlas.albedo[texel..texel + 4].copy_from_slice(&[to_byte(color[0]), to_byte(color[1]), to_byte(color[2]), 255]);
+					atlas.normal[texel..texel + 4].copy_from_slice(&[
+						to_byte(normal.x * 0.5 + 0.5),
+						to_byte(normal.y * 0.5 + 0.5),
+						to_byte(normal.z * 0.5 + 0.5),
+						to_byte(depth * 0.5 + 0.5),
+					]);
+				});
+			}
+		}
+	}
+	atlas
+}
+
+/// Calls `texel` with the barycentric weights of every texel center of the frame inside the triangle, of either winding
+fn rasterize(corners: &[Corner; 3], resolution: u32, mut texel: impl FnMut(u32, u32, [f64; 3])) {
+	let [a, b, c] = corners;
+	let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+	if area == 0.0 {
+		return;
+	}
+
+	let last = resolution as f64 - 1.0;
+	let x0 = flt_min(flt_min(a.x, b.x), c.x).floor().clamp(0.0, last) as u32;
+	let x1 = flt_max(flt_max(a.x, b.x), c.x).ceil().clamp(0.0, last) as u32;
+	let y0 = flt_min(flt_min(a.y, b.y), c.y).floor().clamp(0.0, last) as u32;
+	let y1 = flt_max(flt_max(a.y, b.y), c.y).ceil().clamp(0.0, last) as u32;
+	let edge = |p: &Corner, q: &Corner, x: f64, y: f64| ((q.x - p.x) * (y - p.y) - (x - p.x) * (q.y - p.y)) / area;
+
+	for y in y0..=y1 {
+		for x in x0..=x1 {
+			let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+			let w = [edge(b, c, px, py), edge(c, a, px, py), edge(a, b, px, py)];
+			if w.iter().all(|&w| w >= 0.0) {
+				texel(x, y, w);
+			}
+		}
+	}
+}
+
+/// An uncompressed 32 bit TGA file of an atlas image
+pub fn write_tga(side: u32, rgba: &[u8]) -> Vec<u8> {
+	let mut out = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+	out.extend((side as u16).to_le_bytes());
+	out.extend((side as u16).to_le_bytes());
+	//8 bits of alpha, top to bottom
+	out.extend([32, 0x28]);
+	for texel in rgba.chunks(4) {
+		out.extend([texel[2], texel[1], texel[0], texel[3]]);
+	}
+	out
+}
+
+/// The card the atlas is drawn on: a quad as large as the bounding sphere around its center, facing +Z with its
+/// texture coordinates across one frame, for the runtime to turn towards the camera
+pub fn card_object(atlas: &ImpostorAtlas) -> Object {
+	use wavefront_obj::obj::{Geometry, TVertex};
+	let (c, r) = (atlas.center, atlas.radius);
+	let shape = |a: usize, b: usize, d: usize| Shape::Triangle((a, Some(a), Some(0)), (b, Some(b), Some(0)), (d, Some(d), Some(0)));
+	Object {
+		name: "impostor".to_owned(),
+		vertices: [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter()
+			.map(|&(x, y)| Vertex { x: c.x + x * r, y: c.y + y * r, z: c.z })
+			.collect(),
+		tex_vertices: [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter()
+			.map(|&(x, y)| TVertex { x, y })
+			.collect(),
+		normals: vec![Vertex { x: 0.0, y: 0.0, z: 1.0 }],
+		geometry: vec![Geometry {
+			material_name: None,
+			smooth_shading_group: 0,
+			shapes: vec![shape(0, 1, 2), shape(0, 2, 3)],
+		}],
+	}
+}
@@ -0,0 +1,172 @@
+//! Detection of the objects repeating the geometry of another one, moved and rotated, like the props of
+//! kit-bashed scenes, so that the geometry can be stored once with a list of placements.
+//!
+//! Duplicates must have their vertices and faces in the same order, as exporters write copies of the same prop.
+
+use wavefront_obj::obj::{ObjSet, Object, Vertex};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crate::math::*;
+
+/// Of the distances between the matching positions, relative to the size of the object
+const POSITION_TOLERANCE: f64 = 1e-4;
+const NORMAL_TOLERANCE: f64 = 1e-3;
+const UV_TOLERANCE: f64 = 1e-6;
+
+/// A rotation followed by a translation
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+	/// Rows of the rotation matrix
+	pub rotation: [Vertex; 3],
+	pub translation: Vertex,
+}
+
+impl Transform {
+	pub fn identity() -> Transform {
+		Transform {
+			rotation: [
+				Vertex { x: 1.0, y: 0.0, z: 0.0 },
+				Vertex { x: 0.0, y: 1.0, z: 0.0 },
+				Vertex { x: 0.0, y: 0.0, z: 1.0 },
+			],
+			translation: Vertex { x: 0.0, y: 0.0, z: 0.0 },
+		}
+	}
+
+	pub fn rotate(&self, v: Vertex) -> Vertex {
+		Vertex { x: dot(self.rotation[0], v), y: dot(self.rotation[1], v), z: dot(self.rotation[2], v) }
+	}
+
+	pub fn apply(&self, v: Vertex) -> Vertex {
+		let mut result = self.rotate(v);
+		addmut(&mut result, self.translation);
+		result
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Instance {
+	/// Index of the object in the set
+	pub object: usize,
+	/// From the prototype to this object
+	pub transform: Transform,
+}
+
+/// Objects with the same geometry
+#[derive(Debug, Clone)]
+pub struct InstanceGroup {
+	/// The first of the objects, whose geometry is kept
+	pub prototype: usize,
+	/// Every object of the group, starting with the prototype and its identity transform
+	pub instances: Vec<Instance>,
+}
+
+/// Hashes what a rigid transform doesn't change, to only compare the objects that can match
+fn fingerprint(obj: &Object) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	obj.vertices.len().hash(&mut hasher);
+	obj.tex_vertices.len().hash(&mut hasher);
+	obj.normals.len().hash(&mut hasher);
+	for geo in &obj.geometry {
+		geo.material_name.hash(&mut hasher);
+		geo.shapes.hash(&mut hasher);
+	}
+	hasher.finish()
+}
+
+fn centroid(points: &[Vertex]) -> Vertex {
+	let mut center = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+	for &p in points {
+		addmut(&mut center, mul(p, 1.0 / points.len() as f64));
+	}
+	center
+}
+
+/// An orthonormal frame from the farthest position from the center, and the one farthest from that direction,
+/// picked by index so that the same positions make the frame of the other object
+fn frame(points: &[Vertex], center: Vertex, first: usize, second: usize) -> [Vertex; 3] {
+	let x = normalize(sub(points[first], center));
+	let d = sub(points[second], center);
+	let y = if lenght(cross(x, d)) > 0.0 { normalize(sub(d, mul(x, dot(d, x)))) } else { any_orthogonal(x) };
+	[x, y, cross(x, y)]
+}
+
+/// The transform moving the positions of `from` onto the matching ones of `to`, if there is one
+pub fn rigid_transform(from: &Object, to: &Object) -> Option<Transform> {
+	if from.vertices.len() != to.vertices.len() || from.vertices.is_empty() {
+		return None;
+	}
+	let (a, b) = (&from.vertices[..], &to.vertices[..]);
+	let (ca, cb) = (centroid(a), centroid(b));
+
+	let distance = |i: usize| lenght(sub(a[i], ca));
+	let first = (0..a.len()).max_by(|&i, &j| distance(i).partial_cmp(&distance(j)).unwrap()).unwrap();
+	let size = distance(first);
+	let tolerance = POSITION_TOLERANCE * flt_max(size, 1.0);
+
+	let rotation = if size > 0.0 {
+		let axis = normalize(sub(a[first], ca));
+		let off_axis = |i: usize| lenght(cross(axis, sub(a[i], ca)));
+		let second = (0..a.len()).max_by(|&i, &j| off_axis(i).partial_cmp(&off_axis(j)).unwrap()).unwrap();
+		let fa = frame(a, ca, first, second);
+		let fb = frame(b, cb, first, second);
+		//fb * transpose(fa)
+		let row = |pick: fn(Vertex) -> f64| {
+			let mut row = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+			for k in 0..3 {
+				addmut(&mut row, mul(fa[k], pick(fb[k])));
+			}
+			row
+		};
+		[row(|v| v.x), row(|v| v.y), row(|v| v.z)]
+	}
+	else {
+		Transform::identity().rotation
+	};
+
+	let mut transform = Transform { rotation, translation: Vertex { x: 0.0, y: 0.0, z: 0.0 } };
+	transform.translation = sub(cb, transform.rotate(ca));
+
+	//mirrored or deformed copies fail here
+	let positions = a.iter().zip(b).all(|(&p, &q)| lenght(sub(transform.apply(p), q)) <= tolerance);
+	let normals = from.normals.len() == to.normals.len() && from.normals.iter().zip(&to.normals)
+		.all(|(&n, &m)| lenght(sub(transform.rotate(n), m)) <= NORMAL_TOLERANCE * flt_max(lenght(m), 1.0));
+	let uvs = from.tex_vertices.len() == to.tex_vertices.len() && from.tex_vertices.iter().zip(&to.tex_vertices)
+		.all(|(t, u)| (t.x - u.x).abs() <= UV_TOLERANCE && (t.y - u.y).abs() <= UV_TOLERANCE);
+	let faces = from.geometry.len() == to.geometry.len() && from.geometry.iter().zip(&to.geometry)
+		.all(|(g, h)| g.material_name == h.material_name && g.shapes == h.shapes);
+
+	if positions && normals && uvs && faces { Some(transform) } else { None }
+}
+
+/// Groups the objects of the set that are rigid copies of each other; `compatible` can reject pairs of objects
+/// for the data that isn't in the obj, like per vertex weights. Every object is in exactly one group
+pub fn find_instances(set: &ObjSet, compatible: impl Fn(usize, usize) -> bool) -> Vec<InstanceGroup> {
+	let mut groups: Vec<InstanceGroup> = vec![];
+	//fingerprint => the groups with it
+	let mut candidates: HashMap<u64, Vec<usize>> = HashMap::new();
+
+	for (i, obj) in set.objects.iter().enumerate() {
+		let bucket = candidates.entry(fingerprint(obj)).or_default();
+		let found = bucket.iter().find_map(|&g| {
+			let prototype = groups[g].prototype;
+			if !compatible(prototype, i) {
+				return None;
+			}
+			rigid_transform(&set.objects[prototype], obj).map(|transform| (g, transform))
+		});
+
+		match found {
+			Some((g, transform)) => groups[g].instances.push(Instance { object: i, transform }),
+			None => {
+				bucket.push(groups.len());
+				groups.push(InstanceGroup {
+					prototype: i,
+					instances: vec![Instance { object: i, transform: Transform::identity() }],
+				});
+			},
+		}
+	}
+	groups
+}
@@ -0,0 +1,304 @@
+//! The conversions listed in a job file, for `obj_to_mesh run`.
+//!
+//! The file is YAML, with a `jobs` sequence and optionally `profiles`, the named sets of options the jobs can
+//! start from:
+//!
+//! ```yaml
+//! profiles:
+//!   game:
+//!     gen-tangents: true
+//!     uv-overflow: clamp
+//! jobs:
+//!   - name: crate
+//!     input: props/crate.obj
+//!     output: build/crate.mesh
+//!     profile: game
+//!     options:
+//!       exclude: ["*_collision", "*_helper"]
+//!   - input: [props/barrel.obj, props/lamp.obj]
+//!     output: build/
+//!     profile: game
+//!     after: crate
+//! ```
+//!
+//! The options are the long names of the command line ones without the dashes: `true` passes a flag, `false`
+//! leaves it out, a sequence repeats the option. Those of the job replace those of its profiles with the same
+//! name. A job only runs once the ones of its `after` succeeded; its name defaults to its input. The jobs without a
+//! `profile` can get a default one, like that of `OBJ_TO_MESH_PROFILE`.
+
+use wavefront_obj::ParseError;
+use crate::yaml::{self, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+	pub name: String,
+	pub line: usize,
+	pub inputs: Vec<String>,
+	pub output: Option<String>,
+	/// The options of the profiles then the ones of the job, with their values; none for the flags
+	pub options: Vec<(String, Vec<String>)>,
+	/// The indices of the jobs that have to succeed first
+	pub after: Vec<usize>,
+}
+
+impl Job {
+	/// The command line of the conversion, without the program
+	pub fn args(&self) -> Vec<String> {
+		command_line(&self.inputs, self.output.as_deref(), &self.options)
+	}
+}
+
+/// The command line converting the inputs with the options, without the program; the options without values are
+/// flags
+pub fn command_line(inputs: &[String], output: Option<&str>, options: &[(String, Vec<String>)]) -> Vec<String> {
+	let mut args = inputs.to_vec();
+	if let Some(output) = output {
+		args.push("-o".to_owned());
+		args.push(output.to_owned());
+	}
+	for (name, values) in options {
+		if values.is_empty() {
+			args.push(format!("--{}", name));
+		}
+		for value in values {
+			args.push(format!("--{}", name));
+			args.push(value.clone());
+		}
+	}
+	args
+}
+
+fn error<T>(line_number: usize, message: String) -> Result<T, ParseError> {
+	Err(ParseError {
+		line_number,
+		message,
+	})
+}
+
+/// A scalar, or a sequence of scalars
+fn strings(line: usize, key: &str, value: &Value) -> Result<Vec<String>, ParseError> {
+	match value {
+		Value::Scalar(text) => Ok(vec![text.clone()]),
+		Value::Sequence(items) => items.iter().map(|item| match item {
+			Value::Scalar(text) => Ok(text.clone()),
+			_ => error(line, format!("The values of {} have to be strings", key)),
+		}).collect(),
+		_ => error(line, format!("{} needs a value", key)),
+	}
+}
+
+fn mapping<'a>(line: usize, key: &str, value: &'a Value) -> Result<&'a [(String, usize, Value)], ParseError> {
+	match value {
+		Value::Mapping(members) => Ok(members),
+		Value::Null => Ok(&[]),
+		_ => error(line, format!("{} has to be a mapping", key)),
+	}
+}
+
+/// Adds the options of a profile or job, replacing the ones with the same name
+fn add_options(options: &mut Vec<(String, Vec<String>)>, members: &[(String, usize, Value)]) -> Result<(), ParseError> {
+	for (name, line, value) in members {
+		let name = name.trim_start_matches('-').replace('_', "-");
+		options.retain(|(other, _)| *other != name);
+		match value {
+			Value::Scalar(flag) if flag == "true" => options.push((name, vec![])),
+			Value::Scalar(flag) if flag == "false" => {},
+			value => options.push((name.clone(), strings(*line, &name, value)?)),
+		}
+	}
+	Ok(())
+}
+
+/// Adds the options of the profile named `name`
+fn add_profile(options: &mut Vec<(String, Vec<String>)>, profiles: &[(String, usize, Value)], line: usize, name: &str) -> Result<(), ParseError> {
+	let Some((_, profile_line, profile)) = profiles.iter().find(|(other, _, _)| *other == name) else {
+		return error(line, format!("Unknown profile {}", name));
+	};
+	add_options(options, mapping(*profile_line, name, profile)?)
+}
+
+/// `default_profile` is the one of the jobs without a `profile`, if any
+pub fn parse_jobs(content: &str, default_profile: Option<&str>) -> Result<Vec<Job>, ParseError> {
+	let root = yaml::parse(content)?;
+	let mut profiles: &[(String, usize, Value)] = &[];
+	let mut entries: &[Value] = &[];
+	let mut jobs_line = 1;
+	for (key, line, value) in mapping(1, "The job file", &root)? {
+		match (key.as_str(), value) {
+			("profiles", value) => profiles = mapping(*line, key, value)?,
+			("jobs", Value::Sequence(items)) => {
+				entries = items;
+				jobs_line = *line;
+			},
+			("jobs", _) => return error(*line, "jobs has to be a sequence".to_owned()),
+			_ => return error(*line, format!("Unknown key {}, expected profiles or jobs", key)),
+		}
+	}
+	if entries.is_empty() {
+		return error(jobs_line, "The job file has no jobs".to_owned());
+	}
+
+	//the names of the dependencies, resolved once all the jobs are known
+	let mut jobs = vec![];
+	let mut dependencies = vec![];
+	for entry in entries {
+		let members = match entry {
+			Value::Mapping(members) if !members.is_empty() => members,
+			_ => return error(jobs_line, "Each job has to be a mapping with at least an input".to_owned()),
+		};
+		let line = members[0].1;
+		let mut job = Job {
+			name: String::new(),
+			line,
+			inputs: vec![],
+			output: None,
+			options: vec![],
+			after: vec![],
+		};
+		let mut after = vec![];
+		for (key, line, value) in members {
+			match key.as_str() {
+				"name" => job.name = strings(*line, key, value)?.join(" "),
+				"input" => job.inputs = strings(*line, key, value)?,
+				"output" => job.output = Some(strings(*line, key, value)?.join(" ")),
+				"after" => after = strings(*line, key, value)?.into_iter().map(|name| (*line, name)).collect(),
+				"options" => {},
+				"profile" => {
+					for name in strings(*line, key, value)? {
+						add_profile(&mut job.options, profiles, *line, &name)?;
+					}
+				},
+				_ => return error(*line, format!("Unknown key {}, expected name, input, output, profile, options or after", key)),
+			}
+		}
+		if let (Some(name), false) = (default_profile, members.iter().any(|(key, _, _)| key == "profile")) {
+			add_profile(&mut job.options, profiles, line, name)?;
+		}
+		//after the profiles, wherever it is
+		if let Some((key, line, value)) = members.iter().find(|(key, _, _)| key == "options") {
+			add_options(&mut job.options, mapping(*line, key, value)?)?;
+		}
+		if job.inputs.is_empty() {
+			return error(line, "The job has no input".to_owned());
+		}
+		if job.name.is_empty() {
+			job.name = job.inputs.join(" ");
+		}
+		if jobs.iter().any(|other: &Job| other.name == job.name) {
+			return error(line, format!("There are several jobs named {}", job.name));
+		}
+		jobs.push(job);
+		dependencies.push(after);
+	}
+
+	for (i, after) in dependencies.into_iter().enumerate() {
+		for (line, name) in after {
+			match jobs.iter().position(|job| job.name == name) {
+				Some(dependency) => jobs[i].after.push(dependency),
+				None => return error(line, format!("Unknown job {}", name)),
+			}
+		}
+	}
+
+	//a job reaching itself through its dependencies would never run
+	for start in 0..jobs.len() {
+		let mut stack = jobs[start].after.clone();
+		let mut seen = vec![false; jobs.len()];
+		while let Some(job) = stack.pop() {
+			if job == start {
+				return error(jobs[start].line, format!("{} depends on itself", jobs[start].name));
+			}
+			if !seen[job] {
+				seen[job] = true;
+				stack.extend(&jobs[job].after);
+			}
+		}
+	}
+	Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FILE: &str = "profiles:
+  game:
+    gen-tangents: true
+    uv_overflow: clamp
+    exclude: ['*_collision']
+  mobile:
+    encoding: meshopt
+    gen-tangents: false
+jobs:
+  - name: crate
+    input: props/crate.obj
+    output: build/crate.mesh
+    profile: game
+    options:
+      exclude: ['*_collision', '*_helper']
+  - input: [props/barrel.obj, props/lamp.obj]
+    output: build/
+    profile: [game, mobile]
+    after: crate
+  - input: props/rock.obj
+    options:
+      crease-angle: 30
+";
+
+	fn options(job: &Job) -> Vec<(&str, Vec<&str>)> {
+		job.options.iter().map(|(name, values)| (name.as_str(), values.iter().map(String::as_str).collect())).collect()
+	}
+
+	#[test]
+	fn profiles() {
+		let jobs = parse_jobs(FILE, None).unwrap();
+		assert_eq!(jobs.iter().map(|job| job.name.as_str()).collect::<Vec<_>>(), ["crate", "props/barrel.obj props/lamp.obj", "props/rock.obj"]);
+		//the options of the job replace those of the profile, and the later profiles those of the earlier ones
+		assert_eq!(options(&jobs[0]), [("gen-tangents", vec![]), ("uv-overflow", vec!["clamp"]), ("exclude", vec!["*_collision", "*_helper"])]);
+		assert_eq!(options(&jobs[1]), [("uv-overflow", vec!["clamp"]), ("exclude", vec!["*_collision"]), ("encoding", vec!["meshopt"])]);
+		assert_eq!(options(&jobs[2]), [("crease-angle", vec!["30"])]);
+		assert_eq!((jobs[0].after.as_slice(), jobs[1].after.as_slice()), (&[][..], &[0][..]));
+		assert_eq!(jobs[0].args(), [
+			"props/crate.obj", "-o", "build/crate.mesh", "--gen-tangents", "--uv-overflow", "clamp",
+			"--exclude", "*_collision", "--exclude", "*_helper",
+		]);
+	}
+
+	#[test]
+	fn default_profile() {
+		let jobs = parse_jobs(FILE, Some("mobile")).unwrap();
+		//only for the jobs without a profile
+		assert_eq!(options(&jobs[0]), [("gen-tangents", vec![]), ("uv-overflow", vec!["clamp"]), ("exclude", vec!["*_collision", "*_helper"])]);
+		assert_eq!(options(&jobs[2]), [("encoding", vec!["meshopt"]), ("crease-angle", vec!["30"])]);
+		let err = parse_jobs(FILE, Some("console")).unwrap_err();
+		assert_eq!((err.line_number, err.message.as_str()), (20, "Unknown profile console"));
+	}
+
+	#[test]
+	fn malformed() {
+		let cases = [
+			("", 1, "The job file has no jobs"),
+			("- a.obj\n", 1, "The job file has to be a mapping"),
+			("jobs: a.obj\n", 1, "jobs has to be a sequence"),
+			("job:\n  - input: a.obj\n", 1, "Unknown key job, expected profiles or jobs"),
+			("jobs:\n  - a.obj\n", 1, "Each job has to be a mapping with at least an input"),
+			("jobs:\n  - output: a.mesh\n", 2, "The job has no input"),
+			("jobs:\n  - input: a.obj\n    ouput: a.mesh\n", 3, "Unknown key ouput, expected name, input, output, profile, options or after"),
+			("jobs:\n  - input: a.obj\n    profile: game\n", 3, "Unknown profile game"),
+			("jobs:\n  - input: a.obj\n    options: fast\n", 3, "options has to be a mapping"),
+			("jobs:\n  - input: a.obj\n    options:\n      only:\n        a: b\n", 4, "only needs a value"),
+			("jobs:\n  - input: a.obj\n  - input: a.obj\n", 3, "There are several jobs named a.obj"),
+			("jobs:\n  - input: a.obj\n    after: b\n", 3, "Unknown job b"),
+			("jobs:\n  - input: a.obj\n    after: b.obj\n  - input: b.obj\n    after: a.obj\n", 2, "a.obj depends on itself"),
+		];
+		for (content, line_number, message) in cases {
+			match parse_jobs(content, None) {
+				Err(err) => assert_eq!((err.line_number, err.message.as_str()), (line_number, message), "{:?}", content),
+				Ok(jobs) => panic!("{:?} gave {:?}", content, jobs),
+			}
+		}
+		//the errors of the YAML itself
+		assert!(parse_jobs("jobs:\n  - input: [a.obj\n", None).is_err());
+		assert!(parse_jobs("jobs:\n\t- input: a.obj\n", None).is_err());
+	}
+}
@@ -0,0 +1,191 @@
+//! A small JSON reader for the override files written by hand and the JSON-RPC of the daemon, which reports the
+//! line of the errors, and the escaping of the strings of the files written.
+
+use std::fmt::Write;
+use wavefront_obj::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+	/// In the order of the file, with the line of each key
+	Object(Vec<(String, usize, Value)>),
+}
+
+struct Parser<'a> {
+	text: &'a [u8],
+	pos: usize,
+}
+
+impl Parser<'_> {
+	fn line(&self) -> usize {
+		1 + self.text[..self.pos].iter().filter(|&&c| c == b'\n').count()
+	}
+
+	fn error<T>(&self, message: String) -> Result<T, ParseError> {
+		Err(ParseError {
+			line_number: self.line(),
+			message,
+		})
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+			self.pos += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<u8> {
+		self.skip_whitespace();
+		self.text.get(self.pos).copied()
+	}
+
+	fn expect(&mut self, c: u8) -> Result<(), ParseError> {
+		match self.peek() {
+			Some(found) if found == c => {
+				self.pos += 1;
+				Ok(())
+			},
+			Some(found) => self.error(format!("Expected `{}` but got `{}`", c as char, found as char)),
+			None => self.error(format!("Expected `{}` but got the end of the file", c as char)),
+		}
+	}
+
+	fn value(&mut self) -> Result<Value, ParseError> {
+		match self.peek() {
+			Some(b'{') => {
+				self.pos += 1;
+				let mut members = vec![];
+				if self.peek() == Some(b'}') {
+					self.pos += 1;
+					return Ok(Value::Object(members));
+				}
+				loop {
+					//the line of the key, not of the comma before it
+					self.skip_whitespace();
+					let line = self.line();
+					let Value::String(key) = self.value()? else {
+						return self.error("Expected a key".to_owned());
+					};
+					self.expect(b':')?;
+					members.push((key, line, self.value()?));
+					match self.peek() {
+						Some(b',') => self.pos += 1,
+						_ => break,
+					}
+				}
+				self.expect(b'}')?;
+				Ok(Value::Object(members))
+			},
+			Some(b'[') => {
+				self.pos += 1;
+				let mut items = vec![];
+				if self.peek() == Some(b']') {
+					self.pos += 1;
+					return Ok(Value::Array(items));
+				}
+				loop {
+					items.push(self.value()?);
+					match self.peek() {
+						Some(b',') => self.pos += 1,
+						_ => break,
+					}
+				}
+				self.expect(b']')?;
+				Ok(Value::Array(items))
+			},
+			Some(b'"') => {
+				self.pos += 1;
+				let mut bytes = vec![];
+				loop {
+					let Some(&c) = self.text.get(self.pos) else {
+						return self.error("Unterminated string".to_owned());
+					};
+					self.pos += 1;
+					match c {
+						b'"' => break,
+						b'\\' => {
+							let escaped = self.text.get(self.pos).copied();
+							self.pos += 1;
+							match escaped {
+								Some(b'n') => bytes.push(b'\n'),
+								Some(b't') => bytes.push(b'\t'),
+								Some(b'r') => bytes.push(b'\r'),
+								Some(b'b') => bytes.push(8),
+								Some(b'f') => bytes.push(12),
+								Some(b'u') => {
+									let code = std::str::from_utf8(self.text.get(self.pos..self.pos + 4).unwrap_or(&[])).ok()
+										.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+										.and_then(char::from_u32);
+									let Some(code) = code else {
+										return self.error("Invalid \\u escape".to_owned());
+									};
+									self.pos += 4;
+									bytes.extend_from_slice(code.encode_utf8(&mut [0; 4]).as_bytes());
+								},
+								Some(c @ (b'"' | b'\\' | b'/')) => bytes.push(c),
+								_ => return self.error("Invalid escape".to_owned()),
+							}
+						},
+						c => bytes.push(c),
+					}
+				}
+				match String::from_utf8(bytes) {
+					Ok(text) => Ok(Value::String(text)),
+					Err(_) => self.error("The string isn't valid UTF-8".to_owned()),
+				}
+			},
+			Some(c) if c == b'-' || c.is_ascii_digit() => {
+				let start = self.pos;
+				while self.pos < self.text.len() && matches!(self.text[self.pos], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+					self.pos += 1;
+				}
+				let number = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+				match number.parse() {
+					Ok(value) => Ok(Value::Number(value)),
+					Err(_) => self.error(format!("Invalid number {}", number)),
+				}
+			},
+			Some(_) => {
+				for (word, value) in [("null", Value::Null), ("true", Value::Bool(true)), ("false", Value::Bool(false))] {
+					if self.text[self.pos..].starts_with(word.as_bytes()) {
+						self.pos += word.len();
+						return Ok(value);
+					}
+				}
+				self.error(format!("Unexpected `{}`", self.text[self.pos] as char))
+			},
+			None => self.error("Unexpected end of the file".to_owned()),
+		}
+	}
+}
+
+pub fn parse(content: &str) -> Result<Value, ParseError> {
+	let mut parser = Parser {
+		text: content.as_bytes(),
+		pos: 0,
+	};
+	let value = parser.value()?;
+	if parser.peek().is_some() {
+		return parser.error("Unexpected content after the end".to_owned());
+	}
+	Ok(value)
+}
+
+/// Quoted and escaped
+pub fn string(text: &str) -> String {
+	let mut out = String::from("\"");
+	for c in text.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
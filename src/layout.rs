@@ -0,0 +1,474 @@
+//! Describes how vertex attributes are laid out in the vertex buffer of a `.mesh` file.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+	Position,
+	Color,
+	Normal,
+	Tangent,
+	Bitangent,
+	Tex0,
+	Tex1,
+	BoneIndices,
+	BoneWeights,
+	Curvature,
+	MaterialId,
+	/// Declared by the user, with its own name and format
+	User(u8),
+}
+
+/// How the values of the Color attribute are encoded, which the Color flag of the header records as 1 or 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+	/// As the colors are authored and displayed, so they need to be decoded before lighting
+	#[default]
+	Srgb,
+	/// Ready for lighting, with less precision in the dark colors
+	Linear,
+}
+
+impl ColorSpace {
+	pub fn from_name(name: &str) -> Option<ColorSpace> {
+		match name {
+			"srgb" => Some(ColorSpace::Srgb),
+			"linear" => Some(ColorSpace::Linear),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ColorSpace::Srgb => "srgb",
+			ColorSpace::Linear => "linear",
+		}
+	}
+
+	pub fn to_u8(self) -> u8 {
+		match self {
+			ColorSpace::Srgb => 1,
+			ColorSpace::Linear => 2,
+		}
+	}
+}
+
+/// The sRGB transfer function, for a channel in 0..1
+#[cfg(feature = "std")]
+pub fn srgb_to_linear(c: f64) -> f64 {
+	if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// How an attribute is stored in the vertex buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Float32x3,
+	/// Signed normalized 10-10-10-2, packed little endian into a u32 (GL_INT_2_10_10_10_REV)
+	Snorm10_10_10_2,
+	Float16x2,
+	Uint8x4,
+	Unorm8x4,
+	Float32,
+	Uint32,
+	Float32x2,
+	Float32x4,
+	/// The palette indices
+	Uint8,
+	Uint16,
+	/// The quantized positions, with an unused w
+	Unorm16x4,
+}
+
+impl Format {
+	pub fn size(self) -> usize {
+		match self {
+			Format::Float32x3 => size_of::<f32>() * 3,
+			Format::Snorm10_10_10_2 => size_of::<u32>(),
+			Format::Float16x2 => size_of::<u16>() * 2,
+			Format::Uint8x4 => size_of::<u8>() * 4,
+			Format::Unorm8x4 => size_of::<u8>() * 4,
+			Format::Float32 => size_of::<f32>(),
+			Format::Uint32 => size_of::<u32>(),
+			Format::Float32x2 => size_of::<f32>() * 2,
+			Format::Float32x4 => size_of::<f32>() * 4,
+			Format::Uint8 => size_of::<u8>(),
+			Format::Uint16 => size_of::<u16>(),
+			Format::Unorm16x4 => size_of::<u16>() * 4,
+		}
+	}
+
+	/// Formats that user attributes can be declared with
+	pub fn from_name(name: &str) -> Option<Format> {
+		match name {
+			"float32" => Some(Format::Float32),
+			"float32x2" => Some(Format::Float32x2),
+			"float32x3" => Some(Format::Float32x3),
+			"float32x4" => Some(Format::Float32x4),
+			"uint8x4" => Some(Format::Uint8x4),
+			"unorm8x4" => Some(Format::Unorm8x4),
+			"uint32" => Some(Format::Uint32),
+			_ => None,
+		}
+	}
+
+	/// Code of the format in the header of a `.mesh` file
+	pub fn to_u8(self) -> u8 {
+		match self {
+			Format::Float32x3 => 0,
+			Format::Snorm10_10_10_2 => 1,
+			Format::Float16x2 => 2,
+			Format::Uint8x4 => 3,
+			Format::Unorm8x4 => 4,
+			Format::Float32 => 5,
+			Format::Uint32 => 6,
+			Format::Float32x2 => 7,
+			Format::Float32x4 => 8,
+			Format::Uint8 => 9,
+			Format::Uint16 => 10,
+			Format::Unorm16x4 => 11,
+		}
+	}
+
+	pub fn from_u8(val: u8) -> Option<Format> {
+		match val {
+			0 => Some(Format::Float32x3),
+			1 => Some(Format::Snorm10_10_10_2),
+			2 => Some(Format::Float16x2),
+			3 => Some(Format::Uint8x4),
+			4 => Some(Format::Unorm8x4),
+			5 => Some(Format::Float32),
+			6 => Some(Format::Uint32),
+			7 => Some(Format::Float32x2),
+			8 => Some(Format::Float32x4),
+			9 => Some(Format::Uint8),
+			10 => Some(Format::Uint16),
+			11 => Some(Format::Unorm16x4),
+			_ => None,
+		}
+	}
+
+	pub fn components(self) -> usize {
+		match self {
+			Format::Float32 | Format::Uint32 | Format::Uint8 | Format::Uint16 => 1,
+			Format::Float32x2 | Format::Float16x2 => 2,
+			Format::Float32x3 => 3,
+			Format::Float32x4 | Format::Uint8x4 | Format::Unorm8x4 | Format::Snorm10_10_10_2 | Format::Unorm16x4 => 4,
+		}
+	}
+}
+
+/// Location of the first user attribute; the built-in attributes keep the ones below.
+/// Vulkan and WebGPU only guarantee 16 locations
+pub const USER_LOCATION_BASE: u32 = 12;
+pub const MAX_USER_ATTRIBUTES: usize = 4;
+
+/// A vertex attribute that the converter doesn't know about, copied from a sidecar file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAttribute {
+	pub name: String,
+	pub format: Format,
+}
+
+impl Attribute {
+	/// Every attribute, in the order they are laid out in a vertex
+	pub const ALL: [Attribute; 11] = [
+		Attribute::Position,
+		Attribute::Color,
+		Attribute::Normal,
+		Attribute::Tangent,
+		Attribute::Bitangent,
+		Attribute::Tex0,
+		Attribute::Tex1,
+		Attribute::BoneIndices,
+		Attribute::BoneWeights,
+		Attribute::Curvature,
+		Attribute::MaterialId,
+	];
+
+	/// User attributes declare their format, see `UserAttribute`
+	pub fn format(self) -> Format {
+		match self {
+			Attribute::Position => Format::Float32x3,
+			Attribute::Color => Format::Unorm8x4,
+			Attribute::Normal => Format::Snorm10_10_10_2,
+			Attribute::Tangent => Format::Snorm10_10_10_2,
+			Attribute::Bitangent => Format::Snorm10_10_10_2,
+			Attribute::Tex0 => Format::Float16x2,
+			Attribute::Tex1 => Format::Float16x2,
+			Attribute::BoneIndices => Format::Uint8x4,
+			Attribute::BoneWeights => Format::Unorm8x4,
+			Attribute::Curvature => Format::Float32,
+			Attribute::MaterialId => Format::Uint32,
+			Attribute::User(_) => panic!("User attributes have no fixed format"),
+		}
+	}
+
+	/// Shader location, fixed per attribute so that shaders don't depend on which attributes are present
+	pub fn location(self) -> u32 {
+		match self {
+			Attribute::Position => 0,
+			Attribute::Normal => 1,
+			Attribute::Tangent => 2,
+			Attribute::Tex0 => 3,
+			Attribute::BoneIndices => 4,
+			Attribute::BoneWeights => 5,
+			Attribute::Curvature => 6,
+			Attribute::Tex1 => 7,
+			Attribute::MaterialId => 8,
+			Attribute::Bitangent => 9,
+			Attribute::Color => 10,
+			Attribute::User(i) => USER_LOCATION_BASE + i as u32,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Attribute::Position => "position",
+			Attribute::Color => "color",
+			Attribute::Normal => "normal",
+			Attribute::Tangent => "tangent",
+			Attribute::Bitangent => "bitangent",
+			Attribute::Tex0 => "tex0",
+			Attribute::Tex1 => "tex1",
+			Attribute::BoneIndices => "bone_indices",
+			Attribute::BoneWeights => "bone_weights",
+			Attribute::Curvature => "curvature",
+			Attribute::MaterialId => "material_id",
+			Attribute::User(_) => "user",
+		}
+	}
+}
+
+pub fn size_of_attribute(attr: Attribute) -> usize {
+	attr.format().size()
+}
+
+/// Byte offsets of the optional attributes; the position is always at offset 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexFieldOffsets {
+	pub color: Option<usize>,
+	pub normal: Option<usize>,
+	pub tangent: Option<usize>,
+	pub bitangent: Option<usize>,
+	pub tex0: Option<usize>,
+	pub tex1: Option<usize>,
+	pub bone_indices: Option<usize>,
+	pub bone_weights: Option<usize>,
+	pub curvature: Option<usize>,
+	pub material_id: Option<usize>,
+	/// Format of the position, Unorm16x4 for the quantized positions
+	pub position_format: Format,
+	/// Format of Tex0 and Tex1, Float32x2 for UVs out of the half float range
+	pub uv_format: Format,
+	pub palette: Palette,
+}
+
+/// The attributes stored as indices into tables of their values, after the other built-in attributes; the
+/// vertices are then padded to a multiple of 4 bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Palette {
+	pub color: bool,
+	pub normal: bool,
+	/// Uint16 indices instead of Uint8, for the tables of more than 256 values
+	pub wide: bool,
+}
+
+impl Palette {
+	/// The attributes that can be stored in a palette
+	pub const ATTRIBUTES: [Attribute; 2] = [Attribute::Color, Attribute::Normal];
+
+	pub fn has(&self, attr: Attribute) -> bool {
+		match attr {
+			Attribute::Color => self.color,
+			Attribute::Normal => self.normal,
+			_ => false,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		!self.color && !self.normal
+	}
+
+	pub fn index_format(&self) -> Format {
+		if self.wide { Format::Uint16 } else { Format::Uint8 }
+	}
+
+	/// The palette byte at the end of the header
+	pub fn to_u8(self) -> u8 {
+		self.color as u8 | (self.normal as u8) << 1 | (self.wide as u8) << 2
+	}
+
+	pub fn from_u8(val: u8) -> Option<Palette> {
+		if val > 7 {
+			return None;
+		}
+		Some(Palette { color: val & 1 != 0, normal: val & 2 != 0, wide: val & 4 != 0 })
+	}
+}
+
+fn get_offset(present: bool, size: usize, offset: &mut usize) -> Option<usize> {
+	let orig_offs = *offset;
+	if present {
+		*offset += size;
+		return Some(orig_offs);
+	}
+	None
+}
+
+impl VertexFieldOffsets {
+	/// Lays out the given attributes; the position is always included
+	pub fn new(attributes: &[Attribute]) -> Self {
+		VertexFieldOffsets::with_uv_format(attributes, Format::Float16x2)
+	}
+
+	pub fn with_uv_format(attributes: &[Attribute], uv_format: Format) -> Self {
+		VertexFieldOffsets::with_palette(attributes, uv_format, Palette::default())
+	}
+
+	/// With the indices of the attributes in `palette` after the others
+	pub fn with_palette(attributes: &[Attribute], uv_format: Format, palette: Palette) -> Self {
+		VertexFieldOffsets::with_formats(attributes, Format::Float32x3, uv_format, palette)
+	}
+
+	pub fn with_formats(attributes: &[Attribute], position_format: Format, uv_format: Format, palette: Palette) -> Self {
+		let mut offset = position_format.size();
+		let size = |attr| match attr {
+			Attribute::Tex0 | Attribute::Tex1 => uv_format.size(),
+			_ if palette.has(attr) => palette.index_format().size(),
+			_ => size_of_attribute(attr),
+		};
+		let mut offsets = [None; Attribute::ALL.len()];
+		for indexed in [false, true] {
+			for (i, &attr) in Attribute::ALL.iter().enumerate().skip(1) {
+				if palette.has(attr) == indexed {
+					offsets[i] = get_offset(attributes.contains(&attr), size(attr), &mut offset);
+				}
+			}
+		}
+
+		VertexFieldOffsets {
+			color: offsets[1],
+			normal: offsets[2],
+			tangent: offsets[3],
+			bitangent: offsets[4],
+			tex0: offsets[5],
+			tex1: offsets[6],
+			bone_indices: offsets[7],
+			bone_weights: offsets[8],
+			curvature: offsets[9],
+			material_id: offsets[10],
+			position_format,
+			uv_format,
+			palette,
+		}
+	}
+
+	/// The same attributes, with the values in the vertices
+	pub fn without_palette(&self) -> Self {
+		VertexFieldOffsets::with_formats(&self.attributes(), self.position_format, self.uv_format, Palette::default())
+	}
+
+	/// The same attributes, with the positions in `position_format`
+	pub fn with_position_format(&self, position_format: Format) -> Self {
+		VertexFieldOffsets::with_formats(&self.attributes(), position_format, self.uv_format, self.palette)
+	}
+
+	/// The built-in attributes present, in the order of `Attribute::ALL`
+	pub fn attributes(&self) -> Vec<Attribute> {
+		Attribute::ALL.iter().copied().filter(|&attr| self.has(attr)).collect()
+	}
+
+	/// The format of a built-in attribute in this layout
+	pub fn format(&self, attr: Attribute) -> Format {
+		match attr {
+			Attribute::Position => self.position_format,
+			Attribute::Tex0 | Attribute::Tex1 => self.uv_format,
+			_ if self.palette.has(attr) => self.palette.index_format(),
+			_ => attr.format(),
+		}
+	}
+
+	pub fn offset(&self, attr: Attribute) -> Option<usize> {
+		match attr {
+			Attribute::Position => Some(0),
+			Attribute::Color => self.color,
+			Attribute::Normal => self.normal,
+			Attribute::Tangent => self.tangent,
+			Attribute::Bitangent => self.bitangent,
+			Attribute::Tex0 => self.tex0,
+			Attribute::Tex1 => self.tex1,
+			Attribute::BoneIndices => self.bone_indices,
+			Attribute::BoneWeights => self.bone_weights,
+			Attribute::Curvature => self.curvature,
+			Attribute::MaterialId => self.material_id,
+			Attribute::User(_) => None,
+		}
+	}
+
+	pub fn has(&self, attr: Attribute) -> bool {
+		self.offset(attr).is_some()
+	}
+
+	pub fn layout(&self) -> VertexLayout {
+		let attributes: Vec<VertexAttribute> = Attribute::ALL.iter()
+			.filter_map(|&attr| self.offset(attr).map(|offset| VertexAttribute::new(attr, self.format(attr), offset)))
+			.collect();
+
+		let stride = attributes.iter().map(|a| a.offset + a.format.size()).max().unwrap();
+		//the indices can leave the attributes after them unaligned
+		let stride = if self.palette.is_empty() { stride } else { stride.next_multiple_of(4) };
+
+		VertexLayout {
+			attributes,
+			stride,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexAttribute {
+	pub attribute: Attribute,
+	pub name: String,
+	pub format: Format,
+	pub offset: usize,
+	pub location: u32,
+}
+
+impl VertexAttribute {
+	fn new(attribute: Attribute, format: Format, offset: usize) -> Self {
+		VertexAttribute {
+			attribute,
+			name: attribute.name().to_owned(),
+			format,
+			offset,
+			location: attribute.location(),
+		}
+	}
+}
+
+/// API-independent description of an interleaved vertex buffer, used to generate the layouts for each graphics API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayout {
+	pub attributes: Vec<VertexAttribute>,
+	pub stride: usize,
+}
+
+impl VertexLayout {
+	/// Appends the user attributes after the built-in ones
+	pub fn with_user_attributes(mut self, user_attributes: &[UserAttribute]) -> Self {
+		for (i, user) in user_attributes.iter().enumerate() {
+			self.attributes.push(VertexAttribute {
+				attribute: Attribute::User(i as u8),
+				name: user.name.clone(),
+				format: user.format,
+				offset: self.stride,
+				location: USER_LOCATION_BASE + i as u32,
+			});
+			self.stride += user.format.size();
+		}
+		self
+	}
+}
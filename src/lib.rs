@@ -0,0 +1,128 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate byteorder;
+extern crate half;
+#[cfg(feature = "converter")]
+extern crate wavefront_obj;
+
+#[cfg(feature = "bevy")]
+extern crate bevy_app;
+#[cfg(feature = "bevy")]
+extern crate bevy_asset;
+#[cfg(feature = "bevy")]
+extern crate bevy_mesh;
+#[cfg(feature = "bevy")]
+extern crate bevy_reflect;
+#[cfg(feature = "bevy")]
+extern crate serde;
+#[cfg(feature = "wgpu")]
+extern crate wgpu;
+
+#[cfg(feature = "converter")]
+mod math;
+#[cfg(feature = "converter")]
+mod mesh;
+#[cfg(feature = "converter")]
+mod convert;
+#[cfg(feature = "converter")]
+mod preparse;
+#[cfg(feature = "converter")]
+pub mod json;
+#[cfg(feature = "converter")]
+mod yaml;
+#[cfg(feature = "converter")]
+pub mod filter;
+#[cfg(feature = "converter")]
+mod fill;
+#[cfg(feature = "converter")]
+mod sanitize;
+#[cfg(feature = "converter")]
+mod bounds;
+#[cfg(feature = "converter")]
+mod bvh;
+#[cfg(feature = "converter")]
+mod measure;
+#[cfg(feature = "converter")]
+mod progressive;
+#[cfg(feature = "converter")]
+pub mod skin;
+#[cfg(feature = "converter")]
+pub mod colors;
+#[cfg(feature = "converter")]
+pub mod morph;
+#[cfg(feature = "converter")]
+pub mod curvature;
+#[cfg(feature = "converter")]
+pub mod normals;
+#[cfg(feature = "converter")]
+pub mod lightmap;
+#[cfg(feature = "converter")]
+pub mod normalmap;
+#[cfg(feature = "converter")]
+pub mod atlas;
+#[cfg(feature = "converter")]
+pub mod uvgen;
+#[cfg(feature = "converter")]
+pub mod user_attributes;
+#[cfg(feature = "converter")]
+pub mod vertex_struct;
+#[cfg(feature = "converter")]
+pub mod manifold;
+#[cfg(feature = "converter")]
+pub mod instances;
+#[cfg(feature = "converter")]
+pub mod scene;
+#[cfg(feature = "converter")]
+pub mod simplify;
+#[cfg(feature = "converter")]
+pub mod remesh;
+#[cfg(feature = "converter")]
+pub mod planar;
+#[cfg(feature = "converter")]
+pub mod impostor;
+#[cfg(feature = "converter")]
+pub mod portals;
+#[cfg(feature = "converter")]
+pub mod material;
+#[cfg(feature = "converter")]
+pub mod diagnostics;
+#[cfg(feature = "converter")]
+pub mod report;
+#[cfg(feature = "converter")]
+pub mod quantization;
+#[cfg(feature = "converter")]
+pub mod jobs;
+#[cfg(feature = "converter")]
+pub mod http;
+#[cfg(feature = "converter")]
+pub mod rpc;
+pub mod layout;
+pub mod chunk;
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod blocks;
+#[cfg(feature = "std")]
+pub mod palette;
+pub mod dequantization;
+#[cfg(feature = "std")]
+pub mod zstd;
+pub mod view;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+pub mod emit;
+
+#[cfg(feature = "bevy")]
+pub mod bevy;
+#[cfg(feature = "miniquad")]
+pub mod miniquad;
+#[cfg(feature = "draco")]
+pub mod draco;
+
+#[cfg(all(test, feature = "converter"))]
+mod fixtures;
+
+#[cfg(feature = "converter")]
+pub use convert::{parse_obj, parse_obj_filtered, convert_obj, convert_obj_set, convert_hlod_proxy, convert_impostor, ConvertOptions, ConvertedMesh, Pivot, StaleNormals, UvOverflow};
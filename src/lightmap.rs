@@ -0,0 +1,139 @@
+//! Lightmap UVs generation, for the many obj files that only have texture UVs.
+//!
+//! Triangles are grouped into charts of connected faces facing the same axis, each chart is projected on
+//! its axis plane and the charts are shelf packed with some padding, so that no two charts overlap.
+
+use wavefront_obj::obj::{Object, Shape, TVertex, Vertex};
+use std::collections::HashMap;
+use crate::math::*;
+use crate::atlas::{find, union, pack};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightmapSettings {
+	/// Side of the lightmap in texels, used to size the padding
+	pub resolution: u32,
+	/// Texels between the charts and around the border
+	pub padding: u32,
+}
+
+impl Default for LightmapSettings {
+	fn default() -> Self {
+		LightmapSettings {
+			resolution: 512,
+			padding: 2,
+		}
+	}
+}
+
+/// The chart and lightmap UVs of each triangle of an object, in the order they appear in the obj file
+#[derive(Debug, Clone)]
+pub struct LightmapUvs {
+	pub charts: Vec<usize>,
+	pub uvs: Vec<[TVertex; 3]>,
+}
+
+fn triangles(obj: &Object) -> Vec<[usize; 3]> {
+	let mut triangles = vec![];
+	for geo in &obj.geometry {
+		for shape in &geo.shapes {
+			//lines mixed with the faces are skipped by the converter
+			if let Shape::Triangle(v1, v2, v3) = *shape {
+				triangles.push([v1.0, v2.0, v3.0]);
+			}
+		}
+	}
+	triangles
+}
+
+/// 0-5 for +x, -x, +y, -y, +z, -z
+fn dominant_axis(n: Vertex) -> usize {
+	let abs = [n.x.abs(), n.y.abs(), n.z.abs()];
+	let axis = if abs[0] >= abs[1] && abs[0] >= abs[2] { 0 } else if abs[1] >= abs[2] { 1 } else { 2 };
+	let positive = [n.x, n.y, n.z][axis] >= 0.0;
+	axis * 2 + if positive { 0 } else { 1 }
+}
+
+fn project(v: Vertex, axis: usize) -> (f64, f64) {
+	match axis / 2 {
+		0 => (v.y, v.z),
+		1 => (v.x, v.z),
+		_ => (v.x, v.y),
+	}
+}
+
+struct Chart {
+	axis: usize,
+	min: (f64, f64),
+	size: (f64, f64),
+}
+
+pub fn unwrap(obj: &Object, settings: &LightmapSettings) -> LightmapUvs {
+	let triangles = triangles(obj);
+	let pos = |i: usize| obj.vertices[i];
+
+	let axes: Vec<usize> = triangles.iter()
+		.map(|t| dominant_axis(cross(sub(pos(t[1]), pos(t[0])), sub(pos(t[2]), pos(t[0])))))
+		.collect();
+
+	//join the triangles sharing an edge and facing the same way
+	let mut parents: Vec<usize> = (0..triangles.len()).collect();
+	let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+	for (t, tri) in triangles.iter().enumerate() {
+		for i in 0..3 {
+			let (a, b) = (tri[i], tri[(i + 1) % 3]);
+			let edge = (usize::min(a, b), usize::max(a, b));
+			match edges.get(&edge) {
+				Some(&other) if axes[other] == axes[t] => union(&mut parents, t, other),
+				Some(_) => {},
+				None => { edges.insert(edge, t); },
+			}
+		}
+	}
+
+	let mut chart_ids: HashMap<usize, usize> = HashMap::new();
+	let mut charts: Vec<Chart> = vec![];
+	let mut triangle_charts = Vec::with_capacity(triangles.len());
+	for (t, tri) in triangles.iter().enumerate() {
+		let root = find(&mut parents, t);
+		let id = *chart_ids.entry(root).or_insert_with(|| {
+			charts.push(Chart {
+				axis: axes[t],
+				min: (f64::MAX, f64::MAX),
+				size: (f64::MIN, f64::MIN),
+			});
+			charts.len() - 1
+		});
+		triangle_charts.push(id);
+
+		//size holds the max until all the triangles are in
+		let chart = &mut charts[id];
+		for &v in tri {
+			let (u, v) = project(pos(v), chart.axis);
+			chart.min = (f64::min(chart.min.0, u), f64::min(chart.min.1, v));
+			chart.size = (f64::max(chart.size.0, u), f64::max(chart.size.1, v));
+		}
+	}
+	for chart in &mut charts {
+		chart.size = (chart.size.0 - chart.min.0, chart.size.1 - chart.min.1);
+	}
+
+	let sizes: Vec<(f64, f64)> = charts.iter().map(|c| c.size).collect();
+	let (offsets, extent) = pack(&sizes, settings.resolution, settings.padding);
+
+	let uvs = triangles.iter().zip(&triangle_charts).map(|(tri, &c)| {
+		let chart = &charts[c];
+		let uv = |v: usize| {
+			let (u, v) = project(pos(v), chart.axis);
+			TVertex {
+				x: (u - chart.min.0 + offsets[c].0) / extent,
+				y: (v - chart.min.1 + offsets[c].1) / extent,
+			}
+		};
+		[uv(tri[0]), uv(tri[1]), uv(tri[2])]
+	}).collect();
+
+	LightmapUvs {
+		charts: triangle_charts,
+		uvs,
+	}
+}
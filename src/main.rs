@@ -1,493 +1,2636 @@
-extern crate wavefront_obj;
-extern crate byteorder;
+extern crate obj_to_mesh;
 extern crate clap;
-extern crate half;
 
-use clap::{Arg, App};
-use byteorder::{LittleEndian, WriteBytesExt};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
 use std::io::prelude::*;
 use std::fs::File;
-use wavefront_obj::obj::{ObjSet, Object, Shape, VTNIndex, Vertex, TVertex, Normal};
-use std::mem::size_of;
-use std::collections::HashMap;
-use std::f64;
-use std::path::Path;
-use half::f16;
-
-fn pack_normalized(val: f64, max: u32) -> u32 {
-	f64::ceil(val * max as f64) as u32
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use obj_to_mesh::emit;
+use obj_to_mesh::{json, rpc};
+
+/// Set by `daemon --stdio`, whose standard output carries the answers
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for the progress messages of the conversions
+macro_rules! message {
+	($($arg:tt)*) => {
+		if MESSAGES_TO_STDERR.load(Ordering::Relaxed) {
+			eprintln!($($arg)*);
+		}
+		else {
+			println!($($arg)*);
+		}
+	};
 }
 
-fn pack_i2_10_10_10(normal: Normal, w: f64) -> u32 {
-	(pack_normalized(normal.x, 511) << 0) |
-	(pack_normalized(normal.y, 511) << 10) |
-	(pack_normalized(normal.z, 511) << 20) |
-	(pack_normalized(w, 1)) << 30
+/// `args` is the command line, with the response files expanded
+fn convert(matches: &ArgMatches, args: &[String]) {
+	let inputs: Vec<&str> = matches.values_of("input").unwrap().collect();
+	let output_dir = output_dir(matches, &inputs);
+
+	//the mesh files of all the inputs
+	let mut written = vec![];
+	if let [input] = inputs[..] {
+		let input = Path::new(input);
+		let outcome = convert_file(matches, args, input, &output_of(matches, &output_dir, input), &mut written);
+		match &outcome {
+			Outcome::Converted(asset_report) => write_report(matches, asset_report.as_slice()),
+			//not a failure, but nothing was written, which the scripts converting one file need to tell apart
+			Outcome::Skipped(_) => {
+				print_summary(&inputs, std::slice::from_ref(&outcome), "files");
+				std::process::exit(2);
+			},
+			Outcome::Failed(_) => std::process::exit(1),
+		}
+		compress_with_dictionary(matches, &written);
+		return;
+	}
+
+	//a file that fails doesn't stop the others, its panic is caught and reported at the end
+	let mut outcomes = vec![];
+	for input in &inputs {
+		let input = Path::new(input);
+		outcomes.push(convert_caught(matches, args, input, &output_of(matches, &output_dir, input), &mut written));
+	}
+
+	let failed = print_summary(&inputs, &outcomes, "files");
+	let reports: Vec<_> = outcomes.into_iter().filter_map(|outcome| match outcome {
+		Outcome::Converted(asset_report) => asset_report,
+		_ => None,
+	}).collect();
+	write_report(matches, &reports);
+	compress_with_dictionary(matches, &written);
+	if failed > 0 {
+		std::process::exit(1);
+	}
 }
 
-fn pack_f16(val: f64) -> u16 {
-	//attempt to fix bad exports
-	let mut x = val;
-	while x > 1. {
-		x -= 1.;
+/// The directory --output names, created, after checking that the options allow the inputs
+fn output_dir(matches: &ArgMatches, inputs: &[&str]) -> Option<PathBuf> {
+	//a directory when it ends with a separator, or already is one
+	let output_dir = matches.value_of("output")
+		.filter(|path| path.ends_with('/') || path.ends_with(std::path::MAIN_SEPARATOR) || Path::new(path).is_dir())
+		.map(PathBuf::from);
+	if inputs.len() > 1 && matches.is_present("output") && output_dir.is_none() {
+		panic!("The output has to be a directory to convert several files");
 	}
-	while x < -1. {
-		x += 1.;
+	if matches.is_present("scene") && extension(matches) != "mesh" {
+		panic!("The scenes are made of mesh files");
 	}
-	f16::from_f64(x).as_bits()
+	if let Some(dir) = &output_dir {
+		std::fs::create_dir_all(dir).unwrap();
+	}
+	output_dir
 }
 
-#[derive(Clone, Copy)]
-enum Attribute {
-	Position,
-	Normal,
-	Tangent,
-	Tex0,
+/// In the output directory, at --output, or next to the input
+fn output_of(matches: &ArgMatches, output_dir: &Option<PathBuf>, input: &Path) -> PathBuf {
+	match (output_dir, matches.value_of("output")) {
+		(Some(dir), _) => dir.join(format!("{}.{}", input.file_stem().unwrap().to_str().unwrap(), extension(matches))),
+		(None, Some(path)) => PathBuf::from(path),
+		(None, None) => input.with_extension(extension(matches)),
+	}
 }
 
-fn size_of_attribute(attr: Attribute) -> usize {
-	match attr {
-		Attribute::Position => size_of::<f32>() * 3,
-		Attribute::Normal => size_of::<u32>(),
-		Attribute::Tangent => size_of::<u32>(),
-		Attribute::Tex0 => size_of::<f16>() * 2,
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+	payload.downcast_ref::<&str>().map(|m| m.to_string())
+		.or_else(|| payload.downcast_ref::<String>().cloned())
+		.unwrap_or_default()
+}
+
+/// `convert_file`, failing with the message of its panic
+fn convert_caught(matches: &ArgMatches, args: &[String], input: &Path, output: &Path, mesh_files: &mut Vec<PathBuf>) -> Outcome {
+	std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| convert_file(matches, args, input, output, mesh_files)))
+		.unwrap_or_else(|payload| Outcome::Failed(panic_message(&*payload)))
+}
+
+/// With --zstd-dictionary, trains the dictionary on the files, writes it, and replaces each file with a zstd frame
+/// compressed against it
+fn compress_with_dictionary(matches: &ArgMatches, files: &[PathBuf]) {
+	let Some(path) = matches.value_of("zstd_dictionary") else {
+		return;
+	};
+	let contents: Vec<Vec<u8>> = files.iter().map(|file| std::fs::read(file).unwrap()).collect();
+	let samples: Vec<&[u8]> = contents.iter().map(Vec::as_slice).collect();
+	let size = matches.value_of("zstd_dictionary_size").unwrap().parse().unwrap();
+	let dictionary = obj_to_mesh::zstd::Dictionary::train(&samples, size);
+	File::create(path).unwrap().write_all(&dictionary.to_bytes()).unwrap();
+
+	let (mut before, mut after) = (0, 0);
+	for (file, content) in files.iter().zip(&contents) {
+		let compressed = obj_to_mesh::zstd::compress(content, &dictionary);
+		let mut name = file.clone().into_os_string();
+		name.push(".zst");
+		File::create(name).unwrap().write_all(&compressed).unwrap();
+		std::fs::remove_file(file).unwrap();
+		(before, after) = (before + content.len(), after + compressed.len());
 	}
+	message!("Compressed {} files from {} to {} bytes with dictionary {} of {} bytes",
+		files.len(), before, after, dictionary.id, dictionary.content.len());
 }
 
-#[derive(Debug, Clone, Copy)]
-struct VertexFieldOffsets {
-	normal: Option<usize>,
-	tangent: Option<usize>,
-	tex0: Option<usize>,
+/// Prints what happened to each of the files or jobs, and returns how many failed
+fn print_summary(names: &[&str], outcomes: &[Outcome], what: &str) -> usize {
+	let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|o| f(o)).count();
+	let failed = count(|o| matches!(o, Outcome::Failed(_)));
+	println!("\nConverted {}, skipped {}, failed {} of {} {}",
+		count(|o| matches!(o, Outcome::Converted(_))),
+		count(|o| matches!(o, Outcome::Skipped(_))),
+		failed,
+		names.len(),
+		what);
+	let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+	for (name, outcome) in names.iter().zip(outcomes) {
+		match outcome {
+			Outcome::Converted(_) => println!("  converted  {}", name),
+			Outcome::Skipped(reason) => println!("  skipped    {:width$}  {}", name, reason, width = width),
+			Outcome::Failed(reason) => println!("  failed     {:width$}  {}", name, reason, width = width),
+		}
+	}
+	failed
 }
 
-fn has_attribute(vtni: VTNIndex, attr: Attribute) -> bool {
-	let (_, tex, normal) = vtni;
-	match attr {
-		Attribute::Position => true,
-		Attribute::Normal => normal.is_some(),
-		Attribute::Tex0 => tex.is_some(),
-		_ => false,
+/// The jobs without a profile get the one of `OBJ_TO_MESH_PROFILE`
+fn parse_job_file(content: &str) -> Result<Vec<obj_to_mesh::jobs::Job>, wavefront_obj::ParseError> {
+	obj_to_mesh::jobs::parse_jobs(content, environment_var("PROFILE").as_deref())
+}
+
+/// `--jobs`, or `OBJ_TO_MESH_JOBS`, or one for each core
+fn job_threads(jobs: Option<&str>) -> usize {
+	match jobs.map(str::to_owned).or_else(|| environment_var("JOBS")) {
+		Some(threads) => threads.parse().unwrap_or_else(|_| panic!("{} isn't a number of jobs", threads)),
+		None => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+	}
+}
+
+/// Runs the jobs of a job file, each as a conversion process in the directory of the file; as many at once as
+/// `--jobs` or `OBJ_TO_MESH_JOBS`, and the ones after others once those succeeded
+fn run_jobs(matches: &ArgMatches) {
+	let path = Path::new(matches.value_of("job_file").unwrap());
+	let jobs = match parse_job_file(&std::fs::read_to_string(path).unwrap()) {
+		Ok(jobs) => jobs,
+		Err(err) => panic!("{}:{}: {}", path.display(), err.line_number, err.message),
+	};
+	let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_owned();
+	let threads = job_threads(matches.value_of("jobs"));
+	let exe = std::env::current_exe().unwrap();
+
+	let mut outcomes: Vec<Option<Outcome>> = jobs.iter().map(|_| None).collect();
+	let mut started = vec![false; jobs.len()];
+	let mut running = 0;
+	let (sender, receiver) = std::sync::mpsc::channel();
+	loop {
+		let mut progress = false;
+		for (i, job) in jobs.iter().enumerate() {
+			if started[i] {
+				continue;
+			}
+			if let Some(&dependency) = job.after.iter().find(|&&d| matches!(outcomes[d], Some(Outcome::Failed(_) | Outcome::Skipped(_)))) {
+				outcomes[i] = Some(Outcome::Skipped(format!("{} didn't convert", jobs[dependency].name)));
+			}
+			else if running < threads && job.after.iter().all(|&d| outcomes[d].is_some()) {
+				//like the directories of the outputs, which the conversions only create for -o out/
+				if let Some(parent) = job.output.as_ref().and_then(|output| dir.join(output).parent().map(Path::to_owned)) {
+					std::fs::create_dir_all(parent).unwrap();
+				}
+				let (sender, exe, dir, args) = (sender.clone(), exe.clone(), dir.clone(), job.args());
+				std::thread::spawn(move || {
+					let output = std::process::Command::new(exe).args(args).current_dir(dir).output();
+					sender.send((i, output)).unwrap();
+				});
+				running += 1;
+			}
+			else {
+				continue;
+			}
+			started[i] = true;
+			progress = true;
+		}
+		if running == 0 {
+			if progress {
+				continue;
+			}
+			break;
+		}
+
+		let (i, output) = receiver.recv().unwrap();
+		running -= 1;
+		let output = output.unwrap();
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		for line in String::from_utf8_lossy(&output.stdout).lines() {
+			println!("[{}] {}", jobs[i].name, line);
+		}
+		for line in stderr.lines() {
+			eprintln!("[{}] {}", jobs[i].name, line);
+		}
+		outcomes[i] = Some(if output.status.success() {
+			Outcome::Converted(None)
+		}
+		else {
+			Outcome::Failed(failure_reason(&output))
+		});
+	}
+
+	let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+	let outcomes: Vec<Outcome> = outcomes.into_iter().map(Option::unwrap).collect();
+	if print_summary(&names, &outcomes, "jobs") > 0 {
+		std::process::exit(1);
 	}
 }
 
-fn has_all(obj: &Object, attr: Attribute) -> bool {
+/// Why a conversion process failed: the panic message, the invalid option, or the problems of --strict
+fn failure_reason(output: &std::process::Output) -> String {
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	let lines: Vec<&str> = stderr.lines().collect();
+	let reason = match lines.iter().position(|line| line.contains("panicked at")) {
+		Some(panic) => lines.get(panic + 1),
+		//clap follows the error with the usage
+		None if lines.contains(&"USAGE:") => lines.first(),
+		None => lines.iter().rev().find(|line| !line.is_empty()),
+	};
+	reason.map_or_else(|| output.status.to_string(), |reason| reason.to_string())
+}
 
-	for geo in &obj.geometry {
-		for shape in &geo.shapes {
-			match *shape {
-				Shape::Triangle(v1, v2, v3) => {
-					if !has_attribute(v1, attr) || !has_attribute(v2, attr) || !has_attribute(v3, attr) {
-						return false;
-					}
-				},
-				_=> panic!("Unsupported primitive mode")
+/// The options the server takes in the query: none of them name files, which could be those of the server
+const SERVE_OPTIONS: &[&str] = &[
+	"gen_tangents", "gen-tangents", "emit-bitangent", "uv-overflow", "symmetry", "mirrored-indices", "obb", "kdop",
+	"bake-bvh", "pivot", "only", "exclude", "merge-objects", "detect-instances", "strip-texture-dirs", "texture-prefix",
+	"bake-material-color", "pbr", "portals", "format-version", "encoding", "progressive-base", "format", "draco-bits",
+	"palette", "quantize-positions", "shadow-indices", "max-vertices", "split-by", "split-components",
+	"sort-by-material", "chunk-grid", "repair", "renormalize", "normal-tolerance", "check-manifold", "dedup-faces",
+	"remove-small-components", "fill-holes", "max-hole-perimeter", "fix-winding", "inverted-faces", "remesh",
+	"remesh-iterations", "merge-coplanar", "strict", "deny", "fill-missing", "require", "color-space",
+	"recompute-normals", "crease-angle", "flat", "smooth-seams", "stale-normals", "curvature", "material-ids", "gen-uvs",
+	"lightmap", "lightmap-padding", "repack-uvs", "atlas-padding",
+];
+
+/// Answers the obj files posted to /convert with their mesh, converting each in a process like the jobs, with the
+/// query parameters as options
+fn serve(matches: &ArgMatches) {
+	let max_upload = matches.value_of("max_upload").unwrap().parse::<usize>().unwrap() * 1024 * 1024;
+	let max_connections = matches.value_of("max_connections").unwrap().parse::<usize>().unwrap();
+	let timeout = std::time::Duration::from_secs(matches.value_of("timeout").unwrap().parse().unwrap());
+	let listener = std::net::TcpListener::bind(matches.value_of("address").unwrap()).unwrap();
+	println!("Listening on http://{}", listener.local_addr().unwrap());
+	let exe = std::env::current_exe().unwrap();
+	let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+	for stream in listener.incoming() {
+		let mut stream = match stream {
+			Ok(stream) => stream,
+			Err(err) => {
+				eprintln!("{}", err);
+				continue;
+			},
+		};
+		//an idle client would hold its thread forever
+		let timeouts = stream.set_read_timeout(Some(timeout)).and_then(|()| stream.set_write_timeout(Some(timeout)));
+		if let Err(err) = timeouts {
+			eprintln!("{}", err);
+			continue;
+		}
+		let connection = Connection::open(&connections);
+		if connections.load(Ordering::Relaxed) > max_connections {
+			let response = obj_to_mesh::http::write_response(&mut stream, 503, &[], b"Too many connections, try again later\n");
+			if let Err(err) = response {
+				eprintln!("{}", err);
 			}
+			continue;
 		}
+		let exe = exe.clone();
+		std::thread::spawn(move || {
+			let _connection = connection;
+			if let Err(err) = serve_connection(stream, &exe, max_upload) {
+				eprintln!("{}", err);
+			}
+		});
 	}
-	true
 }
 
-fn get_offset(obj: &Object, attr: Attribute, offset: &mut usize) -> Option<usize> {
-	let orig_offs = *offset;
-	if has_all(obj, attr) {
-		*offset += size_of_attribute(attr);
-		return Some(orig_offs);
+/// Counts a connection of the server while it's open
+struct Connection(Arc<std::sync::atomic::AtomicUsize>);
+
+impl Connection {
+	fn open(count: &Arc<std::sync::atomic::AtomicUsize>) -> Connection {
+		count.fetch_add(1, Ordering::Relaxed);
+		Connection(count.clone())
 	}
-	None
 }
 
-impl VertexFieldOffsets {
-	fn from_object(obj: &Object, with_tangent: bool) -> Self {
-		let mut offset = size_of_attribute(Attribute::Position);
+impl Drop for Connection {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::Relaxed);
+	}
+}
 
-		VertexFieldOffsets {
-			normal: get_offset(obj, Attribute::Normal, &mut offset),
-			tangent: 
-				if with_tangent {
-					let orig_offset = offset;
-					offset += size_of_attribute(Attribute::Tangent);
-					Some(orig_offset)
-				}
-				else {
-					None
+fn serve_connection(mut stream: std::net::TcpStream, exe: &Path, max_upload: usize) -> std::io::Result<()> {
+	use obj_to_mesh::http;
+
+	let mut input = std::io::BufReader::new(stream.try_clone()?);
+	let text = |status, message: String| (status, vec![("Content-Type", "text/plain".to_owned())], (message + "\n").into_bytes());
+	let mut request = match http::read_head(&mut input) {
+		Ok(Some(request)) => request,
+		Ok(None) => return Ok(()),
+		Err(err) => {
+			http::write_response(&mut stream, err.status(), &[], format!("{}\n", err).as_bytes())?;
+			return linger(&stream, input, max_upload);
+		},
+	};
+
+	let (status, headers, body) = match (request.method.as_str(), request.path.as_str()) {
+		("POST", "/convert") => {
+			//the bodies refused aren't sent
+			if request.expects_continue() && http::body_length(&request, max_upload).is_ok() {
+				http::write_continue(&mut stream)?;
+			}
+			match http::read_body(&mut input, &mut request, max_upload) {
+				Ok(()) => match convert_upload(&request, exe) {
+					Ok((file_name, mesh)) => (200, vec![
+						("Content-Type", "application/octet-stream".to_owned()),
+						("Content-Disposition", format!("attachment; filename=\"{}\"", quoted(&file_name))),
+					], mesh),
+					Err((status, message)) => text(status, message),
 				},
-			tex0: get_offset(obj, Attribute::Tex0, &mut offset),
+				Err(err) => text(err.status(), err.to_string()),
+			}
+		},
+		(_, "/convert") => text(405, "Only POST converts".to_owned()),
+		_ => text(404, format!("{} not found, post the obj files to /convert", request.path)),
+	};
+	println!("{} {} {}: {}, {} bytes", stream.peer_addr()?, request.method, request.path, status, body.len());
+
+	let headers: Vec<(&str, &str)> = headers.iter().map(|(name, value)| (*name, value.as_str())).collect();
+	http::write_response(&mut stream, status, &headers, &body)?;
+	linger(&stream, input, max_upload)
+}
+
+/// Closes the connection once answered, reading what the client still sends, up to `limit` bytes: closing it with
+/// unread data resets it, and the client could lose the answer, like the reason its body was refused
+fn linger(stream: &std::net::TcpStream, input: impl Read, limit: usize) -> std::io::Result<()> {
+	stream.shutdown(std::net::Shutdown::Write)?;
+	//until the client closes its side, or the timeout
+	std::io::copy(&mut input.take(limit as u64), &mut std::io::sink()).ok();
+	Ok(())
+}
+
+/// The directory of a conversion of the server, removed however the request ends
+struct UploadDir(PathBuf);
+
+impl Drop for UploadDir {
+	fn drop(&mut self) {
+		if let Err(err) = std::fs::remove_dir_all(&self.0) {
+			eprintln!("{}: {}", self.0.display(), err);
 		}
 	}
 }
 
-#[derive(Clone, Debug)]
-struct GPUVertex {
-	pos: Vertex,
-	normal: Option<Normal>,
-	tangent: Option<Normal>,
-	tangent_handedness: f64,
-	tex: Option<TVertex>,
+/// Escapes a quoted string of a header
+fn quoted(text: &str) -> String {
+	text.chars().map(|c| match c {
+		'"' | '\\' => format!("\\{}", c),
+		c if c.is_control() => "_".to_owned(),
+		c => c.to_string(),
+	}).collect()
 }
 
-impl GPUVertex {
-	fn from_vtni_and_obj(vtni: VTNIndex, obj: &Object, format: &VertexFieldOffsets) -> Self {
-		let (pos_idx, tex_opt_idx, norm_opt_idx) = vtni;
-		GPUVertex {
-			pos: obj.vertices[pos_idx],
-			normal: match norm_opt_idx {
-			    Some(idx) if format.normal.is_some() => Some(obj.normals[idx]),
-			    _ => None,
-			},
-			tangent: None,
-			tangent_handedness: 0.0,
-			tex: match tex_opt_idx {
-			    Some(idx) if format.tex0.is_some() => Some(obj.tex_vertices[idx]),
-			    _ => None,
-			},
+/// The file name and content of the converted mesh, or the status and reason of the failure
+fn convert_upload(request: &obj_to_mesh::http::Request, exe: &Path) -> Result<(String, Vec<u8>), (u16, String)> {
+	static UPLOADS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+	let content_type = request.header("content-type").unwrap_or("");
+	let (file_name, obj) = if content_type.starts_with("multipart/form-data") {
+		match obj_to_mesh::http::multipart_file(&request.body, content_type) {
+			Some(file) => file,
+			None => return Err((400, "The form has no file".to_owned())),
+		}
+	}
+	else {
+		(None, &request.body[..])
+	};
+	//only to name the mesh sent back, the directories of the client mean nothing here
+	let stem = file_name.as_deref()
+		.and_then(|name| name.rsplit(['/', '\\']).next())
+		.and_then(|name| name.strip_suffix(".obj"))
+		.filter(|stem| !stem.is_empty())
+		.unwrap_or("upload")
+		.to_owned();
+
+	//like the environment variables
+	let mut args = vec!["--confine".to_owned()];
+	for (name, value) in &request.query {
+		if !SERVE_OPTIONS.contains(&name.as_str()) {
+			return Err((400, format!("{} isn't an option the server takes", name)));
+		}
+		match value.as_str() {
+			"true" | "" => args.push(format!("--{}", name)),
+			"false" => {},
+			_ => args.push(format!("--{}={}", name, value)),
 		}
 	}
 
-	fn write_to(&self, data: &mut Vec<u8>) {
-		data.write_f32::<LittleEndian>(self.pos.x as f32).unwrap();
-		data.write_f32::<LittleEndian>(self.pos.y as f32).unwrap();
-		data.write_f32::<LittleEndian>(self.pos.z as f32).unwrap();
+	let internal = |err: std::io::Error| (500, err.to_string());
+	let dir = UploadDir(std::env::temp_dir().join(format!("obj_to_mesh_serve_{}_{}", std::process::id(), UPLOADS.fetch_add(1, Ordering::Relaxed))));
+	std::fs::create_dir_all(&dir.0).map_err(internal)?;
+	//the name of the client could read as an option or a response file
+	std::fs::write(dir.0.join("upload.obj"), obj).map_err(internal)?;
+	let output = std::process::Command::new(exe).args(["upload.obj", "-o", "out/"]).args(&args).current_dir(&dir.0).output().map_err(internal)?;
+	if !output.status.success() {
+		return Err((422, failure_reason(&output)));
+	}
 
-		if let Some(normal) = self.normal {
-			data.write_u32::<LittleEndian>(pack_i2_10_10_10(normal, 0.0)).unwrap();
+	//split meshes would need several responses
+	let mut meshes = vec![];
+	for entry in std::fs::read_dir(dir.0.join("out")).map_err(internal)? {
+		let path = entry.map_err(internal)?.path();
+		if let Some(extension @ ("mesh" | "drc")) = path.extension().and_then(|e| e.to_str()) {
+			meshes.push((path.clone(), extension.to_owned()));
 		}
+	}
+	match &meshes[..] {
+		[(mesh, extension)] => Ok((format!("{}.{}", stem, extension), std::fs::read(mesh).map_err(internal)?)),
+		_ => Err((422, format!("The conversion wrote {} meshes, only single meshes can be served", meshes.len()))),
+	}
+}
+
+/// Converts the requests sent to the socket, or to the standard input with --stdio, in this process, which saves
+/// starting one per conversion
+fn daemon(matches: &ArgMatches) {
+	let json_rpc = matches.is_present("json_rpc");
+	if !matches.is_present("stdio") {
+		return listen(Path::new(matches.value_of("socket").unwrap()), json_rpc);
+	}
+
+	MESSAGES_TO_STDERR.store(true, Ordering::Relaxed);
+	let result = if json_rpc {
+		rpc_session(std::io::stdin().lock(), Box::new(std::io::stdout()))
+	}
+	else {
+		daemon_requests(std::io::stdin().lock(), std::io::stdout())
+	};
+	if let Err(err) = result {
+		eprintln!("{}", err);
+	}
+}
+
+/// Each connection has its own thread
+#[cfg(unix)]
+fn listen(path: &Path, json_rpc: bool) {
+	use std::os::unix::fs::FileTypeExt;
 
-		if let Some(tangent) = self.tangent {
-			data.write_u32::<LittleEndian>(pack_i2_10_10_10(tangent, self.tangent_handedness)).unwrap();
+	//left by a daemon that didn't stop cleanly
+	if let Ok(metadata) = std::fs::symlink_metadata(path) {
+		if !metadata.file_type().is_socket() {
+			panic!("{} exists and isn't a socket", path.display());
 		}
+		std::fs::remove_file(path).unwrap();
+	}
+	let listener = std::os::unix::net::UnixListener::bind(path).unwrap();
+	println!("Listening on {}", path.display());
+
+	for stream in listener.incoming() {
+		let stream = match stream {
+			Ok(stream) => stream,
+			Err(err) => {
+				eprintln!("{}", err);
+				continue;
+			},
+		};
+		std::thread::spawn(move || {
+			let result = stream.try_clone().and_then(|input| {
+				let input = std::io::BufReader::new(input);
+				if json_rpc {
+					rpc_session(input, Box::new(stream))
+				}
+				else {
+					daemon_requests(input, stream)
+				}
+			});
+			if let Err(err) = result {
+				eprintln!("{}", err);
+			}
+		});
+	}
+}
 
-		if let Some(tex) = self.tex {
-			data.write_u16::<LittleEndian>(pack_f16(tex.x)).unwrap();
-			data.write_u16::<LittleEndian>(pack_f16(tex.y)).unwrap();
+#[cfg(not(unix))]
+fn listen(_path: &Path, _json_rpc: bool) {
+	panic!("The daemon needs Unix sockets, or --stdio");
+}
+
+/// The command line of a request, after the environment of the daemon like for the command line
+fn daemon_args(request: Vec<String>) -> Vec<String> {
+	let mut args = vec!["obj_to_mesh".to_owned()];
+	args.extend(environment_args());
+	args.extend(request);
+	args
+}
+
+/// Each request is a command line like in the response files, an argument per line, ended by an empty line
+fn daemon_requests(mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+	let deny_values = deny_values();
+	let mut request = vec![];
+	let mut line = String::new();
+	while input.read_line(&mut line)? > 0 {
+		let arg = line.trim_end().to_owned();
+		line.clear();
+		if !arg.is_empty() {
+			request.push(arg);
+			continue;
+		}
+		if request.is_empty() {
+			continue;
 		}
+
+		daemon_convert(&deny_values, &daemon_args(std::mem::take(&mut request)), &mut output)?;
+		output.flush()?;
 	}
+	Ok(())
 }
 
-#[derive(Debug)]
-struct Mesh {
-	vertices: Vec<GPUVertex>,
-	indices: Vec<usize>,
-	map: HashMap<VTNIndex, usize>,
-	format: VertexFieldOffsets,
+/// What a conversion in the daemon did
+struct Conversion {
+	inputs: Vec<String>,
+	outcomes: Vec<Outcome>,
+	written: Vec<PathBuf>,
+	/// Why writing the report or compressing the files failed, after converting them
+	error: Option<String>,
+}
 
-	min: Vertex,
-	max: Vertex,
+impl Conversion {
+	fn failed(&self) -> usize {
+		self.outcomes.iter().filter(|outcome| matches!(outcome, Outcome::Failed(_))).count()
+	}
 }
 
-fn flt_min(a: f64, b: f64) -> f64 {
-	if a < b { a } else { b }
+/// The options of a command line sent to the daemon, or why they're invalid
+fn daemon_matches<'a>(deny_values: &'a [&'a str], args: &[String]) -> Result<ArgMatches<'a>, String> {
+	let matches = app(deny_values).setting(AppSettings::ColorNever).get_matches_from_safe(args).map_err(|err| {
+		let message = err.message.lines().next().unwrap_or_default();
+		message.strip_prefix("error: ").unwrap_or(message).to_owned()
+	})?;
+	if matches.subcommand_name().is_some() {
+		return Err("Only conversions run in the daemon".to_owned());
+	}
+	Ok(matches)
 }
 
-fn flt_max(a: f64, b: f64) -> f64 {
-	if a > b { a } else { b }
+/// Called with each input of a conversion, its index and the number of inputs, before converting it and then with
+/// its outcome
+type Progress<'a> = dyn FnMut(&str, usize, usize, Option<&Outcome>) + 'a;
+
+/// Converts a command line sent to the daemon
+fn run_conversion(deny_values: &[&str], args: &[String], progress: &mut Progress) -> Result<Conversion, String> {
+	let matches = daemon_matches(deny_values, args)?;
+	let inputs: Vec<&str> = matches.values_of("input").unwrap().collect();
+	let output_dir = std::panic::catch_unwind(|| output_dir(&matches, &inputs)).map_err(|payload| panic_message(&*payload))?;
+
+	let mut written = vec![];
+	let mut outcomes = vec![];
+	for (i, input) in inputs.iter().enumerate() {
+		progress(input, i, inputs.len(), None);
+		let path = Path::new(input);
+		let outcome = convert_caught(&matches, args, path, &output_of(&matches, &output_dir, path), &mut written);
+		progress(input, i, inputs.len(), Some(&outcome));
+		outcomes.push(outcome);
+	}
+	let finished = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+		let reports: Vec<_> = outcomes.iter_mut().filter_map(|outcome| match outcome {
+			Outcome::Converted(asset_report) => asset_report.take(),
+			_ => None,
+		}).collect();
+		write_report(&matches, &reports);
+		compress_with_dictionary(&matches, &written);
+	}));
+
+	Ok(Conversion {
+		inputs: inputs.iter().map(|input| input.to_string()).collect(),
+		outcomes,
+		written,
+		error: finished.err().map(|payload| panic_message(&*payload)),
+	})
 }
 
-fn vert_min(a: Vertex, b: Vertex) -> Vertex {
-	Vertex {
-		x: flt_min(a.x, b.x),
-		y: flt_min(a.y, b.y),
-		z: flt_min(a.z, b.z),
+/// Answers a line per mesh written and per file that didn't convert, then `ok`, or `error` and why
+fn daemon_convert(deny_values: &[&str], args: &[String], output: &mut impl Write) -> std::io::Result<()> {
+	let conversion = match run_conversion(deny_values, args, &mut |_, _, _, _| {}) {
+		Ok(conversion) => conversion,
+		Err(message) => return writeln!(output, "error {}", message),
+	};
+
+	for (input, outcome) in conversion.inputs.iter().zip(&conversion.outcomes) {
+		match outcome {
+			Outcome::Converted(_) => {},
+			Outcome::Skipped(reason) => writeln!(output, "skipped {}: {}", input, reason)?,
+			Outcome::Failed(reason) => writeln!(output, "failed {}: {}", input, reason)?,
+		}
+	}
+	for path in &conversion.written {
+		writeln!(output, "wrote {}", path.display())?;
 	}
-} 
 
-fn vert_max(a: Vertex, b: Vertex) -> Vertex {
-	Vertex {
-		x: flt_max(a.x, b.x),
-		y: flt_max(a.y, b.y),
-		z: flt_max(a.z, b.z),
+	match conversion.error {
+		Some(message) => writeln!(output, "error {}", message),
+		None if conversion.failed() > 0 => writeln!(output, "error {} of {} files failed", conversion.failed(), conversion.inputs.len()),
+		None => writeln!(output, "ok"),
 	}
-} 
+}
+
+/// Where a JSON-RPC session writes, with the threads of its subscriptions
+type RpcOutput = Arc<Mutex<Box<dyn Write + Send>>>;
 
-fn addmut(dst: &mut Vertex, src: Vertex) {
-	dst.x += src.x;
-	dst.y += src.y;
-	dst.z += src.z;
+fn send(output: &RpcOutput, message: &str) -> std::io::Result<()> {
+	let mut output = output.lock().unwrap();
+	writeln!(output, "{}", message)?;
+	output.flush()
 }
 
-fn lenght(v: Vertex) -> f64 {
-	f64::sqrt( v.x * v.x + v.y * v.y + v.z * v.z )
+/// A JSON-RPC 2.0 session, a message per line: `convert` and `inspect` answer once done, with a `progress`
+/// notification before and after each input converted, and `watch/subscribe` converts again each time an input
+/// changes, with a `watch/converted` notification, until `watch/unsubscribe` or the end of the session
+fn rpc_session(input: impl BufRead, output: Box<dyn Write + Send>) -> std::io::Result<()> {
+	let output: RpcOutput = Arc::new(Mutex::new(output));
+	let deny_values = deny_values();
+	//what stops the thread of each subscription
+	let mut subscriptions = std::collections::HashMap::new();
+	let mut next_subscription = 1u64;
+
+	for line in input.lines() {
+		let line = line?;
+		if line.trim().is_empty() {
+			continue;
+		}
+		let request = match rpc::parse_request(&line) {
+			Ok(request) => request,
+			Err((id, err)) => {
+				send(&output, &rpc::error_response(&id, &err))?;
+				continue;
+			},
+		};
+
+		let params = &request.params;
+		let result = match request.method.as_str() {
+			"convert" => rpc_args(params).and_then(|args| {
+				let tag = format!("\"request\": {}", request.id.as_deref().unwrap_or("null"));
+				let conversion = run_conversion(&deny_values, &args, &mut rpc_progress(&output, &tag));
+				Ok(conversion_json(&conversion.map_err(rpc::RpcError::invalid_params)?))
+			}),
+			"inspect" => rpc_inspect(params),
+			"watch/subscribe" => rpc_args(params).and_then(|args| {
+				let inputs: Vec<String> = daemon_matches(&deny_values, &args).map_err(rpc::RpcError::invalid_params)?
+					.values_of("input").unwrap().map(str::to_owned).collect();
+				let interval = match rpc::number_param(params, "interval")? {
+					Some(ms) if ms <= 0.0 => return Err(rpc::RpcError::invalid_params("The interval has to be positive".to_owned())),
+					ms => std::time::Duration::from_millis(ms.unwrap_or(500.0) as u64),
+				};
+				let id = next_subscription;
+				next_subscription += 1;
+				let stop = Arc::new(AtomicBool::new(false));
+				subscriptions.insert(id, stop.clone());
+				let output = output.clone();
+				std::thread::spawn(move || watch(id, &args, &inputs, interval, &stop, &output));
+				Ok(format!("{{\"subscription\": {}}}", id))
+			}),
+			"watch/unsubscribe" => rpc::number_param(params, "subscription").and_then(|id| {
+				match id.and_then(|id| subscriptions.remove(&(id as u64))) {
+					Some(stop) => {
+						stop.store(true, Ordering::Relaxed);
+						Ok("null".to_owned())
+					},
+					None => Err(rpc::RpcError::invalid_params("No such subscription".to_owned())),
+				}
+			}),
+			method => Err(rpc::RpcError::new(rpc::METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+		};
+
+		//the notifications get no answer
+		if let Some(id) = &request.id {
+			let message = match result {
+				Ok(result) => rpc::response(id, &result),
+				Err(err) => rpc::error_response(id, &err),
+			};
+			send(&output, &message)?;
+		}
+	}
+
+	for stop in subscriptions.values() {
+		stop.store(true, Ordering::Relaxed);
+	}
+	Ok(())
+}
+
+/// The command line of the `inputs`, `output` and `options` of a request
+fn rpc_args(params: &json::Value) -> Result<Vec<String>, rpc::RpcError> {
+	let inputs = rpc::strings_param(params, "inputs")?;
+	if inputs.is_empty() {
+		return Err(rpc::RpcError::invalid_params("There are no inputs".to_owned()));
+	}
+	let output = rpc::string_param(params, "output")?;
+	let options = rpc::options_param(params, "options")?;
+	Ok(daemon_args(obj_to_mesh::jobs::command_line(&inputs, output.as_deref(), &options)))
+}
+
+/// The state of an outcome, and its reason, as members of a JSON object
+fn outcome_members(outcome: &Outcome) -> String {
+	match outcome {
+		Outcome::Converted(_) => "\"state\": \"converted\"".to_owned(),
+		Outcome::Skipped(reason) => format!("\"state\": \"skipped\", \"reason\": {}", json::string(reason)),
+		Outcome::Failed(reason) => format!("\"state\": \"failed\", \"reason\": {}", json::string(reason)),
+	}
+}
+
+/// Sends the `progress` notifications of a conversion; `tag` is the member naming the request or the subscription
+fn rpc_progress<'a>(output: &'a RpcOutput, tag: &'a str) -> impl FnMut(&str, usize, usize, Option<&Outcome>) + 'a {
+	move |input: &str, index: usize, total: usize, outcome: Option<&Outcome>| {
+		let state = outcome.map_or_else(|| "\"state\": \"converting\"".to_owned(), outcome_members);
+		let params = format!("{{{}, \"input\": {}, \"index\": {}, \"total\": {}, {}}}", tag, json::string(input), index, total, state);
+		//a closed connection ends the session at its next answer
+		send(output, &rpc::notification("progress", &params)).ok();
+	}
+}
+
+fn conversion_json(conversion: &Conversion) -> String {
+	let written: Vec<String> = conversion.written.iter().map(|path| json::string(&path.display().to_string())).collect();
+	let files: Vec<String> = conversion.inputs.iter().zip(&conversion.outcomes)
+		.map(|(input, outcome)| format!("{{\"input\": {}, {}}}", json::string(input), outcome_members(outcome)))
+		.collect();
+	let error = match &conversion.error {
+		Some(message) => format!(", \"error\": {}", json::string(message)),
+		None => String::new(),
+	};
+	format!("{{\"written\": [{}], \"files\": [{}], \"failed\": {}{}}}", written.join(", "), files.join(", "), conversion.failed(), error)
 }
-fn normalize(v: Vertex) -> Vertex {
-	mul(v, 1.0 / lenght(v))
+
+/// Converts the inputs of a subscription, and again each time one of them is modified, until it stops or the
+/// session ends
+fn watch(subscription: u64, args: &[String], inputs: &[String], interval: std::time::Duration, stop: &AtomicBool, output: &RpcOutput) {
+	let deny_values = deny_values();
+	let tag = format!("\"subscription\": {}", subscription);
+	let mut converted = None;
+	while !stop.load(Ordering::Relaxed) {
+		let modified: Vec<Option<std::time::SystemTime>> = inputs.iter()
+			.map(|input| std::fs::metadata(input).and_then(|metadata| metadata.modified()).ok())
+			.collect();
+		if converted.as_ref() != Some(&modified) {
+			let result = match run_conversion(&deny_values, args, &mut rpc_progress(output, &tag)) {
+				Ok(conversion) => conversion_json(&conversion),
+				Err(message) => format!("{{\"error\": {}}}", json::string(&message)),
+			};
+			let params = format!("{{\"subscription\": {}, \"result\": {}}}", subscription, result);
+			if send(output, &rpc::notification("watch/converted", &params)).is_err() {
+				return;
+			}
+			converted = Some(modified);
+		}
+		std::thread::sleep(interval);
+	}
 }
 
-fn dot(a: Vertex, b: Vertex) -> f64 {
-	a.x * b.x + a.y * b.y + a.z * b.z
+/// What `inspect` prints, with the names in camelCase
+fn rpc_inspect(params: &json::Value) -> Result<String, rpc::RpcError> {
+	let path = rpc::string_param(params, "path")?.ok_or_else(|| rpc::RpcError::invalid_params("There is no path".to_owned()))?;
+	let mesh = File::open(&path).map_err(|err| err.to_string())
+		.and_then(|mut file| obj_to_mesh::reader::read_mesh(&mut file).map_err(|err| err.to_string()))
+		.map_err(|message| rpc::RpcError::new(rpc::READ_FAILED, format!("{}: {}", path, message)))?;
+	let layout = mesh.header.layout();
+
+	let topology = match mesh.header.topology {
+		obj_to_mesh::reader::Topology::TriangleList => "triangles",
+		obj_to_mesh::reader::Topology::LineList => "lines",
+		obj_to_mesh::reader::Topology::PointList => "points",
+	};
+	let vector = |v: &[f32; 3]| format!("[{:?}, {:?}, {:?}]", v[0], v[1], v[2]);
+	let attributes: Vec<String> = layout.attributes.iter().map(|attr| format!(
+		"{{\"name\": {}, \"format\": \"{:?}\", \"offset\": {}, \"location\": {}}}",
+		json::string(&attr.name), attr.format, attr.offset, attr.location
+	)).collect();
+	let submeshes: Vec<String> = mesh.submeshes.iter().map(|submesh| format!(
+		"{{\"material\": {}, \"firstIndex\": {}, \"indexCount\": {}}}",
+		submesh.material.as_deref().map_or_else(|| "null".to_owned(), json::string), submesh.first_index, submesh.index_count
+	)).collect();
+
+	Ok(format!(
+		"{{\"vertexCount\": {}, \"indexCount\": {}, \"topology\": \"{}\", \"min\": {}, \"max\": {}, \"version\": {}, \"indexSize\": {}, \"encoding\": \"{}\", \"stride\": {}, \"attributes\": [{}], \"submeshes\": [{}], \"checksum\": {}}}",
+		mesh.positions.len(),
+		mesh.indices.len(),
+		topology,
+		vector(&mesh.header.min),
+		vector(&mesh.header.max),
+		mesh.header.version,
+		mesh.header.index_size,
+		mesh.header.encoding.name(),
+		layout.stride,
+		attributes.join(", "),
+		submeshes.join(", "),
+		mesh.checksum.map_or_else(|| "null".to_owned(), |crc| crc.to_string())
+	))
 }
 
-fn cross(a: Vertex, b: Vertex) -> Vertex {
-	Vertex{
-		x: a.y*b.z - a.z*b.y,
-		y: a.z*b.x - a.x*b.z,
-		z: a.x*b.y - a.y*b.x
+/// Markdown, or HTML when the file name ends with .html
+fn write_report(matches: &ArgMatches, reports: &[obj_to_mesh::report::AssetReport]) {
+	if let Some(path) = matches.value_of("report") {
+		let text = if path.ends_with(".html") || path.ends_with(".htm") {
+			obj_to_mesh::report::html(reports)
+		}
+		else {
+			obj_to_mesh::report::markdown(reports)
+		};
+		File::create(path).unwrap().write_all(text.as_bytes()).unwrap();
 	}
 }
 
-fn sub(a: Vertex, b: Vertex) -> Vertex {
-	Vertex{
-		x: a.x - b.x,
-		y: a.y - b.y,
-		z: a.z - b.z,
+enum Outcome {
+	/// With the report of the asset, when one is written
+	Converted(Option<obj_to_mesh::report::AssetReport>),
+	/// Nothing to write, not an error
+	Skipped(String),
+	/// After reporting the reason; the panics are failures too
+	Failed(String),
+}
+
+thread_local! {
+	/// The file converted on this thread, when its errors are JSON, for the panic hook
+	static JSON_ERRORS_OF: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Reports the panics of the conversions with --message-format json as JSON lines, with the file converted on the
+/// thread, which the daemon and the server have several of
+fn set_panic_hook() {
+	let default_hook = std::panic::take_hook();
+	std::panic::set_hook(Box::new(move |info| {
+		match JSON_ERRORS_OF.with(|file_name| file_name.borrow().clone()) {
+			Some(file_name) => {
+				let message = panic_message(info.payload());
+				eprintln!("{}", obj_to_mesh::diagnostics::json_line("error", "error", &file_name, None, None, &message));
+			},
+			None => default_hook(info),
+		}
+	}));
+}
+
+/// The conversion of a file on this thread, until dropped
+struct Converting;
+
+impl Converting {
+	fn start(file_name: &str, json: bool) -> Converting {
+		JSON_ERRORS_OF.with(|current| *current.borrow_mut() = json.then(|| file_name.to_owned()));
+		Converting
 	}
 }
 
-fn mul(a: Vertex, b: f64) -> Vertex {
-	Vertex{
-		x: a.x * b,
-		y: a.y * b,
-		z: a.z * b,
+impl Drop for Converting {
+	fn drop(&mut self) {
+		JSON_ERRORS_OF.with(|current| *current.borrow_mut() = None);
 	}
 }
 
-impl Mesh {
-	fn from_object(obj: &Object, generate_tangents: bool) -> Self {
-		let format = VertexFieldOffsets::from_object(&obj, generate_tangents);
-		let mut mesh = Mesh {
-			vertices: Vec::new(),
-			indices: Vec::new(),
-			map:HashMap::new(),
-			min: Vertex{x: f64::MAX, y: f64::MAX, z: f64::MAX },
-			max: Vertex{x: f64::MIN, y: f64::MIN, z: f64::MIN },
-			format: format,
+/// Adds the mesh files it writes to `mesh_files`
+fn convert_file(matches: &ArgMatches, args: &[String], input: &Path, output: &Path, mesh_files: &mut Vec<PathBuf>) -> Outcome {
+
+	//the build systems reading them need the errors in the same format as the warnings
+	let json = matches.value_of("message_format") == Some("json");
+	let file_name = input.display().to_string();
+	let _converting = Converting::start(&file_name, json);
+	//what the report lists, as printed
+	let printed_warnings = std::cell::RefCell::new(vec![]);
+	let report = |code: &str, message: &str| {
+		printed_warnings.borrow_mut().push(format!("{} [{}]", message, code));
+		if json {
+			eprintln!("{}", obj_to_mesh::diagnostics::json_line("warning", code, &file_name, None, None, message));
+		}
+		else {
+			eprintln!("warning: {}", message);
+		}
+	};
+
+	message!("Converting {} into {}..",
+		input.file_name().unwrap().to_str().unwrap(),
+		output.file_name().unwrap().to_str().unwrap()
+	);
+
+	let mut file = File::open(input).unwrap();
+
+	let mut content = String::new();
+	file.read_to_string(&mut content).unwrap();
+
+	let mut crc = obj_to_mesh::chunk::Crc32::default();
+	crc.update(content.as_bytes());
+	let source_hash = crc.value();
+
+	let skin = matches.value_of("weights").map(|path| {
+		let mut content = String::new();
+		File::open(path).unwrap().read_to_string(&mut content).unwrap();
+
+		match obj_to_mesh::skin::parse_weights(&content) {
+			Ok(skin) => skin,
+			Err(err) => panic!("{:?}", err),
+		}
+	});
+
+	let user_attributes = matches.value_of("attributes").map(|path| {
+		let mut content = String::new();
+		File::open(path).unwrap().read_to_string(&mut content).unwrap();
+
+		match obj_to_mesh::user_attributes::parse_user_attributes(&content) {
+			Ok(user) => user,
+			Err(err) => panic!("{:?}", err),
+		}
+	});
+
+	let vertex_struct = matches.value_of("vertex_struct").map(|path| {
+		let mut content = String::new();
+		File::open(path).unwrap().read_to_string(&mut content).unwrap();
+
+		match obj_to_mesh::vertex_struct::parse_vertex_struct(&content) {
+			Ok(vertex_struct) => vertex_struct,
+			Err(err) => panic!("{:?}", err),
+		}
+	});
+
+	let morph_targets = matches.values_of("morph").map_or(vec![], |paths| paths.map(|path| {
+		let path = Path::new(path);
+		let mut content = String::new();
+		File::open(path).unwrap().read_to_string(&mut content).unwrap();
+
+		match obj_to_mesh::parse_obj(content) {
+			Ok(set) => obj_to_mesh::morph::MorphTarget {
+				name: path.file_stem().unwrap().to_str().unwrap().to_owned(),
+				set,
+			},
+			Err(err) => panic!("{:?}", err),
+		}
+	}).collect());
+
+	let filter = obj_to_mesh::filter::NameFilter {
+		only: matches.values_of("only").map_or(vec![], |patterns| patterns.map(str::to_owned).collect()),
+		exclude: matches.values_of("exclude").map_or(vec![], |patterns| patterns.map(str::to_owned).collect()),
+	};
+
+	//the texture paths are relative to the mtl files
+	let confine = matches.is_present("confine");
+	let mut libraries: Vec<(PathBuf, obj_to_mesh::material::MaterialLibrary)> = obj_to_mesh::material::material_libraries(&content).iter()
+		.filter_map(|name| {
+			let path = input.with_file_name(name);
+			if confine && !is_confined(name) {
+				report("missing-file", &format!("{} is outside of the directory of the conversion, its materials won't be in the mesh", name));
+				return None;
+			}
+			match std::fs::read_to_string(&path) {
+				Ok(mtl) => Some((path, obj_to_mesh::material::parse_mtl(&mtl))),
+				Err(_) => {
+					report("missing-file", &format!("{} not found, its materials won't be in the mesh", path.display()));
+					None
+				},
+			}
+		})
+		.collect();
+	if let Some(path) = matches.value_of("material_overrides") {
+		let path = Path::new(path);
+		let overrides = match obj_to_mesh::material::parse_overrides(&std::fs::read_to_string(path).unwrap()) {
+			Ok(overrides) => overrides,
+			Err(err) => {
+				let message = format!("{}:{}: {}", path.display(), err.line_number, err.message);
+				if json {
+					eprintln!("{}", obj_to_mesh::diagnostics::json_line("error", "invalid-data", &file_name, None, None, &message));
+				}
+				else {
+					eprintln!("error: {}", message);
+				}
+				return Outcome::Failed(message);
+			},
 		};
+		//the materials that no mtl file has are added, with their textures relative to the override file
+		let mut added = obj_to_mesh::material::MaterialLibrary::default();
+		for o in &overrides {
+			let material = libraries.iter_mut().find_map(|(_, library)| library.materials.iter_mut().find(|m| m.name == o.name));
+			match material {
+				Some(material) => o.apply(material),
+				None => {
+					let mut material = obj_to_mesh::material::Material {
+						name: o.name.clone(),
+						..Default::default()
+					};
+					o.apply(&mut material);
+					added.materials.push(material);
+				},
+			}
+		}
+		if !added.materials.is_empty() {
+			libraries.push((path.to_owned(), added));
+		}
+	}
+	let materials = if libraries.is_empty() { None } else {
+		let mut materials = obj_to_mesh::material::MaterialLibrary::default();
+		for (_, library) in &libraries {
+			materials.append(library.clone());
+		}
+		Some(materials)
+	};
 
-		for geo in &obj.geometry {
-			for shape in &geo.shapes {
-				match *shape {
-					Shape::Triangle(v1, v2, v3) => {
-						mesh.add_index(v1, &obj, &format);
-						mesh.add_index(v2, &obj, &format);
-						mesh.add_index(v3, &obj, &format);
-					},
-					_=> panic!("Unsupported primitive mode")
+	let search_paths: Vec<PathBuf> = matches.values_of("texture_search_path").map_or(vec![], |dirs| dirs.map(PathBuf::from).collect());
+	let mut embedded_textures = vec![];
+	if matches.is_present("embed_textures") {
+		for (mtl, library) in &libraries {
+			for texture in library.materials.iter().flat_map(|m| &m.textures) {
+				if embedded_textures.iter().any(|(path, _)| *path == texture.path) {
+					continue;
+				}
+				match find_texture(mtl, &texture.path, &search_paths, confine) {
+					Some(source) => embedded_textures.push((texture.path.clone(), std::fs::read(source).unwrap())),
+					None => report("missing-file", &format!("texture {} not found, its path is stored instead", texture.path)),
 				}
 			}
 		}
+	}
 
-		if generate_tangents {
-			//http://gamedev.stackexchange.com/questions/68612/how-to-compute-tangent-and-bitangent-vectors
+	use obj_to_mesh::layout::Attribute;
+	let required: Vec<Attribute> = matches.values_of("require").map_or(vec![], |names| names.map(|name| match name {
+		"normals" => Attribute::Normal,
+		"uvs" => Attribute::Tex0,
+		"tangents" => Attribute::Tangent,
+		"bitangents" => Attribute::Bitangent,
+		"colors" => Attribute::Color,
+		_ => unreachable!(),
+	}).collect());
+
+	let mut options = obj_to_mesh::ConvertOptions {
+		//the required tangents are generated
+		generate_tangents: matches.occurrences_of("gen_tangents") > 0 || required.contains(&Attribute::Tangent),
+		vertex_colors: obj_to_mesh::colors::parse_vertex_colors(&content),
+		color_space: obj_to_mesh::layout::ColorSpace::from_name(matches.value_of("color_space").unwrap()).unwrap(),
+		emit_bitangent: matches.occurrences_of("emit_bitangent") > 0 || required.contains(&Attribute::Bitangent),
+		skin,
+		morph_targets,
+		recompute_normals: matches.value_of("recompute_normals").map(|weighting| obj_to_mesh::normals::NormalWeighting::from_name(weighting).unwrap()),
+		crease_angle: matches.value_of("crease_angle").map(|degrees| degrees.parse().unwrap()),
+		flat: matches.is_present("flat"),
+		smooth_seams: matches.value_of("smooth_seams").map(|what| obj_to_mesh::normals::SeamSmoothing::from_name(what).unwrap()),
+		stale_normals: obj_to_mesh::StaleNormals::from_name(matches.value_of("stale_normals").unwrap()).unwrap(),
+		curvature: matches.value_of("curvature").map(|kind| obj_to_mesh::curvature::Curvature::from_name(kind).unwrap()),
+		material_ids: matches.occurrences_of("material_ids") > 0,
+		user_attributes,
+		fill_missing: matches.occurrences_of("fill_missing") > 0,
+		required,
+		repair: matches.occurrences_of("repair") > 0,
+		renormalize: matches.is_present("renormalize"),
+		normal_tolerance: matches.value_of("normal_tolerance").map(|tolerance| tolerance.parse().unwrap()),
+		max_vertices: matches.value_of("max_vertices").map(|count| count.parse().unwrap()),
+		split_components: matches.is_present("split_components"),
+		split_by_material: matches.value_of("split_by") == Some("material"),
+		sort_by_material: matches.is_present("sort_by_material"),
+		grid_cell_size: matches.value_of("chunk_grid").map(|size| size.parse().unwrap()),
+		uv_overflow: obj_to_mesh::UvOverflow::from_name(matches.value_of("uv_overflow").unwrap()).unwrap(),
+		check_manifold: matches.occurrences_of("check_manifold") > 0,
+		dedup_faces: matches.occurrences_of("dedup_faces") > 0,
+		remove_small_components: matches.value_of("remove_small_components").map(|threshold| match obj_to_mesh::manifold::ComponentThreshold::from_name(threshold) {
+			Some(threshold) => threshold,
+			None => panic!("{} is neither a number of triangles nor an area", threshold),
+		}),
+		fill_holes: matches.value_of("fill_holes").map(|edges| edges.parse().unwrap()),
+		max_hole_perimeter: matches.value_of("max_hole_perimeter").map(|length| length.parse().unwrap()),
+		fix_winding: matches.value_of("fix_winding").map(|fix| obj_to_mesh::manifold::WindingFix::from_name(fix).unwrap()),
+		inverted_faces: matches.value_of("inverted_faces").map(|action| obj_to_mesh::manifold::InvertedFaces::from_name(action).unwrap()),
+		remesh: matches.value_of("remesh").map(|length| {
+			let mut settings = obj_to_mesh::remesh::RemeshSettings::new(length.parse().unwrap());
+			if let Some(iterations) = matches.value_of("remesh_iterations") {
+				settings.iterations = iterations.parse().unwrap();
+			}
+			settings
+		}),
+		merge_coplanar: matches.value_of("merge_coplanar").map(|degrees| degrees.parse().unwrap()),
+		symmetry: matches.occurrences_of("symmetry") > 0,
+		mirrored_indices: matches.occurrences_of("mirrored_indices") > 0,
+		emit_remap: matches.is_present("emit_remap"),
+		measure_quantization: matches.is_present("verbose") || matches.is_present("report"),
+		encoding: obj_to_mesh::codec::Encoding::from_name(matches.value_of("encoding").unwrap()).unwrap(),
+		palette: matches.values_of("palette").map_or(vec![], |names| names.map(|name| match name {
+			"normals" => Attribute::Normal,
+			"colors" => Attribute::Color,
+			_ => unreachable!(),
+		}).collect()),
+		quantize_positions: matches.is_present("quantize_positions"),
+		shadow_indices: matches.is_present("shadow_indices"),
+		progressive_base: matches.value_of("progressive_base").unwrap().parse().unwrap(),
+		obb: matches.occurrences_of("obb") > 0,
+		kdop: matches.value_of("kdop").map(|k| k.parse().unwrap()),
+		bake_bvh: matches.occurrences_of("bake_bvh") > 0,
+		pivot: matches.value_of("pivot").map(|pivot| obj_to_mesh::Pivot::from_name(pivot).unwrap()),
+		filter,
+		merge_objects: matches.occurrences_of("merge_objects") > 0,
+		detect_instances: matches.occurrences_of("detect_instances") > 0,
+		portal_gap: matches.value_of("portals").map(|gap| gap.parse().unwrap()),
+		materials,
+		texture_paths: obj_to_mesh::material::TexturePaths {
+			strip_directories: matches.is_present("strip_texture_dirs"),
+			prefix: matches.value_of("texture_prefix").map(str::to_owned),
+		},
+		embedded_textures,
+		pbr: matches.is_present("pbr"),
+		bake_material_color: matches.is_present("bake_material_color"),
+		external_materials: matches.is_present("emit_materials"),
+		format_version: matches.value_of("format_version").map(|version| version.parse().unwrap()),
+		metadata: vec![
+			("source".to_owned(), input.file_name().unwrap().to_str().unwrap().to_owned()),
+			("source_hash".to_owned(), format!("crc32:{:08x}", source_hash)),
+			("command".to_owned(), args.join(" ")),
+		],
+		gen_uvs: matches.value_of("gen_uvs").map(|projection| obj_to_mesh::uvgen::UvProjection::from_name(projection).unwrap()),
+		lightmap: matches.value_of("lightmap").map(|resolution| obj_to_mesh::lightmap::LightmapSettings {
+			resolution: resolution.parse().unwrap(),
+			padding: matches.value_of("lightmap_padding").unwrap().parse().unwrap(),
+		}),
+	};
 
-			let mut tan1 = vec!(Vertex{x: 0.0, y: 0.0, z:0.0}; mesh.vertices.len());
-			let mut tan2 = vec!(Vertex{x: 0.0, y: 0.0, z:0.0}; mesh.vertices.len());
+	let source_bytes = content.len();
+	let mut set = match obj_to_mesh::parse_obj_filtered(content, &options.filter) {
+	    Ok(set) => set,
+	    Err(err) => panic!("{:?}", err),
+	};
+	let mut asset_report = matches.is_present("report")
+		.then(|| obj_to_mesh::report::AssetReport::new(&file_name, &output.display().to_string(), source_bytes, &set));
+
+	if let Some(resolution) = matches.value_of("repack_uvs") {
+		obj_to_mesh::atlas::repack_uvs(&mut set, &obj_to_mesh::atlas::AtlasSettings {
+			resolution: resolution.parse().unwrap(),
+			padding: matches.value_of("atlas_padding").unwrap().parse().unwrap(),
+		});
+	}
 
-			let mut ii = 0;
-			while ii < mesh.indices.len() {
-				let i1 = mesh.indices[ii + 0];
-				let i2 = mesh.indices[ii + 1];
-				let i3 = mesh.indices[ii + 2];
+	if let Some(ref skin) = options.skin {
+		let vertex_count: usize = set.objects.iter().map(|obj| obj.vertices.len()).sum();
+		if skin.vertices.len() > vertex_count {
+			panic!("The weights file has {} entries, but the obj file has {} vertices", skin.vertices.len(), vertex_count);
+		}
+	}
 
-				let v1 = mesh.vertices[i1].pos;
-				let v2 = mesh.vertices[i2].pos;
-				let v3 = mesh.vertices[i3].pos;
+	if let Some(ref user) = options.user_attributes {
+		let vertex_count: usize = set.objects.iter().map(|obj| obj.vertices.len()).sum();
+		if user.vertex_count > vertex_count {
+			panic!("The attributes file refers to vertex {}, but the obj file has {} vertices", user.vertex_count, vertex_count);
+		}
+	}
 
-				let w1 = mesh.vertices[i1].tex.unwrap();
-				let w2 = mesh.vertices[i2].tex.unwrap();
-				let w3 = mesh.vertices[i3].tex.unwrap();
+	let stem = output.file_stem().unwrap().to_str().unwrap().to_owned();
+	let proxy = matches.value_of("hlod").map(|cells| {
+		let mut settings = obj_to_mesh::simplify::SimplifySettings::new(cells.parse().unwrap());
+		for spec in matches.values_of("hlod_preserve").into_iter().flatten() {
+			//a feature alone is locked
+			let (feature, how) = spec.split_once('=').unwrap_or((spec, "lock"));
+			let Some(preserve) = obj_to_mesh::simplify::Preserve::from_name(how) else {
+				panic!("{} is neither lock nor a weight above 0", how);
+			};
+			match feature {
+				"seams" => settings.uv_seams = preserve,
+				"boundaries" => settings.boundaries = preserve,
+				"materials" => settings.material_borders = preserve,
+				_ => panic!("Can't preserve {}, only seams, boundaries and materials", feature),
+			}
+		}
+		let proxy = obj_to_mesh::convert_hlod_proxy(&set, &options, &settings);
+		let files = obj_to_mesh::scene::Scene::hlod_files(&stem, &proxy);
+		//links every object to it
+		options.metadata.push(("hlod_proxy".to_owned(), files.files[0].clone()));
+		(proxy, files)
+	});
+
+	if let Some(frames) = matches.value_of("impostor") {
+		let settings = obj_to_mesh::impostor::ImpostorSettings {
+			frames: frames.parse().unwrap(),
+			resolution: matches.value_of("impostor_resolution").unwrap().parse().unwrap(),
+		};
+		let (card, atlas) = obj_to_mesh::convert_impostor(&set, &options, &settings);
+		let mut file = File::create(output.with_extension("impostor.mesh")).unwrap();
+		file.write_all(&card.parts[0]).unwrap();
+		mesh_files.push(output.with_extension("impostor.mesh"));
+		for (suffix, rgba) in [("albedo", &atlas.albedo), ("normal", &atlas.normal)] {
+			let mut file = File::create(output.with_extension(format!("impostor_{}.tga", suffix))).unwrap();
+			file.write_all(&obj_to_mesh::impostor::write_tga(atlas.side, rgba)).unwrap();
+		}
+		//the last level of detail of the mesh
+		options.metadata.push(("impostor".to_owned(), format!("{}.impostor.mesh", stem)));
+	}
 
-				let x1 = v2.x - v1.x;
-				let x2 = v3.x - v1.x;
-				let y1 = v2.y - v1.y;
-				let y2 = v3.y - v1.y;
-				let z1 = v2.z - v1.z;
-				let z2 = v3.z - v1.z;
+	//the materials the objects use, in order
+	let mut used_materials: Vec<String> = vec![];
+	for name in set.objects.iter().flat_map(|obj| &obj.geometry).filter_map(|geo| geo.material_name.as_ref()) {
+		if !used_materials.contains(name) {
+			used_materials.push(name.clone());
+		}
+	}
 
-				let s1 = w2.x - w1.x;
-				let s2 = w3.x - w1.x;
-				let t1 = w2.y - w1.y;
-				let t2 = w3.y - w1.y;
+	//broken references only show at runtime otherwise
+	let mut texture_warnings = vec![];
+	for name in &used_materials {
+		let Some((mtl, material)) = libraries.iter().find_map(|(mtl, library)| library.find(name).map(|m| (mtl, m))) else {
+			continue;
+		};
+		for texture in &material.textures {
+			if find_texture(mtl, &texture.path, &search_paths, confine).is_none() {
+				texture_warnings.push(obj_to_mesh::diagnostics::Warning::new(obj_to_mesh::diagnostics::WarningKind::MissingFile,
+					format!("The {} texture {} of material {} was not found", texture.kind, texture.path, name)));
+			}
+		}
+	}
 
-				let r = 1.0 / (s1 * t2 - s2 * t1);
-				let sdir = Vertex{
-					x: (t2 * x1 - t1 * x2) * r, 
-					y: (t2 * y1 - t1 * y2) * r,
-					z: (t2 * z1 - t1 * z2) * r,
-				};
-				
-				addmut(&mut tan1[i1], sdir);
-				addmut(&mut tan1[i2], sdir);
-				addmut(&mut tan1[i3], sdir);
-
-				let tdir = Vertex{
-					x: (s1 * x2 - s2 * x1) * r,
-					y: (s1 * y2 - s2 * y1) * r,
-                	z: (s1 * z2 - s2 * z1) * r,
-				};
+	if let Some(path) = matches.value_of("emit_materials") {
+		if options.materials.is_none() {
+			panic!("{} has no mtl file to write the materials of", input.display());
+		}
+		options.metadata.push(("material_library".to_owned(), Path::new(path).file_name().unwrap().to_str().unwrap().to_owned()));
+	}
 
-				addmut(&mut tan2[i1], tdir);
-				addmut(&mut tan2[i2], tdir);
-				addmut(&mut tan2[i3], tdir);
+	let meshes = obj_to_mesh::convert_obj_set(set, &options);
+	if meshes.is_empty() {
+		return Outcome::Skipped("has no object that passes the filters".to_owned());
+	}
+	let strict = matches.occurrences_of("strict") > 0;
+	//--strict is --deny warnings
+	let deny: Vec<&str> = matches.values_of("deny").map_or(vec![], |kinds| kinds.collect());
+	let denied = |warning: &obj_to_mesh::diagnostics::Warning| warning.error || strict || deny.contains(&"warnings") || deny.contains(&warning.kind.name());
+	let mut error_count = 0;
+	let object_warnings = meshes.iter().chain(proxy.as_ref().map(|(proxy, _)| proxy))
+		.flat_map(|mesh| mesh.warnings.iter().map(move |w| (Some(mesh.name.as_str()), w)));
+	for (object, warning) in texture_warnings.iter().map(|w| (None, w)).chain(object_warnings) {
+		let severity = if denied(warning) { "error" } else { "warning" };
+		printed_warnings.borrow_mut().push(warning.to_string());
+		if json {
+			eprintln!("{}", warning.to_json(severity, &file_name, object));
+		}
+		else {
+			eprintln!("{}: {}", severity, warning);
+		}
+		if denied(warning) {
+			error_count += 1;
+		}
+	}
+	if error_count > 0 {
+		let message = format!("Not writing {} because of {} problems", output.display(), error_count);
+		if json {
+			eprintln!("{}", obj_to_mesh::diagnostics::json_line("error", "strict", &file_name, None, None, &message));
+		}
+		else {
+			eprintln!("{}", message);
+		}
+		return Outcome::Failed(format!("{} problems", error_count));
+	}
+	if let Some(path) = matches.value_of("emit_materials") {
+		let library = options.materials.as_ref().unwrap();
+		let used: Vec<&obj_to_mesh::material::Material> = used_materials.iter().filter_map(|name| library.find(name)).collect();
+		let mut file = File::create(path).unwrap();
+		file.write_all(obj_to_mesh::material::to_json(&used, &options.texture_paths, options.pbr).as_bytes()).unwrap();
+	}
+	if !meshes[0].instances.is_empty() {
+		message!("Found {} instances", meshes[0].instances.len());
+	}
+	let parts = &meshes[0].parts;
+	let data = &parts[0];
+	if let Some(asset_report) = &mut asset_report {
+		//the files written below
+		let written: Vec<&Vec<u8>> = if matches.is_present("scene") {
+			meshes.iter().chain(proxy.as_ref().map(|(proxy, _)| proxy)).flat_map(|mesh| &mesh.parts).collect()
+		}
+		else {
+			parts.iter().collect()
+		};
+		for part in written {
+			asset_report.add_part(part);
+		}
+	}
+	//of the meshes written below
+	let mut quantization = obj_to_mesh::quantization::QuantizationReport::default();
+	let written: Vec<&obj_to_mesh::ConvertedMesh> = if matches.is_present("scene") {
+		meshes.iter().chain(proxy.as_ref().map(|(proxy, _)| proxy)).collect()
+	}
+	else {
+		meshes[..1].iter().collect()
+	};
+	for report in written.iter().filter_map(|mesh| mesh.quantization.as_ref()) {
+		quantization.merge(report);
+	}
+	if matches.is_present("verbose") {
+		message!("Quantization errors:\n{}", quantization.to_text().trim_end());
+	}
+	if let Some(asset_report) = &mut asset_report {
+		asset_report.quantization = quantization;
+	}
 
-				ii += 3;
+	if matches.is_present("scene") {
+		//every object, next to the scene
+		let mut scene = obj_to_mesh::scene::Scene::new(&stem, &meshes);
+		if let Some((proxy, files)) = proxy {
+			for (file_name, part) in files.files.iter().zip(&proxy.parts) {
+				let mut file = File::create(output.with_file_name(file_name)).unwrap();
+				file.write_all(part).unwrap();
+				mesh_files.push(output.with_file_name(file_name));
 			}
+			scene.hlod = Some(files);
+		}
+		for (mesh, converted) in scene.meshes.iter().zip(&meshes) {
+			for (file_name, part) in mesh.files.iter().zip(&converted.parts) {
+				let mut file = File::create(output.with_file_name(file_name)).unwrap();
+				file.write_all(part).unwrap();
+				mesh_files.push(output.with_file_name(file_name));
+			}
+			for (file_name, remap) in mesh.files.iter().zip(&converted.remaps) {
+				write_remap(&output.with_file_name(file_name), remap);
+			}
+			if let Some(ref vertex_struct) = vertex_struct {
+				for (file_name, part) in mesh.files.iter().zip(&converted.parts) {
+					write_vertex_struct(&output.with_file_name(file_name), part, vertex_struct);
+				}
+			}
+		}
+		message!("Wrote {} meshes for {} objects", scene.meshes.len(), scene.nodes.len());
 
-			for a in 0..mesh.vertices.len()	{
-				let n = mesh.vertices[a].normal.unwrap();
-				let t = tan1[a];
+		let mut file = File::create(output.with_extension("scene.json")).unwrap();
+		file.write_all(scene.to_json().as_bytes()).unwrap();
+	}
+	else if parts.len() == 1 && meshes[0].part_names[0].is_empty() {
+		let mut file = File::create(output).unwrap();
+		file.write_all(&output_bytes(matches, data, &report)).unwrap();
+		mesh_files.push(output.to_owned());
+		if let Some(remap) = meshes[0].remaps.first() {
+			write_remap(output, remap);
+		}
+		if let Some(ref vertex_struct) = vertex_struct {
+			write_vertex_struct(output, data, vertex_struct);
+		}
+	}
+	else {
+		//name.0.mesh, name.wood.mesh, name.0_0_1.mesh...
+		message!("Split into {} parts", parts.len());
+		for (part, name) in parts.iter().zip(&meshes[0].part_names) {
+			let mut file = File::create(output.with_extension(format!("{}.{}", name, extension(matches)))).unwrap();
+			file.write_all(&output_bytes(matches, part, &report)).unwrap();
+			mesh_files.push(output.with_extension(format!("{}.{}", name, extension(matches))));
+		}
+		for (remap, name) in meshes[0].remaps.iter().zip(&meshes[0].part_names) {
+			write_remap(&output.with_extension(format!("{}.{}", name, extension(matches))), remap);
+		}
+		if let Some(ref vertex_struct) = vertex_struct {
+			for (part, name) in parts.iter().zip(&meshes[0].part_names) {
+				write_vertex_struct(&output.with_extension(format!("{}.{}", name, extension(matches))), part, vertex_struct);
+			}
+		}
+	}
 
-				// Gram-Schmidt orthogonalize
-				mesh.vertices[a].tangent = Some(normalize(sub(t,mul(n, dot(n, t)))));
-				
-				// Calculate handedness
-				mesh.vertices[a].tangent_handedness = if dot(cross(n, t), tan2[a]) < 0.0 {
-					-1.0
+	if matches.is_present("copy_textures") {
+		//where the rewritten paths point, relative to the output
+		for (mtl, library) in &libraries {
+			for texture in library.materials.iter().flat_map(|m| &m.textures) {
+				let rewritten = options.texture_paths.rewrite(&texture.path);
+				if Path::new(&rewritten).is_absolute() {
+					report("unsupported", &format!("not copying {}, its path is absolute", texture.path));
+					continue;
 				}
-				else {
-					1.0
+				let destination = output.with_file_name(rewritten);
+				match find_texture(mtl, &texture.path, &search_paths, confine) {
+					Some(source) => {
+						std::fs::create_dir_all(destination.parent().unwrap()).unwrap();
+						std::fs::copy(source, destination).unwrap();
+					},
+					None => report("missing-file", &format!("texture {} not found", texture.path)),
 				}
 			}
 		}
+	}
 
-		mesh
-	}	
+	let header = obj_to_mesh::reader::read_header(&mut &data[..]).unwrap();
+	if header.encoding == obj_to_mesh::codec::Encoding::Blocks {
+		let layout = header.layout();
+		let blocks = obj_to_mesh::blocks::BlockLayout::new(&layout).unwrap();
+		let mut file = File::create(output.with_extension("blocks.json")).unwrap();
+		file.write_all(emit::blocks::json(&layout, &blocks).as_bytes()).unwrap();
+	}
 
-	fn create_vertex(&mut self, vtni: VTNIndex, obj: &Object, format: &VertexFieldOffsets) -> usize {
-		let idx = self.vertices.len();
+	if let Some(language) = matches.value_of("emit_shader") {
+		let language = emit::shader::Language::from_name(language).unwrap();
 
-		let v = GPUVertex::from_vtni_and_obj(vtni, obj, format);
+		let mut file = File::create(output.with_extension(language.extension())).unwrap();
+		file.write_all(emit::shader::source(&header.layout(), language).as_bytes()).unwrap();
+	}
 
-		self.min = vert_min(self.min, v.pos);
-		self.max = vert_max(self.max, v.pos);
+	if let Some(language) = matches.value_of("emit_gl") {
+		let language = emit::gl::Language::from_name(language).unwrap();
 
-		self.vertices.push( v );
+		let mut file = File::create(output.with_extension(format!("gl.{}", language.extension()))).unwrap();
+		file.write_all(emit::gl::source(&header.layout(), language).as_bytes()).unwrap();
+	}
 
-		idx
+	match matches.value_of("emit_d3d12") {
+		Some("cpp") => {
+			let mut file = File::create(output.with_extension("d3d12.h")).unwrap();
+			file.write_all(emit::d3d12::source(&header.layout(), 0).as_bytes()).unwrap();
+		},
+		Some("json") => {
+			let mut file = File::create(output.with_extension("d3d12.json")).unwrap();
+			file.write_all(emit::d3d12::json(&header.layout(), 0).as_bytes()).unwrap();
+		},
+		_ => {},
 	}
 
-	fn add_index(&mut self, vtni: VTNIndex, obj: &Object, format: &VertexFieldOffsets) {
-		if let Some(idx) = self.map.get(&vtni) {
-			self.indices.push(*idx);
-			return;
-		}
+	match matches.value_of("emit_metal") {
+		Some("swift") => {
+			let mut file = File::create(output.with_extension("metal.swift")).unwrap();
+			file.write_all(emit::metal::source(&header.layout(), 0).as_bytes()).unwrap();
+		},
+		Some("json") => {
+			let mut file = File::create(output.with_extension("metal.json")).unwrap();
+			file.write_all(emit::metal::json(&header.layout(), 0).as_bytes()).unwrap();
+		},
+		_ => {},
+	}
 
-		let idx = self.create_vertex(vtni, obj, format);
-		self.map.insert(vtni, idx);
-		self.indices.push(idx);
+	if let Some(asset_report) = &mut asset_report {
+		asset_report.warnings = printed_warnings.take();
 	}
 
-	fn get_index_size(&self) -> usize {
-		match self.vertices.len() {
-		    n if n <= 0xff => 1,
-		    n if n <= 0xffff => 2,
-		    _ => 4
-		}
+	message!("Done!");
+	Outcome::Converted(asset_report)
+}
+
+/// Of the files in the --format
+fn extension(matches: &ArgMatches) -> &'static str {
+	match matches.value_of("format") {
+		Some("draco") => "drc",
+		_ => "mesh",
 	}
 }
 
-fn convert_obj(obj: Object, generate_tangents: bool) -> Vec<u8> {
+/// What is written for a converted part in the --format
+#[cfg_attr(not(feature = "draco"), allow(unused_variables))]
+fn output_bytes(matches: &ArgMatches, part: &[u8], report: &dyn Fn(&str, &str)) -> Vec<u8> {
+	match matches.value_of("format") {
+		#[cfg(feature = "draco")]
+		Some("draco") => {
+			let mut settings = obj_to_mesh::draco::DracoSettings::default();
+			for spec in matches.values_of("draco_bits").into_iter().flatten() {
+				let parsed = spec.split_once('=').and_then(|(attribute, bits)| Some((attribute, bits.parse().ok()?)));
+				match parsed {
+					Some(("position", bits)) => settings.position_bits = bits,
+					Some(("normal", bits)) => settings.normal_bits = bits,
+					Some(("uv", bits)) => settings.tex_coord_bits = bits,
+					_ => panic!("{} isn't position, normal or uv = bits", spec),
+				}
+			}
+			let mesh = obj_to_mesh::reader::read_mesh(&mut &part[..]).unwrap();
+			let (data, dropped) = obj_to_mesh::draco::encode(&mesh, &settings);
+			if !dropped.is_empty() {
+				report("unsupported", &format!("the Draco file has no {}", dropped.join(", ")));
+			}
+			data
+		},
+		_ => part.to_vec(),
+	}
+}
 
-	//build a VTNIndex => Vertex map and build actual vertices
-	let mesh = Mesh::from_object(&obj, generate_tangents);
+/// Next to the mesh file, as name.remap.csv
+fn write_remap(mesh_file: &Path, remap: &str) {
+	let mut file = File::create(mesh_file.with_extension("remap.csv")).unwrap();
+	file.write_all(remap.as_bytes()).unwrap();
+}
 
-	let mut data = vec![];
+/// Next to the mesh file, as name.vertices.bin
+fn write_vertex_struct(mesh_file: &Path, part: &[u8], vertex_struct: &obj_to_mesh::vertex_struct::VertexStruct) {
+	let mesh = obj_to_mesh::reader::read_mesh(&mut &part[..]).unwrap();
+	match vertex_struct.pack(&mesh) {
+		Ok(vertices) => {
+			let mut file = File::create(mesh_file.with_extension("vertices.bin")).unwrap();
+			file.write_all(&vertices).unwrap();
+		},
+		Err(err) => panic!("Can't pack {}: {}", mesh_file.display(), err),
+	}
+}
 
-	//write the index size in bytes
-	let index_size = mesh.get_index_size() as u8;
-	data.write_u8(index_size).unwrap();
+/// As written from the mtl file, absolute, or moved next to the mtl file, then in the search paths with the
+/// directories of the path and without them; with --confine, only the relative paths without ..
+fn find_texture(mtl: &Path, path: &str, search_paths: &[PathBuf], confine: bool) -> Option<PathBuf> {
+	if confine && !is_confined(path) {
+		return None;
+	}
+	let path = path.replace('\\', "/");
+	let file_name = path.rsplit('/').next().unwrap();
+	let mut candidates = vec![mtl.with_file_name(&path), PathBuf::from(&path), mtl.with_file_name(file_name)];
+	for dir in search_paths {
+		candidates.push(dir.join(path.trim_start_matches('/')));
+		candidates.push(dir.join(file_name));
+	}
+	candidates.into_iter().find(|p| p.is_file())
+}
 
-	data.write_u8(1).unwrap(); //always a triangle list
+/// Whether a path of an obj or mtl file stays in the current directory
+fn is_confined(path: &str) -> bool {
+	Path::new(&path.replace('\\', "/")).components().all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+}
 
-	//write the vertex fields
-	data.write_u8(0).unwrap();  //Position2D
-	data.write_u8(1).unwrap();	//Position3D
-	data.write_u8(0).unwrap();	//Color
-	data.write_u8( if mesh.format.normal.is_some() { 1 } else { 0 } ).unwrap(); //Normal
-	data.write_u8( if mesh.format.tangent.is_some() { 1 } else { 0 } ).unwrap();	//Tangent
-	data.write_u8( if mesh.format.tex0.is_some() { 1 } else { 0 } ).unwrap();  //Tex0
-	data.write_u8(0).unwrap();	//Tex1
+fn inspect(matches: &ArgMatches) {
+	let input = Path::new(matches.value_of("input").unwrap());
 
-	data.write_f32::<LittleEndian>(mesh.max.x as f32).unwrap();
-	data.write_f32::<LittleEndian>(mesh.max.y as f32).unwrap();
-	data.write_f32::<LittleEndian>(mesh.max.z as f32).unwrap();
+	let mut file = File::open(input).unwrap();
+	let mesh = match obj_to_mesh::reader::read_mesh(&mut file) {
+		Ok(mesh) => mesh,
+		Err(err) => panic!("{}", err),
+	};
+	let layout = mesh.header.layout();
 
-	data.write_f32::<LittleEndian>(mesh.min.x as f32).unwrap();
-	data.write_f32::<LittleEndian>(mesh.min.y as f32).unwrap();
-	data.write_f32::<LittleEndian>(mesh.min.z as f32).unwrap();
+	//read_mesh already checked it, if there is one
+	if matches.is_present("verify") {
+		match mesh.checksum {
+			Some(crc) => println!("checksum {:08x} verified", crc),
+			None => panic!("{} has no checksum", input.display()),
+		}
+	}
 
-	data.write_u32::<LittleEndian>(mesh.vertices.len() as u32).unwrap();
-	data.write_u32::<LittleEndian>(mesh.indices.len() as u32).unwrap();
+	let (primitive_size, primitives) = match mesh.header.topology {
+		obj_to_mesh::reader::Topology::TriangleList => (3, "triangles"),
+		obj_to_mesh::reader::Topology::LineList => (2, "lines"),
+		obj_to_mesh::reader::Topology::PointList => (1, "points"),
+	};
+	println!("{}: {} vertices, {} indices ({} {})",
+		input.file_name().unwrap().to_str().unwrap(),
+		mesh.positions.len(),
+		mesh.indices.len(),
+		mesh.indices.len() / primitive_size,
+		primitives
+	);
+	if let Some(ref shadow) = mesh.shadow_indices {
+		println!("shadow indices: {} ({} triangles)", shadow.len(), shadow.len() / 3);
+	}
+	println!("bounds: {:?} - {:?}", mesh.header.min, mesh.header.max);
+	println!("format version: {}", mesh.header.version);
+	println!("index size: {} bytes", mesh.header.index_size);
+	println!("encoding: {}", mesh.header.encoding.name());
+	let palette = mesh.header.format.palette;
+	if !palette.is_empty() {
+		let attributes: Vec<&str> = obj_to_mesh::layout::Palette::ATTRIBUTES.iter().filter(|&&attr| palette.has(attr)).map(|attr| attr.name()).collect();
+		println!("palette: {}, with {:?} indices", attributes.join(", "), palette.index_format());
+	}
+	if let Some(dequantization) = &mesh.header.dequantization {
+		println!("dequantization matrix, by column: {:?}", dequantization.matrix);
+	}
+	println!("stride: {} bytes", layout.stride);
+	for attr in &layout.attributes {
+		println!("\t{:<12} {:<16} offset {:<4} location {}",
+			attr.name,
+			format!("{:?}", attr.format),
+			attr.offset,
+			attr.location
+		);
+	}
+	if mesh.colors.is_some() {
+		println!("color space: {:?}", mesh.header.color_space);
+	}
+	for submesh in &mesh.submeshes {
+		println!("submesh {}: indices {}..{}, bounds {:?} - {:?}, radius {}",
+			submesh.material.as_deref().unwrap_or("(no material)"),
+			submesh.first_index,
+			submesh.first_index + submesh.index_count,
+			submesh.min,
+			submesh.max,
+			submesh.radius
+		);
+	}
+	if let Some(ref obb) = mesh.obb {
+		println!("oriented box: center {:?}, half extents {:?}, axes {:?}", obb.center, obb.half_extents, obb.axes);
+	}
+	if let Some(ref kdop) = mesh.kdop {
+		println!("{}-DOP: {:?}", kdop.k, kdop.slabs);
+	}
+	if let Some(ref measurements) = mesh.measurements {
+		match measurements.volume {
+			Some(volume) => println!("surface area: {}, volume: {}", measurements.surface_area, volume),
+			None => println!("surface area: {}, open", measurements.surface_area),
+		}
+	}
+	if let Some(ref bvh) = mesh.bvh {
+		println!("BVH: {} nodes, {} triangles", bvh.nodes.len(), bvh.triangles.len());
+	}
+	for instance in &mesh.instances {
+		println!("instance {}: {:?}", instance.name, instance.transform);
+	}
+	for material in &mesh.materials {
+		println!("material {}", material.name);
+		if let Some(ref pbr) = material.pbr {
+			println!("\tbase color {:?}, metallic {}, roughness {}, emissive {:?}", pbr.base_color, pbr.metallic, pbr.roughness, pbr.emissive);
+			let slots = [
+				("base color", pbr.base_color_texture),
+				("metallic", pbr.metallic_texture),
+				("roughness", pbr.roughness_texture),
+				("normal", pbr.normal_texture),
+				("emissive", pbr.emissive_texture),
+			];
+			for (slot, texture) in slots {
+				if let Some(i) = texture {
+					println!("\t{} texture: {}", slot, material.textures[i].kind);
+				}
+			}
+		}
+		for texture in &material.textures {
+			match texture.source {
+				obj_to_mesh::reader::TextureSource::Path(ref path) => println!("\t{} {}", texture.kind, path),
+				obj_to_mesh::reader::TextureSource::Embedded(i) => {
+					let embedded = &mesh.textures[i];
+					println!("\t{} {} (embedded, {} bytes)", texture.kind, embedded.path, embedded.content.len());
+				},
+			}
+		}
+	}
+	for cell in &mesh.cells {
+		println!("cell {}: bounds {:?} - {:?}, {} portals", cell.name, cell.min, cell.max, cell.portals.len());
+		for portal in &cell.portals {
+			println!("\tportal to {}: center {:?}, normal {:?}, {} corners", portal.other, portal.center, portal.normal, portal.corners.len());
+		}
+	}
+	if let Some(ref mirror) = mesh.mirror {
+		println!("mirrored through {} = {}{}", ["x", "y", "z"][mirror.axis], mirror.offset,
+			if mirror.mirrored_indices.is_some() { ", with mirrored indices" } else { "" });
+	}
+	for (key, value) in &mesh.metadata {
+		println!("{}: {}", key, value);
+	}
+	for target in &mesh.morph_targets {
+		println!("morph target {}: {} vertices", target.name, target.deltas.len());
+	}
 
-	for v in mesh.vertices {
-		v.write_to(&mut data);
+	if matches.is_present("wgpu") {
+		println!();
+		print!("{}", emit::wgpu::source(&layout));
 	}
 
-	for idx in mesh.indices {
-		match index_size {
-			1 => data.write_u8(idx as u8).unwrap(),
-			2 => data.write_u16::<LittleEndian>(idx as u16).unwrap(),
-			4 => data.write_u32::<LittleEndian>(idx as u32).unwrap(),
-			_ => panic!("Invalid index size"),
+	match matches.value_of("vulkan") {
+		Some("rust") => {
+			println!();
+			print!("{}", emit::vulkan::source(&layout, 0));
+		},
+		Some("json") => {
+			println!();
+			print!("{}", emit::vulkan::json(&layout, 0));
+		},
+		_ => {},
+	}
+
+	if let Some(language) = matches.value_of("gl") {
+		println!();
+		print!("{}", emit::gl::source(&layout, emit::gl::Language::from_name(language).unwrap()));
+	}
+
+	match matches.value_of("d3d12") {
+		Some("cpp") => {
+			println!();
+			print!("{}", emit::d3d12::source(&layout, 0));
+		},
+		Some("json") => {
+			println!();
+			print!("{}", emit::d3d12::json(&layout, 0));
+		},
+		_ => {},
+	}
+
+	match matches.value_of("metal") {
+		Some("swift") => {
+			println!();
+			print!("{}", emit::metal::source(&layout, 0));
+		},
+		Some("json") => {
+			println!();
+			print!("{}", emit::metal::json(&layout, 0));
+		},
+		_ => {},
+	}
+
+	if mesh.header.encoding == obj_to_mesh::codec::Encoding::Blocks {
+		if let Some(blocks) = obj_to_mesh::blocks::BlockLayout::new(&layout) {
+			println!();
+			print!("{}", emit::blocks::json(&layout, &blocks));
 		}
 	}
 
-	data
+	if let Some(language) = matches.value_of("shader") {
+		println!();
+		print!("{}", emit::shader::source(&layout, emit::shader::Language::from_name(language).unwrap()));
+	}
 }
 
-fn convert_obj_set(set: ObjSet, generate_tangents: bool) -> Vec<Vec<u8>> {
-	let mut data: Vec<Vec<u8>> = vec![];
+fn bake_normalmap(matches: &ArgMatches) {
+	let read = |arg: &str| {
+		let path = Path::new(matches.value_of(arg).unwrap());
+		match obj_to_mesh::parse_obj(std::fs::read_to_string(path).unwrap()) {
+			Ok(set) => set,
+			Err(err) => panic!("{}:{}: {}", path.display(), err.line_number, err.message),
+		}
+	};
+	let (high, low) = (read("high"), read("low"));
+
+	let output = match matches.value_of("output") {
+		Some(path) => PathBuf::from(path),
+		None => Path::new(matches.value_of("low").unwrap()).with_extension("normal.png"),
+	};
+	let settings = obj_to_mesh::normalmap::BakeSettings {
+		resolution: matches.value_of("resolution").unwrap().parse().unwrap(),
+		max_distance: matches.value_of("max_distance").map(|d| d.parse().unwrap()),
+		padding: matches.value_of("padding").unwrap().parse().unwrap(),
+	};
+
+	let map = obj_to_mesh::normalmap::bake_normal_map(&high, &low, &settings);
+	File::create(&output).unwrap().write_all(&obj_to_mesh::normalmap::write_png(map.side, map.side, &map.rgb)).unwrap();
+	println!("Baked {} ({}x{})", output.display(), map.side, map.side);
+	if map.missed > 0 {
+		println!("{} texels found no high-poly surface within the max distance", map.missed);
+	}
+}
 
-	for obj in set.objects {
-		data.push(convert_obj(obj, generate_tangents));
+/// Replaces the `@file` arguments with the lines of the file, one argument per line, for the command lines
+/// too long for Windows
+fn expand_response_files(args: impl Iterator<Item = String>) -> Vec<String> {
+	let mut expanded = vec![];
+	for arg in args {
+		match arg.strip_prefix('@') {
+			Some(path) if !path.is_empty() => {
+				let content = match std::fs::read_to_string(path) {
+					Ok(content) => content,
+					Err(err) => panic!("Can't read the response file {}: {}", path, err),
+				};
+				expanded.extend(content.lines().map(str::trim_end).filter(|line| !line.is_empty()).map(str::to_owned));
+			},
+			_ => expanded.push(arg),
+		}
 	}
+	expanded
+}
+
+/// The `OBJ_TO_MESH_*` environment variables of the conversion options, with their option
+const ENVIRONMENT_OPTIONS: &[(&str, &str)] = &[
+	("OUTPUT", "output"),
+	("ENCODING", "encoding"),
+	("FORMAT", "format"),
+	("FORMAT_VERSION", "format-version"),
+	("MESSAGE_FORMAT", "message-format"),
+	("STRICT", "strict"),
+	("DENY", "deny"),
+	("VERBOSE", "verbose"),
+	("GEN_TANGENTS", "gen-tangents"),
+	("UV_OVERFLOW", "uv-overflow"),
+	("RECOMPUTE_NORMALS", "recompute-normals"),
+	("CREASE_ANGLE", "crease-angle"),
+	("COLOR_SPACE", "color-space"),
+	("QUANTIZE_POSITIONS", "quantize-positions"),
+	("SHADOW_INDICES", "shadow-indices"),
+	("MAX_VERTICES", "max-vertices"),
+	("PBR", "pbr"),
+	("STRIP_TEXTURE_DIRS", "strip-texture-dirs"),
+	("TEXTURE_PREFIX", "texture-prefix"),
+	("TEXTURE_SEARCH_PATH", "texture-search-path"),
+	("FILL_MISSING", "fill-missing"),
+	("REQUIRE", "require"),
+];
+
+/// The `OBJ_TO_MESH_*` environment variables `run` reads: the profile of the jobs without one, and how many run
+/// at once
+const ENVIRONMENT_RUN: &[&str] = &["PROFILE", "JOBS"];
+
+fn environment_var(name: &str) -> Option<String> {
+	std::env::var(format!("OBJ_TO_MESH_{}", name)).ok().filter(|value| !value.is_empty())
+}
 
-	data
+/// The `OBJ_TO_MESH_*` environment variables as options, like `OBJ_TO_MESH_CREASE_ANGLE=30` for `--crease-angle=30`;
+/// `true` gives a flag and `false` leaves it out
+fn environment_args() -> Vec<String> {
+	ENVIRONMENT_OPTIONS.iter().filter_map(|(name, option)| match environment_var(name)?.as_str() {
+		"true" => Some(format!("--{}", option)),
+		"false" => None,
+		value => Some(format!("--{}={}", option, value)),
+	}).collect()
+}
+
+/// A typo in a variable would otherwise go unnoticed, as clap would refuse them all
+fn warn_unknown_environment() {
+	let mut unknown: Vec<String> = std::env::vars_os()
+		.filter_map(|(name, _)| name.into_string().ok())
+		.filter(|name| name.strip_prefix("OBJ_TO_MESH_").is_some_and(|name| {
+			!ENVIRONMENT_OPTIONS.iter().any(|(known, _)| *known == name) && !ENVIRONMENT_RUN.contains(&name)
+		}))
+		.collect();
+	unknown.sort();
+	for name in unknown {
+		eprintln!("warning: ignoring {}, which isn't a variable obj_to_mesh reads", name);
+	}
 }
 
 fn main() {
-	let matches = App::new("Obj to mesh converter")
+	set_panic_hook();
+	warn_unknown_environment();
+	//the environment comes first, as the options of the command line override it; it's only for the conversion,
+	//and the subcommands aren't found after other arguments
+	let mut args = expand_response_files(std::env::args());
+	if !matches!(args.get(1).map(String::as_str), Some("inspect" | "bake-normalmap" | "run" | "serve" | "daemon")) {
+		let environment = environment_args();
+		args.splice(1..1, environment);
+	}
+	let deny_values = deny_values();
+	let matches = app(&deny_values).get_matches_from(&args);
+
+	match matches.subcommand() {
+		("inspect", Some(sub)) => inspect(sub),
+		("bake-normalmap", Some(sub)) => bake_normalmap(sub),
+		("run", Some(sub)) => run_jobs(sub),
+		("serve", Some(sub)) => serve(sub),
+		("daemon", Some(sub)) => daemon(sub),
+		_ => convert(&matches, &args),
+	}
+}
+
+/// What `--deny` takes: all the warnings, or a kind
+fn deny_values() -> Vec<&'static str> {
+	std::iter::once("warnings")
+		.chain(obj_to_mesh::diagnostics::WarningKind::ALL.iter().map(|kind| kind.name()))
+		.collect()
+}
+
+fn app<'a>(deny_values: &'a [&'a str]) -> App<'a, 'a> {
+	//draco needs the feature
+	let formats: &[&str] = if cfg!(feature = "draco") { &["mesh", "draco"] } else { &["mesh"] };
+	App::new("Obj to mesh converter")
 		.version("0.1")
 		.about("Still pretty incomplete")
+		.setting(AppSettings::SubcommandsNegateReqs)
+		.setting(AppSettings::AllArgsOverrideSelf)
+		.setting(AppSettings::ArgsNegateSubcommands)
 		.arg(Arg::with_name("input")
-			.help("The obj file to convert")
+			.help("The obj files to convert")
 			.value_name("OBJ_FILE")
 			.takes_value(true)
+			.multiple(true)
 			.required(true))
 		.arg(Arg::with_name("output")
 			.long("output")
 			.short("o")
 			.takes_value(true)
 			.value_name("MESH_FILE")
-			.help("Sets the output file. Defaults to OBJ_FILE.mesh"))
+			.help("Sets the output file. Defaults to OBJ_FILE.mesh; with a directory, like out/, writes each mesh in it, named after its obj file"))
 		.arg(Arg::with_name("gen_tangents")
 			.long("gen_tangents")
+			.alias("gen-tangents")
 			.short("t")
 			.help("Generates the tangents using UVs"))
-		.get_matches();
+		.arg(Arg::with_name("emit_bitangent")
+			.long("emit-bitangent")
+			.help("Also stores the bitangent, as a packed 10-10-10-2 attribute"))
+		.arg(Arg::with_name("uv_overflow")
+			.long("uv-overflow")
+			.takes_value(true)
+			.value_name("POLICY")
+			.possible_values(&["wrap", "clamp", "keep", "error"])
+			.default_value("wrap")
+			.help("What to do with UVs outside of [-1, 1]: keep stores them as 32 bit floats"))
+		.arg(Arg::with_name("symmetry")
+			.long("symmetry")
+			.help("Finds a symmetry plane across an axis through the center of the mesh, like that of characters and vehicles, and only stores the half on its positive side with the plane"))
+		.arg(Arg::with_name("mirrored_indices")
+			.long("mirrored-indices")
+			.requires("symmetry")
+			.help("Also stores the indices of the mirrored half, wound the other way so that it draws with the same culling"))
+		.arg(Arg::with_name("obb")
+			.long("obb")
+			.help("Stores an oriented bounding box along the principal axes of the mesh"))
+		.arg(Arg::with_name("kdop")
+			.long("kdop")
+			.takes_value(true)
+			.value_name("K")
+			.possible_values(&["14", "18", "26"])
+			.help("Stores a k-DOP with K sides, for collision broadphases"))
+		.arg(Arg::with_name("bake_bvh")
+			.long("bake-bvh")
+			.help("Stores a BVH over the triangles, which the runtime can raycast against without building it"))
+		.arg(Arg::with_name("pivot")
+			.long("pivot")
+			.takes_value(true)
+			.value_name("POINT")
+			.possible_values(&["center", "bottom"])
+			.help("Stores the positions of each object relative to the center or the bottom of its bounds, recorded in the metadata"))
+		.arg(Arg::with_name("only")
+			.long("only")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1)
+			.value_name("GLOB")
+			.help("Only converts the elements of the objects and groups whose names match GLOB, like `prop_*`. Can be repeated"))
+		.arg(Arg::with_name("exclude")
+			.long("exclude")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1)
+			.value_name("GLOB")
+			.help("Leaves out the elements of the objects and groups whose names match GLOB, like `*_collision`. Can be repeated"))
+		.arg(Arg::with_name("merge_objects")
+			.long("merge-objects")
+			.conflicts_with("detect_instances")
+			.help("Converts all the objects as one mesh, for props exported as several objects but drawn at once"))
+		.arg(Arg::with_name("detect_instances")
+			.long("detect-instances")
+			.help("Writes the geometry repeated by several objects once, with the transforms of the objects using it"))
+		.arg(Arg::with_name("strip_texture_dirs")
+			.long("strip-texture-dirs")
+			.help("Keeps only the file names of the texture paths in the material table"))
+		.arg(Arg::with_name("texture_prefix")
+			.long("texture-prefix")
+			.takes_value(true)
+			.value_name("PREFIX")
+			.help("Prepends PREFIX to the texture paths in the material table, like `textures/`"))
+		.arg(Arg::with_name("copy_textures")
+			.long("copy-textures")
+			.help("Copies the textures of the mtl files where the paths of the material table point, relative to the output"))
+		.arg(Arg::with_name("embed_textures")
+			.long("embed-textures")
+			.help("Stores the textures of the materials in the mesh, for single file assets"))
+		.arg(Arg::with_name("material_overrides")
+			.long("material-overrides")
+			.takes_value(true)
+			.value_name("FILE")
+			.help("Replaces values and textures of the mtl materials with the ones of a JSON file of the materials by name"))
+		.arg(Arg::with_name("texture_search_path")
+			.long("texture-search-path")
+			.takes_value(true)
+			.value_name("DIR")
+			.multiple(true)
+			.number_of_values(1)
+			.help("Looks for the textures of the materials in DIR too, when they aren't where the mtl file says. Can be repeated"))
+		.arg(Arg::with_name("emit_materials")
+			.long("emit-materials")
+			.takes_value(true)
+			.value_name("FILE")
+			.conflicts_with("embed_textures")
+			.help("Writes the materials to a JSON material library instead of the mesh, which keeps their names, like out.mat.json"))
+		.arg(Arg::with_name("bake_material_color")
+			.long("bake-material-color")
+			.help("Writes the diffuse color of the material of each face into the vertex colors, for untextured shaders"))
+		.arg(Arg::with_name("pbr")
+			.long("pbr")
+			.help("Adds metallic-roughness values and textures converted from the mtl ones to the material table"))
+		.arg(Arg::with_name("portals")
+			.long("portals")
+			.takes_value(true)
+			.value_name("MAX_GAP")
+			.conflicts_with("detect_instances")
+			.help("Stores each object as a cell of an interior, with portals where two cells have matching openings, like doorways, at most MAX_GAP apart"))
+		.arg(Arg::with_name("scene")
+			.long("scene")
+			.help("Writes every object as name.OBJECT.mesh, with name.scene.json listing them and their transforms"))
+		.arg(Arg::with_name("hlod")
+			.long("hlod")
+			.takes_value(true)
+			.value_name("CELLS")
+			.requires("scene")
+			.help("Also writes name.hlod.mesh, all the objects merged and simplified on a grid of CELLS along the longest side, for drawing them at a distance"))
+		.arg(Arg::with_name("hlod_preserve")
+			.long("hlod-preserve")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1)
+			.use_delimiter(true)
+			.value_name("FEATURE[=WEIGHT]")
+			.requires("hlod")
+			.help("Keeps the vertices of the UV seams, open boundaries or material borders (seams, boundaries, materials) in place in the proxy, or with a weight makes them pull the simplified vertices towards them; can be repeated"))
+		.arg(Arg::with_name("impostor")
+			.long("impostor")
+			.takes_value(true)
+			.value_name("FRAMES")
+			.help("Also renders all the objects from FRAMES x FRAMES directions into an octahedral impostor atlas, written as name.impostor_albedo.tga and name.impostor_normal.tga, with the card to draw it on as name.impostor.mesh"))
+		.arg(Arg::with_name("impostor_resolution")
+			.long("impostor-resolution")
+			.takes_value(true)
+			.value_name("TEXELS")
+			.default_value("64")
+			.help("Side of each frame of the impostor atlas"))
+		.arg(Arg::with_name("format_version")
+			.long("format-version")
+			.takes_value(true)
+			.value_name("VERSION")
+			.possible_values(&["1", "2"])
+			.help("Writes an older version of the file format, for runtimes that can't load the latest. Defaults to 2"))
+		.arg(Arg::with_name("encoding")
+			.long("encoding")
+			.takes_value(true)
+			.value_name("ENCODING")
+			.possible_values(&["raw", "meshopt", "delta", "progressive", "blocks"])
+			.default_value("raw")
+			.help("How the vertices and indices are stored: meshopt compresses them with the codecs of meshoptimizer, delta only the indices as varints, which is cheaper to decode, and progressive as a coarse base mesh and the vertex splits refining it, which loaders can draw before the file has arrived, and blocks as fixed size blocks of quantized vertices for compute shaders to decompress, described by name.blocks.json"))
+		.arg(Arg::with_name("progressive_base")
+			.long("progressive-base")
+			.takes_value(true)
+			.value_name("FRACTION")
+			.default_value("0.1")
+			.help("The fraction of the triangles kept in the base mesh of the progressive encoding"))
+		.arg(Arg::with_name("format")
+			.long("format")
+			.takes_value(true)
+			.value_name("FORMAT")
+			.possible_values(formats)
+			.default_value("mesh")
+			.help("The files written: mesh, or draco for the web viewers decoding Draco, as name.drc with the positions, normals, colors and uvs only"))
+		.arg(Arg::with_name("draco_bits")
+			.long("draco-bits")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1)
+			.value_name("ATTRIBUTE=BITS")
+			.help("The quantization of position, normal or uv in the Draco files, from 1 to 30 bits; 11, 8 and 10 by default"))
+		.arg(Arg::with_name("palette")
+			.long("palette")
+			.takes_value(true)
+			.multiple(true)
+			.use_delimiter(true)
+			.value_name("ATTRIBUTES")
+			.possible_values(&["normals", "colors"])
+			.help("Stores each normal or color once in a table, and in the vertices its index of 1 or 2 bytes: much smaller vertices for the voxel and low-poly meshes with few different ones"))
+		.arg(Arg::with_name("zstd_dictionary")
+			.long("zstd-dictionary")
+			.takes_value(true)
+			.value_name("FILE")
+			.help("Trains a zstd dictionary on all the mesh files written, stores it in FILE, and replaces each of them with name.mesh.zst compressed against it, for the batches of small meshes that compress poorly alone"))
+		.arg(Arg::with_name("zstd_dictionary_size")
+			.long("zstd-dictionary-size")
+			.takes_value(true)
+			.value_name("BYTES")
+			.default_value("112640")
+			.help("The largest size of the content of the zstd dictionary"))
+		.arg(Arg::with_name("quantize_positions")
+			.long("quantize-positions")
+			.help("Stores the positions as 16 bit unorms in the bounds of each part, and in the header the matrix taking them back, which shaders multiply them by"))
+		.arg(Arg::with_name("shadow_indices")
+			.long("shadow-indices")
+			.help("Also stores the triangles indexing a single vertex per position, ignoring the UV and normal seams, as a smaller index buffer for shadow and depth passes"))
+		.arg(Arg::with_name("max_vertices")
+			.long("max-vertices")
+			.takes_value(true)
+			.value_name("COUNT")
+			.help("Splits the mesh into name.0.mesh, name.1.mesh... of at most COUNT vertices each, like 65535 for 16 bit indices"))
+		.arg(Arg::with_name("split_by")
+			.long("split-by")
+			.takes_value(true)
+			.value_name("WHAT")
+			.possible_values(&["material"])
+			.help("Writes a mesh for each material, name.MATERIAL.mesh, with only the faces using it"))
+		.arg(Arg::with_name("split_components")
+			.long("split-components")
+			.help("Writes a mesh for each connected piece, name.componentN.mesh, for per-piece physics or destruction"))
+		.arg(Arg::with_name("sort_by_material")
+			.long("sort-by-material")
+			.help("Groups the faces by material, in order of first use, so that each material is a single submesh"))
+		.arg(Arg::with_name("chunk_grid")
+			.long("chunk-grid")
+			.takes_value(true)
+			.value_name("SIZE")
+			.help("Writes a mesh for each cell of a world space grid of SIZE units, name.X_Y_Z.mesh, for streaming large environments"))
+		.arg(Arg::with_name("repair")
+			.long("repair")
+			.help("Drops the triangles with non-finite positions, replaces broken normals and UVs"))
+		.arg(Arg::with_name("renormalize")
+			.long("renormalize")
+			.help("Normalizes the normals of the obj file before they are packed, reporting how many were off"))
+		.arg(Arg::with_name("normal_tolerance")
+			.long("normal-tolerance")
+			.takes_value(true)
+			.value_name("TOLERANCE")
+			.help("How far from unit length a normal can be before it's reported, 0.001 by default"))
+		.arg(Arg::with_name("check_manifold")
+			.long("check-manifold")
+			.help("Warns about non-manifold edges and vertices and about holes, for tools that need closed meshes"))
+		.arg(Arg::with_name("dedup_faces")
+			.long("dedup-faces")
+			.help("Removes the triangles at the same positions as another, which z-fight, and warns about how many"))
+		.arg(Arg::with_name("remove_small_components")
+			.long("remove-small-components")
+			.takes_value(true)
+			.value_name("MIN_TRIS|MIN_AREA")
+			.help("Removes the disconnected pieces with fewer triangles, or less surface area when it has a decimal point like 0.5, such as the debris of scans"))
+		.arg(Arg::with_name("fill_holes")
+			.long("fill-holes")
+			.takes_value(true)
+			.value_name("MAX_EDGES")
+			.help("Closes the holes bordered by up to MAX_EDGES edges, and warns about the rest"))
+		.arg(Arg::with_name("max_hole_perimeter")
+			.long("max-hole-perimeter")
+			.takes_value(true)
+			.value_name("LENGTH")
+			.requires("fill_holes")
+			.help("Only closes the holes that are up to LENGTH around, leaving the larger openings"))
+		.arg(Arg::with_name("fix_winding")
+			.long("fix-winding")
+			.takes_value(true)
+			.value_name("FIX")
+			.possible_values(&["consistent", "outward"])
+			.help("Rewinds the triangles facing the other way from their neighbors; outward also turns the closed pieces facing inward inside out"))
+		.arg(Arg::with_name("inverted_faces")
+			.long("inverted-faces")
+			.takes_value(true)
+			.value_name("ACTION")
+			.possible_values(&["warn", "flip"])
+			.help("Finds the triangles of closed meshes that face into the mesh, by raycasts from their centers, and lists them in a warning or flips them"))
+		.arg(Arg::with_name("remesh")
+			.long("remesh")
+			.takes_value(true)
+			.value_name("LENGTH")
+			.help("Remeshes the triangles to edges about LENGTH long, keeping the open borders and the borders between groups in place; the texture coordinates are dropped"))
+		.arg(Arg::with_name("remesh_iterations")
+			.long("remesh-iterations")
+			.takes_value(true)
+			.value_name("N")
+			.requires("remesh")
+			.help("The split, collapse, flip and smoothing rounds of --remesh, 5 by default"))
+		.arg(Arg::with_name("merge_coplanar")
+			.long("merge-coplanar")
+			.takes_value(true)
+			.value_name("DEGREES")
+			.help("Merges the adjacent triangles facing within DEGREES of each other, like the tessellated walls of architectural models, and triangulates them again with as few triangles as possible"))
+		.arg(Arg::with_name("strict")
+			.long("strict")
+			.help("Fails instead of writing the mesh when there are any warnings"))
+		.arg(Arg::with_name("deny")
+			.long("deny")
+			.takes_value(true)
+			.value_name("KIND")
+			.multiple(true)
+			.number_of_values(1)
+			.possible_values(deny_values)
+			.help("Makes the warnings of this kind errors, like --strict does for all of them with `warnings`; can be repeated"))
+		.arg(Arg::with_name("report")
+			.long("report")
+			.takes_value(true)
+			.value_name("FILE")
+			.help("Writes a report of each converted asset, with the vertex and triangle counts before and after, the vertex format, the sizes, the ACMR and the warnings; HTML when FILE ends with .html, markdown otherwise"))
+		.arg(Arg::with_name("message_format")
+			.long("message-format")
+			.takes_value(true)
+			.value_name("FORMAT")
+			.possible_values(&["human", "json"])
+			.default_value("human")
+			.help("Prints the warnings and errors as text, or as a JSON object per line with the severity, code, file, object, face and message"))
+		.arg(Arg::with_name("fill_missing")
+			.long("fill-missing")
+			.help("Keeps the normals and UVs when only some faces have them, using generated normals and (0,0) UVs for the rest"))
+		.arg(Arg::with_name("require")
+			.long("require")
+			.takes_value(true)
+			.value_name("ATTRIBUTES")
+			.use_delimiter(true)
+			.possible_values(&["normals", "uvs", "tangents", "bitangents", "colors"])
+			.help("Fails the conversion when one of these attributes can't be produced for a mesh, instead of leaving it out; the tangents and bitangents are then generated"))
+		.arg(Arg::with_name("weights")
+			.long("weights")
+			.short("w")
+			.takes_value(true)
+			.value_name("WEIGHTS_FILE")
+			.help("Adds bone indices and weights, read from a file with a line of `bone weight` pairs for each obj vertex"))
+		.arg(Arg::with_name("attributes")
+			.long("attributes")
+			.takes_value(true)
+			.value_name("CSV_FILE")
+			.help("Appends user attributes, read from a CSV file declaring `vertex,name:format,...` and keyed by obj vertex index"))
+		.arg(Arg::with_name("vertex_struct")
+			.long("vertex-struct")
+			.takes_value(true)
+			.value_name("FILE")
+			.help("Also writes the vertices as name.vertices.bin, laid out as the struct declared in the file"))
+		.arg(Arg::with_name("morph")
+			.long("morph")
+			.short("m")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1)
+			.value_name("TARGET_OBJ_FILE")
+			.help("Adds a morph target, named after the file, from an obj file with the same topology. Can be repeated"))
+		.arg(Arg::with_name("color_space")
+			.long("color-space")
+			.takes_value(true)
+			.value_name("SPACE")
+			.possible_values(&["srgb", "linear"])
+			.default_value("srgb")
+			.help("Stores the vertex colors as they are in the obj file, sRGB, or converted to linear, and records it in the header"))
+		.arg(Arg::with_name("recompute_normals")
+			.long("recompute-normals")
+			.takes_value(true)
+			.value_name("WEIGHTING")
+			.possible_values(&["area", "angle", "areaangle"])
+			.help("Replaces the normals of the obj file with smooth ones generated from the positions, weighting the faces by area, corner angle or both"))
+		.arg(Arg::with_name("crease_angle")
+			.long("crease-angle")
+			.takes_value(true)
+			.value_name("DEGREES")
+			.help("Generates the normals with hard edges where the faces meet at more than DEGREES, like 30 for hard-surface models"))
+		.arg(Arg::with_name("flat")
+			.long("flat")
+			.conflicts_with_all(&["recompute_normals", "crease_angle"])
+			.help("Gives every triangle its face normal, with vertices of its own, for low-poly styles"))
+		.arg(Arg::with_name("smooth_seams")
+			.long("smooth-seams")
+			.takes_value(true)
+			.value_name("WHAT")
+			.possible_values(&["normals", "tangents"])
+			.conflicts_with("flat")
+			.help("Averages the normals, or the normals and the tangents, of the vertices split by UV seams, so the seams don't show in the shading; the ones further apart than the crease angle, or 60 degrees, stay hard"))
+		.arg(Arg::with_name("stale_normals")
+			.long("stale-normals")
+			.takes_value(true)
+			.value_name("ACTION")
+			.possible_values(&["recompute", "warn"])
+			.default_value("recompute")
+			.help("Regenerates the normals of the file that the simplification of --hlod or --fix-winding made stale, or keeps them with a warning"))
+		.arg(Arg::with_name("curvature")
+			.long("curvature")
+			.takes_value(true)
+			.value_name("KIND")
+			.possible_values(&["mean", "gaussian"])
+			.help("Adds the mean or gaussian curvature of the surface as a vertex attribute"))
+		.arg(Arg::with_name("material_ids")
+			.long("material-ids")
+			.help("Adds the index of the material of each triangle, numbered in order of first usemtl"))
+		.arg(Arg::with_name("gen_uvs")
+			.long("gen-uvs")
+			.takes_value(true)
+			.value_name("PROJECTION")
+			.possible_values(&["planar", "box", "spherical", "cylindrical"])
+			.help("Projects texture coordinates for the objects that have none"))
+		.arg(Arg::with_name("lightmap")
+			.long("lightmap")
+			.takes_value(true)
+			.value_name("RESOLUTION")
+			.help("Generates non overlapping lightmap UVs into Tex1, for a lightmap of RESOLUTION texels"))
+		.arg(Arg::with_name("lightmap_padding")
+			.long("lightmap-padding")
+			.takes_value(true)
+			.value_name("TEXELS")
+			.default_value("2")
+			.help("Texels between the lightmap charts"))
+		.arg(Arg::with_name("repack_uvs")
+			.long("repack-uvs")
+			.takes_value(true)
+			.value_name("RESOLUTION")
+			.help("Repacks the UV islands of all the objects into a shared atlas of RESOLUTION texels, with the same texel density"))
+		.arg(Arg::with_name("atlas_padding")
+			.long("atlas-padding")
+			.takes_value(true)
+			.value_name("TEXELS")
+			.default_value("2")
+			.help("Texels between the repacked UV islands"))
+		.arg(Arg::with_name("emit_shader")
+			.long("emit-shader")
+			.takes_value(true)
+			.value_name("LANGUAGE")
+			.possible_values(&["glsl", "hlsl", "wgsl"])
+			.help("Also writes the vertex input declarations matching the mesh next to the output file"))
+		.arg(Arg::with_name("emit_gl")
+			.long("emit-gl")
+			.takes_value(true)
+			.value_name("LANGUAGE")
+			.possible_values(&["c", "rust"])
+			.help("Also writes the OpenGL vertex array setup matching the mesh next to the output file, as name.gl.c or name.gl.rs for the gl crate"))
+		.arg(Arg::with_name("emit_d3d12")
+			.long("emit-d3d12")
+			.takes_value(true)
+			.value_name("FORMAT")
+			.possible_values(&["cpp", "json"])
+			.help("Also writes the D3D12 input layout matching the mesh next to the output file, as name.d3d12.h or name.d3d12.json"))
+		.arg(Arg::with_name("emit_metal")
+			.long("emit-metal")
+			.takes_value(true)
+			.value_name("FORMAT")
+			.possible_values(&["swift", "json"])
+			.help("Also writes the Metal vertex descriptor matching the mesh next to the output file, as name.metal.swift or name.metal.json"))
+		.arg(Arg::with_name("verbose")
+			.short("v")
+			.long("verbose")
+			.help("Also prints how far the vertex formats move each attribute, with the vertices moved the most"))
+		.arg(Arg::with_name("confine")
+			.long("confine")
+			.hidden(true)
+			.help("Refuses the mtl files and textures with absolute paths or paths with .., which the server passes to the conversions of the uploads"))
+		.arg(Arg::with_name("emit_remap")
+			.long("emit-remap")
+			.help("Also writes a CSV file next to each mesh file, with the object, face and v/vt/vn indices each vertex comes from"))
+		.subcommand(SubCommand::with_name("inspect")
+			.about("Prints the content and vertex layout of a mesh file")
+			.arg(Arg::with_name("input")
+				.help("The mesh file to inspect")
+				.value_name("MESH_FILE")
+				.takes_value(true)
+				.required(true))
+			.arg(Arg::with_name("verify")
+				.long("verify")
+				.help("Fails if the file has no checksum; files with one are always verified"))
+			.arg(Arg::with_name("wgpu")
+				.long("wgpu")
+				.help("Prints the matching wgpu::VertexBufferLayout"))
+			.arg(Arg::with_name("vulkan")
+				.long("vulkan")
+				.takes_value(true)
+				.value_name("FORMAT")
+				.possible_values(&["rust", "json"])
+				.help("Prints the matching Vulkan vertex input descriptions, as Rust code using ash or as JSON"))
+			.arg(Arg::with_name("gl")
+				.long("gl")
+				.takes_value(true)
+				.value_name("LANGUAGE")
+				.possible_values(&["c", "rust"])
+				.help("Prints the matching OpenGL vertex array setup, as C or as Rust code using the gl crate"))
+			.arg(Arg::with_name("d3d12")
+				.long("d3d12")
+				.takes_value(true)
+				.value_name("FORMAT")
+				.possible_values(&["cpp", "json"])
+				.help("Prints the matching D3D12_INPUT_ELEMENT_DESC array, as C++ or as JSON"))
+			.arg(Arg::with_name("metal")
+				.long("metal")
+				.takes_value(true)
+				.value_name("FORMAT")
+				.possible_values(&["swift", "json"])
+				.help("Prints the matching MTLVertexDescriptor, as Swift code or as JSON"))
+			.arg(Arg::with_name("shader")
+				.long("shader")
+				.takes_value(true)
+				.value_name("LANGUAGE")
+				.possible_values(&["glsl", "hlsl", "wgsl"])
+				.help("Prints the matching vertex input declarations")))
+		.subcommand(SubCommand::with_name("bake-normalmap")
+			.about("Bakes the normals of a high-poly obj into a tangent space normal map for the UVs of a low-poly one")
+			.arg(Arg::with_name("high")
+				.help("The obj file with the detail")
+				.value_name("HIGH_OBJ")
+				.takes_value(true)
+				.required(true))
+			.arg(Arg::with_name("low")
+				.help("The obj file the map is for, with texture coordinates")
+				.value_name("LOW_OBJ")
+				.takes_value(true)
+				.required(true))
+			.arg(Arg::with_name("output")
+				.short("o")
+				.long("output")
+				.takes_value(true)
+				.value_name("PNG_FILE")
+				.help("Where to write the map; by default next to LOW_OBJ, with a .normal.png extension"))
+			.arg(Arg::with_name("resolution")
+				.long("resolution")
+				.takes_value(true)
+				.value_name("TEXELS")
+				.default_value("512")
+				.help("Side of the map"))
+			.arg(Arg::with_name("max_distance")
+				.long("max-distance")
+				.takes_value(true)
+				.value_name("DISTANCE")
+				.help("How far from the low-poly surface the high-poly one is looked for; by default 5% of the size of the low-poly model"))
+			.arg(Arg::with_name("padding")
+				.long("padding")
+				.takes_value(true)
+				.value_name("TEXELS")
+				.default_value("2")
+				.help("Texels the UV islands are grown by")))
+		.subcommand(SubCommand::with_name("run")
+			.about("Runs the conversions listed in a YAML job file, in parallel, each after the ones it depends on")
+			.arg(Arg::with_name("job_file")
+				.help("The jobs, with their inputs, outputs, profiles and options; the paths are relative to it")
+				.value_name("JOB_FILE")
+				.takes_value(true)
+				.required(true))
+			.arg(Arg::with_name("jobs")
+				.short("j")
+				.long("jobs")
+				.takes_value(true)
+				.value_name("N")
+				.help("How many conversions run at once; by default OBJ_TO_MESH_JOBS, or one per core")))
+		.subcommand(SubCommand::with_name("serve")
+			.about("Runs an HTTP server converting the obj files posted to /convert, raw or as a form, with the query parameters as options")
+			.arg(Arg::with_name("address")
+				.long("address")
+				.takes_value(true)
+				.value_name("HOST:PORT")
+				.default_value("127.0.0.1:8080")
+				.help("Where to listen; 0.0.0.0:8080 accepts the other machines"))
+			.arg(Arg::with_name("max_upload")
+				.long("max-upload")
+				.takes_value(true)
+				.value_name("MB")
+				.default_value("256")
+				.help("The largest obj file accepted"))
+			.arg(Arg::with_name("max_connections")
+				.long("max-connections")
+				.takes_value(true)
+				.value_name("COUNT")
+				.default_value("64")
+				.help("How many connections are served at once; the others are answered 503"))
+			.arg(Arg::with_name("timeout")
+				.long("timeout")
+				.takes_value(true)
+				.value_name("SECONDS")
+				.default_value("30")
+				.help("How long a connection can wait on the client when reading the request or writing the response")))
+		.subcommand(SubCommand::with_name("daemon")
+			.about("Keeps running, converting the command lines sent to a Unix socket, an argument per line and ended by an empty line")
+			.arg(Arg::with_name("socket")
+				.long("socket")
+				.takes_value(true)
+				.value_name("PATH")
+				.default_value("obj_to_mesh.sock")
+				.help("Where to create the socket"))
+			.arg(Arg::with_name("stdio")
+				.long("stdio")
+				.help("Reads the requests from the standard input and answers on the standard output instead, for the editors starting the daemon themselves; the messages of the conversions go to the standard error"))
+			.arg(Arg::with_name("json_rpc")
+				.long("json-rpc")
+				.help("Speaks JSON-RPC 2.0 instead, a message per line, with convert, inspect and watch/subscribe methods and progress notifications")))
+}
 
-	let input = Path::new(matches.value_of("input").unwrap());
-	
-	let output = if let Some(path) = matches.value_of("output") {
-		Path::new(path).to_owned()
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	//a single test, as the environment is shared by the threads of the tests
+	#[test]
+	fn environment_overrides() {
+		let file = "profiles:\n  game:\n    gen-tangents: true\njobs:\n  - input: a.obj\n  - input: b.obj\n    profile: game\n    options:\n      flat: true\n";
+		std::env::remove_var("OBJ_TO_MESH_PROFILE");
+		let jobs = parse_job_file(file).unwrap();
+		assert_eq!(jobs[0].args(), ["a.obj"]);
+		assert_eq!(jobs[1].args(), ["b.obj", "--gen-tangents", "--flat"]);
+		std::env::set_var("OBJ_TO_MESH_PROFILE", "game");
+		assert_eq!(parse_job_file(file).unwrap()[0].args(), ["a.obj", "--gen-tangents"]);
+		std::env::set_var("OBJ_TO_MESH_PROFILE", "console");
+		let err = parse_job_file(file).unwrap_err();
+		assert_eq!((err.line_number, err.message.as_str()), (5, "Unknown profile console"));
+		//empty is unset
+		std::env::set_var("OBJ_TO_MESH_PROFILE", "");
+		assert_eq!(parse_job_file(file).unwrap()[0].args(), ["a.obj"]);
+		std::env::remove_var("OBJ_TO_MESH_PROFILE");
+
+		std::env::set_var("OBJ_TO_MESH_JOBS", "3");
+		assert_eq!((job_threads(None), job_threads(Some("5"))), (3, 5));
+		std::env::set_var("OBJ_TO_MESH_JOBS", "");
+		assert!(job_threads(None) >= 1);
+		std::env::set_var("OBJ_TO_MESH_JOBS", "many");
+		assert!(std::panic::catch_unwind(|| job_threads(None)).is_err());
+		std::env::remove_var("OBJ_TO_MESH_JOBS");
 	}
-	else {
-		input.with_extension("mesh")
-	};
 
-	println!("Converting {} into {}..", 
-		input.file_name().unwrap().to_str().unwrap(),
-		output.file_name().unwrap().to_str().unwrap()
-	);
+	/// The status and body of the answer of `serve_connection` to `request`, after checking its Content-Length and
+	/// that the thread of the connection didn't panic
+	fn serve_request(request: &[u8], max_upload: usize) -> (u16, String) {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+		let (stream, _) = listener.accept().unwrap();
+		let worker = std::thread::spawn(move || serve_connection(stream, Path::new("obj_to_mesh"), max_upload));
+		client.write_all(request).unwrap();
+		client.shutdown(std::net::Shutdown::Write).unwrap();
+		let mut response = String::new();
+		client.read_to_string(&mut response).unwrap();
+		worker.join().expect("the connection panicked").unwrap();
+
+		let (head, body) = response.split_once("\r\n\r\n").unwrap();
+		let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+		let length = head.lines().find_map(|line| line.strip_prefix("Content-Length: ")).unwrap();
+		assert_eq!(length.parse::<usize>().unwrap(), body.len(), "{}", response);
+		(status, body.to_owned())
+	}
 
-	let mut file = File::open(input).unwrap();
+	#[test]
+	fn serve_malformed_requests() {
+		for request in ["GET\r\n", "GET / HTTP/2\r\n", "\r\n", "GET /convert HTTP/1.1\r\nno colon\r\n"] {
+			let (status, body) = serve_request(request.as_bytes(), 1024);
+			assert_eq!(status, 400, "{:?}: {}", request, body);
+		}
+		let long = format!("GET /{} HTTP/1.1\r\n", "a".repeat(10000));
+		assert_eq!(serve_request(long.as_bytes(), 1024).0, 400);
+		//the client closing before sending anything gets no answer
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		drop(std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+		let (stream, _) = listener.accept().unwrap();
+		serve_connection(stream, Path::new("obj_to_mesh"), 1024).unwrap();
+	}
 
-	let mut content = String::new();
-	file.read_to_string(&mut content).unwrap();
+	#[test]
+	fn serve_content_length() {
+		let post = |headers: &str, body: &str| serve_request(format!("POST /convert HTTP/1.1\r\n{}\r\n{}", headers, body).as_bytes(), 1024);
+		//answered before the body is sent
+		assert_eq!(post("Content-Length: 1025\r\n", ""), (413, "The body is larger than 1024 bytes\n".to_owned()));
+		assert_eq!(post("Content-Length: 1025\r\nExpect: 100-continue\r\n", "").0, 413);
+		//or after, which it still gets the answer of
+		assert_eq!(post("Content-Length: 5000\r\n", &"v".repeat(5000)).0, 413);
+		assert_eq!(post("Content-Length: 10\r\n", "v 0 0").0, 400);
+		assert_eq!(post("Content-Length: ten\r\n", "").0, 400);
+		assert_eq!(post("Transfer-Encoding: chunked\r\n", "").0, 411);
+		assert_eq!(post("Content-Length: 4\r\nContent-Type: multipart/form-data; boundary=x\r\n", "none"), (400, "The form has no file\n".to_owned()));
+		let unknown = serve_request(b"POST /convert?output=/etc/passwd HTTP/1.1\r\nContent-Length: 0\r\n\r\n", 1024);
+		assert_eq!(unknown, (400, "output isn't an option the server takes\n".to_owned()));
+		assert_eq!(serve_request(b"GET /convert HTTP/1.1\r\n\r\n", 1024).0, 405);
+		assert_eq!(serve_request(b"GET / HTTP/1.1\r\n\r\n", 1024).0, 404);
+	}
 
-	//patch files that don't contain an object name
-	if !content.contains("\no") {
-		content = "o unnamed_object \n".to_owned() + &content;
+	/// Keeps what a session writes, for the test to read once it ended
+	#[derive(Clone, Default)]
+	struct Written(Arc<Mutex<Vec<u8>>>);
+
+	impl Write for Written {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.0.lock().unwrap().extend_from_slice(buf);
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
 	}
 
-	let generate_tangents = matches.occurrences_of("gen_tangents") > 0;
+	/// The messages a JSON-RPC session sends for the lines of `input`
+	fn rpc_messages(input: &str) -> Vec<json::Value> {
+		let output = Written::default();
+		rpc_session(input.as_bytes(), Box::new(output.clone())).unwrap();
+		let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+		text.lines().map(|line| json::parse(line).unwrap()).collect()
+	}
 
-	let data = match wavefront_obj::obj::parse(content) {
-	    Ok(obj) => convert_obj_set(obj, generate_tangents),
-	    Err(err) => panic!("{:?}", err),
-	};
+	/// The value at a path of keys and indices separated by dots, or null
+	fn at<'a>(value: &'a json::Value, path: &str) -> &'a json::Value {
+		path.split('.').fold(value, |value, key| {
+			let next = match value {
+				json::Value::Object(members) => members.iter().find(|(name, _, _)| name == key).map(|(_, _, value)| value),
+				json::Value::Array(items) => key.parse().ok().and_then(|i: usize| items.get(i)),
+				_ => None,
+			};
+			next.unwrap_or(&json::Value::Null)
+		})
+	}
 
-	let mut file = File::create(output).unwrap();
+	fn text(text: &str) -> json::Value {
+		json::Value::String(text.to_owned())
+	}
 
-	file.write_all(&data[0]).unwrap();
+	#[test]
+	fn rpc_errors() {
+		let messages = rpc_messages(r#"{"jsonrpc": "2.0", "id": 1, "method": "inspect"
+{"jsonrpc": "2.0", "id": 2}
+{"id": 3, "method": "inspect", "params": {"path": "a.mesh"}}
+{"jsonrpc": "2.0", "id": {"nested": true}, "method": "inspect"}
+{"jsonrpc": "2.0", "id": 5, "method": "inspect", "params": "a.mesh"}
+{"jsonrpc": "2.0", "id": "six", "method": "convert/all"}
+{"jsonrpc": "2.0", "id": 7, "method": "convert", "params": {"inputs": []}}
+{"jsonrpc": "2.0", "method": "convert/all"}
+{"jsonrpc": "2.0", "method": "inspect", "params": {"path": "missing.mesh"}}
+{"jsonrpc": "2.0", "id": 8, "method": "inspect", "params": {"path": "missing.mesh"}}
+"#);
+		let answers: Vec<(&json::Value, &json::Value)> = messages.iter().map(|m| (at(m, "id"), at(m, "error.code"))).collect();
+		let number = json::Value::Number;
+		//the notifications, without an id, get no answer even when they fail
+		assert_eq!(answers, [
+			(&json::Value::Null, &number(rpc::PARSE_ERROR as f64)),
+			(&number(2.0), &number(rpc::INVALID_REQUEST as f64)),
+			(&number(3.0), &number(rpc::INVALID_REQUEST as f64)),
+			(&json::Value::Null, &number(rpc::INVALID_REQUEST as f64)),
+			(&number(5.0), &number(rpc::INVALID_REQUEST as f64)),
+			(&text("six"), &number(rpc::METHOD_NOT_FOUND as f64)),
+			(&number(7.0), &number(rpc::INVALID_PARAMS as f64)),
+			(&number(8.0), &number(rpc::READ_FAILED as f64)),
+		]);
+		assert!(messages.iter().all(|m| *at(m, "jsonrpc") == text("2.0")));
+		assert_eq!(*at(&messages[5], "error.message"), text("Unknown method convert/all"));
+	}
 
-	println!("Done!");
+	#[test]
+	fn rpc_convert() {
+		let dir = std::env::temp_dir().join(format!("obj_to_mesh_rpc_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let input = dir.join("triangle.obj").display().to_string();
+		let output = dir.join("triangle.mesh").display().to_string();
+		std::fs::write(&input, "o triangle\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+
+		let convert = format!(r#""method": "convert", "params": {{"inputs": {}, "output": {}, "options": {{"flat": true, "encoding": "meshopt"}}}}"#,
+			json::string(&input), json::string(&output));
+		let inspect = format!(r#""method": "inspect", "params": {{"path": {}}}"#, json::string(&output));
+		let request = |id: &str, call: &str| format!(r#"{{"jsonrpc": "2.0", {}{}}}"#, id, call);
+		let messages = rpc_messages(&[request(r#""id": 1, "#, &convert), request(r#""id": 2, "#, &inspect), request("", &convert)].join("\n"));
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(messages.len(), 6);
+		for (message, state) in messages[..2].iter().zip(["converting", "converted"]) {
+			assert_eq!(*at(message, "method"), text("progress"));
+			assert_eq!(*at(message, "params.request"), json::Value::Number(1.0));
+			assert_eq!(*at(message, "params.input"), text(&input));
+			assert_eq!((at(message, "params.index"), at(message, "params.total")), (&json::Value::Number(0.0), &json::Value::Number(1.0)));
+			assert_eq!(*at(message, "params.state"), text(state));
+		}
+		assert_eq!(*at(&messages[2], "id"), json::Value::Number(1.0));
+		assert_eq!(*at(&messages[2], "result.written"), json::Value::Array(vec![text(&output)]));
+		assert_eq!(*at(&messages[2], "result.files.0.state"), text("converted"));
+		assert_eq!(*at(&messages[2], "result.failed"), json::Value::Number(0.0));
+
+		assert_eq!(*at(&messages[3], "id"), json::Value::Number(2.0));
+		assert_eq!(*at(&messages[3], "result.vertexCount"), json::Value::Number(3.0));
+		assert_eq!(*at(&messages[3], "result.encoding"), text("meshopt"));
+
+		//converted again without an answer, only the progress with a null request
+		for message in &messages[4..] {
+			assert_eq!(*at(message, "method"), text("progress"));
+			assert_eq!(*at(message, "params.request"), json::Value::Null);
+		}
+		assert_eq!(*at(&messages[5], "params.state"), text("converted"));
+	}
 }
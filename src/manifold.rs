@@ -0,0 +1,445 @@
+//! Checks for the watertight meshes needed by SDF baking and physics, filling of small holes, and repair of
+//! the triangles wound the wrong way or repeated, and the connected pieces of the meshes.
+//!
+//! Triangles are connected through the positions they share, so vertices split by UV seams count as one.
+
+use wavefront_obj::obj::{Object, Shape, VTNIndex, Vertex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::atlas::{find, union};
+use crate::bvh;
+use crate::convert::ConvertOptions;
+use crate::math::*;
+use crate::mesh::Mesh;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifoldReport {
+	/// Edges shared by more than two triangles
+	pub non_manifold_edges: usize,
+	/// Positions where separate fans of triangles touch
+	pub non_manifold_vertices: usize,
+	/// The number of edges of each hole or open border
+	pub boundary_loops: Vec<usize>,
+}
+
+impl ManifoldReport {
+	pub fn is_watertight(&self) -> bool {
+		self.non_manifold_edges == 0 && self.non_manifold_vertices == 0 && self.boundary_loops.is_empty()
+	}
+}
+
+pub(crate) fn triangles(obj: &Object) -> Vec<[VTNIndex; 3]> {
+	obj.geometry.iter()
+		.flat_map(|geo| &geo.shapes)
+		.filter_map(|shape| match *shape {
+			Shape::Triangle(v1, v2, v3) => Some([v1, v2, v3]),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Directed edges that have no opposite, by the vertex they start from and with the corner they start at
+fn boundary_edges(triangles: &[[VTNIndex; 3]]) -> HashMap<usize, Vec<(usize, VTNIndex)>> {
+	let mut directed: HashMap<(usize, usize), VTNIndex> = HashMap::new();
+	for t in triangles {
+		for i in 0..3 {
+			directed.insert((t[i].0, t[(i + 1) % 3].0), t[i]);
+		}
+	}
+
+	let mut edges: HashMap<usize, Vec<(usize, VTNIndex)>> = HashMap::new();
+	for (&(a, b), &corner) in &directed {
+		if a != b && !directed.contains_key(&(b, a)) {
+			edges.entry(a).or_default().push((b, corner));
+		}
+	}
+	//sorted so that the loops don't depend on the hashing
+	for out in edges.values_mut() {
+		out.sort_by_key(|&(b, _)| b);
+	}
+	edges
+}
+
+/// Follows the boundary edges into loops of corners, in the winding of the triangles around them
+pub(crate) fn boundary_loops(triangles: &[[VTNIndex; 3]]) -> Vec<Vec<VTNIndex>> {
+	let mut edges = boundary_edges(triangles);
+	let mut starts: Vec<usize> = edges.keys().copied().collect();
+	starts.sort();
+
+	let mut loops = vec![];
+	for start in starts {
+		//a vertex where several borders meet is the start of more than one loop
+		while edges.get(&start).is_some_and(|out| !out.is_empty()) {
+			let mut corners = vec![];
+			let mut v = start;
+			while let Some((next, corner)) = edges.get_mut(&v).and_then(|out| out.pop()) {
+				corners.push(corner);
+				v = next;
+				if v == start {
+					break;
+				}
+			}
+			loops.push(corners);
+		}
+	}
+	loops
+}
+
+pub fn analyze(obj: &Object) -> ManifoldReport {
+	let triangles = triangles(obj);
+
+	let mut edge_counts: HashMap<(usize, usize), u32> = HashMap::new();
+	for t in &triangles {
+		for i in 0..3 {
+			let (a, b) = (t[i].0, t[(i + 1) % 3].0);
+			*edge_counts.entry((usize::min(a, b), usize::max(a, b))).or_insert(0) += 1;
+		}
+	}
+
+	//the triangles around each position are connected if they share an edge through it
+	let mut fans: HashMap<usize, Vec<usize>> = HashMap::new();
+	for (i, t) in triangles.iter().enumerate() {
+		for corner in t {
+			fans.entry(corner.0).or_default().push(i);
+		}
+	}
+	let mut non_manifold_vertices = 0;
+	for (&v, fan) in &fans {
+		let mut parents: Vec<usize> = (0..fan.len()).collect();
+		let others = |t: usize| triangles[fan[t]].iter().map(|c| c.0).filter(|&o| o != v).collect::<Vec<usize>>();
+		for a in 0..fan.len() {
+			for b in a + 1..fan.len() {
+				let shared = others(a).iter().any(|o| others(b).contains(o));
+				if shared {
+					union(&mut parents, a, b);
+				}
+			}
+		}
+		let roots = (0..fan.len()).filter(|&t| find(&mut parents, t) == t).count();
+		if roots > 1 {
+			non_manifold_vertices += 1;
+		}
+	}
+
+	ManifoldReport {
+		non_manifold_edges: edge_counts.values().filter(|&&count| count > 2).count(),
+		non_manifold_vertices,
+		boundary_loops: boundary_loops(&triangles).iter().map(Vec::len).collect(),
+	}
+}
+
+/// Returns a copy of `obj` where the holes with up to `max_edges` edges, and at most `max_perimeter` around
+/// when there is one, are closed with a fan of triangles, and the number of holes filled. The new triangles
+/// reuse the normals and UVs of the border corners
+pub fn fill_holes(obj: &Object, max_edges: usize, max_perimeter: Option<f64>) -> (Object, usize) {
+	let mut result = obj.clone();
+	let mut filled = 0;
+
+	let mut patch = vec![];
+	for corners in boundary_loops(&triangles(obj)) {
+		if corners.len() < 3 || corners.len() > max_edges {
+			continue;
+		}
+		let perimeter: f64 = (0..corners.len())
+			.map(|i| lenght(sub(obj.vertices[corners[(i + 1) % corners.len()].0], obj.vertices[corners[i].0])))
+			.sum();
+		if max_perimeter.is_some_and(|max| perimeter > max) {
+			continue;
+		}
+		//the border runs along the triangles, the patch has to face the other way
+		for i in 1..corners.len() - 1 {
+			patch.push(Shape::Triangle(corners[0], corners[i + 1], corners[i]));
+		}
+		filled += 1;
+	}
+
+	if let Some(geo) = result.geometry.last_mut() {
+		geo.shapes.extend(patch);
+	}
+	(result, filled)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindingFix {
+	/// The triangles of each connected piece are wound like its first one
+	Consistent,
+	/// Then the closed pieces whose signed volume is negative, which face inward, are turned inside out
+	Outward,
+}
+
+impl WindingFix {
+	pub fn from_name(name: &str) -> Option<WindingFix> {
+		match name {
+			"consistent" => Some(WindingFix::Consistent),
+			"outward" => Some(WindingFix::Outward),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			WindingFix::Consistent => "consistent",
+			WindingFix::Outward => "outward",
+		}
+	}
+}
+
+/// Returns a copy of `obj` where the winding is propagated across the edges shared by two triangles, and the
+/// number of triangles flipped. The normals of the file are kept
+pub fn fix_winding(obj: &Object, fix: WindingFix) -> (Object, usize) {
+	let triangles = triangles(obj);
+
+	//undirected edge => the triangles along it, and whether they go from the lower position to the higher
+	let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+	for (t, corners) in triangles.iter().enumerate() {
+		for i in 0..3 {
+			let (a, b) = (corners[i].0, corners[(i + 1) % 3].0);
+			if a != b {
+				edges.entry((usize::min(a, b), usize::max(a, b))).or_default().push((t, a < b));
+			}
+		}
+	}
+
+	let mut flipped: Vec<Option<bool>> = vec![None; triangles.len()];
+	let mut pieces: Vec<Vec<usize>> = vec![];
+	for first in 0..triangles.len() {
+		if flipped[first].is_some() {
+			continue;
+		}
+		flipped[first] = Some(false);
+		let mut piece = vec![];
+		let mut queue = VecDeque::from([first]);
+		while let Some(t) = queue.pop_front() {
+			piece.push(t);
+			let corners = triangles[t];
+			for i in 0..3 {
+				let (a, b) = (corners[i].0, corners[(i + 1) % 3].0);
+				let Some(along) = edges.get(&(usize::min(a, b), usize::max(a, b))) else {
+					continue;
+				};
+				//the winding can't be told across the non-manifold edges
+				if along.len() != 2 {
+					continue;
+				}
+				let ascending = (a < b) != flipped[t].unwrap();
+				for &(u, u_ascending) in along.iter().filter(|&&(u, _)| u != t) {
+					if flipped[u].is_none() {
+						//the neighbor has to go along the edge the other way
+						flipped[u] = Some(u_ascending == ascending);
+						queue.push_back(u);
+					}
+				}
+			}
+		}
+		pieces.push(piece);
+	}
+
+	let mut flipped: Vec<bool> = flipped.into_iter().map(Option::unwrap).collect();
+	if fix == WindingFix::Outward {
+		for piece in &pieces {
+			let volume: f64 = piece.iter().map(|&t| {
+				let [p0, p1, p2] = triangles[t].map(|c| obj.vertices[c.0]);
+				let v = dot(p0, cross(p1, p2));
+				if flipped[t] { -v } else { v }
+			}).sum();
+			//only closed pieces have an inside
+			let wound: Vec<[VTNIndex; 3]> = piece.iter().map(|&t| {
+				let [c0, c1, c2] = triangles[t];
+				if flipped[t] { [c0, c2, c1] } else { [c0, c1, c2] }
+			}).collect();
+			let closed = boundary_edges(&wound).is_empty();
+			if closed && volume < 0.0 {
+				for &t in piece {
+					flipped[t] = !flipped[t];
+				}
+			}
+		}
+	}
+
+	let mut result = obj.clone();
+	let mut flipped = flipped.into_iter();
+	let mut count = 0;
+	for geo in &mut result.geometry {
+		for shape in &mut geo.shapes {
+			if let Shape::Triangle(_, v2, v3) = shape {
+				if flipped.next().unwrap() {
+					std::mem::swap(v2, v3);
+					count += 1;
+				}
+			}
+		}
+	}
+	(result, count)
+}
+
+/// What to do with the triangles facing into the mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvertedFaces {
+	/// Lists them in a warning
+	Warn,
+	Flip,
+}
+
+impl InvertedFaces {
+	pub fn from_name(name: &str) -> Option<InvertedFaces> {
+		match name {
+			"warn" => Some(InvertedFaces::Warn),
+			"flip" => Some(InvertedFaces::Flip),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			InvertedFaces::Warn => "warn",
+			InvertedFaces::Flip => "flip",
+		}
+	}
+}
+
+/// The indices, among the triangles of `obj`, of the ones whose face normal points into the mesh: most rays
+/// leaving their center on that side cross the surface an odd number of times. Around the holes of open
+/// meshes the rays can escape, so the results only hold for closed ones
+pub fn inverted_faces(obj: &Object) -> Vec<usize> {
+	let mesh = Mesh::from_object(obj, &ConvertOptions::default(), 0);
+	let bvh = bvh::build(&mesh);
+	let size = lenght(sub(mesh.max, mesh.min));
+
+	triangles(obj).iter().enumerate().filter_map(|(t, corners)| {
+		let [p0, p1, p2] = corners.map(|c| obj.vertices[c.0]);
+		let n = cross(sub(p1, p0), sub(p2, p0));
+		if lenght(n) == 0.0 {
+			return None;
+		}
+		let n = normalize(n);
+		let center = mul(Vertex { x: p0.x + p1.x + p2.x, y: p0.y + p1.y + p2.y, z: p0.z + p1.z + p2.z }, 1.0 / 3.0);
+		let origin = sub(center, mul(n, -1e-6 * size));
+
+		//tilted a little, so that the rays hitting an edge of two triangles of one don't decide alone
+		let (u, v) = (any_orthogonal(n), cross(n, any_orthogonal(n)));
+		let directions = [n, sub(n, mul(u, -0.31)), sub(n, mul(v, 0.37))];
+		let inside = directions.iter().filter(|&&dir| {
+			let mut crossings = 0;
+			bvh::raycast(&bvh, &mesh, origin, normalize(dir), 2.0 * size, |hit, _, _| {
+				if hit != t {
+					crossings += 1;
+				}
+			});
+			crossings % 2 == 1
+		}).count();
+		(inside >= 2).then_some(t)
+	}).collect()
+}
+
+/// Returns a copy of `obj` with the triangles at these indices wound the other way. The normals of the file
+/// are kept
+pub fn flip_triangles(obj: &Object, flipped: &[usize]) -> Object {
+	let flipped: HashSet<usize> = flipped.iter().copied().collect();
+	let mut result = obj.clone();
+	let mut t = 0;
+	for geo in &mut result.geometry {
+		for shape in &mut geo.shapes {
+			if let Shape::Triangle(_, v2, v3) = shape {
+				if flipped.contains(&t) {
+					std::mem::swap(v2, v3);
+				}
+				t += 1;
+			}
+		}
+	}
+	result
+}
+
+/// Returns a copy of `obj` without the triangles at the same positions as an earlier one, in any order or
+/// winding, and the number of triangles removed. Exporters repeat some faces, which then z-fight
+pub fn remove_duplicate_triangles(obj: &Object) -> (Object, usize) {
+	let mut seen = HashSet::new();
+	let mut removed = 0;
+	let mut result = obj.clone();
+	for geo in &mut result.geometry {
+		geo.shapes.retain(|shape| {
+			let Shape::Triangle(v1, v2, v3) = *shape else {
+				return true;
+			};
+			let mut key = [v1.0, v2.0, v3.0].map(|idx| {
+				let p = obj.vertices[idx];
+				[p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+			});
+			key.sort();
+			let unique = seen.insert(key);
+			if !unique {
+				removed += 1;
+			}
+			unique
+		});
+	}
+	(result, removed)
+}
+
+/// The connected piece of each triangle of `obj`, numbered from 0 in the order of their first triangles
+pub fn components(obj: &Object) -> Vec<usize> {
+	let triangles = triangles(obj);
+	let mut parents: Vec<usize> = (0..obj.vertices.len()).collect();
+	for corners in &triangles {
+		union(&mut parents, corners[0].0, corners[1].0);
+		union(&mut parents, corners[1].0, corners[2].0);
+	}
+	let mut numbers = HashMap::new();
+	triangles.iter().map(|corners| {
+		let root = find(&mut parents, corners[0].0);
+		let next = numbers.len();
+		*numbers.entry(root).or_insert(next)
+	}).collect()
+}
+
+/// Below what the pieces are debris
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentThreshold {
+	Triangles(usize),
+	Area(f64),
+}
+
+impl ComponentThreshold {
+	/// A whole number of triangles, or an area with a decimal point like `0.5`
+	pub fn from_name(name: &str) -> Option<ComponentThreshold> {
+		match name.parse() {
+			Ok(triangles) => Some(ComponentThreshold::Triangles(triangles)),
+			Err(_) => name.parse().ok().filter(|area: &f64| *area >= 0.0).map(ComponentThreshold::Area),
+		}
+	}
+
+	/// The areas keep their decimal point, so that they are read back as areas
+	pub fn name(self) -> String {
+		match self {
+			ComponentThreshold::Triangles(triangles) => triangles.to_string(),
+			ComponentThreshold::Area(area) => format!("{:?}", area),
+		}
+	}
+}
+
+/// Returns a copy of `obj` without the connected pieces with fewer triangles or less surface area than
+/// `threshold`, and the number of pieces removed
+pub fn remove_small_components(obj: &Object, threshold: ComponentThreshold) -> (Object, usize) {
+	let components = components(obj);
+	let count = components.iter().max().map_or(0, |&last| last + 1);
+	let mut triangle_counts = vec![0; count];
+	let mut areas = vec![0.0; count];
+	for (corners, &c) in triangles(obj).iter().zip(&components) {
+		let [p0, p1, p2] = corners.map(|corner| obj.vertices[corner.0]);
+		triangle_counts[c] += 1;
+		areas[c] += lenght(cross(sub(p1, p0), sub(p2, p0))) * 0.5;
+	}
+	let small: Vec<bool> = (0..count).map(|c| match threshold {
+		ComponentThreshold::Triangles(min) => triangle_counts[c] < min,
+		ComponentThreshold::Area(min) => areas[c] < min,
+	}).collect();
+
+	let mut result = obj.clone();
+	let mut components = components.into_iter();
+	for geo in &mut result.geometry {
+		geo.shapes.retain(|shape| match shape {
+			Shape::Triangle(..) => !small[components.next().unwrap()],
+			_ => true,
+		});
+	}
+	(result, small.iter().filter(|&&small| small).count())
+}
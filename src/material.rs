@@ -0,0 +1,445 @@
+//! The materials of the mtl files referenced by the obj, stored in the mesh as a material table.
+//!
+//! Texture paths are written by the DCC tools as they were on the artist's machine, often absolute; they can be
+//! rewritten to what the runtime loads.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::ParseError;
+use crate::chunk;
+use crate::json::{self, Value};
+
+/// A texture map of a material
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureRef {
+	/// The mtl statement: `map_Kd`, `map_Ks`, `map_bump`...
+	pub kind: String,
+	/// As written in the mtl file, without the options before it
+	pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Material {
+	pub name: String,
+	/// Kd
+	pub diffuse: Option<[f64; 3]>,
+	/// Ks
+	pub specular: Option<[f64; 3]>,
+	/// Ns
+	pub shininess: Option<f64>,
+	/// d, or 1 - Tr
+	pub dissolve: Option<f64>,
+	/// Ke
+	pub emissive: Option<[f64; 3]>,
+	/// Pm and Pr, from the PBR extension of the format
+	pub metallic: Option<f64>,
+	pub roughness: Option<f64>,
+	pub textures: Vec<TextureRef>,
+}
+
+/// A metallic-roughness material, as PBR renderers take them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial {
+	/// With the alpha
+	pub base_color: [f64; 4],
+	pub metallic: f64,
+	pub roughness: f64,
+	pub emissive: [f64; 3],
+	/// The indices of the textures in the material
+	pub base_color_texture: Option<usize>,
+	pub metallic_texture: Option<usize>,
+	pub roughness_texture: Option<usize>,
+	pub normal_texture: Option<usize>,
+	pub emissive_texture: Option<usize>,
+}
+
+fn luminance(c: [f64; 3]) -> f64 {
+	0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+impl Material {
+	fn texture(&self, kinds: &[&str]) -> Option<usize> {
+		kinds.iter().find_map(|kind| self.textures.iter().position(|t| t.kind == *kind))
+	}
+
+	/// Uses Pm and Pr when the mtl file has them. Otherwise the material is a metal when its specular color is
+	/// tinted and brighter than its diffuse, with the specular as the base color, and the roughness matches the
+	/// highlight of the Phong exponent: it's the fourth root of 2 / (Ns + 2). The metallic and roughness textures
+	/// fall back to map_Ks and map_Ns
+	pub fn to_pbr(&self) -> PbrMaterial {
+		let diffuse = self.diffuse.unwrap_or([1.0; 3]);
+		let specular = self.specular.unwrap_or([0.0; 3]);
+		let tinted = specular.iter().fold(0.0, |m: f64, &c| m.max(c)) - specular.iter().fold(1.0, |m: f64, &c| m.min(c)) > 0.1;
+		let metallic = self.metallic.unwrap_or(if tinted && luminance(specular) > luminance(diffuse) { 1.0 } else { 0.0 });
+		let color = if self.metallic.is_none() && metallic == 1.0 { specular } else { diffuse };
+		let roughness = self.roughness.unwrap_or_else(|| match self.shininess {
+			Some(ns) => (2.0 / (ns.max(0.0) + 2.0)).powf(0.25),
+			None => 1.0,
+		});
+
+		PbrMaterial {
+			base_color: [color[0], color[1], color[2], self.dissolve.unwrap_or(1.0)],
+			metallic,
+			roughness: roughness.clamp(0.0, 1.0),
+			emissive: self.emissive.unwrap_or([0.0; 3]),
+			base_color_texture: self.texture(&["map_Kd"]),
+			metallic_texture: self.texture(&["map_Pm", "map_Ks"]),
+			roughness_texture: self.texture(&["map_Pr", "map_Ns"]),
+			normal_texture: self.texture(&["norm", "map_bump", "bump"]),
+			emissive_texture: self.texture(&["map_Ke"]),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MaterialLibrary {
+	pub materials: Vec<Material>,
+}
+
+impl MaterialLibrary {
+	pub fn find(&self, name: &str) -> Option<&Material> {
+		self.materials.iter().find(|m| m.name == name)
+	}
+
+	/// Adds the materials of another file; the first definition of a name wins, like in most loaders
+	pub fn append(&mut self, other: MaterialLibrary) {
+		for material in other.materials {
+			if self.find(&material.name).is_none() {
+				self.materials.push(material);
+			}
+		}
+	}
+}
+
+/// How the texture paths are written in the material table
+#[derive(Debug, Clone, Default)]
+pub struct TexturePaths {
+	/// Keeps only the file names
+	pub strip_directories: bool,
+	/// Prepended as is, so directories need the trailing /
+	pub prefix: Option<String>,
+}
+
+impl TexturePaths {
+	/// Always with forward slashes
+	pub fn rewrite(&self, path: &str) -> String {
+		let mut path = path.replace('\\', "/");
+		if self.strip_directories {
+			path = path.rsplit('/').next().unwrap().to_owned();
+		}
+		match self.prefix {
+			Some(ref prefix) => format!("{}{}", prefix, path),
+			None => path,
+		}
+	}
+}
+
+/// The file names of the `mtllib` statements of an obj file, in order
+pub fn material_libraries(content: &str) -> Vec<String> {
+	content.lines()
+		.filter_map(|line| line.trim().strip_prefix("mtllib "))
+		.flat_map(|names| names.split_whitespace().map(str::to_owned))
+		.collect()
+}
+
+/// Arguments taken by the options of the texture statements
+fn option_arguments(option: &str) -> usize {
+	match option {
+		"-o" | "-s" | "-t" => 3,
+		"-mm" => 2,
+		_ => 1,
+	}
+}
+
+fn color(values: &[&str]) -> Option<[f64; 3]> {
+	let v: Vec<f64> = values.iter().map_while(|v| v.parse().ok()).collect();
+	match v.len() {
+		0 => None,
+		//a single value is a gray
+		1 | 2 => Some([v[0]; 3]),
+		_ => Some([v[0], v[1], v[2]]),
+	}
+}
+
+/// Reads the colors, the shininess, the dissolve, the PBR values and the texture maps, ignoring the statements it doesn't know
+pub fn parse_mtl(content: &str) -> MaterialLibrary {
+	let mut library = MaterialLibrary::default();
+
+	for line in content.lines() {
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		let Some((&statement, values)) = tokens.split_first() else {
+			continue;
+		};
+		if statement == "newmtl" {
+			library.materials.push(Material {
+				name: values.join(" "),
+				..Default::default()
+			});
+			continue;
+		}
+		let Some(material) = library.materials.last_mut() else {
+			continue;
+		};
+		let scalar = values.first().and_then(|v| v.parse::<f64>().ok());
+
+		match statement {
+			"Kd" => material.diffuse = color(values),
+			"Ks" => material.specular = color(values),
+			"Ns" => material.shininess = scalar,
+			"d" => material.dissolve = scalar,
+			"Tr" => material.dissolve = scalar.map(|t| 1.0 - t),
+			"Ke" => material.emissive = color(values),
+			"Pm" => material.metallic = scalar,
+			"Pr" => material.roughness = scalar,
+			_ if is_texture(statement) => {
+				//the path is what follows the options, and can have spaces
+				let mut i = 0;
+				while i < values.len() && values[i].starts_with('-') {
+					i += 1 + option_arguments(values[i]);
+				}
+				if i < values.len() {
+					material.textures.push(TextureRef {
+						kind: statement.to_owned(),
+						path: values[i..].join(" "),
+					});
+				}
+			},
+			_ => {},
+		}
+	}
+	library
+}
+
+/// Replacement values for a material, read from an override file where the keys are the mtl statements:
+///
+/// `{ "wood": { "Kd": [0.6, 0.4, 0.2], "Ns": 10, "map_Kd": "wood.png", "map_bump": null } }`
+///
+/// A texture set to null is removed, and a material that no mtl file has is added
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MaterialOverride {
+	pub name: String,
+	pub diffuse: Option<[f64; 3]>,
+	pub specular: Option<[f64; 3]>,
+	pub shininess: Option<f64>,
+	pub dissolve: Option<f64>,
+	pub emissive: Option<[f64; 3]>,
+	pub metallic: Option<f64>,
+	pub roughness: Option<f64>,
+	/// By kind, with None to remove the texture
+	pub textures: Vec<(String, Option<String>)>,
+}
+
+impl MaterialOverride {
+	pub fn apply(&self, material: &mut Material) {
+		material.diffuse = self.diffuse.or(material.diffuse);
+		material.specular = self.specular.or(material.specular);
+		material.shininess = self.shininess.or(material.shininess);
+		material.dissolve = self.dissolve.or(material.dissolve);
+		material.emissive = self.emissive.or(material.emissive);
+		material.metallic = self.metallic.or(material.metallic);
+		material.roughness = self.roughness.or(material.roughness);
+		for (kind, path) in &self.textures {
+			match (material.textures.iter_mut().find(|t| t.kind == *kind), path) {
+				(Some(texture), Some(path)) => texture.path = path.clone(),
+				(None, Some(path)) => material.textures.push(TextureRef {
+					kind: kind.clone(),
+					path: path.clone(),
+				}),
+				(_, None) => material.textures.retain(|t| t.kind != *kind),
+			}
+		}
+	}
+}
+
+fn is_texture(statement: &str) -> bool {
+	statement.starts_with("map_") || statement == "bump" || statement == "disp" || statement == "decal" || statement == "norm"
+}
+
+/// The overrides of a JSON file, in order; unknown keys are errors so that typos don't go unnoticed. The `pbr`
+/// of the files written by `to_json` is skipped, it's computed from the other values
+pub fn parse_overrides(content: &str) -> Result<Vec<MaterialOverride>, ParseError> {
+	let error = |line_number: usize, message: String| Err(ParseError { line_number, message });
+	let Value::Object(materials) = json::parse(content)? else {
+		return error(1, "Expected an object of the materials by name".to_owned());
+	};
+
+	let mut overrides = vec![];
+	for (name, line, values) in materials {
+		let Value::Object(values) = values else {
+			return error(line, format!("Expected an object of the values of {}", name));
+		};
+		let mut o = MaterialOverride {
+			name,
+			..Default::default()
+		};
+		for (key, line, value) in values {
+			let scalar = match value {
+				Value::Number(n) => Some(n),
+				_ => None,
+			};
+			//a single number is a gray, like in the mtl files
+			let color = match value {
+				Value::Number(n) => Some([n; 3]),
+				Value::Array(ref c) => match c[..] {
+					[Value::Number(r), Value::Number(g), Value::Number(b)] => Some([r, g, b]),
+					_ => None,
+				},
+				_ => None,
+			};
+			match key.as_str() {
+				"Kd" | "Ks" | "Ke" if color.is_none() => return error(line, format!("{} of {} isn't a color", key, o.name)),
+				"Kd" => o.diffuse = color,
+				"Ks" => o.specular = color,
+				"Ke" => o.emissive = color,
+				"Ns" | "d" | "Tr" | "Pm" | "Pr" if scalar.is_none() => return error(line, format!("{} of {} isn't a number", key, o.name)),
+				"Ns" => o.shininess = scalar,
+				"d" => o.dissolve = scalar,
+				"Tr" => o.dissolve = scalar.map(|t| 1.0 - t),
+				"Pm" => o.metallic = scalar,
+				"Pr" => o.roughness = scalar,
+				"pbr" => {},
+				_ if is_texture(&key) => match value {
+					Value::String(path) => o.textures.push((key, Some(path))),
+					Value::Null => o.textures.push((key, None)),
+					_ => return error(line, format!("{} of {} isn't a path or null", key, o.name)),
+				},
+				_ => return error(line, format!("Unknown key {} in {}", key, o.name)),
+			}
+		}
+		overrides.push(o);
+	}
+	Ok(overrides)
+}
+
+fn write_string(data: &mut Vec<u8>, text: &str) {
+	let len = usize::min(text.len(), u16::MAX as usize);
+	data.write_u16::<LittleEndian>(len as u16).unwrap();
+	data.extend_from_slice(&text.as_bytes()[..len]);
+}
+
+/// Returns the indices into `embedded` of the textures that the table points at, in the order of their chunks;
+/// `embedded` has the content of the texture files by their path in the mtl file
+pub fn write_materials(data: &mut Vec<u8>, materials: &[&Material], paths: &TexturePaths, embedded: &[(String, Vec<u8>)],
+	pbr: bool) -> Vec<usize> {
+	let mut chunks: Vec<usize> = vec![];
+	data.write_u16::<LittleEndian>(materials.len() as u16).unwrap();
+	for material in materials {
+		write_string(data, &material.name);
+		data.write_u16::<LittleEndian>(material.textures.len() as u16).unwrap();
+		for texture in &material.textures {
+			write_string(data, &texture.kind);
+			match embedded.iter().position(|(path, _)| *path == texture.path) {
+				Some(file) => {
+					let index = chunks.iter().position(|&c| c == file).unwrap_or_else(|| {
+						chunks.push(file);
+						chunks.len() - 1
+					});
+					data.push(chunk::TEXTURE_EMBEDDED);
+					data.write_u32::<LittleEndian>(index as u32).unwrap();
+				},
+				None => {
+					data.push(chunk::TEXTURE_PATH);
+					write_string(data, &paths.rewrite(&texture.path));
+				},
+			}
+		}
+
+		data.push(pbr as u8);
+		if pbr {
+			let pbr = material.to_pbr();
+			for c in pbr.base_color.iter().chain(&[pbr.metallic, pbr.roughness]).chain(&pbr.emissive) {
+				data.write_f32::<LittleEndian>(*c as f32).unwrap();
+			}
+			for texture in [pbr.base_color_texture, pbr.metallic_texture, pbr.roughness_texture, pbr.normal_texture, pbr.emissive_texture] {
+				data.write_u16::<LittleEndian>(texture.map_or(chunk::NO_TEXTURE, |t| t as u16)).unwrap();
+			}
+		}
+	}
+	chunks
+}
+
+/// The rewritten path keeps the extension, for the runtime to know how to decode it
+pub fn write_texture(data: &mut Vec<u8>, name: &str, content: &[u8]) {
+	write_string(data, name);
+	data.extend_from_slice(content);
+}
+
+/// A standalone material library for engines that manage the materials separately from the meshes, which only
+/// have the names: an object of the materials by name in the format of the override files, where the
+/// textures have the rewritten paths. With `pbr`, each material has a `pbr` object too, whose textures are
+/// the statements of the maps
+pub fn to_json(materials: &[&Material], paths: &TexturePaths, pbr: bool) -> String {
+	let number = |n: f64| format!("{:?}", n as f32);
+	let color = |c: &[f64]| format!("[{}]", c.iter().map(|&c| number(c)).collect::<Vec<String>>().join(", "));
+
+	let mut out = String::from("{\n");
+	for (i, material) in materials.iter().enumerate() {
+		let mut values = vec![];
+		for (key, value) in [("Kd", material.diffuse), ("Ks", material.specular), ("Ke", material.emissive)] {
+			if let Some(value) = value {
+				values.push(format!("\"{}\": {}", key, color(&value)));
+			}
+		}
+		for (key, value) in [("Ns", material.shininess), ("d", material.dissolve), ("Pm", material.metallic), ("Pr", material.roughness)] {
+			if let Some(value) = value {
+				values.push(format!("\"{}\": {}", key, number(value)));
+			}
+		}
+		for texture in &material.textures {
+			values.push(format!("{}: {}", json::string(&texture.kind), json::string(&paths.rewrite(&texture.path))));
+		}
+		if pbr {
+			let pbr = material.to_pbr();
+			let mut fields = vec![
+				format!("\"base_color\": {}", color(&pbr.base_color)),
+				format!("\"metallic\": {}", number(pbr.metallic)),
+				format!("\"roughness\": {}", number(pbr.roughness)),
+				format!("\"emissive\": {}", color(&pbr.emissive)),
+			];
+			for (slot, texture) in [("base_color_texture", pbr.base_color_texture), ("metallic_texture", pbr.metallic_texture),
+				("roughness_texture", pbr.roughness_texture), ("normal_texture", pbr.normal_texture), ("emissive_texture", pbr.emissive_texture)] {
+				if let Some(t) = texture {
+					fields.push(format!("\"{}\": {}", slot, json::string(&material.textures[t].kind)));
+				}
+			}
+			values.push(format!("\"pbr\": {{ {} }}", fields.join(", ")));
+		}
+
+		let separator = if i + 1 < materials.len() { "," } else { "" };
+		if values.is_empty() {
+			out.push_str(&format!("\t{}: {{}}{}\n", json::string(&material.name), separator));
+		}
+		else {
+			out.push_str(&format!("\t{}: {{\n\t\t{}\n\t}}{}\n", json::string(&material.name), values.join(",\n\t\t"), separator));
+		}
+	}
+	out.push_str("}\n");
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(content: &str) -> (usize, String) {
+		let err = parse_overrides(content).unwrap_err();
+		(err.line_number, err.message)
+	}
+
+	#[test]
+	fn overrides() {
+		let overrides = parse_overrides("{\n\t\"wood\": {\"Kd\": 0.5, \"map_Kd\": \"oak.png\", \"bump\": null}\n}").unwrap();
+		assert_eq!(overrides.len(), 1);
+		assert_eq!(overrides[0].diffuse, Some([0.5; 3]));
+		assert_eq!(overrides[0].textures, [("map_Kd".to_owned(), Some("oak.png".to_owned())), ("bump".to_owned(), None)]);
+	}
+
+	#[test]
+	fn errors_have_the_line() {
+		assert_eq!(error("").0, 1);
+		assert_eq!(error("[]"), (1, "Expected an object of the materials by name".to_owned()));
+		assert_eq!(error("{\n\t\"wood\": 1\n}"), (2, "Expected an object of the values of wood".to_owned()));
+		assert_eq!(error("{\n\t\"wood\": {\n\t\t\"Kd\": \"red\"\n\t}\n}"), (3, "Kd of wood isn't a color".to_owned()));
+		assert_eq!(error("{\n\t\"wood\": {\n\t\t\"Kd\": 1,\n\t\t\"Kb\": 1\n\t}\n}"), (4, "Unknown key Kb in wood".to_owned()));
+		assert_eq!(error("{\n\t\"wood\": {\n\t\t\"Kd\": [1,\n}").0, 4);
+	}
+}
@@ -0,0 +1,80 @@
+use wavefront_obj::obj::Vertex;
+
+pub fn flt_min(a: f64, b: f64) -> f64 {
+	if a < b { a } else { b }
+}
+
+pub fn flt_max(a: f64, b: f64) -> f64 {
+	if a > b { a } else { b }
+}
+
+pub fn vert_min(a: Vertex, b: Vertex) -> Vertex {
+	Vertex {
+		x: flt_min(a.x, b.x),
+		y: flt_min(a.y, b.y),
+		z: flt_min(a.z, b.z),
+	}
+}
+
+pub fn vert_max(a: Vertex, b: Vertex) -> Vertex {
+	Vertex {
+		x: flt_max(a.x, b.x),
+		y: flt_max(a.y, b.y),
+		z: flt_max(a.z, b.z),
+	}
+}
+
+pub fn addmut(dst: &mut Vertex, src: Vertex) {
+	dst.x += src.x;
+	dst.y += src.y;
+	dst.z += src.z;
+}
+
+pub fn lenght(v: Vertex) -> f64 {
+	f64::sqrt( v.x * v.x + v.y * v.y + v.z * v.z )
+}
+pub fn normalize(v: Vertex) -> Vertex {
+	mul(v, 1.0 / lenght(v))
+}
+
+pub fn dot(a: Vertex, b: Vertex) -> f64 {
+	a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+pub fn cross(a: Vertex, b: Vertex) -> Vertex {
+	Vertex{
+		x: a.y*b.z - a.z*b.y,
+		y: a.z*b.x - a.x*b.z,
+		z: a.x*b.y - a.y*b.x
+	}
+}
+
+pub fn sub(a: Vertex, b: Vertex) -> Vertex {
+	Vertex{
+		x: a.x - b.x,
+		y: a.y - b.y,
+		z: a.z - b.z,
+	}
+}
+
+pub fn mul(a: Vertex, b: f64) -> Vertex {
+	Vertex{
+		x: a.x * b,
+		y: a.y * b,
+		z: a.z * b,
+	}
+}
+
+/// Any unit vector perpendicular to `n`, built on the axis least aligned with it
+pub fn any_orthogonal(n: Vertex) -> Vertex {
+	let axis = if n.x.abs() <= n.y.abs() && n.x.abs() <= n.z.abs() {
+		Vertex{ x: 1.0, y: 0.0, z: 0.0 }
+	}
+	else if n.y.abs() <= n.z.abs() {
+		Vertex{ x: 0.0, y: 1.0, z: 0.0 }
+	}
+	else {
+		Vertex{ x: 0.0, y: 0.0, z: 1.0 }
+	};
+	normalize(cross(n, axis))
+}
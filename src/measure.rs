@@ -0,0 +1,41 @@
+//! Surface area and volume, for LOD selection and physics mass.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::{Object, Shape};
+use crate::math::*;
+use crate::manifold;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Measurements {
+	pub surface_area: f64,
+	/// Only for closed meshes
+	pub volume: Option<f64>,
+}
+
+pub fn measure(obj: &Object) -> Measurements {
+	let mut area = 0.0;
+	//the sum of the signed volumes of the tetrahedra from the origin to each triangle
+	let mut volume = 0.0;
+	for geo in &obj.geometry {
+		for shape in &geo.shapes {
+			if let Shape::Triangle(v1, v2, v3) = *shape {
+				let p = [obj.vertices[v1.0], obj.vertices[v2.0], obj.vertices[v3.0]];
+				let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+				area += lenght(n) * 0.5;
+				volume += dot(p[0], cross(p[1], p[2])) / 6.0;
+			}
+		}
+	}
+
+	Measurements {
+		surface_area: area,
+		//inside out meshes have a negative volume
+		volume: if manifold::analyze(obj).is_watertight() { Some(volume.abs()) } else { None },
+	}
+}
+
+pub fn write_measurements(data: &mut Vec<u8>, measurements: &Measurements) {
+	data.write_f32::<LittleEndian>(measurements.surface_area as f32).unwrap();
+	data.write_u8(if measurements.volume.is_some() { 1 } else { 0 }).unwrap();
+	data.write_f32::<LittleEndian>(measurements.volume.unwrap_or(0.0) as f32).unwrap();
+}
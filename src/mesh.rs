@@ -0,0 +1,1260 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::{Object, Shape, VTNIndex, Vertex, TVertex, Normal};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f64;
+use half::f16;
+use crate::math::*;
+use crate::layout::{self, Attribute, ColorSpace, Format, VertexFieldOffsets};
+use crate::skin::BoneInfluences;
+use crate::convert::{ConvertOptions, UvOverflow};
+use crate::morph::{self, MorphDelta, MorphDeltas};
+use crate::curvature;
+use crate::lightmap::{self, LightmapUvs};
+use crate::normals::SeamSmoothing;
+use crate::user_attributes::UserAttributes;
+use crate::diagnostics::{Warning, WarningKind};
+use crate::reader::Topology;
+use crate::atlas::{find, union};
+use crate::progressive::{self, Refinement};
+use crate::dequantization::Dequantization;
+
+fn pack_normalized(val: f64, max: u32) -> u32 {
+	//two's complement, masked to the width of the field
+	(f64::round(val * max as f64) as i32 as u32) & (max * 2 + 1)
+}
+
+fn pack_i2_10_10_10(normal: Normal, w: f64) -> u32 {
+	pack_normalized(normal.x, 511) |
+	(pack_normalized(normal.y, 511) << 10) |
+	(pack_normalized(normal.z, 511) << 20) |
+	(pack_normalized(w, 1)) << 30
+}
+
+fn pack_f16(val: f64) -> u16 {
+	f16::from_f64(val).as_bits()
+}
+
+fn is_uv_in_range(val: f64) -> bool {
+	(-1.0..=1.0).contains(&val)
+}
+
+/// Brings a texture coordinate in [-1, 1] according to the policy; non-finite ones are reported as invalid data
+fn fit_uv(val: f64, policy: UvOverflow) -> f64 {
+	if !val.is_finite() || is_uv_in_range(val) {
+		return val;
+	}
+	match policy {
+		//the same as subtracting 1 until it's in, which was the original fix for bad exports
+		UvOverflow::Wrap if val > 1.0 => val - f64::ceil(val - 1.0),
+		UvOverflow::Wrap => val + f64::ceil(-1.0 - val),
+		UvOverflow::Clamp => val.clamp(-1.0, 1.0),
+		UvOverflow::Keep | UvOverflow::Error => val,
+	}
+}
+
+fn has_attribute(vtni: VTNIndex, attr: Attribute) -> bool {
+	let (_, tex, normal) = vtni;
+	match attr {
+		Attribute::Position => true,
+		Attribute::Normal => normal.is_some(),
+		Attribute::Tex0 => tex.is_some(),
+		_ => false,
+	}
+}
+
+pub fn shape_corners(shape: &Shape) -> Vec<VTNIndex> {
+	match *shape {
+		Shape::Point(v1) => vec![v1],
+		Shape::Line(v1, v2) => vec![v1, v2],
+		Shape::Triangle(v1, v2, v3) => vec![v1, v2, v3],
+	}
+}
+
+/// Objects with any face are converted as triangles, the others as lines if they have any, or as points
+pub fn object_topology(obj: &Object) -> Topology {
+	let shapes = || obj.geometry.iter().flat_map(|geo| &geo.shapes);
+	if shapes().any(|s| matches!(s, Shape::Triangle(..))) {
+		Topology::TriangleList
+	}
+	else if shapes().any(|s| matches!(s, Shape::Line(..))) {
+		Topology::LineList
+	}
+	else if shapes().any(|s| matches!(s, Shape::Point(..))) {
+		Topology::PointList
+	}
+	else {
+		Topology::TriangleList
+	}
+}
+
+pub fn is_primitive_of(shape: &Shape, topology: Topology) -> bool {
+	match *shape {
+		Shape::Triangle(..) => topology == Topology::TriangleList,
+		Shape::Line(..) => topology == Topology::LineList,
+		Shape::Point(..) => topology == Topology::PointList,
+	}
+}
+
+fn has_all(obj: &Object, attr: Attribute, topology: Topology) -> bool {
+	obj.geometry.iter()
+		.flat_map(|geo| &geo.shapes)
+		.filter(|shape| is_primitive_of(shape, topology))
+		.all(|shape| shape_corners(shape).into_iter().all(|c| has_attribute(c, attr)))
+}
+
+impl VertexFieldOffsets {
+	fn from_source(src: &VertexSource, topology: Topology, with_tangent: bool, with_bitangent: bool) -> Self {
+		let mut attributes = vec![];
+		if src.colors.is_some() || src.material_colors {
+			attributes.push(Attribute::Color);
+		}
+		if has_all(src.obj, Attribute::Normal, topology) {
+			attributes.push(Attribute::Normal);
+		}
+		if with_tangent {
+			attributes.push(Attribute::Tangent);
+		}
+		if with_bitangent {
+			attributes.push(Attribute::Bitangent);
+		}
+		if has_all(src.obj, Attribute::Tex0, topology) {
+			attributes.push(Attribute::Tex0);
+		}
+		if src.lightmap.is_some() {
+			attributes.push(Attribute::Tex1);
+		}
+		if src.skin.is_some() {
+			attributes.push(Attribute::BoneIndices);
+			attributes.push(Attribute::BoneWeights);
+		}
+		if src.curvature.is_some() {
+			attributes.push(Attribute::Curvature);
+		}
+		if src.material_ids {
+			attributes.push(Attribute::MaterialId);
+		}
+		let uv_format = if src.uv_overflow == UvOverflow::Keep { Format::Float32x2 } else { Format::Float16x2 };
+		VertexFieldOffsets::with_uv_format(&attributes, uv_format)
+	}
+}
+
+/// The object, plus the per-vertex data coming from outside the obj file
+struct VertexSource<'a> {
+	obj: &'a Object,
+	/// Indexed like obj.vertices
+	colors: Option<&'a [[f64; 4]]>,
+	/// Indexed like obj.vertices
+	skin: Option<&'a [BoneInfluences]>,
+	/// Indexed like obj.vertices
+	curvature: Option<Vec<f64>>,
+	/// Indexed by triangle
+	lightmap: Option<LightmapUvs>,
+	material_ids: bool,
+	/// Multiplies the colors by the diffuse color of the materials
+	material_colors: bool,
+	color_space: ColorSpace,
+	/// Indexed across all the objects, so it comes with the index of the first position of this one
+	user: Option<(&'a UserAttributes, usize)>,
+	uv_overflow: UvOverflow,
+}
+
+/// Data of a triangle corner that doesn't come from the obj indices
+#[derive(Clone, Copy)]
+struct Corner {
+	/// Lightmap chart and UV
+	lightmap: Option<(usize, TVertex)>,
+	/// The index of the material, and its diffuse color when it's baked
+	material: u32,
+	material_id: Option<u32>,
+	material_color: Option<[f64; 3]>,
+	/// The UVs of the triangle are mirrored, when generating tangents
+	mirrored: bool,
+	/// The index of the primitive in the object
+	primitive: usize,
+}
+
+/// Vertices in different lightmap charts, with different materials when they are stored, or on both sides of
+/// a UV mirror when generating tangents, can't be shared
+pub type VertexKey = (VTNIndex, usize, u32, bool);
+
+/// Whether the UVs of the triangle wind the other way from its positions, which flips the bitangent
+fn is_mirrored(obj: &Object, corners: [VTNIndex; 3]) -> bool {
+	let (Some(t1), Some(t2), Some(t3)) = (corners[0].1, corners[1].1, corners[2].1) else {
+		return false;
+	};
+	let [w1, w2, w3] = [t1, t2, t3].map(|idx| obj.tex_vertices[idx]);
+	(w2.x - w1.x) * (w3.y - w1.y) - (w3.x - w1.x) * (w2.y - w1.y) < 0.0
+}
+
+/// Where a vertex comes from in the object it was built from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexOrigin {
+	pub vtn: VTNIndex,
+	/// The first primitive using it, numbered from 0 in the order of the object like in the warnings
+	pub primitive: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct GPUVertex {
+	pos: Vertex,
+	color: Option<[f64; 4]>,
+	normal: Option<Normal>,
+	tangent: Option<Normal>,
+	tangent_handedness: f64,
+	bitangent: Option<Normal>,
+	tex: Option<TVertex>,
+	tex1: Option<TVertex>,
+	skin: Option<BoneInfluences>,
+	curvature: Option<f64>,
+	material_id: Option<u32>,
+	/// Already in the declared formats
+	user: Vec<u8>,
+	origin: VertexOrigin,
+}
+
+impl GPUVertex {
+	fn from_vtni_and_source(vtni: VTNIndex, src: &VertexSource, format: &VertexFieldOffsets) -> Self {
+		let (pos_idx, tex_opt_idx, norm_opt_idx) = vtni;
+		let obj = src.obj;
+		GPUVertex {
+			pos: obj.vertices[pos_idx],
+			color: src.colors.map(|colors| colors[pos_idx]),
+			normal: match norm_opt_idx {
+			    Some(idx) if format.normal.is_some() => Some(obj.normals[idx]),
+			    _ => None,
+			},
+			tangent: None,
+			tangent_handedness: 0.0,
+			bitangent: None,
+			tex: match tex_opt_idx {
+			    Some(idx) if format.tex0.is_some() => {
+					let tex = obj.tex_vertices[idx];
+					Some(TVertex { x: fit_uv(tex.x, src.uv_overflow), y: fit_uv(tex.y, src.uv_overflow) })
+				},
+			    _ => None,
+			},
+			tex1: None,
+			skin: src.skin.map(|skin| skin[pos_idx]),
+			curvature: src.curvature.as_ref().map(|curvature| curvature[pos_idx]),
+			material_id: None,
+			user: match src.user {
+				Some((user, first_vertex)) => {
+					let mut bytes = vec![];
+					user.vertex_bytes(first_vertex + pos_idx, &mut bytes);
+					bytes
+				},
+				None => vec![],
+			},
+			origin: VertexOrigin { vtn: vtni, primitive: 0 },
+		}
+	}
+
+	pub fn pos(&self) -> Vertex {
+		self.pos
+	}
+
+	pub fn normal(&self) -> Option<Normal> {
+		self.normal
+	}
+
+	/// With the handedness of the bitangent
+	pub fn tangent(&self) -> Option<(Normal, f64)> {
+		self.tangent.map(|t| (t, self.tangent_handedness))
+	}
+
+	pub fn tex(&self) -> Option<TVertex> {
+		self.tex
+	}
+
+	pub fn origin(&self) -> VertexOrigin {
+		self.origin
+	}
+
+	/// Reflected through `plane`, with the normal and tangent frame following
+	fn mirrored(&self, plane: MirrorPlane) -> GPUVertex {
+		let axis = plane.axis_vector();
+		let reflect = |v: Normal| sub(v, mul(axis, 2.0 * dot(v, axis)));
+		GPUVertex {
+			pos: sub(self.pos, mul(axis, 2.0 * (dot(self.pos, axis) - plane.offset))),
+			normal: self.normal.map(reflect),
+			tangent: self.tangent.map(reflect),
+			tangent_handedness: -self.tangent_handedness,
+			bitangent: self.bitangent.map(reflect),
+			..self.clone()
+		}
+	}
+
+	pub fn write_to(&self, data: &mut Vec<u8>, uv_format: Format) {
+		data.write_f32::<LittleEndian>(self.pos.x as f32).unwrap();
+		data.write_f32::<LittleEndian>(self.pos.y as f32).unwrap();
+		data.write_f32::<LittleEndian>(self.pos.z as f32).unwrap();
+
+		if let Some(color) = self.color {
+			for c in color {
+				data.write_u8(f64::round(c.clamp(0.0, 1.0) * 255.0) as u8).unwrap();
+			}
+		}
+
+		if let Some(normal) = self.normal {
+			data.write_u32::<LittleEndian>(pack_i2_10_10_10(normal, 0.0)).unwrap();
+		}
+
+		if let Some(tangent) = self.tangent {
+			data.write_u32::<LittleEndian>(pack_i2_10_10_10(tangent, self.tangent_handedness)).unwrap();
+		}
+
+		if let Some(bitangent) = self.bitangent {
+			data.write_u32::<LittleEndian>(pack_i2_10_10_10(bitangent, 0.0)).unwrap();
+		}
+
+		for tex in [self.tex, self.tex1].into_iter().flatten() {
+			if uv_format == Format::Float32x2 {
+				data.write_f32::<LittleEndian>(tex.x as f32).unwrap();
+				data.write_f32::<LittleEndian>(tex.y as f32).unwrap();
+			}
+			else {
+				data.write_u16::<LittleEndian>(pack_f16(tex.x)).unwrap();
+				data.write_u16::<LittleEndian>(pack_f16(tex.y)).unwrap();
+			}
+		}
+
+		if let Some(skin) = self.skin {
+			data.extend_from_slice(&skin.indices);
+			data.extend_from_slice(&skin.weights);
+		}
+
+		if let Some(curvature) = self.curvature {
+			data.write_f32::<LittleEndian>(curvature as f32).unwrap();
+		}
+
+		if let Some(material_id) = self.material_id {
+			data.write_u32::<LittleEndian>(material_id).unwrap();
+		}
+
+		data.extend_from_slice(&self.user);
+	}
+
+	/// How far the attributes written by `write_to` are from their values: the distance for the positions, UVs and
+	/// curvature, the angle in degrees for the directions, the largest channel difference for the colors. The
+	/// positions are quantized with `dequantization`
+	pub fn quantization_errors(&self, uv_format: Format, dequantization: Option<&Dequantization>) -> Vec<(Attribute, f64)> {
+		let mut errors = vec![];
+		let pos = match dequantization {
+			Some(dequantization) => {
+				let [x, y, z] = dequantization.apply(dequantization.quantize([self.pos.x, self.pos.y, self.pos.z]));
+				Vertex { x: x as f64, y: y as f64, z: z as f64 }
+			},
+			None => Vertex { x: self.pos.x as f32 as f64, y: self.pos.y as f32 as f64, z: self.pos.z as f32 as f64 },
+		};
+		errors.push((Attribute::Position, lenght(sub(self.pos, pos))));
+
+		if let Some(color) = self.color {
+			let error = color.iter().map(|&c| (c - f64::round(c.clamp(0.0, 1.0) * 255.0) / 255.0).abs()).fold(0.0, flt_max);
+			errors.push((Attribute::Color, error));
+		}
+
+		//as the GPUs read the snorm components back
+		let unpack = |v: f64| f64::max(f64::round(v * 511.0) / 511.0, -1.0);
+		let angle = |d: Normal| {
+			let stored = Normal { x: unpack(d.x), y: unpack(d.y), z: unpack(d.z) };
+			let cos = dot(d, stored) / (lenght(d) * lenght(stored));
+			if cos.is_finite() { cos.clamp(-1.0, 1.0).acos().to_degrees() } else { 0.0 }
+		};
+		for (attribute, direction) in [(Attribute::Normal, self.normal), (Attribute::Tangent, self.tangent), (Attribute::Bitangent, self.bitangent)] {
+			if let Some(direction) = direction {
+				errors.push((attribute, angle(direction)));
+			}
+		}
+
+		let stored_uv = |v: f64| if uv_format == Format::Float32x2 { v as f32 as f64 } else { f64::from(f16::from_f64(v)) };
+		for (attribute, tex) in [(Attribute::Tex0, self.tex), (Attribute::Tex1, self.tex1)] {
+			if let Some(tex) = tex {
+				errors.push((attribute, f64::hypot(tex.x - stored_uv(tex.x), tex.y - stored_uv(tex.y))));
+			}
+		}
+
+		if let Some(curvature) = self.curvature {
+			errors.push((Attribute::Curvature, (curvature - curvature as f32 as f64).abs()));
+		}
+		errors
+	}
+}
+
+/// A plane across one of the axes, `axis` being 0 for x, 1 for y and 2 for z
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MirrorPlane {
+	pub axis: usize,
+	/// Where the plane crosses the axis
+	pub offset: f64,
+}
+
+impl MirrorPlane {
+	fn axis_vector(&self) -> Vertex {
+		let mut v = [0.0; 3];
+		v[self.axis] = 1.0;
+		Vertex { x: v[0], y: v[1], z: v[2] }
+	}
+}
+
+/// A range of the indices that uses one material
+#[derive(Debug, Clone)]
+pub struct Submesh {
+	pub material: Option<String>,
+	pub first_index: usize,
+	pub index_count: usize,
+}
+
+#[derive(Debug)]
+
+pub struct Mesh {
+	pub vertices: Vec<GPUVertex>,
+	pub indices: Vec<usize>,
+	map: HashMap<VertexKey, usize>,
+	pub format: VertexFieldOffsets,
+	pub topology: Topology,
+	pub morphs: Vec<MorphDeltas>,
+	/// Names of the materials, indexed by material id
+	materials: Vec<Option<String>>,
+	pub submeshes: Vec<Submesh>,
+	pub warnings: Vec<Warning>,
+
+	pub min: Vertex,
+	pub max: Vertex,
+}
+
+impl Mesh {
+	/// `first_vertex` is the index of the first position of the object in the whole obj file
+	pub fn from_object(obj: &Object, options: &ConvertOptions, first_vertex: usize) -> Self {
+		let topology = object_topology(obj);
+		let triangles = topology == Topology::TriangleList;
+		let mut warnings = vec![];
+
+		//these need surfaces
+		let ignored = [
+			(options.generate_tangents || options.emit_bitangent, "tangents"),
+			(options.curvature.is_some(), "curvature"),
+			(options.lightmap.is_some(), "lightmap UVs"),
+			(options.bake_bvh, "BVH"),
+		];
+		for (_, what) in ignored.iter().filter(|&&(used, _)| used && !triangles) {
+			warnings.push(Warning::new(WarningKind::Unsupported, format!("{}: no {} for an object without faces", obj.name, what)));
+		}
+
+		//the bitangent is derived from the tangent
+		let mut generate_tangents = triangles && (options.generate_tangents || options.emit_bitangent);
+		if generate_tangents && !has_all(obj, Attribute::Tex0, topology) {
+			warnings.push(Warning::new(WarningKind::MissingAttribute,
+				format!("{}: no tangents, as not all the triangles have texture coordinates", obj.name)));
+			generate_tangents = false;
+		}
+		//the vertices past the colors of the file are white, like those of the v lines without one
+		let colors = options.vertex_colors.as_ref().map(|colors| {
+			let own = colors.colors.get(first_vertex..).unwrap_or(&[]);
+			if own.len() >= obj.vertices.len() {
+				return Cow::Borrowed(own);
+			}
+			warnings.push(Warning::new(WarningKind::MissingAttribute,
+				format!("{}: {} of the {} vertices have no color, they are white", obj.name, obj.vertices.len() - own.len(), obj.vertices.len())));
+			let mut padded = own.to_vec();
+			padded.resize(obj.vertices.len(), crate::colors::WHITE);
+			Cow::Owned(padded)
+		});
+		let skin = options.skin.as_ref().and_then(|skin| {
+			let own = skin.vertices.get(first_vertex..).unwrap_or(&[]);
+			if own.len() >= obj.vertices.len() {
+				return Some(own);
+			}
+			warnings.push(Warning::new(WarningKind::MissingAttribute,
+				format!("{}: vertex {} has no skin weights, the weights file only has the first {}", obj.name, first_vertex + own.len() + 1, skin.vertices.len())).as_error());
+			None
+		});
+		let src = VertexSource {
+			obj,
+			colors: colors.as_deref(),
+			skin,
+			curvature: options.curvature.filter(|_| triangles).map(|kind| curvature::compute(obj, kind)),
+			lightmap: options.lightmap.filter(|_| triangles).map(|settings| lightmap::unwrap(obj, &settings)),
+			material_ids: options.material_ids,
+			material_colors: options.bake_material_color,
+			color_space: options.color_space,
+			user: options.user_attributes.as_ref().map(|user| (user, first_vertex)),
+			uv_overflow: options.uv_overflow,
+		};
+
+		if options.uv_overflow == UvOverflow::Error {
+			let outside = |t: &TVertex| !is_uv_in_range(t.x) || !is_uv_in_range(t.y);
+			let count = obj.tex_vertices.iter().filter(|t| outside(t)).count();
+			//the first face using one, in the order of the object
+			let face = obj.geometry.iter().flat_map(|geo| &geo.shapes)
+				.filter(|shape| object_topology(obj) == match shape {
+					Shape::Triangle(..) => Topology::TriangleList,
+					Shape::Line(..) => Topology::LineList,
+					Shape::Point(..) => Topology::PointList,
+				})
+				.map(shape_corners)
+				.enumerate()
+				.find_map(|(i, corners)| corners.iter().find_map(|&(_, tex, _)| tex.filter(|&t| outside(&obj.tex_vertices[t])).map(|t| (i, t))));
+			//the ones no face uses aren't stored
+			if let Some((face, t)) = face {
+				warnings.push(Warning::new(WarningKind::UvOverflow, format!(
+					"{}: the texture coordinate ({}, {}) of face {} is outside of [-1, 1]{}",
+					obj.name, obj.tex_vertices[t].x, obj.tex_vertices[t].y, face,
+					if count > 1 { format!(", and {} others are too", count - 1) } else { String::new() }
+				)).at_face(face).as_error());
+			}
+		}
+		let format = VertexFieldOffsets::from_source(&src, topology, generate_tangents && options.generate_tangents, generate_tangents && options.emit_bitangent);
+		let moved = match options.uv_overflow {
+			UvOverflow::Wrap => "wrapped",
+			UvOverflow::Clamp => "clamped",
+			UvOverflow::Keep | UvOverflow::Error => "",
+		};
+		let overflowing = obj.tex_vertices.iter().filter(|t| t.x.is_finite() && t.y.is_finite() && (!is_uv_in_range(t.x) || !is_uv_in_range(t.y))).count();
+		if format.tex0.is_some() && !moved.is_empty() && overflowing > 0 {
+			warnings.push(Warning::new(WarningKind::UvOverflow,
+				format!("{}: {} texture coordinates are outside of [-1, 1] and were {}", obj.name, overflowing, moved)));
+		}
+		let mut mesh = Mesh {
+			vertices: Vec::new(),
+			indices: Vec::new(),
+			map:HashMap::new(),
+			min: Vertex{x: f64::MAX, y: f64::MAX, z: f64::MAX },
+			max: Vertex{x: f64::MIN, y: f64::MIN, z: f64::MIN },
+			format,
+			topology,
+			morphs: Vec::new(),
+			materials: Vec::new(),
+			submeshes: Vec::new(),
+			warnings,
+		};
+
+		let mut triangle = 0;
+		let mut primitive = 0;
+		let mut skipped = 0;
+		for geo in &obj.geometry {
+			//ids follow the order of the first usemtl of each material
+			let material_id = match mesh.materials.iter().position(|m| *m == geo.material_name) {
+				Some(id) => id,
+				None => {
+					mesh.materials.push(geo.material_name.clone());
+					mesh.materials.len() - 1
+				},
+			} as u32;
+
+			let material = material_id;
+			let material_id = if src.material_ids { Some(material_id) } else { None };
+			//white when the mtl files don't have the material, or it has no Kd
+			let material_color = if src.material_colors {
+				let found = options.materials.as_ref().zip(geo.material_name.as_deref()).and_then(|(library, name)| library.find(name));
+				if let (None, Some(name)) = (found, &geo.material_name) {
+					let message = format!("{}: material {} isn't in the mtl files, its faces are white", obj.name, name);
+					if !mesh.warnings.iter().any(|w| w.message == message) {
+						mesh.warnings.push(Warning::new(WarningKind::MissingMaterial, message));
+					}
+				}
+				Some(found.and_then(|m| m.diffuse).unwrap_or([1.0; 3]))
+			}
+			else {
+				None
+			};
+			let first_index = mesh.indices.len();
+			for shape in &geo.shapes {
+				match *shape {
+					Shape::Triangle(v1, v2, v3) => {
+						let mirrored = generate_tangents && is_mirrored(obj, [v1, v2, v3]);
+						let corner = |i: usize| Corner {
+							lightmap: src.lightmap.as_ref().map(|l| (l.charts[triangle], l.uvs[triangle][i])),
+							material,
+							material_id,
+							material_color,
+							mirrored,
+							primitive,
+						};
+						mesh.add_index(v1, corner(0), &src, &format);
+						mesh.add_index(v2, corner(1), &src, &format);
+						mesh.add_index(v3, corner(2), &src, &format);
+						triangle += 1;
+					},
+					Shape::Line(v1, v2) if topology == Topology::LineList => {
+						let corner = Corner {
+							lightmap: None,
+							material,
+							material_id,
+							material_color,
+							mirrored: false,
+							primitive,
+						};
+						mesh.add_index(v1, corner, &src, &format);
+						mesh.add_index(v2, corner, &src, &format);
+					},
+					Shape::Point(v1) if topology == Topology::PointList => {
+						let corner = Corner {
+							lightmap: None,
+							material,
+							material_id,
+							material_color,
+							mirrored: false,
+							primitive,
+						};
+						mesh.add_index(v1, corner, &src, &format);
+					},
+					_ => skipped += 1,
+				}
+				primitive += 1;
+			}
+			mesh.add_submesh(&geo.material_name, first_index);
+		}
+
+		if skipped > 0 {
+			mesh.warnings.push(Warning::new(WarningKind::Unsupported,
+				format!("{}: skipped {} lines and points mixed with other primitives", obj.name, skipped)));
+		}
+		if triangles {
+			let degenerate = mesh.indices.chunks(3).filter(|t| {
+				let [a, b, c] = [t[0], t[1], t[2]].map(|i| mesh.vertices[i].pos);
+				lenght(cross(sub(b, a), sub(c, a))) == 0.0
+			}).count();
+			if degenerate > 0 {
+				mesh.warnings.push(Warning::new(WarningKind::DegenerateTriangles, format!("{}: {} triangles have zero area", obj.name, degenerate)));
+			}
+		}
+
+		//the vertices each one is averaged with, itself included
+		let seams = options.smooth_seams.filter(|_| triangles && mesh.format.normal.is_some())
+			.map(|_| mesh.seam_duplicates(options.crease_angle.unwrap_or(60.0)));
+		if let Some(seams) = &seams {
+			let normals: Vec<Normal> = seams.iter().enumerate().map(|(a, group)| {
+				let mut sum = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+				for &b in group {
+					addmut(&mut sum, mesh.vertices[b].normal.unwrap());
+				}
+				if lenght(sum) > 0.0 { normalize(sum) } else { mesh.vertices[a].normal.unwrap() }
+			}).collect();
+			for (v, n) in mesh.vertices.iter_mut().zip(normals) {
+				v.normal = Some(n);
+			}
+		}
+
+		if generate_tangents {
+			//http://gamedev.stackexchange.com/questions/68612/how-to-compute-tangent-and-bitangent-vectors
+
+			let mut tan1 = vec!(Vertex{x: 0.0, y: 0.0, z:0.0}; mesh.vertices.len());
+			let mut tan2 = vec!(Vertex{x: 0.0, y: 0.0, z:0.0}; mesh.vertices.len());
+
+			let mut degenerate = 0;
+			let mut ii = 0;
+			while ii < mesh.indices.len() {
+				let i1 = mesh.indices[ii];
+				let i2 = mesh.indices[ii + 1];
+				let i3 = mesh.indices[ii + 2];
+
+				let v1 = mesh.vertices[i1].pos;
+				let v2 = mesh.vertices[i2].pos;
+				let v3 = mesh.vertices[i3].pos;
+
+				let w1 = mesh.vertices[i1].tex.unwrap();
+				let w2 = mesh.vertices[i2].tex.unwrap();
+				let w3 = mesh.vertices[i3].tex.unwrap();
+
+				let x1 = v2.x - v1.x;
+				let x2 = v3.x - v1.x;
+				let y1 = v2.y - v1.y;
+				let y2 = v3.y - v1.y;
+				let z1 = v2.z - v1.z;
+				let z2 = v3.z - v1.z;
+
+				let s1 = w2.x - w1.x;
+				let s2 = w3.x - w1.x;
+				let t1 = w2.y - w1.y;
+				let t2 = w3.y - w1.y;
+
+				//zero area in UV space gives no direction, leave it to the other triangles
+				let det = s1 * t2 - s2 * t1;
+				if det.abs() < 1e-12 || !det.is_finite() {
+					degenerate += 1;
+					ii += 3;
+					continue;
+				}
+				let r = 1.0 / det;
+				let sdir = Vertex{
+					x: (t2 * x1 - t1 * x2) * r,
+					y: (t2 * y1 - t1 * y2) * r,
+					z: (t2 * z1 - t1 * z2) * r,
+				};
+
+				addmut(&mut tan1[i1], sdir);
+				addmut(&mut tan1[i2], sdir);
+				addmut(&mut tan1[i3], sdir);
+
+				let tdir = Vertex{
+					x: (s1 * x2 - s2 * x1) * r,
+					y: (s1 * y2 - s2 * y1) * r,
+                	z: (s1 * z2 - s2 * z1) * r,
+				};
+
+				addmut(&mut tan2[i1], tdir);
+				addmut(&mut tan2[i2], tdir);
+				addmut(&mut tan2[i3], tdir);
+
+				ii += 3;
+			}
+
+			for a in 0..mesh.vertices.len()	{
+				let n = mesh.vertices[a].normal.unwrap();
+				let t = tan1[a];
+
+				// Gram-Schmidt orthogonalize
+				let t = sub(t,mul(n, dot(n, t)));
+
+				//only touched by degenerate triangles, any tangent will do
+				if lenght(t) <= 1e-12 {
+					mesh.vertices[a].tangent = Some(any_orthogonal(n));
+					mesh.vertices[a].tangent_handedness = 1.0;
+					continue;
+				}
+				mesh.vertices[a].tangent = Some(normalize(t));
+
+				// Calculate handedness
+				mesh.vertices[a].tangent_handedness = if dot(cross(n, t), tan2[a]) < 0.0 {
+					-1.0
+				}
+				else {
+					1.0
+				}
+			}
+
+			//the mirrored sides keep their own
+			if let (Some(seams), Some(SeamSmoothing::NormalsAndTangents)) = (&seams, options.smooth_seams) {
+				let tangents: Vec<Normal> = (0..mesh.vertices.len()).map(|a| {
+					let v = &mesh.vertices[a];
+					let n = v.normal.unwrap();
+					let mut t = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+					for other in seams[a].iter().map(|&b| &mesh.vertices[b]).filter(|other| other.tangent_handedness == v.tangent_handedness) {
+						addmut(&mut t, other.tangent.unwrap());
+					}
+					let t = sub(t, mul(n, dot(n, t)));
+					if lenght(t) <= 1e-12 { v.tangent.unwrap() } else { normalize(t) }
+				}).collect();
+				for (v, t) in mesh.vertices.iter_mut().zip(tangents) {
+					v.tangent = Some(t);
+				}
+			}
+
+			if degenerate > 0 {
+				mesh.warnings.push(Warning::new(WarningKind::DegenerateUvs,
+					format!("{}: {} triangles have degenerate UVs, their tangents are arbitrary", obj.name, degenerate)));
+			}
+
+			for v in &mut mesh.vertices {
+				if format.bitangent.is_some() {
+					let n = v.normal.unwrap();
+					v.bitangent = Some(mul(cross(n, v.tangent.unwrap()), v.tangent_handedness));
+				}
+				if format.tangent.is_none() {
+					v.tangent = None;
+				}
+			}
+		}
+
+		mesh
+	}
+
+	/// For each vertex, the ones at the same position whose normals are at most `max_angle` degrees from its own
+	fn seam_duplicates(&self, max_angle: f64) -> Vec<Vec<usize>> {
+		let mut at: HashMap<[u64; 3], Vec<usize>> = HashMap::new();
+		for (idx, v) in self.vertices.iter().enumerate() {
+			at.entry([v.pos.x.to_bits(), v.pos.y.to_bits(), v.pos.z.to_bits()]).or_default().push(idx);
+		}
+		let min_cos = max_angle.to_radians().cos();
+		self.vertices.iter().map(|v| {
+			let n = v.normal.unwrap();
+			at[&[v.pos.x.to_bits(), v.pos.y.to_bits(), v.pos.z.to_bits()]].iter()
+				.copied()
+				.filter(|&b| dot(n, self.vertices[b].normal.unwrap()) >= min_cos)
+				.collect()
+		}).collect()
+	}
+
+	/// `target` is the object matching `base` in the morph target obj file
+	pub fn add_morph_target(&mut self, name: &str, base: &Object, target: &Object) {
+		let deltas = morph::compute_deltas(name, base, target, &self.map, self.format.normal.is_some());
+		self.morphs.push(deltas);
+	}
+
+	/// How far the positions move at most when they are stored as 32 bit floats
+	pub fn f32_rounding(&self) -> f64 {
+		self.vertices.iter()
+			.flat_map(|v| [v.pos.x, v.pos.y, v.pos.z])
+			.map(|c| (c - c as f32 as f64).abs())
+			.fold(0.0, flt_max)
+	}
+
+	/// Moves all the positions by `offset`
+	pub fn translate(&mut self, offset: Vertex) {
+		for v in &mut self.vertices {
+			addmut(&mut v.pos, offset);
+		}
+		addmut(&mut self.min, offset);
+		addmut(&mut self.max, offset);
+	}
+
+	/// Merges the vertices that are stored the same and move the same in every morph target, like the ones
+	/// that different obj objects repeat
+	pub fn weld(&mut self) {
+		//the bytes of each vertex, followed by its delta in each target
+		let mut keys: Vec<Vec<u8>> = self.vertices.iter().map(|v| {
+			let mut bytes = vec![];
+			v.write_to(&mut bytes, self.format.uv_format);
+			bytes
+		}).collect();
+		for target in &self.morphs {
+			let mut moved: Vec<Option<&MorphDelta>> = vec![None; self.vertices.len()];
+			for d in &target.deltas {
+				moved[d.vertex as usize] = Some(d);
+			}
+			for (key, delta) in keys.iter_mut().zip(moved) {
+				key.push(delta.is_some() as u8);
+				if let Some(d) = delta {
+					let normal = d.normal.unwrap_or(Vertex { x: 0.0, y: 0.0, z: 0.0 });
+					for c in [d.position.x, d.position.y, d.position.z, normal.x, normal.y, normal.z] {
+						key.extend_from_slice(&c.to_le_bytes());
+					}
+				}
+			}
+		}
+
+		//key => index of the vertex once welded
+		let mut unique: HashMap<Vec<u8>, usize> = HashMap::new();
+		let mut remap = Vec::with_capacity(self.vertices.len());
+		let mut vertices = vec![];
+		for (v, key) in self.vertices.drain(..).zip(keys) {
+			let idx = *unique.entry(key).or_insert_with(|| {
+				vertices.push(v);
+				vertices.len() - 1
+			});
+			remap.push(idx);
+		}
+		self.vertices = vertices;
+
+		for idx in &mut self.indices {
+			*idx = remap[*idx];
+		}
+		for idx in self.map.values_mut() {
+			*idx = remap[*idx];
+		}
+		for target in &mut self.morphs {
+			for d in &mut target.deltas {
+				d.vertex = remap[d.vertex as usize] as u32;
+			}
+			//the welded vertices have the same deltas
+			target.deltas.sort_by_key(|d| d.vertex);
+			target.deltas.dedup_by_key(|d| d.vertex);
+		}
+	}
+
+	fn create_vertex(&mut self, vtni: VTNIndex, corner: Corner, src: &VertexSource, format: &VertexFieldOffsets) -> usize {
+		let idx = self.vertices.len();
+
+		let mut v = GPUVertex::from_vtni_and_source(vtni, src, format);
+		v.tex1 = corner.lightmap.map(|(_, uv)| uv);
+		v.material_id = corner.material_id;
+		v.origin.primitive = corner.primitive;
+		if let Some(diffuse) = corner.material_color {
+			let color = v.color.unwrap_or([1.0; 4]);
+			v.color = Some([color[0] * diffuse[0], color[1] * diffuse[1], color[2] * diffuse[2], color[3]]);
+		}
+		if src.color_space == ColorSpace::Linear {
+			//the alpha isn't a color
+			v.color = v.color.map(|c| [layout::srgb_to_linear(c[0]), layout::srgb_to_linear(c[1]), layout::srgb_to_linear(c[2]), c[3]]);
+		}
+
+		self.min = vert_min(self.min, v.pos);
+		self.max = vert_max(self.max, v.pos);
+
+		self.vertices.push( v );
+
+		idx
+	}
+
+	/// Ends a range of indices started at `first_index`, merging it with the previous one if it has the same material
+	fn add_submesh(&mut self, material: &Option<String>, first_index: usize) {
+		let index_count = self.indices.len() - first_index;
+		if index_count == 0 {
+			return;
+		}
+		match self.submeshes.last_mut() {
+			Some(last) if last.material == *material => last.index_count += index_count,
+			_ => self.submeshes.push(Submesh {
+				material: material.clone(),
+				first_index,
+				index_count,
+			}),
+		}
+	}
+
+	fn add_index(&mut self, vtni: VTNIndex, corner: Corner, src: &VertexSource, format: &VertexFieldOffsets) {
+		let material = if corner.material_id.is_some() || corner.material_color.is_some() { corner.material } else { 0 };
+		let key = (vtni, corner.lightmap.map_or(0, |(chart, _)| chart), material, corner.mirrored);
+		if let Some(idx) = self.map.get(&key) {
+			self.indices.push(*idx);
+			return;
+		}
+
+		let idx = self.create_vertex(vtni, corner, src, format);
+		self.map.insert(key, idx);
+		self.indices.push(idx);
+	}
+
+	/// Reorders the primitives so that each material is a single submesh, in the order the materials are first
+	/// used, keeping the order of the primitives of each material
+	pub fn sort_by_material(&mut self) {
+		let mut materials: Vec<&Option<String>> = vec![];
+		for submesh in &self.submeshes {
+			if !materials.contains(&&submesh.material) {
+				materials.push(&submesh.material);
+			}
+		}
+
+		let mut indices = Vec::with_capacity(self.indices.len());
+		let mut submeshes = vec![];
+		for material in materials {
+			let first_index = indices.len();
+			for submesh in self.submeshes.iter().filter(|s| s.material == *material) {
+				indices.extend_from_slice(&self.indices[submesh.first_index..submesh.first_index + submesh.index_count]);
+			}
+			submeshes.push(Submesh {
+				material: material.clone(),
+				first_index,
+				index_count: indices.len() - first_index,
+			});
+		}
+		self.indices = indices;
+		self.submeshes = submeshes;
+	}
+
+	/// Splits the primitives into meshes of at most `max_vertices` vertices, in order, duplicating the shared vertices
+	/// on the borders. The parts can't have morph targets added
+	pub fn split(&self, max_vertices: usize) -> Vec<Mesh> {
+		let primitive_size = self.primitive_size();
+		assert!(max_vertices >= primitive_size, "Can't split a mesh into parts of {} vertices", max_vertices);
+
+		let mut parts = vec![];
+		//vertex of this mesh => vertex of the current part
+		let mut remap: HashMap<usize, usize> = HashMap::new();
+		let mut part = self.empty_part();
+		let mut first_index = 0;
+		let mut submeshes = self.submeshes.iter().peekable();
+
+		for (i, primitive) in self.indices.chunks(primitive_size).enumerate() {
+			//the submeshes are cut where they cross the parts
+			while let Some(submesh) = submeshes.next_if(|s| s.first_index + s.index_count <= i * primitive_size) {
+				part.add_submesh(&submesh.material, first_index);
+				first_index = part.indices.len();
+			}
+
+			let new_vertices = primitive.iter().filter(|idx| !remap.contains_key(idx)).count();
+			if part.vertices.len() + new_vertices > max_vertices {
+				if let Some(submesh) = submeshes.peek() {
+					part.add_submesh(&submesh.material, first_index);
+				}
+				self.finish_part(&mut part, &remap);
+				parts.push(std::mem::replace(&mut part, self.empty_part()));
+				remap.clear();
+				first_index = 0;
+			}
+
+			for &idx in primitive {
+				let new_idx = *remap.entry(idx).or_insert_with(|| {
+					let v = self.vertices[idx].clone();
+					part.min = vert_min(part.min, v.pos);
+					part.max = vert_max(part.max, v.pos);
+					part.vertices.push(v);
+					part.vertices.len() - 1
+				});
+				part.indices.push(new_idx);
+			}
+		}
+		if let Some(submesh) = submeshes.next() {
+			part.add_submesh(&submesh.material, first_index);
+		}
+		self.finish_part(&mut part, &remap);
+		parts.push(part);
+		parts
+	}
+
+	/// Splits the mesh into one for each material, with the triangles using it in order, duplicating the vertices
+	/// shared across materials
+	pub fn split_by_material(&self) -> Vec<Mesh> {
+		let mut materials: Vec<&Option<String>> = vec![];
+		for submesh in &self.submeshes {
+			if !materials.contains(&&submesh.material) {
+				materials.push(&submesh.material);
+			}
+		}
+
+		let primitive_size = self.primitive_size();
+		materials.into_iter().map(|material| {
+			let primitives: Vec<usize> = self.submeshes.iter()
+				.filter(|s| s.material == *material)
+				.flat_map(|s| s.first_index / primitive_size..(s.first_index + s.index_count) / primitive_size)
+				.collect();
+			self.extract_part(&primitives)
+		}).collect()
+	}
+
+	/// Splits the mesh into the cells of a grid of `cell_size`, each with the primitives whose center is in it,
+	/// sorted by cell
+	pub fn split_by_cell(&self, cell_size: f64) -> Vec<([i64; 3], Mesh)> {
+		let primitive_size = self.primitive_size();
+		let mut cells: BTreeMap<[i64; 3], Vec<usize>> = BTreeMap::new();
+		for (i, primitive) in self.indices.chunks(primitive_size).enumerate() {
+			let mut center = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+			for &idx in primitive {
+				addmut(&mut center, mul(self.vertices[idx].pos, 1.0 / primitive_size as f64));
+			}
+			let cell = [center.x, center.y, center.z].map(|c| (c / cell_size).floor() as i64);
+			cells.entry(cell).or_default().push(i);
+		}
+
+		cells.into_iter().map(|(cell, primitives)| (cell, self.extract_part(&primitives))).collect()
+	}
+
+	/// Splits the mesh into its connected pieces, joined through the positions their vertices share, in the
+	/// order of their first primitives
+	pub fn split_by_component(&self) -> Vec<Mesh> {
+		let mut parents: Vec<usize> = (0..self.vertices.len()).collect();
+		let mut at_position: HashMap<[u64; 3], usize> = HashMap::new();
+		for (i, v) in self.vertices.iter().enumerate() {
+			let first = *at_position.entry([v.pos.x.to_bits(), v.pos.y.to_bits(), v.pos.z.to_bits()]).or_insert(i);
+			union(&mut parents, i, first);
+		}
+		let primitive_size = self.primitive_size();
+		for primitive in self.indices.chunks(primitive_size) {
+			for pair in primitive.windows(2) {
+				union(&mut parents, pair[0], pair[1]);
+			}
+		}
+
+		let mut numbers: HashMap<usize, usize> = HashMap::new();
+		let mut components: Vec<Vec<usize>> = vec![];
+		for (i, primitive) in self.indices.chunks(primitive_size).enumerate() {
+			let root = find(&mut parents, primitive[0]);
+			let c = *numbers.entry(root).or_insert_with(|| {
+				components.push(vec![]);
+				components.len() - 1
+			});
+			components[c].push(i);
+		}
+		components.iter().map(|primitives| self.extract_part(primitives)).collect()
+	}
+
+	/// Finds a plane across an axis, through the center of the bounds, that the triangles are symmetric about
+	/// with all their attributes, and returns it with the half of the mesh on the positive side. Meshes with
+	/// triangles lying on the plane, or with morph targets, are never split
+	pub fn mirror_half(&self) -> Option<(MirrorPlane, Mesh)> {
+		if self.topology != Topology::TriangleList || !self.morphs.is_empty() || self.indices.is_empty() {
+			return None;
+		}
+		//a millionth of the size, for the positions that don't mirror exactly as floats
+		let cell = lenght(sub(self.max, self.min)) * 1e-6;
+		if cell == 0.0 {
+			return None;
+		}
+		let key = |v: &GPUVertex| {
+			let mut bytes = vec![];
+			v.write_to(&mut bytes, self.format.uv_format);
+			for c in [v.pos.x, v.pos.y, v.pos.z] {
+				bytes.extend_from_slice(&((c / cell).round() as i64).to_le_bytes());
+			}
+			//without the position as f32
+			bytes.split_off(12)
+		};
+		let vertices: HashMap<Vec<u8>, usize> = self.vertices.iter().enumerate().map(|(i, v)| (key(v), i)).collect();
+		//turned to start with the lowest index, keeping the winding
+		let canonical = |t: [usize; 3]| {
+			let first = (0..3).min_by_key(|&i| t[i]).unwrap();
+			[t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+		};
+		let triangles: Vec<[usize; 3]> = self.indices.chunks(3).map(|t| [t[0], t[1], t[2]]).collect();
+		let unique: HashSet<[usize; 3]> = triangles.iter().map(|&t| canonical(t)).collect();
+
+		let center = mul(Vertex { x: self.min.x + self.max.x, y: self.min.y + self.max.y, z: self.min.z + self.max.z }, 0.5);
+		(0..3).find_map(|axis| {
+			let plane = MirrorPlane { axis, offset: [center.x, center.y, center.z][axis] };
+			let mirror: Option<Vec<usize>> = self.vertices.iter().map(|v| vertices.get(&key(&v.mirrored(plane))).copied()).collect();
+			let mirror = mirror?;
+			let mut kept = vec![];
+			for (i, &[a, b, c]) in triangles.iter().enumerate() {
+				//winding the other way once mirrored
+				if !unique.contains(&canonical([mirror[a], mirror[c], mirror[b]])) {
+					return None;
+				}
+				let side = [a, b, c].iter().map(|&v| dot(self.vertices[v].pos, plane.axis_vector())).sum::<f64>() / 3.0 - plane.offset;
+				if side.abs() <= cell {
+					return None;
+				}
+				if side > 0.0 {
+					kept.push(i);
+				}
+			}
+			Some((plane, self.extract_part(&kept)))
+		})
+	}
+
+	fn primitive_size(&self) -> usize {
+		match self.topology {
+			Topology::TriangleList => 3,
+			Topology::LineList => 2,
+			Topology::PointList => 1,
+		}
+	}
+
+	/// A mesh with some of the primitives, in the given order and keeping their submeshes
+	fn extract_part(&self, primitives: &[usize]) -> Mesh {
+		let primitive_size = self.primitive_size();
+		let mut part = self.empty_part();
+		//vertex of this mesh => vertex of the part
+		let mut remap: HashMap<usize, usize> = HashMap::new();
+		let mut current: Option<usize> = None;
+		let mut first_index = 0;
+
+		for &p in primitives {
+			let start = p * primitive_size;
+			let submesh = self.submeshes.partition_point(|s| s.first_index <= start).checked_sub(1);
+			if submesh != current {
+				if let Some(c) = current {
+					part.add_submesh(&self.submeshes[c].material, first_index);
+				}
+				first_index = part.indices.len();
+				current = submesh;
+			}
+
+			for &idx in &self.indices[start..start + primitive_size] {
+				let new_idx = *remap.entry(idx).or_insert_with(|| {
+					let v = self.vertices[idx].clone();
+					part.min = vert_min(part.min, v.pos);
+					part.max = vert_max(part.max, v.pos);
+					part.vertices.push(v);
+					part.vertices.len() - 1
+				});
+				part.indices.push(new_idx);
+			}
+		}
+		if let Some(c) = current {
+			part.add_submesh(&self.submeshes[c].material, first_index);
+		}
+		self.finish_part(&mut part, &remap);
+		part
+	}
+
+	fn empty_part(&self) -> Mesh {
+		Mesh {
+			vertices: Vec::new(),
+			indices: Vec::new(),
+			map: HashMap::new(),
+			format: self.format,
+			topology: self.topology,
+			morphs: Vec::new(),
+			materials: self.materials.clone(),
+			submeshes: Vec::new(),
+			warnings: Vec::new(),
+			min: Vertex{x: f64::MAX, y: f64::MAX, z: f64::MAX },
+			max: Vertex{x: f64::MIN, y: f64::MIN, z: f64::MIN },
+		}
+	}
+
+	/// Keeps the morph deltas of the vertices in the part
+	fn finish_part(&self, part: &mut Mesh, remap: &HashMap<usize, usize>) {
+		part.morphs = self.morphs.iter().map(|target| {
+			let mut deltas: Vec<MorphDelta> = target.deltas.iter()
+				.filter_map(|d| remap.get(&(d.vertex as usize)).map(|&vertex| MorphDelta { vertex: vertex as u32, ..*d }))
+				.collect();
+			deltas.sort_by_key(|d| d.vertex);
+			MorphDeltas {
+				name: target.name.clone(),
+				deltas,
+			}
+		}).collect();
+	}
+
+	/// Reorders the vertices and triangles into a base mesh of `base` of the triangles followed by what the
+	/// vertex splits of the returned refinement add, for the progressive encoding
+	pub fn progressive(&self, base: f64) -> (Mesh, Refinement) {
+		assert!(self.topology == Topology::TriangleList, "Only triangle lists can be stored progressively");
+		let positions: Vec<Vertex> = self.vertices.iter().map(|v| v.pos).collect();
+		let (vertex_order, triangle_order, refinement) = progressive::build(&positions, &self.indices, base);
+
+		let mut part = self.empty_part();
+		let remap: HashMap<usize, usize> = vertex_order.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+		part.vertices = vertex_order.iter().map(|&v| self.vertices[v].clone()).collect();
+		part.indices = triangle_order.iter().flat_map(|&t| &self.indices[t * 3..t * 3 + 3]).map(|v| remap[v]).collect();
+		part.submeshes = self.submeshes.clone();
+		part.min = self.min;
+		part.max = self.max;
+		self.finish_part(&mut part, &remap);
+		(part, refinement)
+	}
+
+	/// The triangles indexing the first vertex stored at each position, ignoring the splits of the other
+	/// attributes, without those that collapse; for drawing shadows and depth only
+	pub fn shadow_indices(&self) -> Vec<usize> {
+		assert!(self.topology == Topology::TriangleList, "Only triangle lists have shadow indices");
+		let mut first: HashMap<[u32; 3], usize> = HashMap::new();
+		let remap: Vec<usize> = self.vertices.iter().enumerate().map(|(i, v)| {
+			let key = [v.pos.x as f32, v.pos.y as f32, v.pos.z as f32].map(f32::to_bits);
+			*first.entry(key).or_insert(i)
+		}).collect();
+
+		self.indices.chunks(3)
+			.map(|t| [remap[t[0]], remap[t[1]], remap[t[2]]])
+			.filter(|t| t[0] != t[1] && t[1] != t[2] && t[2] != t[0])
+			.flatten()
+			.collect()
+	}
+
+	pub fn get_index_size(&self) -> usize {
+		match self.vertices.len() {
+		    n if n <= 0xff => 1,
+		    n if n <= 0xffff => 2,
+		    _ => 4
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::convert::ConvertOptions;
+	use crate::fixtures;
+
+	#[test]
+	fn snorm_10_10_10_2() {
+		let normal = |x, y, z| Normal { x, y, z };
+		assert_eq!(pack_i2_10_10_10(normal(0.0, 0.0, 0.0), 0.0), 0);
+		assert_eq!(pack_i2_10_10_10(normal(1.0, 1.0, 1.0), 1.0), 0x1ff | 0x1ff << 10 | 0x1ff << 20 | 0b01 << 30);
+		//-511 in two's complement, which doesn't spill into the next field
+		assert_eq!(pack_i2_10_10_10(normal(-1.0, -1.0, -1.0), -1.0), 0x201 | 0x201 << 10 | 0x201 << 20 | 0b11 << 30);
+		assert_eq!(pack_i2_10_10_10(normal(-1.0, 0.0, 1.0), 1.0), 0x201 | 0x1ff << 20 | 0b01 << 30);
+		//to the nearest step
+		assert_eq!(pack_normalized(0.5, 511), 256);
+		assert_eq!(pack_normalized(-0.5, 511), 0x400 - 256);
+	}
+
+	#[test]
+	fn snorm_10_10_10_2_through_the_reader() {
+		let options = ConvertOptions { generate_tangents: true, ..Default::default() };
+		let mesh = fixtures::read(&fixtures::convert_one(fixtures::CUBE, &options));
+		let mut normals = mesh.normals.unwrap();
+		normals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		normals.dedup();
+		assert_eq!(normals, [
+			[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0],
+			[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0],
+		]);
+		for tangent in mesh.tangents.unwrap() {
+			assert!(tangent[3] == 1.0 || tangent[3] == -1.0, "handedness {}", tangent[3]);
+			assert!(tangent[..3].iter().all(|&c| c == -1.0 || c == 0.0 || c == 1.0), "{:?}", tangent);
+		}
+	}
+}
@@ -0,0 +1,146 @@
+//! Uploads `.mesh` files into miniquad buffers, for miniquad and macroquad games.
+//!
+//! miniquad has no half float, normalized or packed vertex formats, so the vertices are expanded by `read_mesh`
+//! and uploaded as floats, except for the bone indices (`Byte4`), the material ids and the `uint32` user attributes
+//! (`Int1`). The attributes are named like in `layout::Attribute`, and the user attributes `user0` to `user3`;
+//! miniquad binds them to the shader inputs in the order of `MiniquadMesh::attributes`. In macroquad, the context
+//! is `get_internal_gl().quad_context`.
+
+use ::miniquad::{
+	BufferId, BufferLayout, BufferSource, BufferType, BufferUsage, Bindings, PrimitiveType, RenderingBackend,
+	TextureId, VertexAttribute, VertexFormat,
+};
+use crate::layout::{Format, MAX_USER_ATTRIBUTES};
+use crate::reader::{read_mesh, MeshData, ReadError, Topology};
+
+const USER_NAMES: [&str; MAX_USER_ATTRIBUTES] = ["user0", "user1", "user2", "user3"];
+
+/// The buffers of a mesh, with what `Pipeline::new` needs to read them
+#[derive(Debug, Clone)]
+pub struct MiniquadMesh {
+	pub vertex_buffer: BufferId,
+	/// u16 indices for the meshes with up to 65536 vertices, u32 otherwise
+	pub index_buffer: BufferId,
+	pub index_count: i32,
+	pub buffer_layout: BufferLayout,
+	pub attributes: Vec<VertexAttribute>,
+	/// For the `PipelineParams`
+	pub primitive_type: PrimitiveType,
+}
+
+impl MiniquadMesh {
+	/// Bindings drawing the mesh with the images
+	pub fn bindings(&self, images: Vec<TextureId>) -> Bindings {
+		Bindings {
+			vertex_buffers: vec![self.vertex_buffer],
+			index_buffer: self.index_buffer,
+			images,
+		}
+	}
+}
+
+/// The columns of the vertices, in native endianness for the GPU
+#[derive(Default)]
+struct Vertices {
+	attributes: Vec<VertexAttribute>,
+	columns: Vec<Vec<u8>>,
+}
+
+impl Vertices {
+	fn add(&mut self, name: &'static str, format: VertexFormat, column: Vec<u8>) {
+		self.attributes.push(VertexAttribute::new(name, format));
+		self.columns.push(column);
+	}
+
+	fn add_floats<const N: usize>(&mut self, name: &'static str, format: VertexFormat, values: &Option<Vec<[f32; N]>>) {
+		if let Some(values) = values {
+			self.add(name, format, values.iter().flatten().flat_map(|c| c.to_ne_bytes()).collect());
+		}
+	}
+
+	fn interleave(&self, vertex_count: usize) -> (Vec<u8>, usize) {
+		let sizes: Vec<usize> = self.attributes.iter().map(|a| a.format.size_bytes() as usize).collect();
+		let stride = sizes.iter().sum();
+		let mut out = Vec::with_capacity(vertex_count * stride);
+		for v in 0..vertex_count {
+			for (column, size) in self.columns.iter().zip(&sizes) {
+				out.extend_from_slice(&column[v * size..(v + 1) * size]);
+			}
+		}
+		(out, stride)
+	}
+}
+
+/// Uploads a mesh read with `read_mesh`, unfolding its mirrored half
+pub fn upload(ctx: &mut dyn RenderingBackend, mut data: MeshData) -> MiniquadMesh {
+	data.unfold_mirror();
+	let vertex_count = data.positions.len();
+
+	let mut vertices = Vertices::default();
+	vertices.add_floats("position", VertexFormat::Float3, &Some(data.positions));
+	vertices.add_floats("color", VertexFormat::Float4, &data.colors);
+	vertices.add_floats("normal", VertexFormat::Float3, &data.normals);
+	vertices.add_floats("tangent", VertexFormat::Float4, &data.tangents);
+	vertices.add_floats("bitangent", VertexFormat::Float3, &data.bitangents);
+	vertices.add_floats("tex0", VertexFormat::Float2, &data.tex0);
+	vertices.add_floats("tex1", VertexFormat::Float2, &data.tex1);
+	if let Some(bone_indices) = data.bone_indices {
+		vertices.add("bone_indices", VertexFormat::Byte4, bone_indices.concat());
+	}
+	vertices.add_floats("bone_weights", VertexFormat::Float4, &data.bone_weights);
+	if let Some(curvature) = data.curvature {
+		vertices.add("curvature", VertexFormat::Float1, curvature.iter().flat_map(|c| c.to_ne_bytes()).collect());
+	}
+	if let Some(material_ids) = data.material_ids {
+		vertices.add("material_id", VertexFormat::Int1, material_ids.iter().flat_map(|m| m.to_ne_bytes()).collect());
+	}
+
+	for ((attr, bytes), name) in data.header.user_attributes.iter().zip(&data.user_data).zip(USER_NAMES) {
+		match attr.format {
+			Format::Uint8x4 => vertices.add(name, VertexFormat::Byte4, bytes.clone()),
+			Format::Unorm8x4 => vertices.add(name, VertexFormat::Float4, bytes.iter().flat_map(|&b| (b as f32 / 255.0).to_ne_bytes()).collect()),
+			Format::Uint32 => vertices.add(name, VertexFormat::Int1, bytes.chunks_exact(4).flat_map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]).to_ne_bytes()).collect()),
+			format => {
+				let format = match format.components() {
+					1 => VertexFormat::Float1,
+					2 => VertexFormat::Float2,
+					3 => VertexFormat::Float3,
+					_ => VertexFormat::Float4,
+				};
+				vertices.add(name, format, bytes.chunks_exact(4).flat_map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).to_ne_bytes()).collect());
+			},
+		}
+	}
+
+	let (bytes, stride) = vertices.interleave(vertex_count);
+	let vertex_buffer = ctx.new_buffer(BufferType::VertexBuffer, BufferUsage::Immutable, BufferSource::slice(&bytes));
+
+	let index_buffer = if vertex_count <= 0x10000 {
+		let indices: Vec<u16> = data.indices.iter().map(|&i| i as u16).collect();
+		ctx.new_buffer(BufferType::IndexBuffer, BufferUsage::Immutable, BufferSource::slice(&indices))
+	}
+	else {
+		ctx.new_buffer(BufferType::IndexBuffer, BufferUsage::Immutable, BufferSource::slice(&data.indices))
+	};
+
+	MiniquadMesh {
+		vertex_buffer,
+		index_buffer,
+		index_count: data.indices.len() as i32,
+		buffer_layout: BufferLayout {
+			stride: stride as i32,
+			..Default::default()
+		},
+		attributes: vertices.attributes,
+		primitive_type: match data.header.topology {
+			Topology::TriangleList => PrimitiveType::Triangles,
+			Topology::LineList => PrimitiveType::Lines,
+			Topology::PointList => PrimitiveType::Points,
+		},
+	}
+}
+
+/// Reads a `.mesh` file and uploads it
+pub fn load(ctx: &mut dyn RenderingBackend, bytes: &[u8]) -> Result<MiniquadMesh, ReadError> {
+	Ok(upload(ctx, read_mesh(&mut &bytes[..])?))
+}
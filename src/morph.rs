@@ -0,0 +1,91 @@
+//! Morph targets, from obj files sharing the topology of the base mesh.
+//!
+//! Each target is stored as a sparse list of position (and normal) deltas for the output vertices it moves.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::{ObjSet, Object, Vertex, Normal};
+use std::collections::HashMap;
+use crate::math::sub;
+use crate::mesh::VertexKey;
+
+/// Deltas smaller than this are considered noise from the export and skipped
+const EPSILON: f64 = 1e-6;
+
+#[derive(Debug, Clone)]
+pub struct MorphTarget {
+	pub name: String,
+	/// Same objects as the base obj file, in the same order
+	pub set: ObjSet,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphDelta {
+	/// Index of the output vertex
+	pub vertex: u32,
+	pub position: Vertex,
+	pub normal: Option<Normal>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MorphDeltas {
+	pub name: String,
+	pub deltas: Vec<MorphDelta>,
+}
+
+fn is_zero(v: Vertex) -> bool {
+	v.x.abs() < EPSILON && v.y.abs() < EPSILON && v.z.abs() < EPSILON
+}
+
+/// Computes the deltas of `target` for every vertex in `map`, which maps the obj indices to the output vertices
+pub fn compute_deltas(name: &str, base: &Object, target: &Object, map: &HashMap<VertexKey, usize>, with_normals: bool) -> MorphDeltas {
+	if target.vertices.len() != base.vertices.len() {
+		panic!("Morph target {} has {} vertices, but the base mesh has {}", name, target.vertices.len(), base.vertices.len());
+	}
+	if with_normals && target.normals.len() != base.normals.len() {
+		panic!("Morph target {} has {} normals, but the base mesh has {}", name, target.normals.len(), base.normals.len());
+	}
+
+	let mut deltas: Vec<MorphDelta> = map.iter()
+		.map(|(&((pos_idx, _, norm_idx), _, _, _), &vertex)| MorphDelta {
+			vertex: vertex as u32,
+			position: sub(target.vertices[pos_idx], base.vertices[pos_idx]),
+			normal: match norm_idx {
+				Some(idx) if with_normals => Some(sub(target.normals[idx], base.normals[idx])),
+				_ => None,
+			},
+		})
+		.filter(|d| !is_zero(d.position) || !d.normal.is_none_or(is_zero))
+		.collect();
+
+	deltas.sort_by_key(|d| d.vertex);
+
+	MorphDeltas {
+		name: name.to_owned(),
+		deltas,
+	}
+}
+
+fn write_vec3(data: &mut Vec<u8>, v: Vertex) {
+	data.write_f32::<LittleEndian>(v.x as f32).unwrap();
+	data.write_f32::<LittleEndian>(v.y as f32).unwrap();
+	data.write_f32::<LittleEndian>(v.z as f32).unwrap();
+}
+
+/// Writes the morph section that follows the indices
+pub fn write_targets(data: &mut Vec<u8>, targets: &[MorphDeltas], with_normals: bool) {
+	data.write_u32::<LittleEndian>(targets.len() as u32).unwrap();
+
+	for target in targets {
+		data.write_u16::<LittleEndian>(target.name.len() as u16).unwrap();
+		data.extend_from_slice(target.name.as_bytes());
+
+		data.write_u32::<LittleEndian>(target.deltas.len() as u32).unwrap();
+		for delta in &target.deltas {
+			data.write_u32::<LittleEndian>(delta.vertex).unwrap();
+			write_vec3(data, delta.position);
+			if with_normals {
+				write_vec3(data, delta.normal.unwrap_or(Vertex { x: 0.0, y: 0.0, z: 0.0 }));
+			}
+		}
+	}
+}
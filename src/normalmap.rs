@@ -0,0 +1,226 @@
+//! Tangent space normal maps baked from a high-poly version of a model onto the UVs of its low-poly version.
+//!
+//! Each texel covered by a low-poly triangle casts a ray along the interpolated low-poly normal, from both sides
+//! of the surface, and takes the normal of the closest high-poly hit. The tangent basis is the one generated
+//! for the mesh with `-t`, with the bitangent towards +V, so the maps are Y+ like OpenGL expects.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use wavefront_obj::obj::{ObjSet, Vertex};
+use crate::bvh;
+use crate::chunk::Crc32;
+use crate::convert::{merge_objects, ConvertOptions, UvOverflow};
+use crate::manifold;
+use crate::math::*;
+use crate::mesh::Mesh;
+use crate::normals::{self, NormalWeighting};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+	/// Side of the map in texels
+	pub resolution: u32,
+	/// How far from the low-poly surface the high-poly one is looked for, in both directions; by default
+	/// 5% of the diagonal of the low-poly bounds
+	pub max_distance: Option<f64>,
+	/// Texels the islands are grown by, so that filtering doesn't bleed the background in
+	pub padding: u32,
+}
+
+impl Default for BakeSettings {
+	fn default() -> Self {
+		BakeSettings {
+			resolution: 512,
+			max_distance: None,
+			padding: 2,
+		}
+	}
+}
+
+/// RGB8 texels of `side` x `side`, row by row from V = 1 at the top
+#[derive(Debug, Clone)]
+pub struct NormalMap {
+	pub side: u32,
+	pub rgb: Vec<u8>,
+	/// Covered texels whose rays found no high-poly surface, which keep the normal of the low-poly one
+	pub missed: usize,
+}
+
+/// The objects merged into a mesh with a normal on every corner, generated when some are missing
+fn merged_mesh(set: &ObjSet, options: &ConvertOptions) -> Mesh {
+	let mut merged = merge_objects(&set.objects);
+	if manifold::triangles(&merged).iter().flatten().any(|corner| corner.2.is_none()) {
+		merged = normals::recompute_normals(&merged, NormalWeighting::Area, None);
+	}
+	Mesh::from_object(&merged, options, 0)
+}
+
+fn interpolate(values: [Vertex; 3], w: [f64; 3]) -> Vertex {
+	let mut result = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+	for (v, w) in values.iter().zip(w) {
+		addmut(&mut result, mul(*v, w));
+	}
+	result
+}
+
+/// Panics when the low-poly triangles don't all have texture coordinates
+pub fn bake_normal_map(high: &ObjSet, low: &ObjSet, settings: &BakeSettings) -> NormalMap {
+	assert!(settings.resolution > 0, "Can't bake a normal map of 0 texels");
+	let high = merged_mesh(high, &ConvertOptions::default());
+	let low = merged_mesh(low, &ConvertOptions {
+		generate_tangents: true,
+		uv_overflow: UvOverflow::Keep,
+		..Default::default()
+	});
+	if low.vertices.iter().any(|v| v.tangent().is_none()) {
+		panic!("The low-poly triangles need texture coordinates to bake a normal map");
+	}
+	let bvh = bvh::build(&high);
+
+	let side = settings.resolution;
+	let max_distance = settings.max_distance.unwrap_or_else(|| lenght(sub(low.max, low.min)) * 0.05);
+	let mut map = NormalMap {
+		side,
+		rgb: vec![0; (side * side * 3) as usize],
+		missed: 0,
+	};
+	let mut covered = vec![false; (side * side) as usize];
+
+	for triangle in low.indices.chunks(3) {
+		let corners = [0, 1, 2].map(|k| &low.vertices[triangle[k]]);
+		let uv = corners.map(|v| v.tex().unwrap());
+		//texel space, with the rows going down
+		let [a, b, c] = uv.map(|t| (t.x * side as f64, (1.0 - t.y) * side as f64));
+		let area = (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1);
+		if area == 0.0 {
+			continue;
+		}
+
+		let last = side as f64 - 1.0;
+		let x0 = flt_min(flt_min(a.0, b.0), c.0).floor().clamp(0.0, last) as u32;
+		let x1 = flt_max(flt_max(a.0, b.0), c.0).ceil().clamp(0.0, last) as u32;
+		let y0 = flt_min(flt_min(a.1, b.1), c.1).floor().clamp(0.0, last) as u32;
+		let y1 = flt_max(flt_max(a.1, b.1), c.1).ceil().clamp(0.0, last) as u32;
+		let edge = |p: (f64, f64), q: (f64, f64), x: f64, y: f64| ((q.0 - p.0) * (y - p.1) - (x - p.0) * (q.1 - p.1)) / area;
+
+		for y in y0..=y1 {
+			for x in x0..=x1 {
+				let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+				let w = [edge(b, c, px, py), edge(c, a, px, py), edge(a, b, px, py)];
+				if w.iter().any(|&w| w < 0.0) {
+					continue;
+				}
+
+				let position = interpolate(corners.map(|v| v.pos()), w);
+				let n = normalize(interpolate(corners.map(|v| v.normal().unwrap()), w));
+				let (t, handedness) = (interpolate(corners.map(|v| v.tangent().unwrap().0), w), corners[0].tangent().unwrap().1);
+				let t = normalize(sub(t, mul(n, dot(n, t))));
+				let bitangent = mul(cross(n, t), handedness);
+
+				//from outside the surface, keeping the hit closest to it
+				let origin = sub(position, mul(n, -max_distance));
+				let mut closest: Option<(f64, Vertex)> = None;
+				bvh::raycast(&bvh, &high, origin, mul(n, -1.0), 2.0 * max_distance, |hit, distance, weights| {
+					let offset = (distance - max_distance).abs();
+					if closest.is_none_or(|(best, _)| offset < best) {
+						let normals = [0, 1, 2].map(|k| high.vertices[high.indices[hit * 3 + k]].normal().unwrap());
+						closest = Some((offset, interpolate(normals, weights)));
+					}
+				});
+				let normal = match closest {
+					Some((_, normal)) if lenght(normal) > 0.0 => normalize(normal),
+					_ => {
+						map.missed += 1;
+						n
+					},
+				};
+
+				let texel = (y * side + x) as usize;
+				covered[texel] = true;
+				let encoded = [dot(normal, t), dot(normal, bitangent), dot(normal, n)].map(|c| ((c * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8);
+				map.rgb[texel * 3..texel * 3 + 3].copy_from_slice(&encoded);
+			}
+		}
+	}
+
+	dilate(&mut map, &mut covered, settings.padding);
+	//the flat normal everywhere else
+	for (texel, covered) in covered.iter().enumerate() {
+		if !covered {
+			map.rgb[texel * 3..texel * 3 + 3].copy_from_slice(&[128, 128, 255]);
+		}
+	}
+	map
+}
+
+/// Grows the covered texels by `padding`, copying the texels next to them
+fn dilate(map: &mut NormalMap, covered: &mut [bool], padding: u32) {
+	let side = map.side as i64;
+	for _ in 0..padding {
+		let before = covered.to_vec();
+		for y in 0..side {
+			for x in 0..side {
+				let texel = (y * side + x) as usize;
+				if before[texel] {
+					continue;
+				}
+				let neighbor = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+					.map(|&(dx, dy)| (x + dx, y + dy))
+					.filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < side && ny < side)
+					.map(|(nx, ny)| (ny * side + nx) as usize)
+					.find(|&n| before[n]);
+				if let Some(n) = neighbor {
+					map.rgb.copy_within(n * 3..n * 3 + 3, texel * 3);
+					covered[texel] = true;
+				}
+			}
+		}
+	}
+}
+
+fn png_chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+	out.write_u32::<BigEndian>(data.len() as u32).unwrap();
+	out.extend_from_slice(id);
+	out.extend_from_slice(data);
+	let mut crc = Crc32::default();
+	crc.update(id);
+	crc.update(data);
+	out.write_u32::<BigEndian>(crc.value()).unwrap();
+}
+
+/// An RGB8 PNG file, stored without compression
+pub fn write_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+	let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+	let mut header = vec![];
+	header.write_u32::<BigEndian>(width).unwrap();
+	header.write_u32::<BigEndian>(height).unwrap();
+	//8 bits, RGB, deflate, adaptive filtering, no interlacing
+	header.extend([8, 2, 0, 0, 0]);
+	png_chunk(&mut out, b"IHDR", &header);
+
+	//each row starts with its filter, none
+	let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+	for row in rgb.chunks((width * 3) as usize) {
+		raw.push(0);
+		raw.extend_from_slice(row);
+	}
+
+	//a zlib stream of stored deflate blocks
+	let mut zlib = vec![0x78, 0x01];
+	let blocks: Vec<&[u8]> = raw.chunks(u16::MAX as usize).collect();
+	for (i, block) in blocks.iter().enumerate() {
+		zlib.push((i + 1 == blocks.len()) as u8);
+		zlib.extend((block.len() as u16).to_le_bytes());
+		zlib.extend((!(block.len() as u16)).to_le_bytes());
+		zlib.extend_from_slice(block);
+	}
+	let (mut a, mut b) = (1u32, 0u32);
+	for &byte in &raw {
+		a = (a + byte as u32) % 65521;
+		b = (b + a) % 65521;
+	}
+	zlib.write_u32::<BigEndian>((b << 16) | a).unwrap();
+	png_chunk(&mut out, b"IDAT", &zlib);
+
+	png_chunk(&mut out, b"IEND", &[]);
+	out
+}
@@ -0,0 +1,192 @@
+//! Normals generated from the positions, in place of the ones of the obj file.
+
+use std::collections::HashMap;
+use wavefront_obj::obj::{Normal, Object, Shape, Vertex};
+use crate::math::*;
+
+/// How much each triangle around a position weighs in its normal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+	/// By area, so that small bevels don't bend the large faces
+	Area,
+	/// By the angle of the corner, which doesn't depend on how the faces are triangulated
+	Angle,
+	/// By both
+	AreaAngle,
+}
+
+impl NormalWeighting {
+	pub fn from_name(name: &str) -> Option<NormalWeighting> {
+		match name {
+			"area" => Some(NormalWeighting::Area),
+			"angle" => Some(NormalWeighting::Angle),
+			"areaangle" => Some(NormalWeighting::AreaAngle),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			NormalWeighting::Area => "area",
+			NormalWeighting::Angle => "angle",
+			NormalWeighting::AreaAngle => "areaangle",
+		}
+	}
+}
+
+/// What is averaged between the vertices that only the texture coordinates or other attributes split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeamSmoothing {
+	Normals,
+	/// The normals, and the tangents of the sides with the same handedness
+	NormalsAndTangents,
+}
+
+impl SeamSmoothing {
+	pub fn from_name(name: &str) -> Option<SeamSmoothing> {
+		match name {
+			"normals" => Some(SeamSmoothing::Normals),
+			"tangents" => Some(SeamSmoothing::NormalsAndTangents),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			SeamSmoothing::Normals => "normals",
+			SeamSmoothing::NormalsAndTangents => "tangents",
+		}
+	}
+}
+
+fn angle(a: Vertex, b: Vertex) -> f64 {
+	f64::atan2(lenght(cross(a, b)), dot(a, b))
+}
+
+/// The unnormalized normal of each triangle of `obj`, in order, and what it adds to the normal of each corner
+fn triangle_weights(obj: &Object, weighting: NormalWeighting) -> Vec<([usize; 3], Vertex, [Vertex; 3])> {
+	obj.geometry.iter()
+		.flat_map(|geo| &geo.shapes)
+		.filter_map(|shape| match *shape {
+			Shape::Triangle(v1, v2, v3) => Some([v1.0, v2.0, v3.0]),
+			_ => None,
+		})
+		.map(|corners| {
+			let p = corners.map(|idx| obj.vertices[idx]);
+			//twice the area
+			let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+			let weights = [0, 1, 2].map(|i| {
+				if lenght(n) == 0.0 {
+					return n;
+				}
+				let corner_angle = angle(sub(p[(i + 1) % 3], p[i]), sub(p[(i + 2) % 3], p[i]));
+				match weighting {
+					NormalWeighting::Area => n,
+					NormalWeighting::Angle => mul(normalize(n), corner_angle),
+					NormalWeighting::AreaAngle => mul(n, corner_angle),
+				}
+			});
+			(corners, n, weights)
+		})
+		.collect()
+}
+
+fn normalize_or_z(n: Vertex) -> Vertex {
+	if lenght(n) > 0.0 { normalize(n) } else { Vertex { x: 0.0, y: 0.0, z: 1.0 } }
+}
+
+/// The normal of every position, shared by the triangles around it; +Z for the ones without any
+pub fn vertex_normals(obj: &Object, weighting: NormalWeighting) -> Vec<Normal> {
+	let mut normals = vec![Vertex { x: 0.0, y: 0.0, z: 0.0 }; obj.vertices.len()];
+	for (corners, _, weights) in triangle_weights(obj, weighting) {
+		for (idx, w) in corners.into_iter().zip(weights) {
+			addmut(&mut normals[idx], w);
+		}
+	}
+	normals.into_iter().map(normalize_or_z).collect()
+}
+
+/// Returns a copy of `obj` where every triangle corner has a normal generated from the positions, whether it
+/// had a normal or not. Without `crease_angle` the normals are shared by all the triangles around a position;
+/// with it, each corner only averages the triangles whose face normals are at most that many degrees from
+/// its own, so the edges sharper than that are hard
+pub fn recompute_normals(obj: &Object, weighting: NormalWeighting, crease_angle: Option<f64>) -> Object {
+	let mut result = obj.clone();
+	let Some(crease_angle) = crease_angle else {
+		result.normals = vertex_normals(obj, weighting);
+		for geo in &mut result.geometry {
+			for shape in &mut geo.shapes {
+				if let Shape::Triangle(v1, v2, v3) = shape {
+					for corner in [v1, v2, v3] {
+						corner.2 = Some(corner.0);
+					}
+				}
+			}
+		}
+		return result;
+	};
+
+	let triangles = triangle_weights(obj, weighting);
+	//position => the triangles around it, and which of their corners it is
+	let mut around: Vec<Vec<(usize, usize)>> = vec![vec![]; obj.vertices.len()];
+	for (t, (corners, _, _)) in triangles.iter().enumerate() {
+		for (i, &idx) in corners.iter().enumerate() {
+			around[idx].push((t, i));
+		}
+	}
+	let min_cos = crease_angle.to_radians().cos();
+
+	//the corners that end up with the same normal at the same position share it
+	result.normals.clear();
+	let mut unique: HashMap<(usize, [u64; 3]), usize> = HashMap::new();
+	let mut corner_normals = vec![];
+	for (corners, n, _) in &triangles {
+		corner_normals.push(corners.map(|idx| {
+			let mut sum = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+			for &(u, j) in &around[idx] {
+				let (_, other, weights) = &triangles[u];
+				//the degenerate triangles take the normals of the others, and add nothing
+				if lenght(*n) == 0.0 || lenght(*other) == 0.0 || dot(normalize(*n), normalize(*other)) >= min_cos {
+					addmut(&mut sum, weights[j]);
+				}
+			}
+			let normal = normalize_or_z(sum);
+			*unique.entry((idx, [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()])).or_insert_with(|| {
+				result.normals.push(normal);
+				result.normals.len() - 1
+			})
+		}));
+	}
+
+	let mut corner_normals = corner_normals.into_iter();
+	for geo in &mut result.geometry {
+		for shape in &mut geo.shapes {
+			if let Shape::Triangle(v1, v2, v3) = shape {
+				let normals = corner_normals.next().unwrap();
+				for (corner, n) in [v1, v2, v3].into_iter().zip(normals) {
+					corner.2 = Some(n);
+				}
+			}
+		}
+	}
+	result
+}
+
+/// Returns a copy of `obj` where every triangle has its own face normal, so no vertex is shared by two
+/// triangles, even when they are in the same plane
+pub fn flat_normals(obj: &Object) -> Object {
+	let mut result = obj.clone();
+	result.normals.clear();
+	for geo in &mut result.geometry {
+		for shape in &mut geo.shapes {
+			if let Shape::Triangle(v1, v2, v3) = shape {
+				let p = [v1.0, v2.0, v3.0].map(|idx| obj.vertices[idx]);
+				result.normals.push(normalize_or_z(cross(sub(p[1], p[0]), sub(p[2], p[0]))));
+				for corner in [v1, v2, v3] {
+					corner.2 = Some(result.normals.len() - 1);
+				}
+			}
+		}
+	}
+	result
+}
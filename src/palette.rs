@@ -0,0 +1,128 @@
+//! Palettes of the attributes that take few different values, like the normals and colors of voxel and low-poly
+//! meshes: the `PALT` chunk stores each value once, and the vertices the index of theirs.
+
+use std::collections::HashMap;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crate::layout::{Attribute, Format, Palette, UserAttribute, VertexFieldOffsets};
+
+/// The values a palette can hold with Uint16 indices
+pub const MAX_ENTRIES: usize = 1 << 16;
+
+fn write_index(vertex: &mut [u8], index: usize, format: Format) {
+	match format {
+		Format::Uint8 => vertex[0] = index as u8,
+		_ => vertex[..2].copy_from_slice(&(index as u16).to_le_bytes()),
+	}
+}
+
+fn read_index(vertex: &[u8], format: Format) -> usize {
+	match format {
+		Format::Uint8 => vertex[0] as usize,
+		_ => u16::from_le_bytes([vertex[0], vertex[1]]) as usize,
+	}
+}
+
+/// Copies the vertices laid out as `from` into the layout `to`, taking the built-in attributes from `attribute`
+/// and the user attributes after them as they are. Fails with the first vertex `attribute` fails for
+fn relayout(vertices: &[u8], from: &VertexFieldOffsets, to: &VertexFieldOffsets, user_attributes: &[UserAttribute],
+	mut attribute: impl FnMut(usize, Attribute, &[u8], &mut [u8]) -> bool) -> Result<Vec<u8>, usize> {
+	let (from_stride, to_stride) = (from.layout().stride, to.layout().stride);
+	let user_size: usize = user_attributes.iter().map(|a| a.format.size()).sum();
+	let mut out = Vec::with_capacity(vertices.len() / (from_stride + user_size) * (to_stride + user_size));
+	for (i, vertex) in vertices.chunks_exact(from_stride + user_size).enumerate() {
+		let start = out.len();
+		out.resize(start + to_stride, 0);
+		for attr in from.attributes() {
+			let (from_offset, to_offset) = (from.offset(attr).unwrap(), to.offset(attr).unwrap());
+			let source = &vertex[from_offset..from_offset + from.format(attr).size()];
+			if !attribute(i, attr, source, &mut out[start + to_offset..start + to_offset + to.format(attr).size()]) {
+				return Err(i);
+			}
+		}
+		out.extend_from_slice(&vertex[from_stride..]);
+	}
+	Ok(out)
+}
+
+/// Moves the values of `attributes` out of the vertices laid out as `format` into palettes. Returns the layout with
+/// the indices, the vertices in it and the content of the PALT chunk: for each attribute of the palette, in the
+/// order of `Palette::ATTRIBUTES`, a u32 count and the values. The attributes with more than MAX_ENTRIES values
+/// stay in the vertices
+pub fn build(vertices: &[u8], format: &VertexFieldOffsets, user_attributes: &[UserAttribute], attributes: &[Attribute]) -> (VertexFieldOffsets, Vec<u8>, Vec<u8>) {
+	let stride = format.layout().with_user_attributes(user_attributes).stride;
+	let mut tables = vec![];
+	for attr in Palette::ATTRIBUTES.into_iter().filter(|&attr| attributes.contains(&attr) && format.has(attr)) {
+		let (offset, size) = (format.offset(attr).unwrap(), format.format(attr).size());
+		let mut values: Vec<&[u8]> = vec![];
+		let mut found = HashMap::new();
+		let indices: Vec<usize> = vertices.chunks_exact(stride).map(|vertex| {
+			let value = &vertex[offset..offset + size];
+			*found.entry(value).or_insert_with(|| {
+				values.push(value);
+				values.len() - 1
+			})
+		}).collect();
+		if values.len() <= MAX_ENTRIES {
+			tables.push((attr, values, indices));
+		}
+	}
+
+	let palette = Palette {
+		color: tables.iter().any(|(attr, _, _)| *attr == Attribute::Color),
+		normal: tables.iter().any(|(attr, _, _)| *attr == Attribute::Normal),
+		wide: tables.iter().any(|(_, values, _)| values.len() > 256),
+	};
+	let packed = VertexFieldOffsets::with_formats(&format.attributes(), format.position_format, format.uv_format, palette);
+	let packed_vertices = relayout(vertices, format, &packed, user_attributes, |i, attr, source, target| {
+		match tables.iter().find(|(a, _, _)| *a == attr) {
+			Some((_, _, indices)) => write_index(target, indices[i], palette.index_format()),
+			None => target.copy_from_slice(source),
+		}
+		true
+	}).unwrap();
+
+	let mut data = vec![];
+	for (_, values, _) in &tables {
+		data.write_u32::<LittleEndian>(values.len() as u32).unwrap();
+		for value in values {
+			data.extend_from_slice(value);
+		}
+	}
+	(packed, packed_vertices, data)
+}
+
+/// The tables of a PALT chunk for the palette of `format`, in the order of `Palette::ATTRIBUTES`; None when the
+/// chunk doesn't have their size
+pub fn read_tables(mut data: &[u8], format: &VertexFieldOffsets) -> Option<Vec<Vec<u8>>> {
+	let mut tables = vec![];
+	for attr in Palette::ATTRIBUTES.into_iter().filter(|&attr| format.palette.has(attr)) {
+		let count = data.read_u32::<LittleEndian>().ok()? as usize;
+		let size = count.checked_mul(attr.format().size()).filter(|&size| size <= data.len())?;
+		tables.push(data[..size].to_vec());
+		data = &data[size..];
+	}
+	if !data.is_empty() {
+		return None;
+	}
+	Some(tables)
+}
+
+/// The vertices laid out as `format`, with the values of its palette in place of the indices: laid out as
+/// `format.without_palette()`. Fails with the first vertex that has an index past the end of its table
+pub fn expand(vertices: &[u8], format: &VertexFieldOffsets, user_attributes: &[UserAttribute], tables: &[Vec<u8>]) -> Result<Vec<u8>, usize> {
+	let plain = format.without_palette();
+	let tables: Vec<(Attribute, &Vec<u8>)> = Palette::ATTRIBUTES.into_iter().filter(|&attr| format.palette.has(attr)).zip(tables).collect();
+	relayout(vertices, format, &plain, user_attributes, |_, attr, source, target| {
+		match tables.iter().find(|(a, _)| *a == attr) {
+			Some((_, table)) => {
+				let start = read_index(source, format.palette.index_format()) * target.len();
+				match table.get(start..start + target.len()) {
+					Some(value) => target.copy_from_slice(value),
+					None => return false,
+				}
+			},
+			None => target.copy_from_slice(source),
+		}
+		true
+	})
+}
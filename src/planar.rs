@@ -0,0 +1,303 @@
+//! Merging of the coplanar triangles of hard-surface models, whose exporters tessellate flat walls and floors
+//! into many, into polygons triangulated again with as few triangles as their corners allow.
+//!
+//! A region grows from a triangle across the edges to the triangles of the same group facing within the angle
+//! of it, when both sides share the normals and texture coordinates of the edge and the texture coordinates
+//! keep following the mapping of the first triangle. The corners inside the region go away, and so do the ones
+//! in the middle of its straight borders that no other triangle uses, as they would leave cracks otherwise.
+//! Regions with holes, or that touch themselves, keep their triangles.
+
+use wavefront_obj::obj::{Object, Shape, VTNIndex, Vertex};
+use std::collections::HashMap;
+use crate::manifold::boundary_loops;
+use crate::math::*;
+
+/// How far the texture coordinates of a triangle can be from the mapping of the region
+const UV_TOLERANCE: f64 = 1e-4;
+
+/// The two axes of the plane with `normal`, in the winding of the triangles facing it
+fn plane_axes(normal: Vertex) -> (Vertex, Vertex) {
+	let other = if normal.x.abs() < 0.9 { Vertex { x: 1.0, y: 0.0, z: 0.0 } } else { Vertex { x: 0.0, y: 1.0, z: 0.0 } };
+	let u = normalize(cross(other, normal));
+	(u, cross(normal, u))
+}
+
+fn cross2(a: (f64, f64), b: (f64, f64)) -> f64 {
+	a.0 * b.1 - a.1 * b.0
+}
+
+fn sub2(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+	(a.0 - b.0, a.1 - b.1)
+}
+
+/// The linear mapping from the plane to the texture coordinates of a triangle, as the texture coordinates of
+/// its first corner and of the two axes
+struct UvMapping {
+	origin: (f64, f64),
+	at: (f64, f64),
+	du: (f64, f64),
+	dv: (f64, f64),
+}
+
+impl UvMapping {
+	fn new(points: [(f64, f64); 3], uvs: [(f64, f64); 3]) -> Option<Self> {
+		let (q1, q2) = (sub2(points[1], points[0]), sub2(points[2], points[0]));
+		let (t1, t2) = (sub2(uvs[1], uvs[0]), sub2(uvs[2], uvs[0]));
+		let det = cross2(q1, q2);
+		if det.abs() < 1e-12 {
+			return None;
+		}
+		Some(UvMapping {
+			origin: points[0],
+			at: uvs[0],
+			du: ((t1.0 * q2.1 - t2.0 * q1.1) / det, (t1.1 * q2.1 - t2.1 * q1.1) / det),
+			dv: ((t2.0 * q1.0 - t1.0 * q2.0) / det, (t2.1 * q1.0 - t1.1 * q2.0) / det),
+		})
+	}
+
+	fn uv(&self, point: (f64, f64)) -> (f64, f64) {
+		let d = sub2(point, self.origin);
+		(self.at.0 + self.du.0 * d.0 + self.dv.0 * d.1, self.at.1 + self.du.1 * d.0 + self.dv.1 * d.1)
+	}
+}
+
+/// Triangulates the polygon with corners at `points`, counterclockwise, by cutting off ears; none if it isn't
+/// simple
+fn ear_clip(points: &[(f64, f64)]) -> Option<Vec<[usize; 3]>> {
+	if points.len() < 3 {
+		return None;
+	}
+	let mut remaining: Vec<usize> = (0..points.len()).collect();
+	let mut result = vec![];
+	while remaining.len() > 3 {
+		let n = remaining.len();
+		let ear = (0..n).find(|&i| {
+			let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+			let (pa, pb, pc) = (points[a], points[b], points[c]);
+			if cross2(sub2(pb, pa), sub2(pc, pb)) <= 1e-12 {
+				return false;
+			}
+			//the corners on the edges would be left out of the triangles
+			!remaining.iter().any(|&other| {
+				let p = points[other];
+				other != a && other != b && other != c && p != pa && p != pb && p != pc
+					&& cross2(sub2(pb, pa), sub2(p, pa)) >= 0.0
+					&& cross2(sub2(pc, pb), sub2(p, pb)) >= 0.0
+					&& cross2(sub2(pa, pc), sub2(p, pc)) >= 0.0
+			})
+		})?;
+		result.push([remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]]);
+		remaining.remove(ear);
+	}
+	result.push([remaining[0], remaining[1], remaining[2]]);
+	Some(result)
+}
+
+/// The border of `region`, in its winding, if it is a single loop through distinct positions and no other
+/// triangle uses the positions inside
+fn border(region: &[[VTNIndex; 3]], uses: &HashMap<usize, usize>) -> Option<Vec<VTNIndex>> {
+	let loops = boundary_loops(region);
+	let [corners] = loops.as_slice() else {
+		return None;
+	};
+	let mut on_border: Vec<usize> = corners.iter().map(|corner| corner.0).collect();
+	on_border.sort();
+	on_border.dedup();
+	if on_border.len() != corners.len() {
+		return None;
+	}
+	let mut region_uses: HashMap<usize, usize> = HashMap::new();
+	for t in region {
+		for corner in t {
+			*region_uses.entry(corner.0).or_insert(0) += 1;
+		}
+	}
+	let inside_used = region_uses.iter().any(|(v, &count)| uses[v] > count && on_border.binary_search(v).is_err());
+	(!inside_used).then(|| corners.clone())
+}
+
+/// The corners of `corners` in the middle of a straight stretch of the border
+fn straight_corners<'a>(obj: &'a Object, corners: &'a [VTNIndex]) -> impl Iterator<Item = usize> + 'a {
+	let n = corners.len();
+	(0..n).filter(move |&i| {
+		let [a, b, c] = [(i + n - 1) % n, i, (i + 1) % n].map(|j| obj.vertices[corners[j].0]);
+		let (ab, bc) = (sub(b, a), sub(c, b));
+		lenght(cross(ab, bc)) <= 1e-9 * lenght(ab) * lenght(bc) && dot(ab, bc) > 0.0
+	})
+}
+
+/// Returns a copy of `obj` where the regions of coplanar triangles, facing within `max_angle` degrees of each
+/// other, are triangulated again with as few triangles as possible, and the number of triangles removed
+pub fn merge_coplanar(obj: &Object, max_angle: f64) -> (Object, usize) {
+	let min_cos = max_angle.to_radians().cos();
+	let mut triangles = vec![];
+	for (g, geo) in obj.geometry.iter().enumerate() {
+		for (s, shape) in geo.shapes.iter().enumerate() {
+			if let Shape::Triangle(v1, v2, v3) = *shape {
+				triangles.push((g, s, [v1, v2, v3]));
+			}
+		}
+	}
+	let normals: Vec<Option<Vertex>> = triangles.iter().map(|(_, _, t)| {
+		let [p0, p1, p2] = t.map(|corner| obj.vertices[corner.0]);
+		let n = cross(sub(p1, p0), sub(p2, p0));
+		(lenght(n) > 1e-12).then(|| normalize(n))
+	}).collect();
+
+	let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+	let mut uses: HashMap<usize, usize> = HashMap::new();
+	for (i, (_, _, t)) in triangles.iter().enumerate() {
+		for k in 0..3 {
+			let (a, b) = (t[k].0, t[(k + 1) % 3].0);
+			edges.entry((usize::min(a, b), usize::max(a, b))).or_default().push(i);
+			*uses.entry(a).or_insert(0) += 1;
+		}
+	}
+	//the triangle across each edge of each triangle, when both sides can merge
+	let across = |i: usize, k: usize| -> Option<usize> {
+		let t = triangles[i].2;
+		let (a, b) = (t[k], t[(k + 1) % 3]);
+		let [x, y] = edges[&(usize::min(a.0, b.0), usize::max(a.0, b.0))][..] else {
+			return None;
+		};
+		let other = if x == i { y } else { x };
+		let o = triangles[other].2;
+		let same_group = triangles[other].0 == triangles[i].0;
+		let reversed = (0..3).any(|j| o[j] == b && o[(j + 1) % 3] == a);
+		(same_group && reversed).then_some(other)
+	};
+
+	//the regions by their first triangle, with their normal and border
+	let mut region_of = vec![usize::MAX; triangles.len()];
+	let mut regions: Vec<(usize, Vec<usize>, Vertex, Vec<VTNIndex>)> = vec![];
+	for seed in 0..triangles.len() {
+		let Some(normal) = normals[seed] else {
+			continue;
+		};
+		if region_of[seed] != usize::MAX {
+			continue;
+		}
+		let (u, v) = plane_axes(normal);
+		let planar = |corner: VTNIndex| {
+			let p = obj.vertices[corner.0];
+			(dot(p, u), dot(p, v))
+		};
+		let uv = |corner: VTNIndex| corner.1.map(|t| (obj.tex_vertices[t].x, obj.tex_vertices[t].y));
+		let seed_corners = triangles[seed].2;
+		let mapping = match seed_corners.map(uv) {
+			[Some(t0), Some(t1), Some(t2)] => match UvMapping::new(seed_corners.map(planar), [t0, t1, t2]) {
+				Some(mapping) => Some(mapping),
+				None => continue,
+			},
+			_ => None,
+		};
+		let follows_mapping = |t: [VTNIndex; 3]| match &mapping {
+			Some(mapping) => t.iter().all(|&corner| {
+				let (Some(actual), expected) = (uv(corner), mapping.uv(planar(corner))) else {
+					return false;
+				};
+				(actual.0 - expected.0).abs() <= UV_TOLERANCE && (actual.1 - expected.1).abs() <= UV_TOLERANCE
+			}),
+			_ => true,
+		};
+
+		let mut region = vec![seed];
+		region_of[seed] = seed;
+		let mut next = 0;
+		while next < region.len() {
+			let i = region[next];
+			next += 1;
+			for k in 0..3 {
+				let Some(other) = across(i, k) else {
+					continue;
+				};
+				if region_of[other] != usize::MAX || normals[other].is_none_or(|n| dot(n, normal) < min_cos) || !follows_mapping(triangles[other].2) {
+					continue;
+				}
+				region_of[other] = seed;
+				region.push(other);
+			}
+		}
+		let corners: Vec<[VTNIndex; 3]> = region.iter().map(|&i| triangles[i].2).collect();
+		if let Some(border) = border(&corners, &uses).filter(|_| region.len() > 1) {
+			regions.push((seed, region, normal, border));
+		}
+	}
+
+	//a corner along a straight border can only go when it does from every triangle around it, or it would leave
+	//a crack; a region that can't be triangulated keeps its triangles and corners, so the others are tried again
+	let replaced = loop {
+		let mut straight_uses: HashMap<usize, usize> = HashMap::new();
+		for (_, region, _, border) in &regions {
+			for i in straight_corners(obj, border) {
+				let v = border[i].0;
+				let count = region.iter().filter(|&&t| triangles[t].2.iter().any(|corner| corner.0 == v)).count();
+				*straight_uses.entry(v).or_insert(0) += count;
+			}
+		}
+		let removable = |v: usize| straight_uses.get(&v) == uses.get(&v);
+
+		let mut replaced: HashMap<usize, Vec<[VTNIndex; 3]>> = HashMap::new();
+		let mut failed = None;
+		for (r, (seed, region, normal, border)) in regions.iter().enumerate() {
+			let straight: Vec<usize> = straight_corners(obj, border).collect();
+			let corners: Vec<VTNIndex> = (0..border.len())
+				.filter(|i| !(straight.contains(i) && removable(border[*i].0)))
+				.map(|i| border[i])
+				.collect();
+			//a disc without corners inside nor removed is as small as it gets
+			if corners.len() >= region.len() + 2 {
+				continue;
+			}
+			let (u, v) = plane_axes(*normal);
+			let points: Vec<(f64, f64)> = corners.iter().map(|corner| {
+				let p = obj.vertices[corner.0];
+				(dot(p, u), dot(p, v))
+			}).collect();
+			match ear_clip(&points) {
+				Some(result) => {
+					replaced.insert(*seed, result.into_iter().map(|t| t.map(|i| corners[i])).collect());
+				},
+				None => {
+					failed = Some(r);
+					break;
+				},
+			}
+		}
+		match failed {
+			Some(r) => {
+				regions.remove(r);
+			},
+			None => break replaced,
+		}
+	};
+
+	let removed = regions.iter()
+		.filter_map(|(seed, region, _, _)| replaced.get(seed).map(|triangles| region.len() - triangles.len()))
+		.sum();
+	let mut result = obj.clone();
+	let mut positions: HashMap<(usize, usize), usize> = HashMap::new();
+	for (i, &(g, s, _)) in triangles.iter().enumerate() {
+		positions.insert((g, s), i);
+	}
+	for (g, geo) in result.geometry.iter_mut().enumerate() {
+		let mut shapes = vec![];
+		for (s, shape) in geo.shapes.drain(..).enumerate() {
+			let Some(&i) = positions.get(&(g, s)) else {
+				shapes.push(shape);
+				continue;
+			};
+			let region = region_of[i];
+			match replaced.get(&region) {
+				//in place of the first of the region
+				Some(triangles) if region == i => {
+					shapes.extend(triangles.iter().map(|t| Shape::Triangle(t[0], t[1], t[2])));
+				},
+				Some(_) => {},
+				None => shapes.push(shape),
+			}
+		}
+		geo.shapes = shapes;
+	}
+	(result, removed)
+}
@@ -0,0 +1,128 @@
+//! Cells and portals for the occlusion culling of interiors, where each object of the obj file is a room.
+//!
+//! The openings of a room are the loops of its open border, like the hole of a doorway in a wall. Two rooms
+//! are connected by a portal where both have an opening of about the same size at about the same place, so the
+//! gap between them can be as thick as the walls.
+
+use wavefront_obj::obj::{ObjSet, Object, Vertex};
+use crate::math::*;
+use crate::manifold;
+
+#[derive(Debug, Clone)]
+pub struct Portal {
+	/// The index of the cell on the other side
+	pub other: usize,
+	/// The border of the opening on this side
+	pub corners: Vec<Vertex>,
+	pub center: Vertex,
+	/// Of the plane of the opening, pointing out of this cell
+	pub normal: Vertex,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+	/// The object of the obj file, and its index in the set
+	pub name: String,
+	pub object: usize,
+	pub min: Vertex,
+	pub max: Vertex,
+	pub portals: Vec<Portal>,
+}
+
+/// A loop of the open border of an object
+struct Opening {
+	corners: Vec<Vertex>,
+	center: Vertex,
+	normal: Vertex,
+	min: Vertex,
+	max: Vertex,
+}
+
+/// Without the slivers left by the T-junctions, which have no area
+fn openings(obj: &Object) -> Vec<Opening> {
+	manifold::boundary_loops(&manifold::triangles(obj)).iter()
+		.filter(|corners| corners.len() >= 3)
+		.filter_map(|corners| {
+			let corners: Vec<Vertex> = corners.iter().map(|c| obj.vertices[c.0]).collect();
+			let min = corners.iter().fold(corners[0], |m, &v| vert_min(m, v));
+			let max = corners.iter().fold(corners[0], |m, &v| vert_max(m, v));
+			let mut center = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+			for &c in &corners {
+				addmut(&mut center, mul(c, 1.0 / corners.len() as f64));
+			}
+			let normal = polygon_normal(&corners);
+			if lenght(normal) > 0.0 { Some(Opening { corners, center, normal, min, max }) } else { None }
+		})
+		.collect()
+}
+
+/// Newell's normal of a polygon, which works for the ones that aren't flat or convex, and is zero without an area
+fn polygon_normal(corners: &[Vertex]) -> Vertex {
+	let mut normal = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+	for (i, a) in corners.iter().enumerate() {
+		let b = corners[(i + 1) % corners.len()];
+		addmut(&mut normal, Vertex {
+			x: (a.y - b.y) * (a.z + b.z),
+			y: (a.z - b.z) * (a.x + b.x),
+			z: (a.x - b.x) * (a.y + b.y),
+		});
+	}
+	if lenght(normal) > 0.0 { normalize(normal) } else { normal }
+}
+
+fn matches(a: &Opening, b: &Opening, max_gap: f64) -> bool {
+	let (size_a, size_b) = (sub(a.max, a.min), sub(b.max, b.min));
+	let size_difference = sub(size_a, size_b);
+	lenght(sub(a.center, b.center)) <= max_gap
+		&& [size_difference.x, size_difference.y, size_difference.z].iter().all(|d| d.abs() <= 2.0 * max_gap)
+}
+
+/// A cell for every object with triangles, in order, with a portal on each side of the matching openings of
+/// two cells whose centers are at most `max_gap` apart
+pub fn find_cells(set: &ObjSet, max_gap: f64) -> Vec<Cell> {
+	let objects: Vec<(usize, &Object)> = set.objects.iter().enumerate()
+		.filter(|(_, obj)| !manifold::triangles(obj).is_empty())
+		.collect();
+	let openings: Vec<Vec<Opening>> = objects.iter().map(|(_, obj)| openings(obj)).collect();
+
+	let mut cells: Vec<Cell> = objects.iter().map(|&(object, obj)| {
+		let used: Vec<Vertex> = manifold::triangles(obj).iter().flatten().map(|c| obj.vertices[c.0]).collect();
+		Cell {
+			name: obj.name.clone(),
+			object,
+			min: used.iter().fold(used[0], |m, &v| vert_min(m, v)),
+			max: used.iter().fold(used[0], |m, &v| vert_max(m, v)),
+			portals: vec![],
+		}
+	}).collect();
+
+	for i in 0..cells.len() {
+		for j in i + 1..cells.len() {
+			for a in &openings[i] {
+				//the closest opening of the other cell, if one matches
+				let b = openings[j].iter()
+					.filter(|b| matches(a, b, max_gap))
+					.min_by(|b, c| lenght(sub(a.center, b.center)).partial_cmp(&lenght(sub(a.center, c.center))).unwrap());
+				let Some(b) = b else {
+					continue;
+				};
+				for (cell, other, opening) in [(i, j, a), (j, i, b)] {
+					let mut room_center = cells[cell].min;
+					addmut(&mut room_center, cells[cell].max);
+					let room_center = mul(room_center, 0.5);
+					let mut normal = opening.normal;
+					if dot(normal, sub(opening.center, room_center)) < 0.0 {
+						normal = mul(normal, -1.0);
+					}
+					cells[cell].portals.push(Portal {
+						other,
+						corners: opening.corners.clone(),
+						center: opening.center,
+						normal,
+					});
+				}
+			}
+		}
+	}
+	cells
+}
@@ -0,0 +1,154 @@
+//! Fixes up the obj text before handing it to the parser, which doesn't handle everything exporters write.
+
+use wavefront_obj::ParseError;
+use crate::filter::NameFilter;
+
+fn error(line_number: usize, message: String) -> ParseError {
+	ParseError {
+		line_number,
+		message,
+	}
+}
+
+/// Counts of the `v`, `vt` and `vn` lines seen so far
+#[derive(Default)]
+struct Counts {
+	vertices: usize,
+	tex_vertices: usize,
+	normals: usize,
+}
+
+/// Makes a single index absolute, checking that it refers to an element defined before the face
+fn resolve_index(token: &str, count: usize, kind: &str, line_number: usize) -> Result<String, ParseError> {
+	let idx = match token.parse::<isize>() {
+		Ok(idx) => idx,
+		//leave the error to the parser
+		Err(_) => return Ok(token.to_owned()),
+	};
+
+	//-1 is the last one defined so far
+	let absolute = if idx < 0 { count as isize + 1 + idx } else { idx };
+
+	if absolute < 1 || absolute > count as isize {
+		return Err(error(line_number, format!("Face refers to {} {}, but only {} are defined at this point", kind, idx, count)));
+	}
+	Ok(absolute.to_string())
+}
+
+fn resolve_corner(corner: &str, counts: &Counts, line_number: usize) -> Result<String, ParseError> {
+	let mut parts = vec![];
+	for (i, part) in corner.split('/').enumerate() {
+		let (count, kind) = match i {
+			0 => (counts.vertices, "vertex"),
+			1 => (counts.tex_vertices, "texture coordinate"),
+			_ => (counts.normals, "normal"),
+		};
+
+		//v//vn leaves the texture coordinate empty
+		if part.is_empty() && i > 0 {
+			parts.push(String::new());
+		}
+		else {
+			parts.push(resolve_index(part, count, kind, line_number)?);
+		}
+	}
+	Ok(parts.join("/"))
+}
+
+/// The obj text given to the parser, and for each of its lines the line of the original file it comes from
+pub struct Preparsed {
+	pub content: String,
+	pub line_numbers: Vec<usize>,
+}
+
+/// Turns relative (negative) indices into absolute ones, and validates every index of every face.
+/// Also drops the vertex colors, groups and extra material libraries, that the parser doesn't accept, moves the
+/// first material library at the top, where the parser wants it, splits polylines into segments and
+/// turns point elements into single corner faces, that the parser reads as points.
+/// The elements of the objects and groups that `filter` doesn't keep are dropped, but not their vertices,
+/// which keeps the indices of the rest
+pub fn resolve_indices(content: &str, filter: &NameFilter) -> Result<Preparsed, ParseError> {
+	let mut counts = Counts::default();
+	//the names the elements are filtered by
+	let mut object = "unnamed_object";
+	let mut groups: Vec<&str> = vec![];
+	//the parser only takes it before everything else
+	let mut library: Option<(String, usize)> = None;
+	let mut result = Preparsed {
+		content: String::with_capacity(content.len()),
+		line_numbers: vec![],
+	};
+
+	for (i, line) in content.split('\n').enumerate() {
+		let mut push_line = |text: &str| {
+			if !result.line_numbers.is_empty() {
+				result.content.push('\n');
+			}
+			result.content.push_str(text);
+			result.line_numbers.push(i + 1);
+		};
+
+		let mut tokens = line.split_whitespace();
+		match tokens.next() {
+			Some("v") => {
+				counts.vertices += 1;
+				//read separately, see `colors::parse_vertex_colors`
+				let position: Vec<&str> = tokens.take(3).collect();
+				push_line(&format!("v {}", position.join(" ")));
+				continue;
+			},
+			Some("vt") => counts.tex_vertices += 1,
+			Some("vn") => counts.normals += 1,
+			Some("o") => {
+				object = tokens.next().unwrap_or("");
+				groups.clear();
+			},
+			Some("g") => {
+				groups = tokens.collect();
+				continue;
+			},
+			//the parser takes a single one, see `material::material_libraries` for all of them
+			Some("mtllib") => {
+				if library.is_none() {
+					library = Some((format!("mtllib {}", tokens.next().unwrap_or("")), i + 1));
+				}
+				continue;
+			},
+			Some(element @ "f") | Some(element @ "l") | Some(element @ "p") => {
+				let mut names = vec![object];
+				names.extend_from_slice(&groups);
+				if !filter.keeps(&names) {
+					continue;
+				}
+
+				let corners = tokens.map(|c| resolve_corner(c, &counts, i + 1)).collect::<Result<Vec<String>, ParseError>>()?;
+
+				//the parser would make a fan of triangles out of a polyline
+				if element == "l" && corners.len() > 2 {
+					for segment in corners.windows(2) {
+						push_line(&format!("l {} {}", segment[0], segment[1]));
+					}
+				}
+				else if element == "p" {
+					for corner in &corners {
+						push_line(&format!("f {}", corner));
+					}
+				}
+				else {
+					push_line(&format!("{} {}", element, corners.join(" ")));
+				}
+				continue;
+			},
+			_ => {},
+		}
+
+		push_line(line);
+	}
+
+	if let Some((line, line_number)) = library {
+		let separator = if result.line_numbers.is_empty() { "" } else { "\n" };
+		result.content = format!("{}{}{}", line, separator, result.content);
+		result.line_numbers.insert(0, line_number);
+	}
+	Ok(result)
+}
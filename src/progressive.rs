@@ -0,0 +1,384 @@
+//! Progressive meshes, which loaders can draw from a coarse base while the rest of the file arrives.
+//!
+//! The base is what is left after collapsing edges in the order of the error they introduce, each moving a vertex
+//! onto a neighbour, like the progressive meshes of Hoppe. The vertex splits undo the collapses in reverse: each
+//! adds back the vertex, moves to it the corners of the triangles that had it, and adds back the triangles that
+//! the collapse removed. Since the vertices keep their attributes, the whole mesh is the original one reordered.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::obj::Vertex;
+use crate::math::{cross, dot, lenght, sub};
+
+#[derive(Debug, Clone)]
+pub struct VertexSplit {
+	/// The vertex the new one splits from
+	pub parent: usize,
+	/// The positions in the index list of the corners of `parent` that move to the new vertex
+	pub corners: Vec<usize>,
+	/// Appended to the index list
+	pub triangles: Vec<[usize; 3]>,
+}
+
+/// How the base mesh refines into the whole one: split `i` adds the vertex `base_vertices + i`
+#[derive(Debug, Clone)]
+pub struct Refinement {
+	pub base_vertices: usize,
+	/// With the corners the splits move still on the vertices they split from
+	pub base_triangles: Vec<[usize; 3]>,
+	pub splits: Vec<VertexSplit>,
+}
+
+struct Collapse {
+	/// Moved onto `to`
+	from: usize,
+	to: usize,
+	/// The triangles that had `from`, and where
+	moved: Vec<(usize, usize)>,
+	removed: Vec<usize>,
+}
+
+/// The plane equations of the triangles around a vertex, summed and weighted by their area: the upper triangle
+/// of the symmetric matrix, then the vector and the constant
+type Quadric = [f64; 10];
+
+fn plane_quadric(a: Vertex, b: Vertex, c: Vertex) -> Quadric {
+	let n = cross(sub(b, a), sub(c, a));
+	let len = lenght(n);
+	if len == 0.0 {
+		return [0.0; 10];
+	}
+	let (x, y, z) = (n.x / len, n.y / len, n.z / len);
+	let d = -(x * a.x + y * a.y + z * a.z);
+	let area = len * 0.5;
+	[x * x, x * y, x * z, y * y, y * z, z * z, x * d, y * d, z * d, d * d].map(|q| q * area)
+}
+
+fn quadric_error(q: &Quadric, p: Vertex) -> f64 {
+	let (x, y, z) = (p.x, p.y, p.z);
+	let error = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + q[3] * y * y + 2.0 * q[4] * y * z + q[5] * z * z
+		+ 2.0 * (q[6] * x + q[7] * y + q[8] * z) + q[9];
+	//the sums can round below 0
+	error.max(0.0)
+}
+
+struct Collapser<'a> {
+	positions: &'a [Vertex],
+	triangles: Vec<[usize; 3]>,
+	alive: Vec<bool>,
+	/// The living triangles around each vertex
+	faces: Vec<Vec<usize>>,
+	quadrics: Vec<Quadric>,
+	locked: Vec<bool>,
+	removed: Vec<bool>,
+	/// Bumped when the collapses around a vertex change, to skip the outdated ones in the heap
+	stamps: Vec<u32>,
+	heap: BinaryHeap<Reverse<(u64, usize, usize, u32)>>,
+}
+
+impl Collapser<'_> {
+	fn neighbours(&self, v: usize) -> Vec<usize> {
+		let mut found: Vec<usize> = self.faces[v].iter().flat_map(|&t| self.triangles[t]).filter(|&w| w != v).collect();
+		found.sort_unstable();
+		found.dedup();
+		found
+	}
+
+	fn push_collapses(&mut self, from: usize) {
+		if self.locked[from] || self.removed[from] {
+			return;
+		}
+		for to in self.neighbours(from) {
+			let mut q = self.quadrics[from];
+			for (a, b) in q.iter_mut().zip(&self.quadrics[to]) {
+				*a += b;
+			}
+			//non-negative floats sort like their bits
+			let cost = quadric_error(&q, self.positions[to]).to_bits();
+			self.heap.push(Reverse((cost, from, to, self.stamps[from])));
+		}
+	}
+
+	/// Whether moving `from` onto `to` keeps the surface manifold and doesn't flip triangles
+	fn can_collapse(&self, from: usize, to: usize) -> bool {
+		let shared = self.faces[from].iter().filter(|&&t| self.triangles[t].contains(&to)).count();
+		let from_neighbours = self.neighbours(from);
+		if shared == 0 || !from_neighbours.contains(&to) {
+			return false;
+		}
+		//the vertices around both are the ones opposite the edge, or the collapse would pinch the surface
+		let to_neighbours = self.neighbours(to);
+		let common = from_neighbours.iter().filter(|w| to_neighbours.contains(w)).count();
+		if common != shared {
+			return false;
+		}
+
+		self.faces[from].iter().filter(|&&t| !self.triangles[t].contains(&to)).all(|&t| {
+			let [a, b, c] = self.triangles[t].map(|v| self.positions[v]);
+			let [a2, b2, c2] = self.triangles[t].map(|v| self.positions[if v == from { to } else { v }]);
+			let before = cross(sub(b, a), sub(c, a));
+			let after = cross(sub(b2, a2), sub(c2, a2));
+			dot(before, after) > 0.0
+		})
+	}
+
+	fn collapse(&mut self, from: usize, to: usize) -> Collapse {
+		let mut moved = vec![];
+		let mut removed = vec![];
+		for t in std::mem::take(&mut self.faces[from]) {
+			if self.triangles[t].contains(&to) {
+				self.alive[t] = false;
+				for v in self.triangles[t] {
+					self.faces[v].retain(|&f| f != t);
+				}
+				removed.push(t);
+			}
+			else {
+				let corner = self.triangles[t].iter().position(|&v| v == from).unwrap();
+				self.triangles[t][corner] = to;
+				self.faces[to].push(t);
+				moved.push((t, corner));
+			}
+		}
+		self.removed[from] = true;
+		let q = self.quadrics[from];
+		for (a, b) in self.quadrics[to].iter_mut().zip(&q) {
+			*a += b;
+		}
+
+		let around = self.neighbours(to);
+		for &v in around.iter().chain(&[to]) {
+			self.stamps[v] += 1;
+		}
+		for &v in around.iter().chain(&[to]) {
+			self.push_collapses(v);
+		}
+		Collapse { from, to, moved, removed }
+	}
+}
+
+/// Collapses the triangles of `indices` down to `base` of them, a fraction, or as far as they go. The vertices with
+/// the same position as another, on the seams of the attributes, and those on the borders never move. Returns the
+/// new order of the vertices and of the triangles, as their old indices, and the refinement of the reordered mesh
+pub fn build(positions: &[Vertex], indices: &[usize], base: f64) -> (Vec<usize>, Vec<usize>, Refinement) {
+	let triangles: Vec<[usize; 3]> = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+	let mut faces = vec![vec![]; positions.len()];
+	let mut quadrics = vec![[0.0; 10]; positions.len()];
+	let mut locked = vec![false; positions.len()];
+	let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+	for (i, t) in triangles.iter().enumerate() {
+		let q = plane_quadric(positions[t[0]], positions[t[1]], positions[t[2]]);
+		for c in 0..3 {
+			faces[t[c]].push(i);
+			for (a, b) in quadrics[t[c]].iter_mut().zip(&q) {
+				*a += b;
+			}
+			let (a, b) = (t[c], t[(c + 1) % 3]);
+			if a == b {
+				locked[a] = true;
+			}
+			*edges.entry((a.min(b), a.max(b))).or_default() += 1;
+		}
+	}
+	for ((a, b), count) in edges {
+		if count != 2 {
+			locked[a] = true;
+			locked[b] = true;
+		}
+	}
+	let mut at_position: HashMap<[u64; 3], usize> = HashMap::new();
+	for (i, p) in positions.iter().enumerate() {
+		if let Some(&first) = at_position.get(&[p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]) {
+			locked[first] = true;
+			locked[i] = true;
+		}
+		at_position.insert([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()], i);
+	}
+
+	let mut collapser = Collapser {
+		positions,
+		alive: vec![true; triangles.len()],
+		triangles,
+		faces,
+		quadrics,
+		locked,
+		removed: vec![false; positions.len()],
+		stamps: vec![0; positions.len()],
+		heap: BinaryHeap::new(),
+	};
+	for v in 0..positions.len() {
+		collapser.push_collapses(v);
+	}
+	let target = (collapser.triangles.len() as f64 * base).ceil() as usize;
+	let mut remaining = collapser.triangles.len();
+	let mut collapses = vec![];
+	while remaining > target {
+		let Some(Reverse((_, from, to, stamp))) = collapser.heap.pop() else {
+			break;
+		};
+		if stamp != collapser.stamps[from] || collapser.removed[from] || collapser.removed[to] || !collapser.can_collapse(from, to) {
+			continue;
+		}
+		//the removed triangles keep their corners from before it, which the split adds back
+		let collapse = collapser.collapse(from, to);
+		remaining -= collapse.removed.len();
+		collapses.push(collapse);
+	}
+
+	//the base first, then the vertices and triangles in the order the splits add them
+	let mut vertex_order: Vec<usize> = (0..positions.len()).filter(|&v| !collapser.removed[v]).collect();
+	let base_vertices = vertex_order.len();
+	vertex_order.extend(collapses.iter().rev().map(|c| c.from));
+	let mut new_vertex = vec![0; positions.len()];
+	for (i, &v) in vertex_order.iter().enumerate() {
+		new_vertex[v] = i;
+	}
+	let mut triangle_order: Vec<usize> = (0..collapser.triangles.len()).filter(|&t| collapser.alive[t]).collect();
+	let base_triangles = triangle_order.iter().map(|&t| collapser.triangles[t].map(|v| new_vertex[v])).collect();
+	triangle_order.extend(collapses.iter().rev().flat_map(|c| c.removed.iter().copied()));
+	let mut new_triangle = vec![0; collapser.triangles.len()];
+	for (i, &t) in triangle_order.iter().enumerate() {
+		new_triangle[t] = i;
+	}
+
+	let splits = collapses.iter().rev().map(|c| VertexSplit {
+		parent: new_vertex[c.to],
+		corners: c.moved.iter().map(|&(t, corner)| new_triangle[t] * 3 + corner).collect(),
+		triangles: c.removed.iter().map(|&t| collapser.triangles[t].map(|v| new_vertex[v])).collect(),
+	}).collect();
+	(vertex_order, triangle_order, Refinement { base_vertices, base_triangles, splits })
+}
+
+fn write_index(data: &mut Vec<u8>, index: usize, index_size: u8) {
+	match index_size {
+		1 => data.write_u8(index as u8).unwrap(),
+		2 => data.write_u16::<LittleEndian>(index as u16).unwrap(),
+		_ => data.write_u32::<LittleEndian>(index as u32).unwrap(),
+	}
+}
+
+/// Writes the splits of the PROG chunk, each with the vertex it adds from `vertices`, the vertex buffer of the
+/// whole mesh with vertices of `stride` bytes
+pub fn write_splits(data: &mut Vec<u8>, refinement: &Refinement, vertices: &[u8], stride: usize, index_size: u8) {
+	for (i, split) in refinement.splits.iter().enumerate() {
+		if split.corners.len() > u16::MAX as usize || split.triangles.len() > u16::MAX as usize {
+			panic!("Vertex {} has {} triangles, more than a vertex split can store", refinement.base_vertices + i, split.corners.len() + split.triangles.len());
+		}
+		write_index(data, split.parent, index_size);
+		let vertex = refinement.base_vertices + i;
+		data.extend_from_slice(&vertices[vertex * stride..(vertex + 1) * stride]);
+		data.write_u16::<LittleEndian>(split.corners.len() as u16).unwrap();
+		for &corner in &split.corners {
+			data.write_u32::<LittleEndian>(corner as u32).unwrap();
+		}
+		data.write_u16::<LittleEndian>(split.triangles.len() as u16).unwrap();
+		for &index in split.triangles.iter().flatten() {
+			write_index(data, index, index_size);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use byteorder::ReadBytesExt;
+	use super::*;
+	use crate::convert::ConvertOptions;
+	use crate::codec::Encoding;
+	use crate::fixtures;
+	use crate::reader::{self, MeshData};
+
+	/// The positions and triangles of a bumpy square of `size` by `size` positions
+	fn grid(size: usize) -> (Vec<Vertex>, Vec<usize>) {
+		let positions = (0..size * size).map(|i| {
+			let (x, y) = (i % size, i / size);
+			Vertex { x: x as f64, y: ((x * 7 + y * 13) % 5) as f64 * 0.1, z: y as f64 }
+		}).collect();
+		let mut indices = vec![];
+		for y in 0..size - 1 {
+			for x in 0..size - 1 {
+				let corner = y * size + x;
+				indices.extend_from_slice(&[corner, corner + size, corner + size + 1, corner, corner + size + 1, corner + 1]);
+			}
+		}
+		(positions, indices)
+	}
+
+	#[test]
+	fn splits_rebuild_the_whole_mesh() {
+		let (positions, indices) = grid(20);
+		let (vertex_order, triangle_order, refinement) = build(&positions, &indices, 0.1);
+		assert!(refinement.base_triangles.len() * 5 < indices.len() / 3, "{} triangles in the base", refinement.base_triangles.len());
+		assert_eq!(refinement.base_vertices + refinement.splits.len(), positions.len());
+
+		//each vertex stores its index in the original mesh
+		let vertices: Vec<u8> = vertex_order.iter().flat_map(|&v| (v as u32).to_le_bytes()).collect();
+		let mut data = vec![];
+		write_splits(&mut data, &refinement, &vertices, 4, 4);
+
+		let mut input = &data[..];
+		let mut rebuilt: Vec<u32> = refinement.base_triangles.iter().flatten().map(|&v| v as u32).collect();
+		for i in 0..refinement.splits.len() {
+			let added = (refinement.base_vertices + i) as u32;
+			let parent = input.read_u32::<LittleEndian>().unwrap();
+			assert_eq!(input.read_u32::<LittleEndian>().unwrap() as usize, vertex_order[added as usize]);
+			for _ in 0..input.read_u16::<LittleEndian>().unwrap() {
+				let corner = &mut rebuilt[input.read_u32::<LittleEndian>().unwrap() as usize];
+				assert_eq!(*corner, parent);
+				*corner = added;
+			}
+			for _ in 0..input.read_u16::<LittleEndian>().unwrap() as usize * 3 {
+				let index = input.read_u32::<LittleEndian>().unwrap();
+				assert!(index <= added);
+				rebuilt.push(index);
+			}
+		}
+		assert!(input.is_empty());
+
+		let mut new_vertex = vec![0; positions.len()];
+		for (i, &v) in vertex_order.iter().enumerate() {
+			new_vertex[v] = i as u32;
+		}
+		let reordered: Vec<u32> = triangle_order.iter().flat_map(|&t| &indices[t * 3..t * 3 + 3]).map(|&v| new_vertex[v]).collect();
+		assert!(rebuilt == reordered);
+	}
+
+	/// The triangles of a mesh by the positions and UVs of their corners, in any order
+	fn triangles(mesh: &MeshData) -> Vec<[[u32; 5]; 3]> {
+		let tex0 = mesh.tex0.as_ref().unwrap();
+		let corner = |v: u32| {
+			let (p, uv) = (mesh.positions[v as usize], tex0[v as usize]);
+			[p[0], p[1], p[2], uv[0], uv[1]].map(f32::to_bits)
+		};
+		let mut triangles: Vec<_> = mesh.indices.chunks(3).map(|t| [corner(t[0]), corner(t[1]), corner(t[2])]).collect();
+		triangles.sort_unstable();
+		triangles
+	}
+
+	#[test]
+	fn through_the_reader() {
+		let raw = fixtures::convert(&fixtures::grid(30), &ConvertOptions { split_by_material: true, ..Default::default() });
+		let options = ConvertOptions { split_by_material: true, encoding: Encoding::Progressive, ..Default::default() };
+		let progressive = fixtures::convert(&fixtures::grid(30), &options);
+		assert_eq!(progressive[0].parts.len(), 2);
+		for (raw, part) in raw[0].parts.iter().zip(&progressive[0].parts) {
+			let (raw, whole) = (fixtures::read(raw), fixtures::read(part));
+			assert_eq!(whole.header.encoding, Encoding::Progressive);
+			assert_eq!(whole.positions.len(), raw.positions.len());
+			assert!(triangles(&whole) == triangles(&raw));
+
+			//the start of the file has the base and the first splits, with their vertices
+			let start = reader::read_progressive(&part[..part.len() * 2 / 3]).unwrap();
+			assert!(start.indices.len() < whole.indices.len());
+			assert_eq!(start.positions[..], whole.positions[..start.positions.len()]);
+		}
+	}
+
+	#[test]
+	fn several_materials_are_an_error() {
+		let converted = fixtures::convert(&fixtures::grid(10), &ConvertOptions { encoding: Encoding::Progressive, ..Default::default() });
+		assert!(converted[0].parts.is_empty());
+		let warning = &converted[0].warnings[0];
+		assert!(warning.error && warning.message.contains("--split-by material"), "{}", warning.message);
+	}
+}
@@ -0,0 +1,106 @@
+//! How much the vertex formats move the attributes: the distance between the values the conversion computes and
+//! the ones stored in the mesh files, like the positions rounded to 32 bit floats, the normals packed in 10 bits
+//! per component or the UVs stored as 16 bit floats.
+
+use std::fmt::Write;
+use crate::layout::Attribute;
+use crate::math::flt_max;
+
+/// How many of the vertices with the largest errors are kept for each attribute
+pub const WORST_VERTICES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexError {
+	/// The object, and the part of it for the split ones, like `rock.1`
+	pub part: String,
+	pub vertex: usize,
+	pub error: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeError {
+	pub attribute: Attribute,
+	pub max: f64,
+	sum: f64,
+	count: usize,
+	/// The largest first
+	pub worst: Vec<VertexError>,
+}
+
+impl AttributeError {
+	pub fn mean(&self) -> f64 {
+		if self.count == 0 { 0.0 } else { self.sum / self.count as f64 }
+	}
+
+	/// Of the errors: the directions are compared by their angle, the colors by their largest channel
+	pub fn unit(&self) -> &'static str {
+		match self.attribute {
+			Attribute::Normal | Attribute::Tangent | Attribute::Bitangent => "degrees",
+			Attribute::Color => "of the channel range",
+			Attribute::Tex0 | Attribute::Tex1 => "in UV space",
+			_ => "in object units",
+		}
+	}
+
+	fn add_worst(&mut self, error: VertexError) {
+		//the ties keep the first vertices
+		let at = self.worst.iter().position(|w| w.error < error.error).unwrap_or(self.worst.len());
+		if at < WORST_VERTICES {
+			self.worst.insert(at, error);
+			self.worst.truncate(WORST_VERTICES);
+		}
+	}
+}
+
+/// The errors of all the vertices of a mesh, by attribute in the order of the layout
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QuantizationReport {
+	pub attributes: Vec<AttributeError>,
+}
+
+impl QuantizationReport {
+	fn attribute(&mut self, attribute: Attribute) -> &mut AttributeError {
+		match self.attributes.iter().position(|a| a.attribute == attribute) {
+			Some(i) => &mut self.attributes[i],
+			None => {
+				self.attributes.push(AttributeError { attribute, max: 0.0, sum: 0.0, count: 0, worst: vec![] });
+				self.attributes.last_mut().unwrap()
+			},
+		}
+	}
+
+	pub fn add(&mut self, part: &str, vertex: usize, attribute: Attribute, error: f64) {
+		let errors = self.attribute(attribute);
+		errors.max = flt_max(errors.max, error);
+		errors.sum += error;
+		errors.count += 1;
+		if error > 0.0 {
+			errors.add_worst(VertexError { part: part.to_owned(), vertex, error });
+		}
+	}
+
+	/// Adds the errors of another mesh, like the other objects of a scene
+	pub fn merge(&mut self, other: &QuantizationReport) {
+		for other in &other.attributes {
+			let errors = self.attribute(other.attribute);
+			errors.max = flt_max(errors.max, other.max);
+			errors.sum += other.sum;
+			errors.count += other.count;
+			for worst in &other.worst {
+				errors.add_worst(worst.clone());
+			}
+		}
+	}
+
+	/// A line for each attribute, then its worst vertices
+	pub fn to_text(&self) -> String {
+		let mut out = String::new();
+		for errors in &self.attributes {
+			writeln!(out, "{}: max error {:.3e}, mean {:.3e} {}", errors.attribute.name(), errors.max, errors.mean(), errors.unit()).unwrap();
+			for worst in &errors.worst {
+				writeln!(out, "\t{:.3e} at vertex {} of {}", worst.error, worst.vertex, worst.part).unwrap();
+			}
+		}
+		out
+	}
+}
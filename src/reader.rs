@@ -0,0 +1,1485 @@
+//! Loads `.mesh` files back into memory, decoding the packed vertex attributes.
+//!
+//! Only depends on `byteorder` and `half`, so games can use it with `default-features = false, features = ["std"]`
+//! without pulling in the obj parser and the command line tool. Runtimes without `std` can use `view` instead.
+
+use byteorder::{self, LittleEndian, ReadBytesExt};
+use half::f16;
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io::{self, Read};
+use crate::layout::{Attribute, ColorSpace, Format, Palette, UserAttribute, VertexFieldOffsets, VertexLayout, MAX_USER_ATTRIBUTES};
+use crate::chunk;
+use crate::codec::{self, Encoding};
+use crate::blocks::{self, BlockLayout};
+use crate::palette;
+use crate::dequantization::{self, Dequantization};
+pub use crate::view::Topology;
+
+#[derive(Debug)]
+pub enum ReadError {
+	Io(io::Error),
+	UnsupportedTopology(u8),
+	InvalidIndexSize(u8),
+	/// An attribute flag that is neither 0 nor 1
+	InvalidFlag(&'static str, u8),
+	/// A vertex attribute the converter never produces
+	UnsupportedAttribute(&'static str),
+	MissingPosition,
+	InvalidBounds,
+	/// A triangle list whose index count isn't a multiple of 3
+	IncompleteTriangle(usize),
+	/// A line list with an odd index count
+	IncompleteLine(usize),
+	NonFinitePosition(usize),
+	PositionOutOfBounds(usize),
+	IndexOutOfRange { index: usize, value: u32, vertex_count: usize },
+	/// A morph target name that isn't valid UTF-8
+	InvalidMorphName(usize),
+	MorphVertexOutOfRange { target: usize, vertex: u32, vertex_count: usize },
+	/// A submesh with an invalid material name, or a range past the indices
+	InvalidSubmesh(usize),
+	/// A BVH node pointing outside of the nodes or of the triangles
+	InvalidBvhNode(usize),
+	BvhTriangleOutOfRange { entry: usize, triangle: u32, triangle_count: usize },
+	/// A user attribute with an unknown format or an invalid name
+	InvalidUserAttribute(usize),
+	TooManyUserAttributes(usize),
+	UnsupportedVersion(u32),
+	MissingChunk(&'static str),
+	DuplicateChunk(&'static str),
+	/// A chunk whose length doesn't match the counts of the header
+	InvalidChunkSize(&'static str),
+	/// A chunk cut short by the end of the file
+	TruncatedChunk(String),
+	/// A metadata pair that isn't valid UTF-8
+	InvalidMetadata(usize),
+	/// An instance whose object name isn't valid UTF-8
+	InvalidInstance(usize),
+	/// A material whose name or texture isn't valid UTF-8
+	InvalidMaterial(usize),
+	/// A cell or portal whose name isn't valid UTF-8, by the index of the cell
+	InvalidCell(usize),
+	/// A symmetry plane across an axis other than 0 to 2
+	InvalidMirrorAxis(u8),
+	/// A chunk that doesn't decode with the encoding of the header
+	InvalidEncoding(&'static str),
+	/// A palette index past the end of its table, by the vertex
+	InvalidPaletteIndex(usize),
+	/// A vertex split from a vertex, or moving corners, that the mesh doesn't have, by the vertex it adds
+	InvalidSplit(usize),
+	/// The matrix of the quantized positions isn't finite
+	InvalidDequantization,
+	/// The file was corrupted after it was written
+	ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ReadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReadError::Io(ref err) => write!(f, "{}", err),
+			ReadError::UnsupportedTopology(t) => write!(f, "Unsupported topology {}", t),
+			ReadError::InvalidIndexSize(size) => write!(f, "Invalid index size {}", size),
+			ReadError::InvalidFlag(name, val) => write!(f, "Invalid value {} for the {} flag", val, name),
+			ReadError::UnsupportedAttribute(name) => write!(f, "Unsupported vertex attribute {}", name),
+			ReadError::MissingPosition => write!(f, "The vertex format has no 3D position"),
+			ReadError::InvalidBounds => write!(f, "The bounding box is invalid"),
+			ReadError::IncompleteTriangle(count) => write!(f, "{} indices don't make a triangle list", count),
+			ReadError::IncompleteLine(count) => write!(f, "{} indices don't make a line list", count),
+			ReadError::NonFinitePosition(v) => write!(f, "Vertex {} has a non finite position", v),
+			ReadError::PositionOutOfBounds(v) => write!(f, "Vertex {} is outside of the bounding box", v),
+			ReadError::IndexOutOfRange { index, value, vertex_count } =>
+				write!(f, "Index {} is {}, but there are only {} vertices", index, value, vertex_count),
+			ReadError::InvalidMorphName(target) => write!(f, "Morph target {} has an invalid name", target),
+			ReadError::MorphVertexOutOfRange { target, vertex, vertex_count } =>
+				write!(f, "Morph target {} moves vertex {}, but there are only {} vertices", target, vertex, vertex_count),
+			ReadError::InvalidSubmesh(i) => write!(f, "Submesh {} is invalid", i),
+			ReadError::InvalidBvhNode(i) => write!(f, "BVH node {} is invalid", i),
+			ReadError::BvhTriangleOutOfRange { entry, triangle, triangle_count } =>
+				write!(f, "BVH entry {} is triangle {}, but there are only {} triangles", entry, triangle, triangle_count),
+			ReadError::InvalidUserAttribute(i) => write!(f, "User attribute {} is invalid", i),
+			ReadError::TooManyUserAttributes(count) => write!(f, "{} user attributes, but at most {} are supported", count, MAX_USER_ATTRIBUTES),
+			ReadError::UnsupportedVersion(version) => write!(f, "Unsupported format version {}", version),
+			ReadError::MissingChunk(name) => write!(f, "The {} chunk is missing", name),
+			ReadError::DuplicateChunk(name) => write!(f, "The {} chunk appears more than once", name),
+			ReadError::InvalidChunkSize(name) => write!(f, "The size of the {} chunk doesn't match the header", name),
+			ReadError::TruncatedChunk(ref name) => write!(f, "The {} chunk is truncated", name),
+			ReadError::InvalidMetadata(i) => write!(f, "Metadata entry {} is invalid", i),
+			ReadError::InvalidInstance(i) => write!(f, "Instance {} is invalid", i),
+			ReadError::InvalidMaterial(i) => write!(f, "Material {} is invalid", i),
+			ReadError::InvalidCell(i) => write!(f, "Cell {} is invalid", i),
+			ReadError::InvalidMirrorAxis(axis) => write!(f, "Invalid symmetry axis {}", axis),
+			ReadError::InvalidEncoding(name) => write!(f, "The {} chunk isn't validly encoded", name),
+			ReadError::InvalidPaletteIndex(i) => write!(f, "Vertex {} has a palette index out of range", i),
+			ReadError::InvalidSplit(i) => write!(f, "The split adding vertex {} doesn't match the mesh", i),
+			ReadError::InvalidDequantization => write!(f, "The dequantization matrix isn't finite"),
+			ReadError::ChecksumMismatch { expected, actual } =>
+				write!(f, "The file is corrupted: its checksum is {:08x}, but it should be {:08x}", actual, expected),
+		}
+	}
+}
+
+impl error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+	fn from(err: io::Error) -> Self {
+		ReadError::Io(err)
+	}
+}
+
+impl From<byteorder::Error> for ReadError {
+	fn from(err: byteorder::Error) -> Self {
+		ReadError::Io(err.into())
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Header {
+	/// The format version of the file, 1 for the files without chunks
+	pub version: u32,
+	/// Size in bytes of each index: 1, 2 or 4
+	pub index_size: u8,
+	pub topology: Topology,
+	pub format: VertexFieldOffsets,
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+	pub vertex_count: usize,
+	pub index_count: usize,
+	/// Whether the morph target section follows the indices
+	pub has_morph_targets: bool,
+	/// Of the colors, when there are some
+	pub color_space: ColorSpace,
+	/// Laid out after the built-in attributes, in this order
+	pub user_attributes: Vec<UserAttribute>,
+	/// Of the vertex and index chunks
+	pub encoding: Encoding,
+	/// Of the quantized positions
+	pub dequantization: Option<Dequantization>,
+	/// Of the `SHDW` chunk, 0 when there's none
+	pub shadow_index_count: usize,
+}
+
+impl Header {
+	/// The layout of the vertices, including the user attributes
+	pub fn layout(&self) -> VertexLayout {
+		self.format.layout().with_user_attributes(&self.user_attributes)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MeshData {
+	pub header: Header,
+	pub positions: Vec<[f32; 3]>,
+	pub colors: Option<Vec<[f32; 4]>>,
+	pub normals: Option<Vec<[f32; 3]>>,
+	/// xyz is the tangent direction, w the handedness of the bitangent
+	pub tangents: Option<Vec<[f32; 4]>>,
+	pub bitangents: Option<Vec<[f32; 3]>>,
+	pub tex0: Option<Vec<[f32; 2]>>,
+	/// Lightmap UVs
+	pub tex1: Option<Vec<[f32; 2]>>,
+	pub bone_indices: Option<Vec<[u8; 4]>>,
+	/// Add up to 1
+	pub bone_weights: Option<Vec<[f32; 4]>>,
+	pub curvature: Option<Vec<f32>>,
+	pub material_ids: Option<Vec<u32>>,
+	/// For each user attribute, its bytes for every vertex in the declared format
+	pub user_data: Vec<Vec<u8>>,
+	pub indices: Vec<u32>,
+	/// The triangles indexing a single vertex per position, for shadow and depth passes
+	pub shadow_indices: Option<Vec<u32>>,
+	pub morph_targets: Vec<MorphTargetData>,
+	/// Empty for version 1 files
+	pub submeshes: Vec<SubmeshData>,
+	pub obb: Option<OrientedBoxData>,
+	pub kdop: Option<KdopData>,
+	pub bvh: Option<BvhData>,
+	pub measurements: Option<MeasurementsData>,
+	/// The CRC-32 of the file, when it has one; it's always verified
+	pub checksum: Option<u32>,
+	/// Where the mesh comes from: the converter version, its options, the source file...
+	pub metadata: Vec<(String, String)>,
+	/// The placements of the objects sharing this geometry; empty when it's only drawn where it is
+	pub instances: Vec<InstanceData>,
+	/// The materials of the submeshes that were found in the mtl files
+	pub materials: Vec<MaterialData>,
+	/// The texture files the materials point at, when they were embedded
+	pub textures: Vec<EmbeddedTexture>,
+	/// The rooms of an interior stored in this mesh, with the portals to the others
+	pub cells: Vec<CellData>,
+	/// The plane the mesh is drawn mirrored through, when it only stores one half
+	pub mirror: Option<MirrorData>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphDelta {
+	pub vertex: u32,
+	pub position: [f32; 3],
+	/// Only present when the mesh has normals
+	pub normal: Option<[f32; 3]>,
+}
+
+/// Sparse deltas for the vertices moved by a morph target
+#[derive(Debug, Clone)]
+pub struct MorphTargetData {
+	pub name: String,
+	pub deltas: Vec<MorphDelta>,
+}
+
+/// A range of indices using one material, with its bounding box and sphere
+#[derive(Debug, Clone)]
+pub struct SubmeshData {
+	pub material: Option<String>,
+	pub first_index: u32,
+	pub index_count: u32,
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+	pub center: [f32; 3],
+	pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OrientedBoxData {
+	pub center: [f32; 3],
+	pub half_extents: [f32; 3],
+	/// Orthonormal, from the longest to the shortest
+	pub axes: [[f32; 3]; 3],
+}
+
+#[derive(Debug, Clone)]
+pub struct KdopData {
+	pub k: u8,
+	/// Min and max along each of `chunk::kdop_axes(k)`
+	pub slabs: Vec<[f32; 2]>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhNodeData {
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+	/// For leaves the first entry of `BvhData::triangles`, for the others the index of the right child;
+	/// the left child always follows its parent
+	pub offset: u32,
+	/// 0 for the nodes that aren't leaves
+	pub count: u32,
+}
+
+/// A BVH over the triangles, with the root first
+#[derive(Debug, Clone)]
+pub struct BvhData {
+	pub nodes: Vec<BvhNodeData>,
+	/// Triangles in leaf order, each the index of its first index divided by 3
+	pub triangles: Vec<u32>,
+}
+
+/// Of the whole object, when it was split into several files
+#[derive(Debug, Clone, Copy)]
+pub struct MeasurementsData {
+	pub surface_area: f32,
+	/// Only for closed meshes
+	pub volume: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstanceData {
+	/// The object of the obj file placed by this instance
+	pub name: String,
+	/// Row major, with the rotation on the left and the translation in the last column
+	pub transform: [[f32; 4]; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureSource {
+	Path(String),
+	/// The index in `MeshData::textures`
+	Embedded(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureData {
+	/// The mtl statement, like `map_Kd`
+	pub kind: String,
+	pub source: TextureSource,
+}
+
+/// Metallic-roughness values converted from the mtl ones, with the indices of the textures of the material
+#[derive(Debug, Clone)]
+pub struct PbrData {
+	pub base_color: [f32; 4],
+	pub metallic: f32,
+	pub roughness: f32,
+	pub emissive: [f32; 3],
+	pub base_color_texture: Option<usize>,
+	pub metallic_texture: Option<usize>,
+	pub roughness_texture: Option<usize>,
+	pub normal_texture: Option<usize>,
+	pub emissive_texture: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaterialData {
+	pub name: String,
+	pub textures: Vec<TextureData>,
+	pub pbr: Option<PbrData>,
+}
+
+/// A texture file stored in the mesh
+#[derive(Debug, Clone)]
+pub struct EmbeddedTexture {
+	/// Where it was copied from, with the extension of its format
+	pub path: String,
+	pub content: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PortalData {
+	/// The cell on the other side
+	pub other: String,
+	pub center: [f32; 3],
+	/// Pointing out of the cell
+	pub normal: [f32; 3],
+	/// The border of the opening
+	pub corners: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CellData {
+	pub name: String,
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+	pub portals: Vec<PortalData>,
+}
+
+/// A symmetry plane across an axis
+#[derive(Debug, Clone)]
+pub struct MirrorData {
+	/// 0 for x, 1 for y and 2 for z
+	pub axis: usize,
+	/// Where the plane crosses the axis
+	pub offset: f32,
+	/// Those of the mirrored copy, with the triangles wound the other way
+	pub mirrored_indices: Option<Vec<u32>>,
+}
+
+impl MirrorData {
+	pub fn mirror_position(&self, mut p: [f32; 3]) -> [f32; 3] {
+		p[self.axis] = 2.0 * self.offset - p[self.axis];
+		p
+	}
+
+	pub fn mirror_direction(&self, mut d: [f32; 3]) -> [f32; 3] {
+		d[self.axis] = -d[self.axis];
+		d
+	}
+}
+
+impl MeshData {
+	pub fn format(&self) -> VertexFieldOffsets {
+		self.header.format
+	}
+
+	/// Appends the mirrored copy of the half stored with a symmetry plane, for the loaders that draw the whole
+	/// mesh at once. The BVH still only covers the first half
+	pub fn unfold_mirror(&mut self) {
+		let Some(mirror) = self.mirror.take() else {
+			return;
+		};
+		let vertex_count = self.positions.len() as u32;
+		let index_count = self.indices.len() as u32;
+
+		fn repeat<T: Clone>(values: &mut Option<Vec<T>>, change: impl Fn(&T) -> T) {
+			if let Some(values) = values {
+				let copies: Vec<T> = values.iter().map(change).collect();
+				values.extend(copies);
+			}
+		}
+		let positions: Vec<[f32; 3]> = self.positions.iter().map(|&p| mirror.mirror_position(p)).collect();
+		self.positions.extend(positions);
+		repeat(&mut self.normals, |&n| mirror.mirror_direction(n));
+		repeat(&mut self.tangents, |&[x, y, z, w]| {
+			let [x, y, z] = mirror.mirror_direction([x, y, z]);
+			[x, y, z, -w]
+		});
+		repeat(&mut self.bitangents, |&b| mirror.mirror_direction(b));
+		repeat(&mut self.colors, Clone::clone);
+		repeat(&mut self.tex0, Clone::clone);
+		repeat(&mut self.tex1, Clone::clone);
+		repeat(&mut self.bone_indices, Clone::clone);
+		repeat(&mut self.bone_weights, Clone::clone);
+		repeat(&mut self.curvature, Clone::clone);
+		repeat(&mut self.material_ids, Clone::clone);
+		for data in &mut self.user_data {
+			data.extend_from_within(..);
+		}
+
+		let mirrored: Vec<u32> = self.indices.chunks(3).flat_map(|t| [t[0], t[2], t[1]]).map(|i| i + vertex_count).collect();
+		self.indices.extend(mirrored);
+
+		let submeshes: Vec<SubmeshData> = self.submeshes.iter().map(|s| {
+			let [a, b] = [mirror.mirror_position(s.min), mirror.mirror_position(s.max)];
+			SubmeshData {
+				material: s.material.clone(),
+				first_index: s.first_index + index_count,
+				index_count: s.index_count,
+				min: [0, 1, 2].map(|i| f32::min(a[i], b[i])),
+				max: [0, 1, 2].map(|i| f32::max(a[i], b[i])),
+				center: mirror.mirror_position(s.center),
+				radius: s.radius,
+			}
+		}).collect();
+		self.submeshes.extend(submeshes);
+
+		let [a, b] = [mirror.mirror_position(self.header.min), mirror.mirror_position(self.header.max)];
+		self.header.min = [0, 1, 2].map(|i| f32::min(self.header.min[i], f32::min(a[i], b[i])));
+		self.header.max = [0, 1, 2].map(|i| f32::max(self.header.max[i], f32::max(a[i], b[i])));
+		self.header.vertex_count = self.positions.len();
+		self.header.index_count = self.indices.len();
+	}
+}
+
+fn unpack_normalized(val: u32, bits: u32) -> f32 {
+	let max = ((1 << (bits - 1)) - 1) as f32;
+	//sign extend the field
+	let shift = 32 - bits;
+	let signed = ((val << shift) as i32) >> shift;
+	f32::max(signed as f32 / max, -1.0)
+}
+
+fn unpack_i2_10_10_10(val: u32) -> [f32; 4] {
+	[
+		unpack_normalized(val & 0x3ff, 10),
+		unpack_normalized((val >> 10) & 0x3ff, 10),
+		unpack_normalized((val >> 20) & 0x3ff, 10),
+		unpack_normalized(val >> 30, 2),
+	]
+}
+
+fn read_uv<R: Read>(input: &mut R, format: Format) -> Result<[f32; 2], ReadError> {
+	if format == Format::Float32x2 {
+		return Ok([input.read_f32::<LittleEndian>()?, input.read_f32::<LittleEndian>()?]);
+	}
+	let u = f32::from(f16::from_bits(input.read_u16::<LittleEndian>()?));
+	let v = f32::from(f16::from_bits(input.read_u16::<LittleEndian>()?));
+	Ok([u, v])
+}
+
+fn read_vec3<R: Read>(input: &mut R) -> Result<[f32; 3], ReadError> {
+	Ok([
+		input.read_f32::<LittleEndian>()?,
+		input.read_f32::<LittleEndian>()?,
+		input.read_f32::<LittleEndian>()?,
+	])
+}
+
+/// The UV flags are 2 for Float32x2 UVs
+fn read_uv_flag<R: Read>(input: &mut R, name: &'static str) -> Result<Option<Format>, ReadError> {
+	match input.read_u8()? {
+		0 => Ok(None),
+		1 => Ok(Some(Format::Float16x2)),
+		2 => Ok(Some(Format::Float32x2)),
+		val => Err(ReadError::InvalidFlag(name, val)),
+	}
+}
+
+fn read_encoding<R: Read>(input: &mut R) -> Result<Encoding, ReadError> {
+	let mut val = [0];
+	if input.read(&mut val)? == 0 {
+		return Ok(Encoding::Raw);
+	}
+	Encoding::from_u8(val[0]).ok_or(ReadError::InvalidFlag("Encoding", val[0]))
+}
+
+fn read_palette<R: Read>(input: &mut R, attributes: &[Attribute]) -> Result<Palette, ReadError> {
+	let mut val = [0];
+	if input.read(&mut val)? == 0 {
+		return Ok(Palette::default());
+	}
+	match Palette::from_u8(val[0]) {
+		Some(palette) if Palette::ATTRIBUTES.iter().all(|attr| !palette.has(*attr) || attributes.contains(attr)) => Ok(palette),
+		_ => Err(ReadError::InvalidFlag("Palette", val[0])),
+	}
+}
+
+/// The 16 floats after the palette byte
+fn read_dequantization<R: Read>(input: &mut R) -> Result<Dequantization, ReadError> {
+	let mut matrix = [[0.0; 4]; 4];
+	for v in matrix.iter_mut().flatten() {
+		*v = input.read_f32::<LittleEndian>()?;
+	}
+	let dequantization = Dequantization { matrix };
+	if !dequantization.is_finite() {
+		return Err(ReadError::InvalidDequantization);
+	}
+	Ok(dequantization)
+}
+
+fn read_flag<R: Read>(input: &mut R, name: &'static str) -> Result<bool, ReadError> {
+	match input.read_u8()? {
+		0 => Ok(false),
+		1 => Ok(true),
+		val => Err(ReadError::InvalidFlag(name, val)),
+	}
+}
+
+fn is_finite(v: [f32; 3]) -> bool {
+	v.iter().all(|c| c.is_finite())
+}
+
+fn is_inside(v: [f32; 3], min: [f32; 3], max: [f32; 3]) -> bool {
+	(0..3).all(|i| v[i] >= min[i] && v[i] <= max[i])
+}
+
+/// After the palette byte and the dequantization matrix, 0 when the header ends before
+fn read_shadow_index_count<R: Read>(input: &mut R) -> Result<usize, ReadError> {
+	let mut val = [0; 4];
+	if input.read(&mut val[..1])? == 0 {
+		return Ok(0);
+	}
+	input.read_exact(&mut val[1..])?;
+	Ok(u32::from_le_bytes(val) as usize)
+}
+
+/// Moves the positions a few ulps past the bounds onto them, which the rounding of the dequantization can leave
+fn snap_inside(v: [f32; 3], min: [f32; 3], max: [f32; 3]) -> [f32; 3] {
+	[0, 1, 2].map(|i| {
+		let slack = 4.0 * f32::EPSILON * f32::max(min[i].abs(), max[i].abs());
+		if v[i] >= min[i] - slack && v[i] <= max[i] + slack { v[i].clamp(min[i], max[i]) } else { v[i] }
+	})
+}
+
+/// Reads the header of a file of any version
+pub fn read_header<R: Read>(input: &mut R) -> Result<Header, ReadError> {
+	let mut magic = [0; 4];
+	input.read_exact(&mut magic)?;
+	if magic != chunk::MAGIC {
+		return read_header_fields(&mut (&magic[..]).chain(input), chunk::LEGACY_VERSION);
+	}
+	read_chunked_header(input)
+}
+
+/// Reads the version and the header chunk that follow the magic number, leaving `input` at the next chunk
+fn read_chunked_header<R: Read>(input: &mut R) -> Result<Header, ReadError> {
+	let version = chunk::read_version(input)?;
+	match chunk::read_chunk(input)? {
+		Some(header) if header.id == chunk::HEADER => read_header_fields(&mut &header.data[..], version),
+		_ => Err(ReadError::MissingChunk("HEAD")),
+	}
+}
+
+//newer versions can append fields to the chunk, so it may be longer than what is read here
+fn read_header_fields<R: Read>(input: &mut R, version: u32) -> Result<Header, ReadError> {
+	let index_size = input.read_u8()?;
+	if index_size != 1 && index_size != 2 && index_size != 4 {
+		return Err(ReadError::InvalidIndexSize(index_size));
+	}
+
+	let topology = input.read_u8()?;
+	let topology = Topology::from_u8(topology).ok_or(ReadError::UnsupportedTopology(topology))?;
+
+	if read_flag(input, "Position2D")? {
+		return Err(ReadError::UnsupportedAttribute("Position2D"));
+	}
+	//2 for the quantized positions
+	let position_format = match input.read_u8()? {
+		0 => return Err(ReadError::MissingPosition),
+		1 => Format::Float32x3,
+		2 => dequantization::POSITION_FORMAT,
+		val => return Err(ReadError::InvalidFlag("Position3D", val)),
+	};
+	let mut attributes = vec![];
+	let color_space = match input.read_u8()? {
+		0 => ColorSpace::Srgb,
+		1 => {
+			attributes.push(Attribute::Color);
+			ColorSpace::Srgb
+		},
+		2 => {
+			attributes.push(Attribute::Color);
+			ColorSpace::Linear
+		},
+		val => return Err(ReadError::InvalidFlag("Color", val)),
+	};
+	if read_flag(input, "Normal")? {
+		attributes.push(Attribute::Normal);
+	}
+	if read_flag(input, "Tangent")? {
+		attributes.push(Attribute::Tangent);
+	}
+	let tex0 = read_uv_flag(input, "Tex0")?;
+	if tex0.is_some() {
+		attributes.push(Attribute::Tex0);
+	}
+	let tex1 = read_uv_flag(input, "Tex1")?;
+	if tex1.is_some() {
+		attributes.push(Attribute::Tex1);
+	}
+	//both UV sets share the format
+	let uv_format = match (tex0, tex1) {
+		(Some(a), Some(b)) if a != b => return Err(ReadError::InvalidFlag("Tex1", 2)),
+		(Some(format), _) | (_, Some(format)) => format,
+		(None, None) => Format::Float16x2,
+	};
+	if read_flag(input, "Skin")? {
+		attributes.push(Attribute::BoneIndices);
+		attributes.push(Attribute::BoneWeights);
+	}
+	let has_morph_targets = read_flag(input, "Morph")?;
+	if read_flag(input, "Curvature")? {
+		attributes.push(Attribute::Curvature);
+	}
+	if read_flag(input, "MaterialId")? {
+		attributes.push(Attribute::MaterialId);
+	}
+	if read_flag(input, "Bitangent")? {
+		attributes.push(Attribute::Bitangent);
+	}
+
+	let user_count = input.read_u8()? as usize;
+	if user_count > MAX_USER_ATTRIBUTES {
+		return Err(ReadError::TooManyUserAttributes(user_count));
+	}
+	let mut user_attributes = Vec::with_capacity(user_count);
+	for i in 0..user_count {
+		let format = Format::from_u8(input.read_u8()?).ok_or(ReadError::InvalidUserAttribute(i))?;
+		let mut name = vec![0; input.read_u8()? as usize];
+		input.read_exact(&mut name)?;
+		let name = String::from_utf8(name).map_err(|_| ReadError::InvalidUserAttribute(i))?;
+		user_attributes.push(UserAttribute { name, format });
+	}
+
+	let max = read_vec3(input)?;
+	let min = read_vec3(input)?;
+
+	let vertex_count = input.read_u32::<LittleEndian>()? as usize;
+	let index_count = input.read_u32::<LittleEndian>()? as usize;
+	//the files written before them ended the header with the counts
+	let (encoding, palette) = if version == chunk::LEGACY_VERSION {
+		(Encoding::Raw, Palette::default())
+	}
+	else {
+		(read_encoding(input)?, read_palette(input, &attributes)?)
+	};
+	let dequantization = match position_format {
+		Format::Float32x3 => None,
+		_ if version == chunk::LEGACY_VERSION => return Err(ReadError::InvalidFlag("Position3D", 2)),
+		_ => Some(read_dequantization(input)?),
+	};
+	let shadow_index_count = if version == chunk::LEGACY_VERSION { 0 } else { read_shadow_index_count(input)? };
+
+	let header = Header {
+		version,
+		index_size,
+		topology,
+		format: VertexFieldOffsets::with_formats(&attributes, position_format, uv_format, palette),
+		min,
+		max,
+		vertex_count,
+		index_count,
+		has_morph_targets,
+		color_space,
+		user_attributes,
+		encoding,
+		dequantization,
+		shadow_index_count,
+	};
+
+	//empty meshes keep the inverted bounds they start from
+	if header.vertex_count > 0 && !(is_finite(min) && is_finite(max) && is_inside(min, min, max)) {
+		return Err(ReadError::InvalidBounds);
+	}
+
+	match header.topology {
+		Topology::TriangleList if !header.index_count.is_multiple_of(3) => return Err(ReadError::IncompleteTriangle(header.index_count)),
+		Topology::LineList if !header.index_count.is_multiple_of(2) => return Err(ReadError::IncompleteLine(header.index_count)),
+		_ => {},
+	}
+
+	Ok(header)
+}
+
+//the counts come from the file, so don't trust them for large allocations
+fn capacity(count: usize) -> usize {
+	usize::min(count, 1 << 16)
+}
+
+/// Reads and validates a whole mesh, of any version
+pub fn read_mesh<R: Read>(input: &mut R) -> Result<MeshData, ReadError> {
+	let input = &mut chunk::ChecksumReader::new(input);
+	let mut magic = [0; 4];
+	input.read_exact(&mut magic)?;
+	if magic != chunk::MAGIC {
+		return read_legacy_mesh(&mut (&magic[..]).chain(input));
+	}
+
+	let version = chunk::read_version(input)?;
+
+	//verify the checksum before parsing anything, so that corruption isn't reported as invalid data
+	let mut chunks = vec![];
+	let mut checksum = None;
+	loop {
+		let crc = input.crc.value();
+		let Some(chunk) = chunk::read_chunk(input)? else {
+			break;
+		};
+		//covers everything before it
+		if chunk.id == chunk::CHECKSUM {
+			let expected = (&mut &chunk.data[..]).read_u32::<LittleEndian>()?;
+			if expected != crc {
+				return Err(ReadError::ChecksumMismatch { expected, actual: crc });
+			}
+			checksum = Some(crc);
+			break;
+		}
+		chunks.push(chunk);
+	}
+	read_chunks(version, chunks, checksum, false)
+}
+
+/// Reads what has arrived of a file, like `read_mesh` does the whole file. A progressive mesh has the base and all
+/// the vertex splits that arrived, and the counts and the other chunks of the whole mesh, but the submeshes cut
+/// where the indices end. The meshes of the other encodings only read once they have arrived
+pub fn read_progressive(data: &[u8]) -> Result<MeshData, ReadError> {
+	if !data.starts_with(&chunk::MAGIC) {
+		return read_mesh(&mut &data[..]);
+	}
+	let version = chunk::read_version(&mut &data[4..])?;
+	let mut crc = chunk::Crc32::default();
+	crc.update(&data[..8]);
+	let mut rest = &data[8..];
+	let mut chunks = vec![];
+	let mut checksum = None;
+	while rest.len() >= 8 {
+		let id = [rest[0], rest[1], rest[2], rest[3]];
+		let length = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+		if id == chunk::CHECKSUM && rest.len() >= 12 {
+			let expected = u32::from_le_bytes([rest[8], rest[9], rest[10], rest[11]]);
+			if expected != crc.value() {
+				return Err(ReadError::ChecksumMismatch { expected, actual: crc.value() });
+			}
+			checksum = Some(expected);
+			break;
+		}
+		//the splits are read up to the last one that arrived whole
+		if rest.len() - 8 < length {
+			if id == chunk::SPLITS {
+				chunks.push(chunk::Chunk { id, data: rest[8..].to_vec() });
+			}
+			break;
+		}
+		crc.update(&rest[..8 + length]);
+		chunks.push(chunk::Chunk { id, data: rest[8..8 + length].to_vec() });
+		rest = &rest[8 + length..];
+	}
+	read_chunks(version, chunks, checksum, checksum.is_none())
+}
+
+/// Reads the mesh from the chunks after the version; without all of them when `partial`
+fn read_chunks(version: u32, chunks: Vec<chunk::Chunk>, checksum: Option<u32>, partial: bool) -> Result<MeshData, ReadError> {
+	let mut chunks = chunks.into_iter();
+	let header = match chunks.next() {
+		Some(header) if header.id == chunk::HEADER => read_header_fields(&mut &header.data[..], version)?,
+		_ => return Err(ReadError::MissingChunk("HEAD")),
+	};
+	let vertex_count = header.vertex_count;
+	let index_count = header.index_count;
+	let format = header.format;
+	let mut mesh = empty_mesh(&header);
+	mesh.checksum = checksum;
+
+	let mut seen = vec![];
+	let mut palette_tables = None;
+	for chunk in chunks {
+		//the only chunk that repeats, once for each texture
+		if chunk.id == chunk::TEXTURE {
+			let data = &mut &chunk.data[..];
+			let path = read_text(data, ReadError::InvalidChunkSize("TEXR"))?;
+			mesh.textures.push(EmbeddedTexture { path, content: data.to_vec() });
+			continue;
+		}
+		let name = match chunk.id {
+			chunk::VERTICES => "VERT",
+			chunk::INDICES => "INDX",
+			chunk::SHADOW_INDICES => "SHDW",
+			chunk::MORPH_TARGETS => "MRPH",
+			chunk::SUBMESHES => "SUBM",
+			chunk::BOUNDS => "BNDS",
+			chunk::BVH => "BVH",
+			chunk::MEASUREMENTS => "MEAS",
+			chunk::METADATA => "META",
+			chunk::INSTANCES => "INST",
+			chunk::CELLS => "CELL",
+			chunk::MIRROR => "MIRR",
+			chunk::MATERIALS => "MATL",
+			chunk::PALETTE => "PALT",
+			chunk::SPLITS if header.encoding == Encoding::Progressive => "PROG",
+			//left for newer readers
+			_ => continue,
+		};
+		if seen.contains(&name) {
+			return Err(ReadError::DuplicateChunk(name));
+		}
+		seen.push(name);
+
+		let data = &mut &chunk.data[..];
+		match chunk.id {
+			chunk::PALETTE => palette_tables = Some(palette::read_tables(data, &format).ok_or(ReadError::InvalidChunkSize(name))?),
+			chunk::VERTICES => {
+				let count = if header.encoding == Encoding::Progressive { data.len() / header.layout().stride } else { vertex_count };
+				let vertices = match header.encoding {
+					Encoding::Meshopt => Cow::Owned(codec::decode_vertex_buffer(data, vertex_count, header.layout().stride).ok_or(ReadError::InvalidEncoding(name))?),
+					//the base, which the splits add to
+					Encoding::Progressive if !data.len().is_multiple_of(header.layout().stride) || data.len() > vertex_count * header.layout().stride =>
+						return Err(ReadError::InvalidChunkSize(name)),
+					Encoding::Progressive => Cow::Borrowed(*data),
+					Encoding::Blocks => Cow::Owned(BlockLayout::new(&header.layout()).and_then(|layout| blocks::decode(data, vertex_count, &layout))
+						.ok_or(ReadError::InvalidEncoding(name))?),
+					_ if data.len() != vertex_count * header.layout().stride => return Err(ReadError::InvalidChunkSize(name)),
+					_ => Cow::Borrowed(*data),
+				};
+				let vertices = if format.palette.is_empty() { vertices } else {
+					//the tables come first
+					let Some(tables) = &palette_tables else {
+						return Err(ReadError::MissingChunk("PALT"));
+					};
+					Cow::Owned(palette::expand(&vertices, &format, &header.user_attributes, tables).map_err(ReadError::InvalidPaletteIndex)?)
+				};
+				read_vertices(&mut &vertices[..], &header, count, &mut mesh)?;
+			},
+			chunk::INDICES if header.encoding == Encoding::Meshopt => {
+				let indices = match header.topology {
+					Topology::TriangleList => codec::decode_index_buffer(data, index_count),
+					_ => codec::decode_index_sequence(data, index_count),
+				};
+				mesh.indices = indices.ok_or(ReadError::InvalidEncoding(name))?;
+				check_indices(&mesh.indices, vertex_count)?;
+			},
+			chunk::INDICES if header.encoding == Encoding::Delta => {
+				mesh.indices = codec::decode_index_deltas(data, index_count).ok_or(ReadError::InvalidEncoding(name))?;
+				check_indices(&mesh.indices, vertex_count)?;
+			},
+			chunk::INDICES if header.encoding == Encoding::Progressive => {
+				let count = data.len() / header.index_size as usize;
+				if !data.len().is_multiple_of(header.index_size as usize) || count > index_count || !count.is_multiple_of(3) {
+					return Err(ReadError::InvalidChunkSize(name));
+				}
+				mesh.indices = read_index_list(data, &header, count)?;
+			},
+			chunk::INDICES => {
+				if data.len() != index_count * header.index_size as usize {
+					return Err(ReadError::InvalidChunkSize(name));
+				}
+				read_indices(data, &header, &mut mesh)?;
+			},
+			chunk::SHADOW_INDICES => {
+				let count = header.shadow_index_count;
+				let indices = match header.encoding {
+					Encoding::Meshopt => codec::decode_index_buffer(data, count).ok_or(ReadError::InvalidEncoding(name))?,
+					Encoding::Delta => codec::decode_index_deltas(data, count).ok_or(ReadError::InvalidEncoding(name))?,
+					_ if data.len() != count * header.index_size as usize => return Err(ReadError::InvalidChunkSize(name)),
+					_ => read_index_list(data, &header, count)?,
+				};
+				check_indices(&indices, vertex_count)?;
+				mesh.shadow_indices = Some(indices);
+			},
+			chunk::MORPH_TARGETS => mesh.morph_targets = read_morph_targets(data, vertex_count, format.normal.is_some())?,
+			chunk::SUBMESHES => mesh.submeshes = read_submeshes(data, index_count)?,
+			chunk::BOUNDS => read_bounds(data, &mut mesh)?,
+			chunk::BVH => mesh.bvh = Some(read_bvh(data, index_count)?),
+			chunk::METADATA => mesh.metadata = read_metadata(data)?,
+			chunk::INSTANCES => mesh.instances = read_instances(data)?,
+			chunk::CELLS => mesh.cells = read_cells(data)?,
+			chunk::MIRROR => mesh.mirror = Some(read_mirror(data, &header)?),
+			chunk::MATERIALS => mesh.materials = read_materials(data)?,
+			chunk::SPLITS => {
+				for base in ["VERT", "INDX"] {
+					if !seen.contains(&base) {
+						return Err(ReadError::MissingChunk(base));
+					}
+				}
+				read_splits(data, &header, palette_tables.as_deref(), &mut mesh, partial)?;
+			},
+			_ => {
+				let surface_area = data.read_f32::<LittleEndian>()?;
+				let closed = read_flag(data, "closed")?;
+				let volume = data.read_f32::<LittleEndian>()?;
+				mesh.measurements = Some(MeasurementsData {
+					surface_area,
+					volume: if closed { Some(volume) } else { None },
+				});
+			},
+		}
+	}
+
+	for (i, material) in mesh.materials.iter().enumerate() {
+		if material.textures.iter().any(|t| matches!(t.source, TextureSource::Embedded(index) if index >= mesh.textures.len())) {
+			return Err(ReadError::InvalidMaterial(i));
+		}
+	}
+
+	for name in ["VERT", "INDX"] {
+		if !seen.contains(&name) {
+			return Err(ReadError::MissingChunk(name));
+		}
+	}
+	if header.has_morph_targets && !seen.contains(&"MRPH") && !partial {
+		return Err(ReadError::MissingChunk("MRPH"));
+	}
+	if header.shadow_index_count > 0 && !seen.contains(&"SHDW") && !partial {
+		return Err(ReadError::MissingChunk("SHDW"));
+	}
+	if header.encoding == Encoding::Progressive {
+		if !partial && (mesh.positions.len() != vertex_count || mesh.indices.len() != index_count) {
+			return Err(if seen.contains(&"PROG") { ReadError::InvalidChunkSize("PROG") } else { ReadError::MissingChunk("PROG") });
+		}
+		check_indices(&mesh.indices, mesh.positions.len())?;
+		let arrived = mesh.indices.len() as u32;
+		for submesh in &mut mesh.submeshes {
+			submesh.first_index = submesh.first_index.min(arrived);
+			submesh.index_count = submesh.index_count.min(arrived - submesh.first_index);
+		}
+	}
+
+	Ok(mesh)
+}
+
+/// Version 1 files have no chunks: the header is followed by the vertices, the indices and the morph targets
+fn read_legacy_mesh<R: Read>(input: &mut R) -> Result<MeshData, ReadError> {
+	let header = read_header_fields(input, chunk::LEGACY_VERSION)?;
+	let mut mesh = empty_mesh(&header);
+	read_vertices(input, &header, header.vertex_count, &mut mesh)?;
+	read_indices(input, &header, &mut mesh)?;
+	if header.has_morph_targets {
+		mesh.morph_targets = read_morph_targets(input, header.vertex_count, header.format.normal.is_some())?;
+	}
+	Ok(mesh)
+}
+
+fn empty_mesh(header: &Header) -> MeshData {
+	let vertex_count = header.vertex_count;
+	let format = header.format;
+	MeshData {
+		header: header.clone(),
+		positions: Vec::with_capacity(capacity(vertex_count)),
+		colors: if format.color.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		normals: if format.normal.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		tangents: if format.tangent.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		bitangents: if format.bitangent.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		tex0: if format.tex0.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		tex1: if format.tex1.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		bone_indices: if format.bone_indices.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		bone_weights: if format.bone_weights.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		curvature: if format.curvature.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		material_ids: if format.material_id.is_some() { Some(Vec::with_capacity(capacity(vertex_count))) } else { None },
+		user_data: header.user_attributes.iter().map(|a| Vec::with_capacity(capacity(vertex_count * a.format.size()))).collect(),
+		indices: Vec::with_capacity(capacity(header.index_count)),
+		shadow_indices: None,
+		morph_targets: vec![],
+		submeshes: vec![],
+		obb: None,
+		kdop: None,
+		bvh: None,
+		measurements: None,
+		checksum: None,
+		metadata: vec![],
+		instances: vec![],
+		materials: vec![],
+		textures: vec![],
+		cells: vec![],
+		mirror: None,
+	}
+}
+
+/// Appends `count` vertices to the mesh
+fn read_vertices<R: Read>(input: &mut R, header: &Header, count: usize, mesh: &mut MeshData) -> Result<(), ReadError> {
+	let format = header.format;
+	for _ in 0..count {
+		let i = mesh.positions.len();
+		let pos = match header.dequantization {
+			Some(dequantization) => {
+				let mut q = [0; 4];
+				for c in &mut q {
+					*c = input.read_u16::<LittleEndian>()?;
+				}
+				snap_inside(dequantization.apply(q), header.min, header.max)
+			},
+			None => read_vec3(input)?,
+		};
+		if !is_finite(pos) {
+			return Err(ReadError::NonFinitePosition(i));
+		}
+		if !is_inside(pos, header.min, header.max) {
+			return Err(ReadError::PositionOutOfBounds(i));
+		}
+		mesh.positions.push(pos);
+
+		if let Some(ref mut colors) = mesh.colors {
+			let mut color = [0; 4];
+			input.read_exact(&mut color)?;
+			colors.push(color.map(|c| c as f32 / 255.0));
+		}
+
+		if let Some(ref mut normals) = mesh.normals {
+			let n = unpack_i2_10_10_10(input.read_u32::<LittleEndian>()?);
+			normals.push([n[0], n[1], n[2]]);
+		}
+
+		if let Some(ref mut tangents) = mesh.tangents {
+			tangents.push(unpack_i2_10_10_10(input.read_u32::<LittleEndian>()?));
+		}
+
+		if let Some(ref mut bitangents) = mesh.bitangents {
+			let b = unpack_i2_10_10_10(input.read_u32::<LittleEndian>()?);
+			bitangents.push([b[0], b[1], b[2]]);
+		}
+
+		if let Some(ref mut tex0) = mesh.tex0 {
+			tex0.push(read_uv(input, format.uv_format)?);
+		}
+
+		if let Some(ref mut tex1) = mesh.tex1 {
+			tex1.push(read_uv(input, format.uv_format)?);
+		}
+
+		if let Some(ref mut bone_indices) = mesh.bone_indices {
+			let mut bones = [0; 4];
+			input.read_exact(&mut bones)?;
+			bone_indices.push(bones);
+		}
+
+		if let Some(ref mut bone_weights) = mesh.bone_weights {
+			let mut weights = [0; 4];
+			input.read_exact(&mut weights)?;
+			bone_weights.push(weights.map(|w| w as f32 / 255.0));
+		}
+
+		if let Some(ref mut curvature) = mesh.curvature {
+			curvature.push(input.read_f32::<LittleEndian>()?);
+		}
+
+		if let Some(ref mut material_ids) = mesh.material_ids {
+			material_ids.push(input.read_u32::<LittleEndian>()?);
+		}
+
+		for (attr, data) in header.user_attributes.iter().zip(&mut mesh.user_data) {
+			let start = data.len();
+			data.resize(start + attr.format.size(), 0);
+			input.read_exact(&mut data[start..])?;
+		}
+	}
+
+	Ok(())
+}
+
+fn read_indices<R: Read>(input: &mut R, header: &Header, mesh: &mut MeshData) -> Result<(), ReadError> {
+	mesh.indices = read_index_list(input, header, header.index_count)?;
+	Ok(())
+}
+
+fn read_index<R: Read>(input: &mut R, index_size: u8) -> Result<u32, ReadError> {
+	Ok(match index_size {
+		1 => input.read_u8()? as u32,
+		2 => input.read_u16::<LittleEndian>()? as u32,
+		_ => input.read_u32::<LittleEndian>()?,
+	})
+}
+
+fn read_index_list<R: Read>(input: &mut R, header: &Header, count: usize) -> Result<Vec<u32>, ReadError> {
+	let mut indices = Vec::with_capacity(capacity(count));
+	for _ in 0..count {
+		indices.push(read_index(input, header.index_size)?);
+	}
+	check_indices(&indices, header.vertex_count)?;
+	Ok(indices)
+}
+
+/// Applies the vertex splits of a PROG chunk in order. When `partial`, the last one can be cut short and is left out
+fn read_splits(mut data: &[u8], header: &Header, palette_tables: Option<&[Vec<u8>]>, mesh: &mut MeshData, partial: bool) -> Result<(), ReadError> {
+	let stride = header.layout().stride;
+	while !data.is_empty() {
+		let input = &mut data;
+		let split = (|| -> Result<_, ReadError> {
+			let parent = read_index(input, header.index_size)?;
+			let mut vertex = vec![0; stride];
+			input.read_exact(&mut vertex)?;
+			let corners = (0..input.read_u16::<LittleEndian>()?).map(|_| input.read_u32::<LittleEndian>()).collect::<Result<Vec<u32>, _>>()?;
+			let indices = (0..input.read_u16::<LittleEndian>()? as usize * 3).map(|_| read_index(input, header.index_size)).collect::<Result<Vec<u32>, _>>()?;
+			Ok((parent, vertex, corners, indices))
+		})();
+		let (parent, vertex, corners, indices) = match split {
+			Ok(split) => split,
+			Err(_) if partial => break,
+			Err(_) => return Err(ReadError::InvalidChunkSize("PROG")),
+		};
+		data = *input;
+
+		let added = mesh.positions.len();
+		if added >= header.vertex_count || parent as usize >= added || mesh.indices.len() + indices.len() > header.index_count {
+			return Err(ReadError::InvalidSplit(added));
+		}
+		let vertex = match palette_tables {
+			Some(tables) if !header.format.palette.is_empty() =>
+				Cow::Owned(palette::expand(&vertex, &header.format, &header.user_attributes, tables).map_err(|_| ReadError::InvalidPaletteIndex(added))?),
+			_ => Cow::Borrowed(&vertex[..]),
+		};
+		read_vertices(&mut &vertex[..], header, 1, mesh)?;
+		for corner in corners {
+			match mesh.indices.get_mut(corner as usize) {
+				Some(index) if *index == parent => *index = added as u32,
+				_ => return Err(ReadError::InvalidSplit(added)),
+			}
+		}
+		if indices.iter().any(|&index| index as usize > added) {
+			return Err(ReadError::InvalidSplit(added));
+		}
+		mesh.indices.extend(indices);
+	}
+	Ok(())
+}
+
+fn check_indices(indices: &[u32], vertex_count: usize) -> Result<(), ReadError> {
+	match indices.iter().position(|&idx| idx as usize >= vertex_count) {
+		Some(index) => Err(ReadError::IndexOutOfRange { index, value: indices[index], vertex_count }),
+		None => Ok(()),
+	}
+}
+
+fn read_mirror<R: Read>(input: &mut R, header: &Header) -> Result<MirrorData, ReadError> {
+	let axis = input.read_u8()?;
+	if axis > 2 {
+		return Err(ReadError::InvalidMirrorAxis(axis));
+	}
+	let offset = input.read_f32::<LittleEndian>()?;
+	let mirrored_indices = if read_flag(input, "mirrored indices")? { Some(read_index_list(input, header, header.index_count)?) } else { None };
+	Ok(MirrorData {
+		axis: axis as usize,
+		offset,
+		mirrored_indices,
+	})
+}
+fn read_morph_targets<R: Read>(input: &mut R, vertex_count: usize, with_normals: bool) -> Result<Vec<MorphTargetData>, ReadError> {
+	let target_count = input.read_u32::<LittleEndian>()? as usize;
+	let mut targets = Vec::with_capacity(capacity(target_count));
+
+	for target in 0..target_count {
+		let mut name = vec![0; input.read_u16::<LittleEndian>()? as usize];
+		input.read_exact(&mut name)?;
+		let name = String::from_utf8(name).map_err(|_| ReadError::InvalidMorphName(target))?;
+
+		let delta_count = input.read_u32::<LittleEndian>()? as usize;
+		let mut deltas = Vec::with_capacity(capacity(delta_count));
+		for _ in 0..delta_count {
+			let vertex = input.read_u32::<LittleEndian>()?;
+			if vertex as usize >= vertex_count {
+				return Err(ReadError::MorphVertexOutOfRange { target, vertex, vertex_count });
+			}
+
+			deltas.push(MorphDelta {
+				vertex,
+				position: read_vec3(input)?,
+				normal: if with_normals { Some(read_vec3(input)?) } else { None },
+			});
+		}
+
+		targets.push(MorphTargetData {
+			name,
+			deltas,
+		});
+	}
+
+	Ok(targets)
+}
+
+fn read_submeshes<R: Read>(input: &mut R, index_count: usize) -> Result<Vec<SubmeshData>, ReadError> {
+	let count = input.read_u32::<LittleEndian>()? as usize;
+	let mut submeshes = Vec::with_capacity(capacity(count));
+
+	for i in 0..count {
+		let first_index = input.read_u32::<LittleEndian>()?;
+		let submesh_count = input.read_u32::<LittleEndian>()?;
+		if first_index as usize + submesh_count as usize > index_count {
+			return Err(ReadError::InvalidSubmesh(i));
+		}
+
+		let mut name = vec![0; input.read_u16::<LittleEndian>()? as usize];
+		input.read_exact(&mut name)?;
+		let name = String::from_utf8(name).map_err(|_| ReadError::InvalidSubmesh(i))?;
+
+		submeshes.push(SubmeshData {
+			material: if name.is_empty() { None } else { Some(name) },
+			first_index,
+			index_count: submesh_count,
+			min: read_vec3(input)?,
+			max: read_vec3(input)?,
+			center: read_vec3(input)?,
+			radius: input.read_f32::<LittleEndian>()?,
+		});
+	}
+
+	Ok(submeshes)
+}
+
+fn read_bounds(input: &mut &[u8], mesh: &mut MeshData) -> Result<(), ReadError> {
+	while !input.is_empty() {
+		let kind = input.read_u8()?;
+		let size = input.read_u32::<LittleEndian>()? as usize;
+		if size > input.len() {
+			return Err(ReadError::InvalidChunkSize("BNDS"));
+		}
+		let (body, rest) = input.split_at(size);
+		*input = rest;
+
+		let body = &mut &body[..];
+		match kind {
+			chunk::BOUNDS_OBB => mesh.obb = Some(OrientedBoxData {
+				center: read_vec3(body)?,
+				half_extents: read_vec3(body)?,
+				axes: [read_vec3(body)?, read_vec3(body)?, read_vec3(body)?],
+			}),
+			chunk::BOUNDS_KDOP => {
+				let k = body.read_u8()?;
+				//skipped like the unknown volumes
+				let Some(axes) = chunk::kdop_axes(k) else {
+					continue;
+				};
+				let mut slabs = Vec::with_capacity(axes.len());
+				for _ in &axes {
+					slabs.push([body.read_f32::<LittleEndian>()?, body.read_f32::<LittleEndian>()?]);
+				}
+				mesh.kdop = Some(KdopData { k, slabs });
+			},
+			//unknown volumes are skipped
+			_ => {},
+		}
+	}
+	Ok(())
+}
+
+fn read_bvh(input: &mut &[u8], index_count: usize) -> Result<BvhData, ReadError> {
+	let size = input.len();
+	let node_count = input.read_u32::<LittleEndian>()? as usize;
+	let triangle_count = input.read_u32::<LittleEndian>()? as usize;
+	let nodes_offset = input.read_u32::<LittleEndian>()? as usize;
+	if nodes_offset < 12 || nodes_offset.saturating_add(node_count * 32).saturating_add(triangle_count * 4) != size {
+		return Err(ReadError::InvalidChunkSize("BVH"));
+	}
+	*input = &input[nodes_offset - 12..];
+
+	let mut nodes = Vec::with_capacity(node_count);
+	for i in 0..node_count {
+		let node = BvhNodeData {
+			min: read_vec3(input)?,
+			max: read_vec3(input)?,
+			offset: input.read_u32::<LittleEndian>()?,
+			count: input.read_u32::<LittleEndian>()?,
+		};
+		let valid = if node.count == 0 {
+			node.offset as usize > i + 1 && (node.offset as usize) < node_count
+		}
+		else {
+			node.offset as usize + node.count as usize <= triangle_count
+		};
+		if !valid {
+			return Err(ReadError::InvalidBvhNode(i));
+		}
+		nodes.push(node);
+	}
+
+	let mut triangles = Vec::with_capacity(triangle_count);
+	for i in 0..triangle_count {
+		let t = input.read_u32::<LittleEndian>()?;
+		if t as usize >= index_count / 3 {
+			return Err(ReadError::BvhTriangleOutOfRange { entry: i, triangle: t, triangle_count: index_count / 3 });
+		}
+		triangles.push(t);
+	}
+
+	Ok(BvhData { nodes, triangles })
+}
+
+fn read_metadata<R: Read>(input: &mut R) -> Result<Vec<(String, String)>, ReadError> {
+	let count = input.read_u16::<LittleEndian>()? as usize;
+	let mut pairs = Vec::with_capacity(count);
+	for i in 0..count {
+		let mut text = || -> Result<String, ReadError> {
+			let mut bytes = vec![0; input.read_u16::<LittleEndian>()? as usize];
+			input.read_exact(&mut bytes)?;
+			String::from_utf8(bytes).map_err(|_| ReadError::InvalidMetadata(i))
+		};
+		let key = text()?;
+		let value = text()?;
+		pairs.push((key, value));
+	}
+	Ok(pairs)
+}
+
+fn read_instances<R: Read>(input: &mut R) -> Result<Vec<InstanceData>, ReadError> {
+	let count = input.read_u32::<LittleEndian>()? as usize;
+	let mut instances = Vec::with_capacity(capacity(count));
+	for i in 0..count {
+		let mut name = vec![0; input.read_u16::<LittleEndian>()? as usize];
+		input.read_exact(&mut name)?;
+		let name = String::from_utf8(name).map_err(|_| ReadError::InvalidInstance(i))?;
+
+		let mut transform = [[0.0; 4]; 3];
+		for row in &mut transform {
+			for c in row.iter_mut() {
+				*c = input.read_f32::<LittleEndian>()?;
+			}
+		}
+		instances.push(InstanceData { name, transform });
+	}
+	Ok(instances)
+}
+
+fn read_materials<R: Read>(input: &mut R) -> Result<Vec<MaterialData>, ReadError> {
+	let count = input.read_u16::<LittleEndian>()? as usize;
+	let mut materials = Vec::with_capacity(count);
+	for i in 0..count {
+		let name = read_text(input, ReadError::InvalidMaterial(i))?;
+		let texture_count = input.read_u16::<LittleEndian>()? as usize;
+		let mut textures = Vec::with_capacity(texture_count);
+		for _ in 0..texture_count {
+			let kind = read_text(input, ReadError::InvalidMaterial(i))?;
+			let source = match input.read_u8()? {
+				chunk::TEXTURE_PATH => TextureSource::Path(read_text(input, ReadError::InvalidMaterial(i))?),
+				chunk::TEXTURE_EMBEDDED => TextureSource::Embedded(input.read_u32::<LittleEndian>()? as usize),
+				_ => return Err(ReadError::InvalidMaterial(i)),
+			};
+			textures.push(TextureData { kind, source });
+		}
+
+		let pbr = if read_flag(input, "pbr")? {
+			let mut base_color = [0.0; 4];
+			for c in &mut base_color {
+				*c = input.read_f32::<LittleEndian>()?;
+			}
+			let metallic = input.read_f32::<LittleEndian>()?;
+			let roughness = input.read_f32::<LittleEndian>()?;
+			let emissive = read_vec3(input)?;
+			let mut slots = [None; 5];
+			for slot in &mut slots {
+				let index = input.read_u16::<LittleEndian>()?;
+				if index != chunk::NO_TEXTURE {
+					if index as usize >= texture_count {
+						return Err(ReadError::InvalidMaterial(i));
+					}
+					*slot = Some(index as usize);
+				}
+			}
+			let [base_color_texture, metallic_texture, roughness_texture, normal_texture, emissive_texture] = slots;
+			Some(PbrData { base_color, metallic, roughness, emissive, base_color_texture, metallic_texture, roughness_texture, normal_texture, emissive_texture })
+		}
+		else {
+			None
+		};
+		materials.push(MaterialData { name, textures, pbr });
+	}
+	Ok(materials)
+}
+
+/// A u16 length and UTF-8 text, failing with `invalid` if it isn't valid
+fn read_text<R: Read>(input: &mut R, invalid: ReadError) -> Result<String, ReadError> {
+	let mut text = vec![0; input.read_u16::<LittleEndian>()? as usize];
+	input.read_exact(&mut text)?;
+	String::from_utf8(text).map_err(|_| invalid)
+}
+
+fn read_cells<R: Read>(input: &mut R) -> Result<Vec<CellData>, ReadError> {
+	let count = input.read_u32::<LittleEndian>()? as usize;
+	let mut cells = Vec::with_capacity(capacity(count));
+	for i in 0..count {
+		let name = read_text(input, ReadError::InvalidCell(i))?;
+		let (min, max) = (read_vec3(input)?, read_vec3(input)?);
+
+		let portal_count = input.read_u32::<LittleEndian>()? as usize;
+		let mut portals = Vec::with_capacity(capacity(portal_count));
+		for _ in 0..portal_count {
+			let other = read_text(input, ReadError::InvalidCell(i))?;
+			let (center, normal) = (read_vec3(input)?, read_vec3(input)?);
+			let corner_count = input.read_u32::<LittleEndian>()? as usize;
+			let corners = (0..corner_count).map(|_| read_vec3(input)).collect::<Result<_, _>>()?;
+			portals.push(PortalData { other, center, normal, corners });
+		}
+		cells.push(CellData { name, min, max, portals });
+	}
+	Ok(cells)
+}
+
+#[cfg(all(test, feature = "converter"))]
+mod tests {
+	use super::*;
+	use crate::convert::ConvertOptions;
+	use crate::fixtures;
+
+	fn cube(format_version: Option<u32>) -> Vec<u8> {
+		let options = ConvertOptions { generate_tangents: true, format_version, ..Default::default() };
+		fixtures::convert_one(fixtures::CUBE, &options)
+	}
+
+	#[test]
+	fn both_versions() {
+		let (legacy, latest) = (cube(Some(chunk::LEGACY_VERSION)), cube(None));
+		assert!(!legacy.starts_with(&chunk::MAGIC) && latest.starts_with(&chunk::MAGIC));
+		assert_eq!(read_header(&mut &legacy[..]).unwrap().version, chunk::LEGACY_VERSION);
+		assert_eq!(read_header(&mut &latest[..]).unwrap().version, chunk::VERSION);
+
+		let (legacy, latest) = (fixtures::read(&legacy), fixtures::read(&latest));
+		assert_eq!((legacy.positions.len(), legacy.indices.len()), (24, 36));
+		assert_eq!(legacy.positions, latest.positions);
+		assert_eq!(legacy.normals, latest.normals);
+		assert_eq!(legacy.tangents, latest.tangents);
+		assert_eq!(legacy.colors, latest.colors);
+		assert_eq!(legacy.tex0, latest.tex0);
+		assert_eq!(legacy.indices, latest.indices);
+		//only the chunks have the submeshes and the checksum
+		assert!(legacy.submeshes.is_empty() && legacy.checksum.is_none());
+		assert_eq!(latest.submeshes.len(), 1);
+		assert!(latest.checksum.is_some());
+	}
+
+	#[test]
+	fn truncated() {
+		let legacy = cube(Some(chunk::LEGACY_VERSION));
+		for len in 0..legacy.len() {
+			assert!(read_mesh(&mut &legacy[..len]).is_err(), "{} of {} bytes", len, legacy.len());
+		}
+		let latest = cube(None);
+		let ends = fixtures::chunk_ends(&latest);
+		for len in 0..latest.len() {
+			let read = read_mesh(&mut &latest[..len]);
+			assert!(read.is_err() || ends.contains(&len), "{} of {} bytes", len, latest.len());
+			assert!(read_header(&mut &latest[..len]).is_err() || len >= ends[0]);
+		}
+	}
+
+	#[test]
+	fn corrupted() {
+		let file = cube(None);
+		//every byte before the checksum is covered by it
+		let checked = file.len() - 12;
+		for i in 8..checked {
+			let mut corrupted = file.clone();
+			corrupted[i] ^= 0x10;
+			match read_mesh(&mut &corrupted[..]) {
+				Err(ReadError::ChecksumMismatch { expected, .. }) => assert_eq!(expected, fixtures::read(&file).checksum.unwrap()),
+				//the chunk lengths can move where the checksum is read from
+				Err(_) => {},
+				Ok(_) => panic!("byte {} was changed", i),
+			}
+		}
+		let mut vertex = file.clone();
+		vertex[fixtures::chunk_ends(&file)[0] + 8] ^= 1;
+		assert!(matches!(read_mesh(&mut &vertex[..]), Err(ReadError::ChecksumMismatch { .. })));
+
+		//the values of the header are checked without a checksum, and no byte makes the reader panic
+		let mut legacy = cube(Some(chunk::LEGACY_VERSION));
+		legacy[0] = 3;
+		assert!(matches!(read_mesh(&mut &legacy[..]), Err(ReadError::InvalidIndexSize(3))));
+		for i in 0..legacy.len() {
+			let mut corrupted = cube(Some(chunk::LEGACY_VERSION));
+			corrupted[i] = 0xff;
+			let _ = read_mesh(&mut &corrupted[..]);
+		}
+	}
+}
@@ -0,0 +1,317 @@
+//! Isotropic remeshing, which turns the slivers and uneven triangles of scans into triangles of about the same
+//! size, better for deformation and simplification.
+//!
+//! Each iteration splits the edges longer than 4/3 of the target length, collapses the ones shorter than 4/5 of
+//! it, flips the edges that bring the vertices closer to 6 neighbors, and moves the vertices towards the center
+//! of their neighbors along the surface. The open borders, the non-manifold edges and the borders between groups
+//! stay in place. The texture coordinates and normals of the file don't survive it.
+
+use std::collections::HashSet;
+use wavefront_obj::obj::{Object, Shape, Vertex};
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemeshSettings {
+	/// The length the edges get close to
+	pub edge_length: f64,
+	pub iterations: usize,
+}
+
+impl RemeshSettings {
+	pub fn new(edge_length: f64) -> Self {
+		RemeshSettings {
+			edge_length,
+			iterations: 5,
+		}
+	}
+}
+
+struct TriMesh {
+	positions: Vec<Vertex>,
+	triangles: Vec<[usize; 3]>,
+	/// The geometry of the object each triangle comes from
+	groups: Vec<usize>,
+	alive: Vec<bool>,
+	/// The live triangles around each position
+	around: Vec<Vec<usize>>,
+}
+
+fn add(a: Vertex, b: Vertex) -> Vertex {
+	let mut sum = a;
+	addmut(&mut sum, b);
+	sum
+}
+
+fn normal(p: [Vertex; 3]) -> Vertex {
+	cross(sub(p[1], p[0]), sub(p[2], p[0]))
+}
+
+impl TriMesh {
+	fn add(&mut self, t: [usize; 3], group: usize) {
+		for v in t {
+			self.around[v].push(self.triangles.len());
+		}
+		self.triangles.push(t);
+		self.groups.push(group);
+		self.alive.push(true);
+	}
+
+	fn remove(&mut self, t: usize) {
+		self.alive[t] = false;
+		for v in self.triangles[t] {
+			self.around[v].retain(|&other| other != t);
+		}
+	}
+
+	fn length(&self, a: usize, b: usize) -> f64 {
+		lenght(sub(self.positions[a], self.positions[b]))
+	}
+
+	fn face_normal(&self, t: [usize; 3]) -> Vertex {
+		normal(t.map(|v| self.positions[v]))
+	}
+
+	fn edge_triangles(&self, a: usize, b: usize) -> Vec<usize> {
+		self.around[a].iter().copied().filter(|&t| self.triangles[t].contains(&b)).collect()
+	}
+
+	fn neighbors(&self, v: usize) -> Vec<usize> {
+		let mut neighbors: Vec<usize> = self.around[v].iter()
+			.flat_map(|&t| self.triangles[t])
+			.filter(|&other| other != v)
+			.collect();
+		neighbors.sort();
+		neighbors.dedup();
+		neighbors
+	}
+
+	/// Every edge once, from the lower position
+	fn edges(&self) -> Vec<(usize, usize)> {
+		let mut edges = HashSet::new();
+		for (corners, _) in self.triangles.iter().zip(&self.alive).filter(|(_, &alive)| alive) {
+			for i in 0..3 {
+				let (a, b) = (corners[i], corners[(i + 1) % 3]);
+				edges.insert((usize::min(a, b), usize::max(a, b)));
+			}
+		}
+		let mut edges: Vec<(usize, usize)> = edges.into_iter().collect();
+		edges.sort();
+		edges
+	}
+
+	/// Open, non-manifold or between two groups
+	fn is_feature(&self, a: usize, b: usize) -> bool {
+		let triangles = self.edge_triangles(a, b);
+		triangles.len() != 2 || self.groups[triangles[0]] != self.groups[triangles[1]]
+	}
+
+	/// The positions along the features, which stay in place
+	fn fixed(&self) -> Vec<bool> {
+		let mut fixed = vec![false; self.positions.len()];
+		for (a, b) in self.edges() {
+			if self.is_feature(a, b) {
+				fixed[a] = true;
+				fixed[b] = true;
+			}
+		}
+		fixed
+	}
+
+	/// `t` turned so that it starts with the edge from `a` to `b`, if it goes that way
+	fn starting_with(&self, t: usize, a: usize, b: usize) -> Option<[usize; 3]> {
+		let [x, y, z] = self.triangles[t];
+		[[x, y, z], [y, z, x], [z, x, y]].into_iter().find(|c| c[0] == a && c[1] == b)
+	}
+
+	fn split(&mut self, a: usize, b: usize) {
+		let m = self.positions.len();
+		self.positions.push(mul(add(self.positions[a], self.positions[b]), 0.5));
+		self.around.push(vec![]);
+		for t in self.edge_triangles(a, b) {
+			let [p, q, c] = self.starting_with(t, a, b).or_else(|| self.starting_with(t, b, a)).unwrap();
+			self.remove(t);
+			self.add([p, m, c], self.groups[t]);
+			self.add([m, q, c], self.groups[t]);
+		}
+	}
+
+	/// Moves `keep` to `target` and replaces `gone` with it, unless that would tear or fold the surface or make
+	/// edges longer than `max_length`
+	fn collapse(&mut self, keep: usize, gone: usize, target: Vertex, max_length: f64) -> bool {
+		let shared = self.edge_triangles(keep, gone);
+		if shared.len() != 2 {
+			return false;
+		}
+		let (keep_neighbors, gone_neighbors) = (self.neighbors(keep), self.neighbors(gone));
+		if keep_neighbors.len() <= 3 || gone_neighbors.len() <= 3 {
+			return false;
+		}
+		//only the two opposite corners can be next to both, or the surface pinches
+		let common = keep_neighbors.iter().filter(|v| gone_neighbors.contains(v)).count();
+		if common != 2 {
+			return false;
+		}
+		if gone_neighbors.iter().chain(&keep_neighbors).any(|&n| n != keep && n != gone && lenght(sub(target, self.positions[n])) > max_length) {
+			return false;
+		}
+		let moved = |v: usize| if v == gone { keep } else { v };
+		for &t in self.around[keep].iter().chain(&self.around[gone]).filter(|t| !shared.contains(t)) {
+			let before = self.face_normal(self.triangles[t]);
+			let after = normal(self.triangles[t].map(|v| if moved(v) == keep { target } else { self.positions[v] }));
+			if dot(before, after) <= 0.0 {
+				return false;
+			}
+		}
+
+		for t in shared {
+			self.remove(t);
+		}
+		for t in self.around[gone].clone() {
+			let corners = self.triangles[t].map(moved);
+			self.remove(t);
+			self.add(corners, self.groups[t]);
+		}
+		self.positions[keep] = target;
+		true
+	}
+
+	/// Flips the edge between `a` and `b` when it brings its four vertices closer to their ideal valence, 6 inside
+	/// and 4 along the features
+	fn flip(&mut self, a: usize, b: usize, fixed: &[bool]) {
+		let triangles = self.edge_triangles(a, b);
+		if triangles.len() != 2 || self.groups[triangles[0]] != self.groups[triangles[1]] {
+			return;
+		}
+		let (t1, t2) = match self.starting_with(triangles[0], a, b) {
+			Some(_) => (triangles[0], triangles[1]),
+			None => (triangles[1], triangles[0]),
+		};
+		let (Some([_, _, c]), Some([_, _, d])) = (self.starting_with(t1, a, b), self.starting_with(t2, b, a)) else {
+			return;
+		};
+		if c == d || self.neighbors(c).contains(&d) {
+			return;
+		}
+
+		let deviation = |v: usize, change: i64| {
+			let ideal = if fixed[v] { 4 } else { 6 };
+			(self.neighbors(v).len() as i64 + change - ideal).abs()
+		};
+		let before = deviation(a, 0) + deviation(b, 0) + deviation(c, 0) + deviation(d, 0);
+		let after = deviation(a, -1) + deviation(b, -1) + deviation(c, 1) + deviation(d, 1);
+		if after >= before {
+			return;
+		}
+		let up = add(self.face_normal(self.triangles[t1]), self.face_normal(self.triangles[t2]));
+		let (n1, n2) = (self.face_normal([a, d, c]), self.face_normal([d, b, c]));
+		if dot(n1, up) <= 0.0 || dot(n2, up) <= 0.0 {
+			return;
+		}
+
+		let group = self.groups[t1];
+		self.remove(t1);
+		self.remove(t2);
+		self.add([a, d, c], group);
+		self.add([d, b, c], group);
+	}
+
+	/// Moves each free vertex halfway to the center of its neighbors, in the plane of its normal
+	fn smooth(&mut self, fixed: &[bool]) {
+		let moved: Vec<Vertex> = (0..self.positions.len()).map(|v| {
+			let p = self.positions[v];
+			let neighbors = self.neighbors(v);
+			if fixed[v] || neighbors.is_empty() {
+				return p;
+			}
+			let mut center = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+			for &n in &neighbors {
+				addmut(&mut center, mul(self.positions[n], 1.0 / neighbors.len() as f64));
+			}
+			let mut n = Vertex { x: 0.0, y: 0.0, z: 0.0 };
+			for &t in &self.around[v] {
+				addmut(&mut n, self.face_normal(self.triangles[t]));
+			}
+			if lenght(n) == 0.0 {
+				return p;
+			}
+			let n = normalize(n);
+			let d = sub(center, p);
+			add(p, mul(sub(d, mul(n, dot(n, d))), 0.5))
+		}).collect();
+		self.positions = moved;
+	}
+}
+
+/// Returns a copy of the triangles of `obj` remeshed with `settings`, and keeping the groups and materials of
+/// the geometries. The positions no triangle uses anymore are left in place
+pub fn remesh(obj: &Object, settings: &RemeshSettings) -> Object {
+	assert!(settings.edge_length > 0.0, "Can't remesh to edges of length 0");
+	let mut mesh = TriMesh {
+		positions: obj.vertices.clone(),
+		triangles: vec![],
+		groups: vec![],
+		alive: vec![],
+		around: vec![vec![]; obj.vertices.len()],
+	};
+	for (g, geo) in obj.geometry.iter().enumerate() {
+		for shape in &geo.shapes {
+			if let Shape::Triangle(a, b, c) = *shape {
+				if a.0 != b.0 && b.0 != c.0 && a.0 != c.0 {
+					mesh.add([a.0, b.0, c.0], g);
+				}
+			}
+		}
+	}
+
+	let (high, low) = (settings.edge_length * 4.0 / 3.0, settings.edge_length * 4.0 / 5.0);
+	for _ in 0..settings.iterations {
+		//the halves can still be too long
+		loop {
+			let long: Vec<(usize, usize)> = mesh.edges().into_iter().filter(|&(a, b)| mesh.length(a, b) > high).collect();
+			if long.is_empty() {
+				break;
+			}
+			for (a, b) in long {
+				mesh.split(a, b);
+			}
+		}
+
+		let fixed = mesh.fixed();
+		let mut short: Vec<(usize, usize)> = mesh.edges().into_iter().filter(|&(a, b)| mesh.length(a, b) < low).collect();
+		short.sort_by(|&(a, b), &(c, d)| mesh.length(a, b).total_cmp(&mesh.length(c, d)));
+		for (a, b) in short {
+			//the collapses before can have removed it
+			if mesh.around[a].is_empty() || mesh.around[b].is_empty() || mesh.edge_triangles(a, b).is_empty() {
+				continue;
+			}
+			let (keep, gone, target) = match (fixed[a], fixed[b]) {
+				(true, true) => continue,
+				(true, false) => (a, b, mesh.positions[a]),
+				(false, true) => (b, a, mesh.positions[b]),
+				(false, false) => (a, b, mul(add(mesh.positions[a], mesh.positions[b]), 0.5)),
+			};
+			mesh.collapse(keep, gone, target, high);
+		}
+
+		let fixed = mesh.fixed();
+		for (a, b) in mesh.edges() {
+			mesh.flip(a, b, &fixed);
+		}
+		mesh.smooth(&fixed);
+	}
+
+	let mut result = obj.clone();
+	result.vertices = mesh.positions.clone();
+	result.tex_vertices.clear();
+	result.normals.clear();
+	for (g, geo) in result.geometry.iter_mut().enumerate() {
+		geo.shapes = (0..mesh.triangles.len())
+			.filter(|&t| mesh.alive[t] && mesh.groups[t] == g)
+			.map(|t| {
+				let [a, b, c] = mesh.triangles[t];
+				Shape::Triangle((a, None, None), (b, None, None), (c, None, None))
+			})
+			.collect();
+	}
+	result
+}
@@ -0,0 +1,201 @@
+//! A summary of what the conversion of each asset did, written as markdown or HTML for asset reviews.
+
+use std::fmt::Write;
+use wavefront_obj::obj::{ObjSet, Shape};
+use crate::layout::Format;
+use crate::quantization::{AttributeError, QuantizationReport};
+use crate::reader::{self, Topology};
+
+/// The size of the FIFO cache the ACMR is measured with, about what the GPUs have
+pub const ACMR_CACHE_SIZE: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetReport {
+	pub source: String,
+	pub output: String,
+	pub source_bytes: usize,
+	/// The positions of the obj file
+	pub source_vertices: usize,
+	pub source_triangles: usize,
+	/// Of all the mesh files written for the asset
+	pub vertices: usize,
+	pub triangles: usize,
+	pub bytes: usize,
+	/// Of the first mesh file, which the others share
+	pub attributes: Vec<(String, Format)>,
+	pub stride: usize,
+	pub index_size: u8,
+	/// Vertices transformed per triangle, with a cache of `ACMR_CACHE_SIZE`; from 3 down to about 0.5
+	pub acmr: f64,
+	/// How far the vertex formats move the attributes
+	pub quantization: QuantizationReport,
+	pub warnings: Vec<String>,
+}
+
+impl AssetReport {
+	pub fn new(source: &str, output: &str, source_bytes: usize, set: &ObjSet) -> Self {
+		let triangles = set.objects.iter()
+			.flat_map(|obj| &obj.geometry)
+			.flat_map(|geo| &geo.shapes)
+			.filter(|shape| matches!(shape, Shape::Triangle(..)))
+			.count();
+		AssetReport {
+			source: source.to_owned(),
+			output: output.to_owned(),
+			source_bytes,
+			source_vertices: set.objects.iter().map(|obj| obj.vertices.len()).sum(),
+			source_triangles: triangles,
+			vertices: 0,
+			triangles: 0,
+			bytes: 0,
+			attributes: vec![],
+			stride: 0,
+			index_size: 0,
+			acmr: 0.0,
+			quantization: QuantizationReport::default(),
+			warnings: vec![],
+		}
+	}
+
+	/// Adds a mesh file written for the asset
+	pub fn add_part(&mut self, data: &[u8]) {
+		let mesh = reader::read_mesh(&mut &data[..]).unwrap();
+		if self.bytes == 0 {
+			let layout = mesh.header.layout();
+			self.attributes = layout.attributes.iter().map(|attr| (attr.name.clone(), attr.format)).collect();
+			self.stride = layout.stride;
+			self.index_size = mesh.header.index_size;
+		}
+		self.bytes += data.len();
+		self.vertices += mesh.positions.len();
+		if mesh.header.topology == Topology::TriangleList {
+			//weighted by the triangles of each part
+			let triangles = mesh.indices.len() / 3;
+			let total = self.triangles + triangles;
+			if total > 0 {
+				self.acmr = (self.acmr * self.triangles as f64 + acmr(&mesh.indices, ACMR_CACHE_SIZE) * triangles as f64) / total as f64;
+			}
+			self.triangles = total;
+		}
+	}
+}
+
+/// The average cache miss ratio of a triangle list drawn through a FIFO cache of `cache_size` vertices
+pub fn acmr(indices: &[u32], cache_size: usize) -> f64 {
+	if indices.len() < 3 {
+		return 0.0;
+	}
+	let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+	let mut misses = 0;
+	for &index in indices {
+		if !cache.contains(&index) {
+			misses += 1;
+			if cache.len() == cache_size {
+				cache.pop_front();
+			}
+			cache.push_back(index);
+		}
+	}
+	misses as f64 / (indices.len() / 3) as f64
+}
+
+/// The max and mean error of an attribute, empty for those that aren't measured
+fn error_columns(report: &AssetReport, name: &str) -> (String, String) {
+	match errors(report, name) {
+		Some(errors) => (format!("{:.3e}", errors.max), format!("{:.3e}", errors.mean())),
+		None => (String::new(), String::new()),
+	}
+}
+
+fn errors<'a>(report: &'a AssetReport, name: &str) -> Option<&'a AttributeError> {
+	report.quantization.attributes.iter().find(|errors| errors.attribute.name() == name)
+}
+
+fn rows(report: &AssetReport) -> [(&'static str, String, String); 3] {
+	[
+		("Vertices", report.source_vertices.to_string(), report.vertices.to_string()),
+		("Triangles", report.source_triangles.to_string(), report.triangles.to_string()),
+		("Bytes", report.source_bytes.to_string(), report.bytes.to_string()),
+	]
+}
+
+pub fn markdown(reports: &[AssetReport]) -> String {
+	let mut out = String::from("# Conversion report\n");
+	for report in reports {
+		writeln!(out, "\n## {} → {}\n", report.source, report.output).unwrap();
+		out.push_str("| | Source | Converted |\n|---|---:|---:|\n");
+		for (name, before, after) in rows(report) {
+			writeln!(out, "| {} | {} | {} |", name, before, after).unwrap();
+		}
+		writeln!(out, "\nACMR {:.3} with a cache of {} vertices, {} byte indices, {} byte vertices:\n", report.acmr, ACMR_CACHE_SIZE, report.index_size, report.stride).unwrap();
+		out.push_str("| Attribute | Format | Max error | Mean error |\n|---|---|---:|---:|\n");
+		for (name, format) in &report.attributes {
+			let (max, mean) = error_columns(report, name);
+			writeln!(out, "| {} | {:?} | {} | {} |", name, format, max, mean).unwrap();
+		}
+		let worst: Vec<&AttributeError> = report.quantization.attributes.iter().filter(|errors| !errors.worst.is_empty()).collect();
+		if !worst.is_empty() {
+			out.push_str("\nLargest errors:\n\n");
+			for errors in worst {
+				let vertices: Vec<String> = errors.worst.iter().map(|w| format!("{} of {} ({:.3e})", w.vertex, w.part, w.error)).collect();
+				writeln!(out, "- {}, {}: vertices {}", errors.attribute.name(), errors.unit(), vertices.join(", ")).unwrap();
+			}
+		}
+		if report.warnings.is_empty() {
+			out.push_str("\nNo warnings.\n");
+		}
+		else {
+			out.push_str("\nWarnings:\n\n");
+			for warning in &report.warnings {
+				writeln!(out, "- {}", warning).unwrap();
+			}
+		}
+	}
+	out
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn html(reports: &[AssetReport]) -> String {
+	let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Conversion report</title>\n");
+	out.push_str("<style>body { font-family: sans-serif; } table { border-collapse: collapse; } td, th { border: 1px solid #ccc; padding: 2px 8px; } td.n { text-align: right; }</style>\n");
+	out.push_str("</head>\n<body>\n<h1>Conversion report</h1>\n");
+	for report in reports {
+		writeln!(out, "<h2>{} → {}</h2>", escape(&report.source), escape(&report.output)).unwrap();
+		out.push_str("<table>\n<tr><th></th><th>Source</th><th>Converted</th></tr>\n");
+		for (name, before, after) in rows(report) {
+			writeln!(out, "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>", name, before, after).unwrap();
+		}
+		out.push_str("</table>\n");
+		writeln!(out, "<p>ACMR {:.3} with a cache of {} vertices, {} byte indices, {} byte vertices:</p>", report.acmr, ACMR_CACHE_SIZE, report.index_size, report.stride).unwrap();
+		out.push_str("<table>\n<tr><th>Attribute</th><th>Format</th><th>Max error</th><th>Mean error</th></tr>\n");
+		for (name, format) in &report.attributes {
+			let (max, mean) = error_columns(report, name);
+			writeln!(out, "<tr><td>{}</td><td>{:?}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>", escape(name), format, max, mean).unwrap();
+		}
+		out.push_str("</table>\n");
+		let worst: Vec<&AttributeError> = report.quantization.attributes.iter().filter(|errors| !errors.worst.is_empty()).collect();
+		if !worst.is_empty() {
+			out.push_str("<p>Largest errors:</p>\n<ul>\n");
+			for errors in worst {
+				let vertices: Vec<String> = errors.worst.iter().map(|w| format!("{} of {} ({:.3e})", w.vertex, escape(&w.part), w.error)).collect();
+				writeln!(out, "<li>{}, {}: vertices {}</li>", errors.attribute.name(), errors.unit(), vertices.join(", ")).unwrap();
+			}
+			out.push_str("</ul>\n");
+		}
+		if report.warnings.is_empty() {
+			out.push_str("<p>No warnings.</p>\n");
+		}
+		else {
+			out.push_str("<p>Warnings:</p>\n<ul>\n");
+			for warning in &report.warnings {
+				writeln!(out, "<li>{}</li>", escape(warning)).unwrap();
+			}
+			out.push_str("</ul>\n");
+		}
+	}
+	out.push_str("</body>\n</html>\n");
+	out
+}
@@ -0,0 +1,161 @@
+//! The JSON-RPC 2.0 messages of `obj_to_mesh daemon --json-rpc`, one per line: reading the requests and their
+//! parameters, and writing the responses and notifications. Batches aren't supported.
+
+use crate::json::{self, Value};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// A mesh file that can't be read
+pub const READ_FAILED: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+	pub code: i64,
+	pub message: String,
+}
+
+impl RpcError {
+	pub fn new(code: i64, message: String) -> RpcError {
+		RpcError { code, message }
+	}
+
+	pub fn invalid_params(message: String) -> RpcError {
+		RpcError::new(INVALID_PARAMS, message)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+	/// As JSON, to answer with; None for the notifications, which get no response
+	pub id: Option<String>,
+	pub method: String,
+	/// Null when there are none
+	pub params: Value,
+}
+
+/// A number or a string as JSON, the only ids JSON-RPC allows besides null
+fn id_json(id: &Value) -> Option<String> {
+	match id {
+		Value::Number(n) => Some(format!("{}", n)),
+		Value::String(text) => Some(json::string(text)),
+		Value::Null => Some("null".to_owned()),
+		_ => None,
+	}
+}
+
+fn member<'a>(object: &'a Value, name: &str) -> Option<&'a Value> {
+	match object {
+		Value::Object(members) => members.iter().find(|(key, _, _)| key == name).map(|(_, _, value)| value),
+		_ => None,
+	}
+}
+
+/// The request of a line, or the error to answer with and the id to answer it to, which is null when the request
+/// has no valid one
+pub fn parse_request(line: &str) -> Result<Request, (String, RpcError)> {
+	let message = match json::parse(line) {
+		Ok(message) => message,
+		Err(err) => return Err(("null".to_owned(), RpcError::new(PARSE_ERROR, err.message))),
+	};
+	let id = match member(&message, "id") {
+		Some(id) => match id_json(id) {
+			Some(id) => Some(id),
+			None => return Err(("null".to_owned(), RpcError::new(INVALID_REQUEST, "The id has to be a number or a string".to_owned()))),
+		},
+		None => None,
+	};
+	let invalid = |message: &str| Err((id.clone().unwrap_or_else(|| "null".to_owned()), RpcError::new(INVALID_REQUEST, message.to_owned())));
+	if member(&message, "jsonrpc") != Some(&Value::String("2.0".to_owned())) {
+		return invalid("Only JSON-RPC 2.0 is supported");
+	}
+	let method = match member(&message, "method") {
+		Some(Value::String(method)) => method.clone(),
+		_ => return invalid("The method has to be a string"),
+	};
+	let params = match member(&message, "params") {
+		None => Value::Null,
+		Some(params @ (Value::Object(_) | Value::Array(_))) => params.clone(),
+		Some(_) => return invalid("The params have to be an object or an array"),
+	};
+	Ok(Request { id, method, params })
+}
+
+pub fn response(id: &str, result: &str) -> String {
+	format!("{{\"jsonrpc\": \"2.0\", \"id\": {}, \"result\": {}}}", id, result)
+}
+
+pub fn error_response(id: &str, error: &RpcError) -> String {
+	format!("{{\"jsonrpc\": \"2.0\", \"id\": {}, \"error\": {{\"code\": {}, \"message\": {}}}}}", id, error.code, json::string(&error.message))
+}
+
+pub fn notification(method: &str, params: &str) -> String {
+	format!("{{\"jsonrpc\": \"2.0\", \"method\": {}, \"params\": {}}}", json::string(method), params)
+}
+
+/// A string parameter, when there is one
+pub fn string_param(params: &Value, name: &str) -> Result<Option<String>, RpcError> {
+	match member(params, name) {
+		None | Some(Value::Null) => Ok(None),
+		Some(Value::String(text)) => Ok(Some(text.clone())),
+		Some(_) => Err(RpcError::invalid_params(format!("{} has to be a string", name))),
+	}
+}
+
+/// A number parameter, when there is one
+pub fn number_param(params: &Value, name: &str) -> Result<Option<f64>, RpcError> {
+	match member(params, name) {
+		None | Some(Value::Null) => Ok(None),
+		Some(Value::Number(n)) => Ok(Some(*n)),
+		Some(_) => Err(RpcError::invalid_params(format!("{} has to be a number", name))),
+	}
+}
+
+/// A string or an array of strings
+pub fn strings_param(params: &Value, name: &str) -> Result<Vec<String>, RpcError> {
+	let invalid = || RpcError::invalid_params(format!("{} has to be a string or an array of strings", name));
+	match member(params, name) {
+		None | Some(Value::Null) => Ok(vec![]),
+		Some(Value::String(text)) => Ok(vec![text.clone()]),
+		Some(Value::Array(items)) => items.iter().map(|item| match item {
+			Value::String(text) => Ok(text.clone()),
+			_ => Err(invalid()),
+		}).collect(),
+		Some(_) => Err(invalid()),
+	}
+}
+
+/// An option value as on the command line
+fn option_value(value: &Value) -> Option<String> {
+	match value {
+		Value::String(text) => Some(text.clone()),
+		Value::Number(n) => Some(format!("{}", n)),
+		_ => None,
+	}
+}
+
+/// The options of the command line as an object, like in the job files: `true` passes a flag, `false` leaves it
+/// out, an array repeats the option
+pub fn options_param(params: &Value, name: &str) -> Result<Vec<(String, Vec<String>)>, RpcError> {
+	let members = match member(params, name) {
+		None | Some(Value::Null) => return Ok(vec![]),
+		Some(Value::Object(members)) => members,
+		Some(_) => return Err(RpcError::invalid_params(format!("{} has to be an object", name))),
+	};
+	let mut options = vec![];
+	for (option, _, value) in members {
+		let option = option.trim_start_matches('-').replace('_', "-");
+		let invalid = || RpcError::invalid_params(format!("{} has to be a boolean, a string, a number or an array of them", option));
+		match value {
+			Value::Bool(true) => options.push((option, vec![])),
+			Value::Bool(false) => {},
+			Value::Array(items) => {
+				let values = items.iter().map(option_value).collect::<Option<Vec<String>>>().ok_or_else(invalid)?;
+				options.push((option, values));
+			},
+			value => options.push((option.clone(), vec![option_value(value).ok_or_else(invalid)?])),
+		}
+	}
+	Ok(options)
+}
@@ -0,0 +1,126 @@
+//! Detection of the non-finite positions, texture coordinates and broken normals that corrupt exports contain.
+
+use wavefront_obj::obj::{Object, Shape, TVertex, Vertex};
+use crate::diagnostics::{Warning, WarningKind};
+use crate::fill;
+use crate::math::*;
+use crate::mesh::shape_corners;
+
+fn is_finite(v: Vertex) -> bool {
+	v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+}
+
+/// Zero length or non-finite normals can't be packed; these are substituted, not normalized
+fn is_broken_normal(n: Vertex) -> bool {
+	!is_finite(n) || lenght(n) < 1e-12
+}
+
+/// How far from unit length the normals of the file can be before they are reported
+pub const NORMAL_TOLERANCE: f64 = 1e-3;
+
+/// Warns about invalid values in `obj`, and the normals more than `tolerance` from unit length; with `repair`,
+/// returns a copy where the primitives using invalid positions are dropped, broken normals are replaced by
+/// generated ones, other normals are normalized and invalid texture coordinates become (0,0). With only
+/// `renormalize`, the copy just has the normals normalized
+pub fn sanitize(obj: &Object, repair: bool, renormalize: bool, tolerance: f64, warnings: &mut Vec<Warning>) -> Option<Object> {
+	let bad_positions = obj.vertices.iter().filter(|&&v| !is_finite(v)).count();
+	let bad_normals = obj.normals.iter().filter(|&&n| is_broken_normal(n)).count();
+	let unnormalized = obj.normals.iter().filter(|&&n| !is_broken_normal(n) && (lenght(n) - 1.0).abs() > tolerance).count();
+	let bad_tex = obj.tex_vertices.iter().filter(|t| !t.x.is_finite() || !t.y.is_finite()).count();
+
+	let problems = [
+		(bad_positions, "positions are not finite"),
+		(bad_normals, "normals are not finite or have zero length"),
+		(bad_tex, "texture coordinates are not finite"),
+	];
+	for &(count, problem) in &problems {
+		if count > 0 {
+			warnings.push(Warning::new(WarningKind::InvalidData, format!("{}: {} {}", obj.name, count, problem)));
+		}
+	}
+	if unnormalized > 0 {
+		let message = if repair || renormalize {
+			format!("{}: normalized {} normals more than {} from unit length", obj.name, unnormalized, tolerance)
+		}
+		else {
+			format!("{}: {} normals are not unit length", obj.name, unnormalized)
+		};
+		warnings.push(Warning::new(WarningKind::InvalidData, message));
+	}
+
+	if renormalize && !repair {
+		let mut result = obj.clone();
+		for n in &mut result.normals {
+			if !is_broken_normal(*n) {
+				*n = normalize(*n);
+			}
+		}
+		return Some(result);
+	}
+	if !repair || unnormalized == 0 && problems.iter().all(|&(count, _)| count == 0) {
+		return None;
+	}
+
+	let mut result = obj.clone();
+
+	//the positions stay, as the skin and user attributes are indexed by them
+	let mut dropped = 0;
+	for geo in &mut result.geometry {
+		geo.shapes.retain(|shape| {
+			let valid = shape_corners(shape).iter().all(|c| is_finite(obj.vertices[c.0]));
+			if !valid {
+				dropped += 1;
+			}
+			valid
+		});
+	}
+
+	//move them onto a valid one, so that they don't spoil bounding boxes
+	let replacement = obj.vertices.iter().copied().find(|&v| is_finite(v)).unwrap_or(Vertex { x: 0.0, y: 0.0, z: 0.0 });
+	for v in &mut result.vertices {
+		if !is_finite(*v) {
+			*v = replacement;
+		}
+	}
+
+	if bad_normals > 0 {
+		//the generated normals go after the ones of the file, one per position
+		let first_generated = result.normals.len();
+		result.normals.extend(fill::smooth_normals(&result));
+
+		let normals = &obj.normals;
+		for geo in &mut result.geometry {
+			for shape in &mut geo.shapes {
+				let corners = match shape {
+					Shape::Point(v1) => vec![v1],
+					Shape::Line(v1, v2) => vec![v1, v2],
+					Shape::Triangle(v1, v2, v3) => vec![v1, v2, v3],
+				};
+				for c in corners {
+					if let Some(n) = c.2 {
+						if is_broken_normal(normals[n]) {
+							c.2 = Some(first_generated + c.0);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	for n in &mut result.normals {
+		if !is_broken_normal(*n) {
+			*n = normalize(*n);
+		}
+	}
+
+	for t in &mut result.tex_vertices {
+		if !t.x.is_finite() || !t.y.is_finite() {
+			*t = TVertex { x: 0.0, y: 0.0 };
+		}
+	}
+
+	if dropped > 0 {
+		warnings.push(Warning::new(WarningKind::InvalidData, format!("{}: dropped {} primitives with invalid positions", obj.name, dropped)));
+	}
+	Some(result)
+}
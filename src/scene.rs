@@ -0,0 +1,132 @@
+//! A description of the objects of a multi-object file, written next to their meshes so that the engine can
+//! place them as they were laid out.
+//!
+//! Obj files have no hierarchy, so every node is at the root, and the transforms only differ from the identity
+//! for the instances found by `detect_instances` and the objects moved to a pivot.
+
+use std::fmt::Write;
+use crate::convert::{file_name, ConvertedMesh};
+use crate::instances::Transform;
+use crate::json;
+
+#[derive(Debug, Clone)]
+pub struct SceneMesh {
+	pub name: String,
+	/// The `.mesh` file of each part
+	pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneNode {
+	/// The object of the obj file
+	pub name: String,
+	/// The index of its mesh
+	pub mesh: usize,
+	pub transform: Transform,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scene {
+	pub meshes: Vec<SceneMesh>,
+	pub nodes: Vec<SceneNode>,
+	/// The simplified mesh of all the nodes together, drawn instead of them at a distance
+	pub hlod: Option<SceneMesh>,
+}
+
+impl Scene {
+	/// Names the files of each object after `stem` and the object: `stem.NAME.mesh`, or `stem.NAME.PART.mesh`
+	/// for the split objects, numbering the objects whose names are the same once made safe for file names
+	pub fn new(stem: &str, meshes: &[ConvertedMesh]) -> Scene {
+		let mut scene = Scene {
+			meshes: vec![],
+			nodes: vec![],
+			hlod: None,
+		};
+		let mut used: Vec<String> = vec![];
+		for (i, converted) in meshes.iter().enumerate() {
+			let mut name = file_name(&converted.name);
+			if used.contains(&name) {
+				name = format!("{}_{}", name, i);
+			}
+			used.push(name.clone());
+
+			let files = converted.part_names.iter().map(|part| {
+				if part.is_empty() { format!("{}.{}.mesh", stem, name) } else { format!("{}.{}.{}.mesh", stem, name, part) }
+			}).collect();
+			scene.meshes.push(SceneMesh {
+				name: converted.name.clone(),
+				files,
+			});
+
+			if converted.instances.is_empty() {
+				//back to where it was in the obj
+				let mut transform = Transform::identity();
+				if let Some(pivot) = converted.pivot {
+					transform.translation = pivot;
+				}
+				scene.nodes.push(SceneNode {
+					name: converted.name.clone(),
+					mesh: i,
+					transform,
+				});
+			}
+			for (name, transform) in &converted.instances {
+				scene.nodes.push(SceneNode {
+					name: name.clone(),
+					mesh: i,
+					transform: *transform,
+				});
+			}
+		}
+		scene
+	}
+
+	/// Names the files of the HLOD proxy `stem.hlod.mesh`, or `stem.hlod.PART.mesh` when it's split
+	pub fn hlod_files(stem: &str, proxy: &ConvertedMesh) -> SceneMesh {
+		SceneMesh {
+			name: proxy.name.clone(),
+			files: proxy.part_names.iter().map(|part| {
+				if part.is_empty() { format!("{}.hlod.mesh", stem) } else { format!("{}.hlod.{}.mesh", stem, part) }
+			}).collect(),
+		}
+	}
+
+	/// The transforms are row major 3x4 matrices, like in the instance lists
+	pub fn to_json(&self) -> String {
+		let mut out = String::new();
+
+		writeln!(out, "{{").unwrap();
+		writeln!(out, "\t\"meshes\": [").unwrap();
+		for (i, mesh) in self.meshes.iter().enumerate() {
+			let separator = if i + 1 < self.meshes.len() { "," } else { "" };
+			let files: Vec<String> = mesh.files.iter().map(|f| json::string(f)).collect();
+			writeln!(out, "\t\t{{ \"name\": {}, \"files\": [{}] }}{}", json::string(&mesh.name), files.join(", "), separator).unwrap();
+		}
+		writeln!(out, "\t],").unwrap();
+		writeln!(out, "\t\"nodes\": [").unwrap();
+		for (i, node) in self.nodes.iter().enumerate() {
+			let separator = if i + 1 < self.nodes.len() { "," } else { "" };
+			let t = node.transform.translation;
+			let mut transform = vec![];
+			for (row, offset) in node.transform.rotation.iter().zip([t.x, t.y, t.z]) {
+				transform.extend([row.x, row.y, row.z, offset].map(|c| format!("{:?}", c as f32)));
+			}
+			writeln!(out, "\t\t{{ \"name\": {}, \"mesh\": {}, \"transform\": [{}] }}{}",
+				json::string(&node.name),
+				node.mesh,
+				transform.join(", "),
+				separator).unwrap();
+		}
+		match self.hlod {
+			Some(ref hlod) => {
+				writeln!(out, "\t],").unwrap();
+				let files: Vec<String> = hlod.files.iter().map(|f| json::string(f)).collect();
+				writeln!(out, "\t\"hlod\": {{ \"name\": {}, \"files\": [{}] }}", json::string(&hlod.name), files.join(", ")).unwrap();
+			},
+			None => writeln!(out, "\t]").unwrap(),
+		}
+		writeln!(out, "}}").unwrap();
+
+		out
+	}
+}
@@ -0,0 +1,172 @@
+//! Aggressive simplification by vertex clustering, for the proxies drawn in place of whole groups of objects
+//! at a distance.
+//!
+//! The positions in each cell of a grid are collapsed into one, and the triangles that lose an edge are dropped.
+//! It doesn't preserve the topology, which doesn't matter for far away proxies. The UV seams, open boundaries
+//! and material borders can be kept in place, or pull their cells towards them, so that the textures don't smear.
+
+use wavefront_obj::obj::{Object, Shape, Vertex};
+use std::collections::{HashMap, HashSet};
+use crate::math::*;
+
+/// How the simplification treats the positions along a feature
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Preserve {
+	/// Like the others
+	#[default]
+	Free,
+	/// Weighs this many times more than the others in the average its cell moves to
+	Weight(f64),
+	/// Never collapsed nor moved
+	Lock,
+}
+
+impl Preserve {
+	/// `lock`, or a weight like `4`
+	pub fn from_name(name: &str) -> Option<Preserve> {
+		match name {
+			"lock" => Some(Preserve::Lock),
+			_ => name.parse().ok().filter(|weight: &f64| *weight > 0.0).map(Preserve::Weight),
+		}
+	}
+
+	fn weight(self) -> f64 {
+		match self {
+			Preserve::Free => 1.0,
+			Preserve::Weight(weight) => weight,
+			Preserve::Lock => f64::INFINITY,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimplifySettings {
+	/// Along the longest side of the bounds
+	pub cells: usize,
+	/// The positions with several texture coordinates
+	pub uv_seams: Preserve,
+	/// The positions on the edges of a single triangle
+	pub boundaries: Preserve,
+	/// The positions shared by faces with different materials
+	pub material_borders: Preserve,
+}
+
+impl SimplifySettings {
+	pub fn new(cells: usize) -> Self {
+		SimplifySettings {
+			cells,
+			uv_seams: Preserve::Free,
+			boundaries: Preserve::Free,
+			material_borders: Preserve::Free,
+		}
+	}
+}
+
+/// The weight of each position of `obj`, the highest of the features it is on
+fn feature_weights(obj: &Object, settings: &SimplifySettings) -> Vec<f64> {
+	//position => its first texture coordinate and material, and whether others follow
+	let mut uvs: Vec<Option<(Option<usize>, bool)>> = vec![None; obj.vertices.len()];
+	let mut materials: Vec<Option<(&Option<String>, bool)>> = vec![None; obj.vertices.len()];
+	let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+	for geo in &obj.geometry {
+		for shape in &geo.shapes {
+			let Shape::Triangle(a, b, c) = *shape else {
+				continue;
+			};
+			for corner in [a, b, c] {
+				let uv = uvs[corner.0].get_or_insert((corner.1, false));
+				uv.1 |= uv.0 != corner.1;
+				let material = materials[corner.0].get_or_insert((&geo.material_name, false));
+				material.1 |= *material.0 != geo.material_name;
+			}
+			for (p, q) in [(a.0, b.0), (b.0, c.0), (c.0, a.0)] {
+				*edges.entry((usize::min(p, q), usize::max(p, q))).or_insert(0) += 1;
+			}
+		}
+	}
+
+	let mut weights: Vec<f64> = (0..obj.vertices.len()).map(|i| {
+		let mut weight: f64 = 1.0;
+		if uvs[i].is_some_and(|(_, seam)| seam) {
+			weight = weight.max(settings.uv_seams.weight());
+		}
+		if materials[i].is_some_and(|(_, border)| border) {
+			weight = weight.max(settings.material_borders.weight());
+		}
+		weight
+	}).collect();
+	for ((p, q), count) in edges {
+		if count == 1 {
+			weights[p] = weights[p].max(settings.boundaries.weight());
+			weights[q] = weights[q].max(settings.boundaries.weight());
+		}
+	}
+	weights
+}
+
+/// Returns a copy of `obj` on a grid of `settings.cells` along the longest side of its bounds. The positions of
+/// each cell move to their weighted average and are all replaced by the first of them, so the positions keep
+/// their indices and the per-position data of the obj file still applies. The locked positions stay as they are
+pub fn cluster_vertices(obj: &Object, settings: &SimplifySettings) -> Object {
+	let cells = settings.cells;
+	assert!(cells > 0, "Can't simplify on a grid of 0 cells");
+	let mut result = obj.clone();
+	if obj.vertices.is_empty() {
+		return result;
+	}
+
+	let min = obj.vertices.iter().fold(obj.vertices[0], |m, &v| vert_min(m, v));
+	let max = obj.vertices.iter().fold(obj.vertices[0], |m, &v| vert_max(m, v));
+	let extent = sub(max, min);
+	let size = flt_max(flt_max(extent.x, extent.y), extent.z) / cells as f64;
+	let cell_of = |v| {
+		let d = sub(v, min);
+		[d.x, d.y, d.z].map(|c| if size > 0.0 { (c / size).floor() as i64 } else { 0 })
+	};
+
+	//only the positions used by faces, so that the others don't pull the averages
+	let used: HashSet<usize> = obj.geometry.iter()
+		.flat_map(|geo| &geo.shapes)
+		.flat_map(|shape| match *shape {
+			Shape::Triangle(a, b, c) => vec![a.0, b.0, c.0],
+			_ => vec![],
+		})
+		.collect();
+	let mut used: Vec<usize> = used.into_iter().collect();
+	used.sort();
+
+	//cell => the first position in it, and the weighted sum and the total weight of its positions
+	let weights = feature_weights(obj, settings);
+	let mut clusters: HashMap<[i64; 3], (usize, Vertex, f64)> = HashMap::new();
+	let mut representative: Vec<usize> = (0..obj.vertices.len()).collect();
+	for &i in &used {
+		if weights[i].is_infinite() {
+			continue;
+		}
+		let v = obj.vertices[i];
+		let cluster = clusters.entry(cell_of(v)).or_insert((i, Vertex { x: 0.0, y: 0.0, z: 0.0 }, 0.0));
+		addmut(&mut cluster.1, mul(v, weights[i]));
+		cluster.2 += weights[i];
+		representative[i] = cluster.0;
+	}
+	for &(first, sum, weight) in clusters.values() {
+		result.vertices[first] = mul(sum, 1.0 / weight);
+	}
+
+	//the same triangle can come out of several
+	let mut seen = HashSet::new();
+	for geo in &mut result.geometry {
+		geo.shapes.retain_mut(|shape| match shape {
+			Shape::Triangle(a, b, c) => {
+				a.0 = representative[a.0];
+				b.0 = representative[b.0];
+				c.0 = representative[c.0];
+				let mut key = [a.0, b.0, c.0];
+				key.sort();
+				a.0 != b.0 && b.0 != c.0 && a.0 != c.0 && seen.insert(key)
+			},
+			_ => false,
+		});
+	}
+	result
+}
@@ -0,0 +1,94 @@
+//! Skinning weights, loaded from a sidecar file since obj can't store them.
+//!
+//! The file has one line per `v` line of the obj file, in the same order, each listing up to 4
+//! `bone weight` pairs. Empty lines and lines starting with `#` are skipped.
+
+use wavefront_obj::ParseError;
+
+pub const MAX_INFLUENCES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoneInfluences {
+	pub indices: [u8; MAX_INFLUENCES],
+	/// Unorm weights, adding up to 255
+	pub weights: [u8; MAX_INFLUENCES],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SkinWeights {
+	/// Indexed like the positions of the obj file, across all its objects
+	pub vertices: Vec<BoneInfluences>,
+}
+
+fn error(line_number: usize, message: String) -> ParseError {
+	ParseError {
+		line_number,
+		message,
+	}
+}
+
+/// Keeps the strongest influences and quantizes them so that the weights add up to exactly 255
+fn quantize(mut influences: Vec<(u8, f64)>) -> BoneInfluences {
+	influences.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+	influences.truncate(MAX_INFLUENCES);
+
+	let total: f64 = influences.iter().map(|i| i.1).sum();
+
+	let mut result = BoneInfluences {
+		indices: [0; MAX_INFLUENCES],
+		weights: [0; MAX_INFLUENCES],
+	};
+
+	let mut sum = 0;
+	for (i, &(bone, weight)) in influences.iter().enumerate() {
+		result.indices[i] = bone;
+		result.weights[i] = f64::round(weight / total * 255.0) as u8;
+		sum += result.weights[i] as i32;
+	}
+
+	//the strongest influence absorbs the rounding error
+	result.weights[0] = (result.weights[0] as i32 + 255 - sum) as u8;
+
+	result
+}
+
+pub fn parse_weights(content: &str) -> Result<SkinWeights, ParseError> {
+	let mut weights = SkinWeights::default();
+
+	for (i, line) in content.lines().enumerate() {
+		let line_number = i + 1;
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		if !tokens.len().is_multiple_of(2) {
+			return Err(error(line_number, "Expected `bone weight` pairs".to_owned()));
+		}
+
+		let mut influences = vec![];
+		for pair in tokens.chunks(2) {
+			let bone = match pair[0].parse::<u8>() {
+				Ok(bone) => bone,
+				Err(_) => return Err(error(line_number, format!("Expected a bone index between 0 and 255 but got {}", pair[0]))),
+			};
+			let weight = match pair[1].parse::<f64>() {
+				Ok(weight) if weight.is_finite() && weight >= 0.0 => weight,
+				_ => return Err(error(line_number, format!("Expected a positive weight but got {}", pair[1]))),
+			};
+
+			if weight > 0.0 {
+				influences.push((bone, weight));
+			}
+		}
+
+		if influences.is_empty() {
+			return Err(error(line_number, "The vertex has no influences".to_owned()));
+		}
+
+		weights.vertices.push(quantize(influences));
+	}
+
+	Ok(weights)
+}
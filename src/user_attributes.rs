@@ -0,0 +1,146 @@
+//! Extra per-vertex data computed by other tools, like wind weights or damage masks, read from a CSV sidecar file.
+//!
+//! The first line declares the columns: `vertex` followed by a `name:format` for each attribute, with the formats
+//! accepted by `Format::from_name`. Each following line has the obj index of a position (starting from 1 like in
+//! the obj file) and the values of each attribute, with the components separated by spaces. Positions without
+//! a line get zeroes. Empty lines and lines starting with `#` are skipped.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wavefront_obj::ParseError;
+use crate::layout::{Format, UserAttribute, MAX_USER_ATTRIBUTES};
+
+#[derive(Debug, Clone, Default)]
+pub struct UserAttributes {
+	pub attributes: Vec<UserAttribute>,
+	/// For each attribute, its bytes for every position of the obj file
+	pub values: Vec<Vec<u8>>,
+	pub vertex_count: usize,
+}
+
+impl UserAttributes {
+	/// The bytes of all the attributes of a position of the obj file, across all its objects
+	pub fn vertex_bytes(&self, vertex: usize, out: &mut Vec<u8>) {
+		for (attr, values) in self.attributes.iter().zip(&self.values) {
+			let size = attr.format.size();
+			match values.get(vertex * size..(vertex + 1) * size) {
+				Some(bytes) => out.extend_from_slice(bytes),
+				None => out.extend(std::iter::repeat_n(0, size)),
+			}
+		}
+	}
+}
+
+fn error(line_number: usize, message: String) -> ParseError {
+	ParseError {
+		line_number,
+		message,
+	}
+}
+
+fn write_component(out: &mut Vec<u8>, format: Format, token: &str) -> Result<(), String> {
+	let invalid = || format!("Invalid {:?} component {}", format, token);
+	match format {
+		Format::Uint8x4 => out.push(token.parse::<u8>().map_err(|_| invalid())?),
+		Format::Unorm8x4 => {
+			let val = token.parse::<f32>().map_err(|_| invalid())?;
+			if !(0.0..=1.0).contains(&val) {
+				return Err(invalid());
+			}
+			out.push(f32::round(val * 255.0) as u8);
+		},
+		Format::Uint32 => out.write_u32::<LittleEndian>(token.parse().map_err(|_| invalid())?).unwrap(),
+		_ => {
+			let val = token.parse::<f32>().map_err(|_| invalid())?;
+			if !val.is_finite() {
+				return Err(invalid());
+			}
+			out.write_f32::<LittleEndian>(val).unwrap();
+		},
+	}
+	Ok(())
+}
+
+fn parse_declaration(line_number: usize, line: &str) -> Result<Vec<UserAttribute>, ParseError> {
+	let mut columns = line.split(',').map(str::trim);
+	if columns.next() != Some("vertex") {
+		return Err(error(line_number, "The first column must be `vertex`".to_owned()));
+	}
+
+	let mut attributes: Vec<UserAttribute> = vec![];
+	for column in columns {
+		let (name, format) = match column.split_once(':') {
+			Some((name, format)) => (name.trim(), format.trim()),
+			None => return Err(error(line_number, format!("Expected `name:format` but got {}", column))),
+		};
+
+		let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+			&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+		if !valid_name || attributes.iter().any(|a| a.name == name) {
+			return Err(error(line_number, format!("Invalid attribute name {}", name)));
+		}
+
+		match Format::from_name(format) {
+			Some(format) => attributes.push(UserAttribute { name: name.to_owned(), format }),
+			None => return Err(error(line_number, format!("Unknown format {}", format))),
+		}
+	}
+
+	if attributes.is_empty() || attributes.len() > MAX_USER_ATTRIBUTES {
+		return Err(error(line_number, format!("Expected between 1 and {} attributes", MAX_USER_ATTRIBUTES)));
+	}
+	Ok(attributes)
+}
+
+pub fn parse_user_attributes(content: &str) -> Result<UserAttributes, ParseError> {
+	let mut result = UserAttributes::default();
+	let mut declared = false;
+
+	for (i, line) in content.lines().enumerate() {
+		let line_number = i + 1;
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		if !declared {
+			result.attributes = parse_declaration(line_number, line)?;
+			result.values = vec![vec![]; result.attributes.len()];
+			declared = true;
+			continue;
+		}
+
+		let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+		if cells.len() != result.attributes.len() + 1 {
+			return Err(error(line_number, format!("Expected {} columns", result.attributes.len() + 1)));
+		}
+
+		let vertex = match cells[0].parse::<usize>() {
+			Ok(v) if v > 0 => v - 1,
+			_ => return Err(error(line_number, format!("Invalid vertex index {}", cells[0]))),
+		};
+		result.vertex_count = usize::max(result.vertex_count, vertex + 1);
+
+		for ((attr, values), cell) in result.attributes.iter().zip(result.values.iter_mut()).zip(&cells[1..]) {
+			let size = attr.format.size();
+			if values.len() < (vertex + 1) * size {
+				values.resize((vertex + 1) * size, 0);
+			}
+
+			let tokens: Vec<&str> = cell.split_whitespace().collect();
+			if tokens.len() != attr.format.components() {
+				return Err(error(line_number, format!("{} needs {} components", attr.name, attr.format.components())));
+			}
+
+			let mut bytes = vec![];
+			for token in tokens {
+				write_component(&mut bytes, attr.format, token).map_err(|message| error(line_number, message))?;
+			}
+			values[vertex * size..(vertex + 1) * size].copy_from_slice(&bytes);
+		}
+	}
+
+	if !declared {
+		return Err(error(1, "Missing the column declarations".to_owned()));
+	}
+	Ok(result)
+}
@@ -0,0 +1,147 @@
+//! Texture coordinates projected from the positions, for the meshes exported without any.
+
+use wavefront_obj::obj::{Object, Shape, TVertex, Vertex};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvProjection {
+	/// On the plane of the two largest sides of the bounding box
+	Planar,
+	/// On the axis plane each triangle faces the most
+	Box,
+	/// Around the center of the bounding box, with the poles on Y
+	Spherical,
+	/// Around the Y axis
+	Cylindrical,
+}
+
+impl UvProjection {
+	pub fn from_name(name: &str) -> Option<UvProjection> {
+		match name {
+			"planar" => Some(UvProjection::Planar),
+			"box" => Some(UvProjection::Box),
+			"spherical" => Some(UvProjection::Spherical),
+			"cylindrical" => Some(UvProjection::Cylindrical),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			UvProjection::Planar => "planar",
+			UvProjection::Box => "box",
+			UvProjection::Spherical => "spherical",
+			UvProjection::Cylindrical => "cylindrical",
+		}
+	}
+}
+
+fn component(v: Vertex, axis: usize) -> f64 {
+	match axis {
+		0 => v.x,
+		1 => v.y,
+		_ => v.z,
+	}
+}
+
+fn dominant_axis(n: Vertex) -> usize {
+	let abs = [n.x.abs(), n.y.abs(), n.z.abs()];
+	if abs[0] >= abs[1] && abs[0] >= abs[2] { 0 } else if abs[1] >= abs[2] { 1 } else { 2 }
+}
+
+/// Drops `axis` and normalizes the other two by the largest side, so that the aspect ratio is kept
+fn project_on_axis(v: Vertex, axis: usize, min: Vertex, size: f64) -> TVertex {
+	let (a, b) = match axis {
+		0 => (2, 1),
+		1 => (0, 2),
+		_ => (0, 1),
+	};
+	TVertex {
+		x: (component(v, a) - component(min, a)) / size,
+		y: (component(v, b) - component(min, b)) / size,
+	}
+}
+
+/// Returns a copy of `obj` with generated texture coordinates for all its triangles
+pub fn generate(obj: &Object, projection: UvProjection) -> Object {
+	let mut min = Vertex { x: f64::MAX, y: f64::MAX, z: f64::MAX };
+	let mut max = Vertex { x: f64::MIN, y: f64::MIN, z: f64::MIN };
+	for &v in &obj.vertices {
+		min = vert_min(min, v);
+		max = vert_max(max, v);
+	}
+	let extent = sub(max, min);
+	let largest = f64::max(f64::max(extent.x, extent.y), f64::max(extent.z, f64::MIN_POSITIVE));
+	let center = Vertex { x: (min.x + max.x) / 2.0, y: (min.y + max.y) / 2.0, z: (min.z + max.z) / 2.0 };
+
+	//the smallest side for planar
+	let flat_axis = (0..3).min_by(|&a, &b| component(extent, a).partial_cmp(&component(extent, b)).unwrap()).unwrap();
+
+	let mut result = obj.clone();
+	result.tex_vertices.clear();
+
+	//a position gets a texture coordinate per box side, or per side of the seam
+	let mut ids: HashMap<(usize, usize), usize> = HashMap::new();
+
+	for geo in &mut result.geometry {
+		for shape in &mut geo.shapes {
+			let (v1, v2, v3) = match *shape {
+				Shape::Triangle(v1, v2, v3) => (v1, v2, v3),
+				_ => panic!("Unsupported primitive mode"),
+			};
+			let corners = [v1.0, v2.0, v3.0];
+			let pos = |i: usize| obj.vertices[corners[i]];
+
+			let uvs: Vec<(usize, TVertex)> = match projection {
+				UvProjection::Planar => (0..3).map(|i| (0, project_on_axis(pos(i), flat_axis, min, largest))).collect(),
+				UvProjection::Box => {
+					let axis = dominant_axis(cross(sub(pos(1), pos(0)), sub(pos(2), pos(0))));
+					(0..3).map(|i| (axis, project_on_axis(pos(i), axis, min, largest))).collect()
+				},
+				UvProjection::Spherical | UvProjection::Cylindrical => {
+					let mut uvs: Vec<(usize, TVertex)> = (0..3).map(|i| {
+						let d = sub(pos(i), center);
+						let v = if projection == UvProjection::Spherical {
+							let r = lenght(d);
+							if r > 0.0 { f64::acos(d.y / r) / PI } else { 0.5 }
+						}
+						else {
+							(pos(i).y - min.y) / f64::max(extent.y, f64::MIN_POSITIVE)
+						};
+						(0, TVertex { x: f64::atan2(d.z, d.x) / (2.0 * PI) + 0.5, y: v })
+					}).collect();
+
+					//triangles crossing the seam would stretch over the whole texture: move their far side
+					//below 0 instead, as over 1 gets wrapped back when packing
+					let (lo, hi) = uvs.iter().fold((f64::MAX, f64::MIN), |(lo, hi), uv| (f64::min(lo, uv.1.x), f64::max(hi, uv.1.x)));
+					if hi - lo > 0.5 {
+						for uv in &mut uvs {
+							if uv.1.x > 0.5 {
+								*uv = (1, TVertex { x: uv.1.x - 1.0, y: uv.1.y });
+							}
+						}
+					}
+					uvs
+				},
+			};
+
+			let mut tex_index = |i: usize| {
+				let (variant, uv) = uvs[i];
+				*ids.entry((corners[i], variant)).or_insert_with(|| {
+					result.tex_vertices.push(uv);
+					result.tex_vertices.len() - 1
+				})
+			};
+
+			*shape = Shape::Triangle(
+				(v1.0, Some(tex_index(0)), v1.2),
+				(v2.0, Some(tex_index(1)), v2.2),
+				(v3.0, Some(tex_index(2)), v3.2),
+			);
+		}
+	}
+
+	result
+}
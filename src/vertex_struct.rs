@@ -0,0 +1,291 @@
+//! Packs the vertices into a struct declared by the engine, for runtimes copying them into their own vertex type
+//! as they are.
+//!
+//! The description has a line per field, in the order of the struct, like a C declaration: the type, the name of
+//! the attribute it holds with the number of components in brackets, and `unorm` or `snorm` for the integers
+//! holding normalized values, like `int16 normal[3] snorm`. The types are `float`, `half`, `int8`, `uint8`,
+//! `int16`, `uint16`, `int32` and `uint32`, and the names those of the built-in attributes, like `tex0` or
+//! `bone_weights`, or of the user attributes. The components past those of the attribute are zeroes, and so are
+//! the fields whose name starts with `_`, for explicit padding. Like C compilers, each field is aligned to the
+//! size of its type and the struct to its largest field; a `pack N` line caps the alignment of the fields like
+//! `#pragma pack(N)`, and an `align N` line aligns the struct to N bytes like `alignas(N)`. Empty lines and lines
+//! starting with `#` are skipped.
+
+use half::f16;
+use wavefront_obj::ParseError;
+use crate::layout::{Attribute, Format};
+use crate::reader::MeshData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+	Float,
+	Half,
+	Int8,
+	Uint8,
+	Int16,
+	Uint16,
+	Int32,
+	Uint32,
+}
+
+impl ScalarType {
+	pub fn from_name(name: &str) -> Option<ScalarType> {
+		match name {
+			"float" => Some(ScalarType::Float),
+			"half" => Some(ScalarType::Half),
+			"int8" => Some(ScalarType::Int8),
+			"uint8" => Some(ScalarType::Uint8),
+			"int16" => Some(ScalarType::Int16),
+			"uint16" => Some(ScalarType::Uint16),
+			"int32" => Some(ScalarType::Int32),
+			"uint32" => Some(ScalarType::Uint32),
+			_ => None,
+		}
+	}
+
+	pub fn size(self) -> usize {
+		match self {
+			ScalarType::Int8 | ScalarType::Uint8 => 1,
+			ScalarType::Half | ScalarType::Int16 | ScalarType::Uint16 => 2,
+			ScalarType::Float | ScalarType::Int32 | ScalarType::Uint32 => 4,
+		}
+	}
+
+	/// The range of the integer types, None for the floats
+	fn range(self) -> Option<(f64, f64)> {
+		match self {
+			ScalarType::Float | ScalarType::Half => None,
+			ScalarType::Int8 => Some((i8::MIN as f64, i8::MAX as f64)),
+			ScalarType::Uint8 => Some((0.0, u8::MAX as f64)),
+			ScalarType::Int16 => Some((i16::MIN as f64, i16::MAX as f64)),
+			ScalarType::Uint16 => Some((0.0, u16::MAX as f64)),
+			ScalarType::Int32 => Some((i32::MIN as f64, i32::MAX as f64)),
+			ScalarType::Uint32 => Some((0.0, u32::MAX as f64)),
+		}
+	}
+
+	fn write(self, out: &mut Vec<u8>, val: f64) {
+		match self {
+			ScalarType::Float => out.extend_from_slice(&(val as f32).to_le_bytes()),
+			ScalarType::Half => out.extend_from_slice(&f16::from_f32(val as f32).as_bits().to_le_bytes()),
+			ScalarType::Int8 => out.extend_from_slice(&(val as i8).to_le_bytes()),
+			ScalarType::Uint8 => out.extend_from_slice(&(val as u8).to_le_bytes()),
+			ScalarType::Int16 => out.extend_from_slice(&(val as i16).to_le_bytes()),
+			ScalarType::Uint16 => out.extend_from_slice(&(val as u16).to_le_bytes()),
+			ScalarType::Int32 => out.extend_from_slice(&(val as i32).to_le_bytes()),
+			ScalarType::Uint32 => out.extend_from_slice(&(val as u32).to_le_bytes()),
+		}
+	}
+}
+
+/// How an integer field stores the values in 0..1 or -1..1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+	/// The values as they are, which must be integers in range
+	None,
+	Unorm,
+	Snorm,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructField {
+	pub name: String,
+	pub ty: ScalarType,
+	pub count: usize,
+	pub normalization: Normalization,
+	pub offset: usize,
+}
+
+impl StructField {
+	pub fn is_padding(&self) -> bool {
+		self.name.starts_with('_')
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexStruct {
+	pub fields: Vec<StructField>,
+	/// `sizeof` the struct, with the padding at the end
+	pub size: usize,
+}
+
+fn error(line_number: usize, message: String) -> ParseError {
+	ParseError {
+		line_number,
+		message,
+	}
+}
+
+fn parse_power_of_two(line_number: usize, directive: &str, value: &str) -> Result<usize, ParseError> {
+	match value.parse::<usize>() {
+		Ok(n) if n.is_power_of_two() => Ok(n),
+		_ => Err(error(line_number, format!("{} needs a power of two, not {}", directive, value))),
+	}
+}
+
+/// `name[count]`, or `name` for a single component
+fn parse_declarator(line_number: usize, declarator: &str) -> Result<(String, usize), ParseError> {
+	let (name, count) = match declarator.split_once('[') {
+		Some((name, rest)) => {
+			let count = rest.strip_suffix(']').and_then(|c| c.trim().parse::<usize>().ok()).filter(|&c| c > 0);
+			match count {
+				Some(count) => (name.trim(), count),
+				None => return Err(error(line_number, format!("Invalid array size in {}", declarator))),
+			}
+		},
+		None => (declarator, 1),
+	};
+	let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+		&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+	if !valid_name {
+		return Err(error(line_number, format!("Invalid field name {}", name)));
+	}
+	Ok((name.to_owned(), count))
+}
+
+pub fn parse_vertex_struct(content: &str) -> Result<VertexStruct, ParseError> {
+	let mut declared = vec![];
+	let mut pack = None;
+	let mut align = 1;
+
+	for (i, line) in content.lines().enumerate() {
+		let line_number = i + 1;
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		match tokens[..] {
+			["pack", value] => pack = Some(parse_power_of_two(line_number, "pack", value)?),
+			["align", value] => align = parse_power_of_two(line_number, "align", value)?,
+			[ty, declarator] | [ty, declarator, _] => {
+				let Some(ty) = ScalarType::from_name(ty) else {
+					return Err(error(line_number, format!("Unknown type {}", ty)));
+				};
+				let (name, count) = parse_declarator(line_number, declarator)?;
+				let normalization = match tokens.get(2) {
+					None => Normalization::None,
+					Some(&"unorm") => Normalization::Unorm,
+					Some(&"snorm") => Normalization::Snorm,
+					Some(other) => return Err(error(line_number, format!("Expected unorm or snorm but got {}", other))),
+				};
+				let valid_normalization = match (normalization, ty.range()) {
+					(Normalization::None, _) => true,
+					(_, None) => false,
+					(Normalization::Unorm, Some((min, _))) => min == 0.0,
+					(Normalization::Snorm, Some((min, _))) => min < 0.0,
+				};
+				if !valid_normalization {
+					return Err(error(line_number, format!("{} can't be {:?}", line, normalization)));
+				}
+				if declared.iter().any(|f: &StructField| f.name == name) {
+					return Err(error(line_number, format!("{} is declared twice", name)));
+				}
+				declared.push(StructField { name, ty, count, normalization, offset: 0 });
+			},
+			_ => return Err(error(line_number, format!("Expected `type name[count]` but got {}", line))),
+		}
+	}
+
+	if declared.is_empty() {
+		return Err(error(0, "The vertex struct has no fields".to_owned()));
+	}
+
+	let mut fields = Vec::with_capacity(declared.len());
+	let mut offset = 0usize;
+	for mut field in declared {
+		let alignment = pack.map_or(field.ty.size(), |pack| usize::min(pack, field.ty.size()));
+		align = usize::max(align, alignment);
+		field.offset = offset.next_multiple_of(alignment);
+		offset = field.offset + field.ty.size() * field.count;
+		fields.push(field);
+	}
+
+	Ok(VertexStruct {
+		fields,
+		size: offset.next_multiple_of(align),
+	})
+}
+
+/// The components of every vertex for an attribute, and how many each vertex has
+fn attribute_values(mesh: &MeshData, name: &str) -> Option<(Vec<f64>, usize)> {
+	fn flatten<const N: usize, T: Copy + Into<f64>>(values: &Option<Vec<[T; N]>>) -> Option<(Vec<f64>, usize)> {
+		values.as_ref().map(|values| (values.iter().flatten().map(|&c| c.into()).collect(), N))
+	}
+	fn single<T: Copy + Into<f64>>(values: &Option<Vec<T>>) -> Option<(Vec<f64>, usize)> {
+		values.as_ref().map(|values| (values.iter().map(|&c| c.into()).collect(), 1))
+	}
+
+	if let Some(i) = mesh.header.user_attributes.iter().position(|a| a.name == name) {
+		let format = mesh.header.user_attributes[i].format;
+		let bytes = &mesh.user_data[i];
+		let values = match format {
+			Format::Uint8x4 => bytes.iter().map(|&b| b as f64).collect(),
+			Format::Unorm8x4 => bytes.iter().map(|&b| b as f64 / 255.0).collect(),
+			Format::Uint32 => bytes.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64).collect(),
+			_ => bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64).collect(),
+		};
+		return Some((values, format.components()));
+	}
+
+	let attribute = Attribute::ALL.into_iter().find(|a| a.name() == name)?;
+	match attribute {
+		Attribute::Position => Some((mesh.positions.iter().flatten().map(|&c| c as f64).collect(), 3)),
+		Attribute::Color => flatten(&mesh.colors),
+		Attribute::Normal => flatten(&mesh.normals),
+		Attribute::Tangent => flatten(&mesh.tangents),
+		Attribute::Bitangent => flatten(&mesh.bitangents),
+		Attribute::Tex0 => flatten(&mesh.tex0),
+		Attribute::Tex1 => flatten(&mesh.tex1),
+		Attribute::BoneIndices => flatten(&mesh.bone_indices),
+		Attribute::BoneWeights => flatten(&mesh.bone_weights),
+		Attribute::Curvature => single(&mesh.curvature),
+		Attribute::MaterialId => single(&mesh.material_ids),
+		Attribute::User(_) => None,
+	}
+}
+
+impl VertexStruct {
+	/// The vertices of the mesh laid out as the struct, or why the mesh doesn't have what the struct holds
+	pub fn pack(&self, mesh: &MeshData) -> Result<Vec<u8>, String> {
+		let mut sources = Vec::with_capacity(self.fields.len());
+		let mut missing = vec![];
+		for field in &self.fields {
+			match attribute_values(mesh, &field.name) {
+				_ if field.is_padding() => sources.push((vec![], 0)),
+				Some(values) => sources.push(values),
+				None => missing.push(field.name.as_str()),
+			}
+		}
+		if !missing.is_empty() {
+			return Err(format!("the mesh has no {}, which the vertex struct holds", missing.join(", ")));
+		}
+
+		let mut out = Vec::with_capacity(mesh.positions.len() * self.size);
+		for v in 0..mesh.positions.len() {
+			let start = out.len();
+			for (field, (values, components)) in self.fields.iter().zip(&sources) {
+				out.resize(start + field.offset, 0);
+				for c in 0..field.count {
+					let val = if c < *components { values[v * components + c] } else { 0.0 };
+					let val = match (field.normalization, field.ty.range()) {
+						(Normalization::Unorm, Some((_, max))) => (val.clamp(0.0, 1.0) * max).round(),
+						(Normalization::Snorm, Some((_, max))) => (val.clamp(-1.0, 1.0) * max).round(),
+						(_, Some((min, max))) => {
+							let rounded = val.round();
+							if !(min..=max).contains(&rounded) {
+								return Err(format!("vertex {} has {} for component {} of {}, which doesn't fit in {:?}", v, val, c, field.name, field.ty));
+							}
+							rounded
+						},
+						(_, None) => val,
+					};
+					field.ty.write(&mut out, val);
+				}
+			}
+			out.resize(start + self.size, 0);
+		}
+		Ok(out)
+	}
+}
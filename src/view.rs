@@ -0,0 +1,528 @@
+//! Reads `.mesh` files in place, for runtimes that upload the vertices and indices as they are stored.
+//!
+//! Unlike `reader`, nothing is decoded or copied: `MeshView` borrows the chunks from the bytes of the file, and
+//! only the names of the user attributes are allocated. It needs `core` and `alloc` only, so it is available
+//! without the `std` feature. It reads the chunked versions of the format; the encoded chunks can be decoded
+//! with `codec`.
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::fmt;
+use crate::chunk::{self, Crc32};
+use crate::codec::Encoding;
+use crate::dequantization::{self, Dequantization};
+use crate::layout::{Attribute, ColorSpace, Format, Palette, UserAttribute, VertexFieldOffsets, VertexLayout, MAX_USER_ATTRIBUTES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+	TriangleList,
+	LineList,
+	PointList,
+}
+
+impl Topology {
+	pub fn from_u8(val: u8) -> Option<Topology> {
+		match val {
+			1 => Some(Topology::TriangleList),
+			2 => Some(Topology::LineList),
+			3 => Some(Topology::PointList),
+			_ => None,
+		}
+	}
+
+	pub fn to_u8(self) -> u8 {
+		match self {
+			Topology::TriangleList => 1,
+			Topology::LineList => 2,
+			Topology::PointList => 3,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewError {
+	/// The data doesn't start with the magic number; version 1 files have none
+	NotChunked,
+	UnsupportedVersion(u32),
+	/// The data ends inside of a chunk, or of a field of the header
+	Truncated(&'static str),
+	MissingChunk(&'static str),
+	/// A field of the header with a value the converter never writes
+	InvalidHeader(&'static str),
+	/// A raw chunk whose length doesn't match the counts of the header, or a submesh past the indices
+	InvalidChunkSize(&'static str),
+	IndexOutOfRange { index: usize, value: u32, vertex_count: usize },
+	/// The file was corrupted after it was written
+	ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ViewError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ViewError::NotChunked => write!(f, "The data isn't a chunked mesh file"),
+			ViewError::UnsupportedVersion(version) => write!(f, "Unsupported format version {}", version),
+			ViewError::Truncated(name) => write!(f, "The {} chunk is truncated", name),
+			ViewError::MissingChunk(name) => write!(f, "The {} chunk is missing", name),
+			ViewError::InvalidHeader(field) => write!(f, "Invalid value for the {} field of the header", field),
+			ViewError::InvalidChunkSize(name) => write!(f, "The size of the {} chunk doesn't match the header", name),
+			ViewError::IndexOutOfRange { index, value, vertex_count } =>
+				write!(f, "Index {} is {}, but there are only {} vertices", index, value, vertex_count),
+			ViewError::ChecksumMismatch { expected, actual } =>
+				write!(f, "The file is corrupted: its checksum is {:08x}, but it should be {:08x}", actual, expected),
+		}
+	}
+}
+
+impl core::error::Error for ViewError {}
+
+/// Reads little endian values from the front of a slice, failing with the name of what is read
+#[derive(Debug, Clone)]
+struct Cursor<'a> {
+	data: &'a [u8],
+	name: &'static str,
+}
+
+impl<'a> Cursor<'a> {
+	fn take(&mut self, count: usize) -> Result<&'a [u8], ViewError> {
+		if count > self.data.len() {
+			return Err(ViewError::Truncated(self.name));
+		}
+		let (taken, rest) = self.data.split_at(count);
+		self.data = rest;
+		Ok(taken)
+	}
+
+	fn u8(&mut self) -> Result<u8, ViewError> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, ViewError> {
+		let b = self.take(2)?;
+		Ok(u16::from_le_bytes([b[0], b[1]]))
+	}
+
+	fn u32(&mut self) -> Result<u32, ViewError> {
+		let b = self.take(4)?;
+		Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+	}
+
+	fn f32(&mut self) -> Result<f32, ViewError> {
+		Ok(f32::from_bits(self.u32()?))
+	}
+
+	fn vec3(&mut self) -> Result<[f32; 3], ViewError> {
+		Ok([self.f32()?, self.f32()?, self.f32()?])
+	}
+
+	/// A flag that is 0 or 1
+	fn flag(&mut self, field: &'static str) -> Result<bool, ViewError> {
+		match self.u8()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(ViewError::InvalidHeader(field)),
+		}
+	}
+
+	/// The newer fields at the end of the header, absent from the files written before them
+	fn optional_u8(&mut self) -> Option<u8> {
+		self.u8().ok()
+	}
+}
+
+/// A submesh of the `SUBM` chunk, with the name of its material borrowed from the file
+#[derive(Debug, Clone, Copy)]
+pub struct SubmeshView<'a> {
+	pub material: Option<&'a str>,
+	pub first_index: u32,
+	pub index_count: u32,
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+	pub center: [f32; 3],
+	pub radius: f32,
+}
+
+/// The submeshes of the `SUBM` chunk, already validated by `MeshView::parse`
+#[derive(Debug, Clone)]
+pub struct Submeshes<'a> {
+	cursor: Cursor<'a>,
+	left: u32,
+}
+
+impl<'a> Iterator for Submeshes<'a> {
+	type Item = SubmeshView<'a>;
+
+	fn next(&mut self) -> Option<SubmeshView<'a>> {
+		if self.left == 0 {
+			return None;
+		}
+		self.left -= 1;
+		Some(read_submesh(&mut self.cursor).expect("validated by MeshView::parse"))
+	}
+}
+
+fn read_submesh<'a>(cursor: &mut Cursor<'a>) -> Result<SubmeshView<'a>, ViewError> {
+	let first_index = cursor.u32()?;
+	let index_count = cursor.u32()?;
+	let len = cursor.u16()? as usize;
+	let material = core::str::from_utf8(cursor.take(len)?).map_err(|_| ViewError::InvalidHeader("SUBM"))?;
+	Ok(SubmeshView {
+		material: if material.is_empty() { None } else { Some(material) },
+		first_index,
+		index_count,
+		min: cursor.vec3()?,
+		max: cursor.vec3()?,
+		center: cursor.vec3()?,
+		radius: cursor.f32()?,
+	})
+}
+
+/// A `.mesh` file read in place
+#[derive(Debug, Clone)]
+pub struct MeshView<'a> {
+	pub version: u32,
+	/// Size in bytes of each index: 1, 2 or 4
+	pub index_size: u8,
+	pub topology: Topology,
+	pub format: VertexFieldOffsets,
+	pub color_space: ColorSpace,
+	/// Laid out after the built-in attributes, in this order
+	pub user_attributes: Vec<UserAttribute>,
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+	pub vertex_count: usize,
+	pub index_count: usize,
+	pub has_morph_targets: bool,
+	pub encoding: Encoding,
+	/// Of the quantized positions, which the shaders apply
+	pub dequantization: Option<Dequantization>,
+	pub shadow_index_count: usize,
+	/// The `VERT` chunk: with the raw encoding the vertices laid out as `layout`, ready to upload
+	pub vertices: &'a [u8],
+	/// The `INDX` chunk: with the raw encoding the indices of the index size
+	pub indices: &'a [u8],
+	/// The `SHDW` chunk, stored like `indices`
+	pub shadow_indices: Option<&'a [u8]>,
+	/// The CRC-32 of the file, when it has one; it's always verified
+	pub checksum: Option<u32>,
+	submeshes: &'a [u8],
+	chunks: &'a [u8],
+}
+
+impl<'a> MeshView<'a> {
+	/// Validates the container, the checksum and the header, and with the raw encoding the sizes of the vertices
+	/// and indices and that the indices are in range
+	pub fn parse(data: &'a [u8]) -> Result<MeshView<'a>, ViewError> {
+		let mut file = Cursor { data, name: "MESH" };
+		if file.take(4).ok() != Some(&chunk::MAGIC[..]) {
+			return Err(ViewError::NotChunked);
+		}
+		let version = file.u32()?;
+		if version <= chunk::LEGACY_VERSION || version > chunk::VERSION {
+			return Err(ViewError::UnsupportedVersion(version));
+		}
+
+		let mut checksum = None;
+		let mut head = None;
+		let mut vertices = None;
+		let mut indices = None;
+		let mut shadow_indices = None;
+		let mut submeshes = None;
+		let chunks = file.data;
+		while !file.data.is_empty() {
+			//everything before the checksum chunk
+			let checked = &data[..data.len() - file.data.len()];
+			let id = file.take(4)?;
+			let len = file.u32()? as usize;
+			let body = file.take(len)?;
+			match [id[0], id[1], id[2], id[3]] {
+				chunk::HEADER => head = head.or(Some(body)),
+				chunk::VERTICES => vertices = vertices.or(Some(body)),
+				chunk::INDICES => indices = indices.or(Some(body)),
+				chunk::SHADOW_INDICES => shadow_indices = shadow_indices.or(Some(body)),
+				chunk::SUBMESHES => submeshes = submeshes.or(Some(body)),
+				chunk::CHECKSUM => {
+					let expected = Cursor { data: body, name: "CRC " }.u32()?;
+					let mut crc = Crc32::default();
+					crc.update(checked);
+					if crc.value() != expected {
+						return Err(ViewError::ChecksumMismatch { expected, actual: crc.value() });
+					}
+					checksum = Some(expected);
+				},
+				_ => {},
+			}
+		}
+
+		let mut view = read_header(head.ok_or(ViewError::MissingChunk("HEAD"))?, version)?;
+		view.checksum = checksum;
+		view.chunks = chunks;
+		view.vertices = vertices.ok_or(ViewError::MissingChunk("VERT"))?;
+		view.indices = indices.ok_or(ViewError::MissingChunk("INDX"))?;
+		view.shadow_indices = shadow_indices;
+		if view.shadow_index_count > 0 && shadow_indices.is_none() {
+			return Err(ViewError::MissingChunk("SHDW"));
+		}
+		if view.encoding == Encoding::Raw {
+			if view.vertices.len() != view.vertex_count * view.layout().stride {
+				return Err(ViewError::InvalidChunkSize("VERT"));
+			}
+			if view.indices.len() != view.index_count * view.index_size as usize {
+				return Err(ViewError::InvalidChunkSize("INDX"));
+			}
+			view.check_indices(view.indices)?;
+			if let Some(shadow) = shadow_indices {
+				if shadow.len() != view.shadow_index_count * view.index_size as usize {
+					return Err(ViewError::InvalidChunkSize("SHDW"));
+				}
+				view.check_indices(shadow)?;
+			}
+		}
+
+		if let Some(data) = submeshes {
+			let mut cursor = Cursor { data, name: "SUBM" };
+			for _ in 0..cursor.u32()? {
+				let submesh = read_submesh(&mut cursor)?;
+				if submesh.first_index as usize + submesh.index_count as usize > view.index_count {
+					return Err(ViewError::InvalidChunkSize("SUBM"));
+				}
+			}
+			view.submeshes = data;
+		}
+
+		Ok(view)
+	}
+
+	/// The layout of the vertices, including the user attributes
+	pub fn layout(&self) -> VertexLayout {
+		self.format.layout().with_user_attributes(&self.user_attributes)
+	}
+
+	/// An index of a raw index list, like `indices`
+	pub fn index(&self, indices: &[u8], i: usize) -> u32 {
+		let size = self.index_size as usize;
+		let b = &indices[i * size..(i + 1) * size];
+		match size {
+			1 => b[0] as u32,
+			2 => u16::from_le_bytes([b[0], b[1]]) as u32,
+			_ => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+		}
+	}
+
+	fn check_indices(&self, indices: &[u8]) -> Result<(), ViewError> {
+		for i in 0..indices.len() / self.index_size as usize {
+			let value = self.index(indices, i);
+			if value as usize >= self.vertex_count {
+				return Err(ViewError::IndexOutOfRange { index: i, value, vertex_count: self.vertex_count });
+			}
+		}
+		Ok(())
+	}
+
+	/// Empty for the files without a `SUBM` chunk
+	pub fn submeshes(&self) -> Submeshes<'a> {
+		let mut cursor = Cursor { data: self.submeshes, name: "SUBM" };
+		let left = cursor.u32().unwrap_or(0);
+		Submeshes { cursor, left }
+	}
+
+	/// The body of the first chunk with this FourCC, for the chunks the view doesn't interpret
+	pub fn chunk(&self, id: [u8; 4]) -> Option<&'a [u8]> {
+		let mut file = Cursor { data: self.chunks, name: "MESH" };
+		while !file.data.is_empty() {
+			let found = file.take(4).ok()?;
+			let len = file.u32().ok()? as usize;
+			let body = file.take(len).ok()?;
+			if found == id {
+				return Some(body);
+			}
+		}
+		None
+	}
+}
+
+/// The fields of the `HEAD` chunk, as `reader::read_header` reads them
+fn read_header(data: &[u8], version: u32) -> Result<MeshView<'_>, ViewError> {
+	let mut head = Cursor { data, name: "HEAD" };
+	let index_size = head.u8()?;
+	if index_size != 1 && index_size != 2 && index_size != 4 {
+		return Err(ViewError::InvalidHeader("index size"));
+	}
+	let topology = Topology::from_u8(head.u8()?).ok_or(ViewError::InvalidHeader("topology"))?;
+
+	if head.flag("Position2D")? {
+		return Err(ViewError::InvalidHeader("Position2D"));
+	}
+	let position_format = match head.u8()? {
+		1 => Format::Float32x3,
+		2 => dequantization::POSITION_FORMAT,
+		_ => return Err(ViewError::InvalidHeader("Position3D")),
+	};
+	let mut attributes = Vec::with_capacity(Attribute::ALL.len());
+	let color_space = match head.u8()? {
+		0 => ColorSpace::Srgb,
+		1 => {
+			attributes.push(Attribute::Color);
+			ColorSpace::Srgb
+		},
+		2 => {
+			attributes.push(Attribute::Color);
+			ColorSpace::Linear
+		},
+		_ => return Err(ViewError::InvalidHeader("Color")),
+	};
+	if head.flag("Normal")? {
+		attributes.push(Attribute::Normal);
+	}
+	if head.flag("Tangent")? {
+		attributes.push(Attribute::Tangent);
+	}
+	let mut uv_format = None;
+	for (attr, field) in [(Attribute::Tex0, "Tex0"), (Attribute::Tex1, "Tex1")] {
+		let format = match head.u8()? {
+			0 => continue,
+			1 => Format::Float16x2,
+			2 => Format::Float32x2,
+			_ => return Err(ViewError::InvalidHeader(field)),
+		};
+		//both UV sets share the format
+		if uv_format.is_some_and(|f| f != format) {
+			return Err(ViewError::InvalidHeader(field));
+		}
+		uv_format = Some(format);
+		attributes.push(attr);
+	}
+	if head.flag("Skin")? {
+		attributes.push(Attribute::BoneIndices);
+		attributes.push(Attribute::BoneWeights);
+	}
+	let has_morph_targets = head.flag("Morph")?;
+	if head.flag("Curvature")? {
+		attributes.push(Attribute::Curvature);
+	}
+	if head.flag("MaterialId")? {
+		attributes.push(Attribute::MaterialId);
+	}
+	if head.flag("Bitangent")? {
+		attributes.push(Attribute::Bitangent);
+	}
+
+	let user_count = head.u8()? as usize;
+	if user_count > MAX_USER_ATTRIBUTES {
+		return Err(ViewError::InvalidHeader("user attributes"));
+	}
+	let mut user_attributes = Vec::with_capacity(user_count);
+	for _ in 0..user_count {
+		let format = Format::from_u8(head.u8()?).ok_or(ViewError::InvalidHeader("user attributes"))?;
+		let len = head.u8()? as usize;
+		let name = core::str::from_utf8(head.take(len)?).map_err(|_| ViewError::InvalidHeader("user attributes"))?;
+		user_attributes.push(UserAttribute { name: name.to_owned(), format });
+	}
+
+	let max = head.vec3()?;
+	let min = head.vec3()?;
+	let vertex_count = head.u32()? as usize;
+	let index_count = head.u32()? as usize;
+	let encoding = match head.optional_u8() {
+		Some(val) => Encoding::from_u8(val).ok_or(ViewError::InvalidHeader("Encoding"))?,
+		None => Encoding::Raw,
+	};
+	let palette = match head.optional_u8() {
+		Some(val) => Palette::from_u8(val)
+			.filter(|p| Palette::ATTRIBUTES.iter().all(|attr| !p.has(*attr) || attributes.contains(attr)))
+			.ok_or(ViewError::InvalidHeader("Palette"))?,
+		None => Palette::default(),
+	};
+	let dequantization = match position_format {
+		Format::Float32x3 => None,
+		_ => {
+			let mut matrix = [[0.0; 4]; 4];
+			for v in matrix.iter_mut().flatten() {
+				*v = head.f32()?;
+			}
+			let dequantization = Dequantization { matrix };
+			if !dequantization.is_finite() {
+				return Err(ViewError::InvalidHeader("dequantization"));
+			}
+			Some(dequantization)
+		},
+	};
+	let shadow_index_count = if head.data.is_empty() { 0 } else { head.u32()? as usize };
+
+	let primitive_size = match topology {
+		Topology::TriangleList => 3,
+		Topology::LineList => 2,
+		Topology::PointList => 1,
+	};
+	if !index_count.is_multiple_of(primitive_size) || !shadow_index_count.is_multiple_of(primitive_size) {
+		return Err(ViewError::InvalidHeader("index count"));
+	}
+
+	Ok(MeshView {
+		version,
+		index_size,
+		topology,
+		format: VertexFieldOffsets::with_formats(&attributes, position_format, uv_format.unwrap_or(Format::Float16x2), palette),
+		color_space,
+		user_attributes,
+		min,
+		max,
+		vertex_count,
+		index_count,
+		has_morph_targets,
+		encoding,
+		dequantization,
+		shadow_index_count,
+		vertices: &[],
+		indices: &[],
+		shadow_indices: None,
+		checksum: None,
+		submeshes: &[],
+		chunks: &[],
+	})
+}
+
+#[cfg(all(test, feature = "converter"))]
+mod tests {
+	use super::*;
+	use crate::convert::ConvertOptions;
+	use crate::fixtures;
+
+	#[test]
+	fn like_the_reader() {
+		let file = fixtures::convert_one(fixtures::CUBE, &ConvertOptions { generate_tangents: true, ..Default::default() });
+		let view = MeshView::parse(&file).unwrap();
+		let mesh = fixtures::read(&file);
+		assert_eq!(view.version, chunk::VERSION);
+		assert_eq!((view.vertex_count, view.index_count), (mesh.positions.len(), mesh.indices.len()));
+		assert_eq!(view.layout().stride, mesh.header.layout().stride);
+		assert_eq!(view.vertices.len(), view.vertex_count * view.layout().stride);
+		let indices: Vec<u32> = (0..view.index_count).map(|i| view.index(view.indices, i)).collect();
+		assert_eq!(indices, mesh.indices);
+		assert_eq!(view.submeshes().count(), mesh.submeshes.len());
+		assert_eq!(view.checksum, mesh.checksum);
+	}
+
+	#[test]
+	fn legacy_version() {
+		let file = fixtures::convert_one(fixtures::CUBE, &ConvertOptions { format_version: Some(chunk::LEGACY_VERSION), ..Default::default() });
+		assert_eq!(MeshView::parse(&file).unwrap_err(), ViewError::NotChunked);
+	}
+
+	#[test]
+	fn truncated_and_corrupted() {
+		let file = fixtures::convert_one(fixtures::CUBE, &ConvertOptions::default());
+		let ends = fixtures::chunk_ends(&file);
+		for len in 0..file.len() {
+			assert!(MeshView::parse(&file[..len]).is_err() || ends.contains(&len), "{} of {} bytes", len, file.len());
+		}
+		let checked = file.len() - 12;
+		for i in 8..checked {
+			let mut corrupted = file.clone();
+			corrupted[i] ^= 0x10;
+			assert!(MeshView::parse(&corrupted).is_err(), "byte {} was changed", i);
+		}
+		let mut vertex = file.clone();
+		vertex[ends[0] + 8] ^= 1;
+		assert!(matches!(MeshView::parse(&vertex), Err(ViewError::ChecksumMismatch { .. })));
+	}
+}
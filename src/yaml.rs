@@ -0,0 +1,207 @@
+//! The part of YAML the job files need: nested block mappings and sequences, plain and quoted scalars, flow
+//! sequences of scalars and comments. Anchors, tags, multiline scalars and flow mappings aren't supported.
+
+use wavefront_obj::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	/// An empty value, like `key:` with nothing under it
+	Null,
+	Scalar(String),
+	Sequence(Vec<Value>),
+	/// In the order of the file, with the line of each key
+	Mapping(Vec<(String, usize, Value)>),
+}
+
+struct Line {
+	number: usize,
+	indent: usize,
+	text: String,
+}
+
+fn error<T>(line_number: usize, message: String) -> Result<T, ParseError> {
+	Err(ParseError {
+		line_number,
+		message,
+	})
+}
+
+/// Without the comment, which starts with a # after a space outside of the quotes
+fn strip_comment(line: &str) -> &str {
+	let mut quote = None;
+	let mut previous = ' ';
+	for (i, c) in line.char_indices() {
+		match quote {
+			Some(q) if c == q => quote = None,
+			Some(_) => {},
+			None if c == '"' || c == '\'' => quote = Some(c),
+			None if c == '#' && previous.is_whitespace() => return &line[..i],
+			None => {},
+		}
+		previous = c;
+	}
+	line
+}
+
+/// Where `key: value` splits, outside of the quotes
+fn find_colon(text: &str) -> Option<usize> {
+	let mut quote = None;
+	let bytes = text.as_bytes();
+	for (i, &c) in bytes.iter().enumerate() {
+		match quote {
+			Some(q) if c == q => quote = None,
+			Some(_) => {},
+			None if c == b'"' || c == b'\'' => quote = Some(c),
+			None if c == b':' && bytes.get(i + 1).is_none_or(|next| *next == b' ') => return Some(i),
+			None => {},
+		}
+	}
+	None
+}
+
+fn scalar(line: usize, text: &str) -> Result<Value, ParseError> {
+	let text = text.trim();
+	if let Some(quoted) = text.strip_prefix('"') {
+		let Some(quoted) = quoted.strip_suffix('"') else {
+			return error(line, "Unterminated string".to_owned());
+		};
+		let mut out = String::new();
+		let mut chars = quoted.chars();
+		while let Some(c) = chars.next() {
+			match c {
+				'\\' => match chars.next() {
+					Some('n') => out.push('\n'),
+					Some('t') => out.push('\t'),
+					Some(c @ ('"' | '\\' | '/')) => out.push(c),
+					_ => return error(line, "Invalid escape".to_owned()),
+				},
+				c => out.push(c),
+			}
+		}
+		Ok(Value::Scalar(out))
+	}
+	else if let Some(quoted) = text.strip_prefix('\'') {
+		match quoted.strip_suffix('\'') {
+			Some(quoted) => Ok(Value::Scalar(quoted.replace("''", "'"))),
+			None => error(line, "Unterminated string".to_owned()),
+		}
+	}
+	else if let Some(items) = text.strip_prefix('[') {
+		let Some(items) = items.strip_suffix(']') else {
+			return error(line, "Unterminated sequence".to_owned());
+		};
+		if items.trim().is_empty() {
+			return Ok(Value::Sequence(vec![]));
+		}
+		items.split(',').map(|item| scalar(line, item)).collect::<Result<_, _>>().map(Value::Sequence)
+	}
+	else if text.starts_with('{') {
+		error(line, "Flow mappings aren't supported, write the keys on their own lines".to_owned())
+	}
+	else if text.is_empty() {
+		Ok(Value::Null)
+	}
+	else {
+		Ok(Value::Scalar(text.to_owned()))
+	}
+}
+
+fn is_item(text: &str) -> bool {
+	text == "-" || text.starts_with("- ")
+}
+
+/// The block starting at `lines[*pos]`, whose lines are all indented by `indent`
+fn block(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value, ParseError> {
+	if is_item(&lines[*pos].text) {
+		let mut items = vec![];
+		while *pos < lines.len() && lines[*pos].indent == indent && is_item(&lines[*pos].text) {
+			let rest = lines[*pos].text[1..].trim_start().to_owned();
+			if rest.is_empty() {
+				*pos += 1;
+				items.push(match lines.get(*pos) {
+					Some(line) if line.indent > indent => {
+						let indent = line.indent;
+						block(lines, pos, indent)?
+					},
+					_ => Value::Null,
+				});
+			}
+			else if find_colon(&rest).is_some() || is_item(&rest) {
+				//`- key: value` starts a mapping, indented like its first key
+				let line = &mut lines[*pos];
+				line.indent += line.text.len() - rest.len();
+				line.text = rest;
+				let indent = line.indent;
+				items.push(block(lines, pos, indent)?);
+			}
+			else {
+				items.push(scalar(lines[*pos].number, &rest)?);
+				*pos += 1;
+			}
+		}
+		return Ok(Value::Sequence(items));
+	}
+
+	let mut members: Vec<(String, usize, Value)> = vec![];
+	while *pos < lines.len() && lines[*pos].indent == indent {
+		let Line { number, ref text, .. } = lines[*pos];
+		let Some(colon) = find_colon(text) else {
+			return error(number, format!("Expected `key: value` but got `{}`", text));
+		};
+		let key = match scalar(number, &text[..colon])? {
+			Value::Scalar(key) => key,
+			_ => return error(number, "Expected a key".to_owned()),
+		};
+		if members.iter().any(|(other, _, _)| *other == key) {
+			return error(number, format!("{} is repeated", key));
+		}
+		let value = scalar(number, &text[colon + 1..])?;
+		*pos += 1;
+		let value = match value {
+			Value::Null => nested(lines, pos, indent)?,
+			value => value,
+		};
+		members.push((key, number, value));
+	}
+	Ok(Value::Mapping(members))
+}
+
+/// The block under a key indented by `indent`, which can also be a sequence at the same indentation
+fn nested(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value, ParseError> {
+	match lines.get(*pos) {
+		Some(line) if line.indent > indent || (line.indent == indent && is_item(&line.text)) => {
+			let indent = line.indent;
+			block(lines, pos, indent)
+		},
+		_ => Ok(Value::Null),
+	}
+}
+
+pub fn parse(content: &str) -> Result<Value, ParseError> {
+	let mut lines = vec![];
+	for (i, line) in content.lines().enumerate() {
+		let text = strip_comment(line).trim_end();
+		let trimmed = text.trim_start();
+		if trimmed.is_empty() || trimmed == "---" {
+			continue;
+		}
+		if text.starts_with('\t') {
+			return error(i + 1, "YAML is indented with spaces, not tabs".to_owned());
+		}
+		lines.push(Line {
+			number: i + 1,
+			indent: text.len() - trimmed.len(),
+			text: trimmed.to_owned(),
+		});
+	}
+	if lines.is_empty() {
+		return Ok(Value::Null);
+	}
+
+	let (mut pos, indent) = (0, lines[0].indent);
+	let value = block(&mut lines, &mut pos, indent)?;
+	if let Some(line) = lines.get(pos) {
+		return error(line.number, "This line isn't indented like the ones before".to_owned());
+	}
+	Ok(value)
+}
@@ -0,0 +1,556 @@
+//! Zstandard frames compressed against a dictionary trained on a batch of files, for the many small meshes that
+//! compress poorly alone: their headers, chunk ids and metadata repeat from file to file, so the dictionary holds
+//! them once and each file only refers to it.
+//!
+//! The frames record the id of the dictionary, and libzstd (`ZSTD_decompress_usingDict`, or `zstd -d -D`) decodes
+//! them. The compressor is a plain LZ77 over the dictionary and the data, with the literals stored raw and the
+//! sequences coded with the predefined tables, so the entropy tables of the dictionary are those defaults too.
+
+use std::collections::HashMap;
+use crate::chunk::Crc32;
+
+const FRAME_MAGIC: u32 = 0xFD2F_B528;
+const DICTIONARY_MAGIC: u32 = 0xEC30_A437;
+
+/// The default size of `zstd --train`
+pub const DEFAULT_DICTIONARY_SIZE: usize = 112640;
+
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 65536;
+/// Of the hash chains walked for each position
+const MAX_CANDIDATES: usize = 32;
+/// Bytes of the substrings the training counts
+const DMER: usize = 8;
+/// Bytes of the segments the dictionary is made of
+const SEGMENT: usize = 256;
+
+/// Baseline and extra bits of each literal length code, then of each match length code
+const LL_CODES: [(u32, u32); 36] = [
+	(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0), (11, 0), (12, 0), (13, 0),
+	(14, 0), (15, 0), (16, 1), (18, 1), (20, 1), (22, 1), (24, 2), (28, 2), (32, 3), (40, 3), (48, 4), (64, 6),
+	(128, 7), (256, 8), (512, 9), (1024, 10), (2048, 11), (4096, 12), (8192, 13), (16384, 14), (32768, 15), (65536, 16),
+];
+const ML_CODES: [(u32, u32); 53] = [
+	(3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0), (11, 0), (12, 0), (13, 0), (14, 0), (15, 0),
+	(16, 0), (17, 0), (18, 0), (19, 0), (20, 0), (21, 0), (22, 0), (23, 0), (24, 0), (25, 0), (26, 0), (27, 0),
+	(28, 0), (29, 0), (30, 0), (31, 0), (32, 0), (33, 0), (34, 0), (35, 1), (37, 1), (39, 1), (41, 1), (43, 2),
+	(47, 2), (51, 3), (59, 3), (67, 4), (83, 4), (99, 5), (131, 7), (259, 8), (515, 9), (1027, 10), (2051, 11),
+	(4099, 12), (8195, 13), (16387, 14), (32771, 15), (65539, 16),
+];
+
+/// The predefined distributions of the format, with their accuracy logs
+const LL_DISTRIBUTION: (&[i16], u32) = (&[
+	4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1, -1, -1, -1, -1,
+], 6);
+const ML_DISTRIBUTION: (&[i16], u32) = (&[
+	1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+	1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+], 6);
+const OF_DISTRIBUTION: (&[i16], u32) = (&[
+	1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+], 5);
+
+/// The content of a dictionary and its id, which the frames compressed with it record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dictionary {
+	pub id: u32,
+	pub content: Vec<u8>,
+}
+
+/// Writes bits from the lowest, as the decoders read them back from the end
+#[derive(Default)]
+struct BitWriter {
+	data: Vec<u8>,
+	bits: u64,
+	count: u32,
+}
+
+impl BitWriter {
+	fn add(&mut self, value: u32, count: u32) {
+		self.bits |= (value as u64 & ((1 << count) - 1)) << self.count;
+		self.count += count;
+		while self.count >= 8 {
+			self.data.push(self.bits as u8);
+			self.bits >>= 8;
+			self.count -= 8;
+		}
+	}
+
+	/// With the bit marking the end
+	fn close(mut self) -> Vec<u8> {
+		self.add(1, 1);
+		if self.count > 0 {
+			self.data.push(self.bits as u8);
+		}
+		self.data
+	}
+}
+
+fn highbit(v: u32) -> u32 {
+	31 - v.leading_zeros()
+}
+
+/// An FSE table description, as `FSE_writeNCount` writes it
+fn write_distribution(out: &mut Vec<u8>, (counts, log): (&[i16], u32)) {
+	let mut bits = BitWriter::default();
+	bits.add(log - 5, 4);
+	let mut remaining = (1i32 << log) + 1;
+	let mut threshold = 1i32 << log;
+	let mut nb_bits = log + 1;
+	let mut symbol = 0;
+	let mut previous_is_0 = false;
+	while symbol < counts.len() && remaining > 1 {
+		if previous_is_0 {
+			let mut start = symbol;
+			while symbol < counts.len() && counts[symbol] == 0 {
+				symbol += 1;
+			}
+			if symbol == counts.len() {
+				break;
+			}
+			while symbol >= start + 3 {
+				start += 3;
+				bits.add(3, 2);
+			}
+			bits.add((symbol - start) as u32, 2);
+		}
+		let count = counts[symbol] as i32;
+		symbol += 1;
+		let max = (2 * threshold - 1) - remaining;
+		remaining -= count.abs();
+		let mut value = count + 1;
+		if value >= threshold {
+			value += max;
+		}
+		bits.add(value as u32, if value < max { nb_bits - 1 } else { nb_bits });
+		previous_is_0 = value == 1;
+		while remaining < threshold {
+			nb_bits -= 1;
+			threshold >>= 1;
+		}
+	}
+	out.extend(bits.data);
+	if bits.count > 0 {
+		out.push(bits.bits as u8);
+	}
+}
+
+/// The encoding table of a distribution, like `FSE_buildCTable`
+struct FseTable {
+	log: u32,
+	states: Vec<u16>,
+	/// The delta of the bits and of the state of each symbol
+	symbols: Vec<(u32, i32)>,
+}
+
+impl FseTable {
+	fn new((counts, log): (&[i16], u32)) -> FseTable {
+		let size = 1usize << log;
+		let mask = size - 1;
+		let step = (size >> 1) + (size >> 3) + 3;
+		let mut high = size - 1;
+		let mut spread = vec![0usize; size];
+		let mut cumul = vec![0usize; counts.len() + 1];
+		for (s, &count) in counts.iter().enumerate() {
+			cumul[s + 1] = cumul[s] + if count == -1 { 1 } else { count as usize };
+			if count == -1 {
+				spread[high] = s;
+				high -= 1;
+			}
+		}
+		let mut position = 0;
+		for (s, &count) in counts.iter().enumerate() {
+			for _ in 0..count.max(0) {
+				spread[position] = s;
+				position = (position + step) & mask;
+				while position > high {
+					position = (position + step) & mask;
+				}
+			}
+		}
+
+		let mut states = vec![0; size];
+		for (u, &s) in spread.iter().enumerate() {
+			states[cumul[s]] = (size + u) as u16;
+			cumul[s] += 1;
+		}
+
+		let mut total = 0i32;
+		let symbols = counts.iter().map(|&count| match count {
+			0 => (((log + 1) << 16).wrapping_sub(1 << log), 0),
+			-1 | 1 => {
+				total += 1;
+				((log << 16).wrapping_sub(1 << log), total - 2)
+			},
+			_ => {
+				let max_bits_out = log - highbit(count as u32 - 1);
+				let delta_bits = (max_bits_out << 16).wrapping_sub((count as u32) << max_bits_out);
+				total += count as i32;
+				(delta_bits, total - 2 * count as i32)
+			},
+		}).collect();
+		FseTable { log, states, symbols }
+	}
+
+	fn init(&self, symbol: usize) -> u32 {
+		let (delta_bits, delta_state) = self.symbols[symbol];
+		let bits_out = (delta_bits.wrapping_add(1 << 15)) >> 16;
+		let value = (bits_out << 16).wrapping_sub(delta_bits);
+		self.states[((value >> bits_out) as i32 + delta_state) as usize] as u32
+	}
+
+	fn encode(&self, bits: &mut BitWriter, state: &mut u32, symbol: usize) {
+		let (delta_bits, delta_state) = self.symbols[symbol];
+		let bits_out = state.wrapping_add(delta_bits) >> 16;
+		bits.add(*state, bits_out);
+		*state = self.states[((*state >> bits_out) as i32 + delta_state) as usize] as u32;
+	}
+
+	fn flush(&self, bits: &mut BitWriter, state: u32) {
+		bits.add(state, self.log);
+	}
+}
+
+fn code(codes: &[(u32, u32)], value: u32) -> usize {
+	codes.iter().rposition(|&(baseline, _)| baseline <= value).unwrap()
+}
+
+struct Sequence {
+	literals: u32,
+	/// The match length
+	length: u32,
+	offset: u32,
+}
+
+/// The literals section and the sequences section of a compressed block
+fn encode_block(literals: &[u8], sequences: &[Sequence], tables: &[FseTable; 3]) -> Vec<u8> {
+	let mut out = vec![];
+	//raw literals
+	let size = literals.len() as u32;
+	match size {
+		0..32 => out.push((size << 3) as u8),
+		32..4096 => out.extend_from_slice(&((1 << 2) | (size << 4) as u16).to_le_bytes()),
+		_ => out.extend_from_slice(&((3 << 2) | (size << 4)).to_le_bytes()[..3]),
+	}
+	out.extend_from_slice(literals);
+
+	let count = sequences.len();
+	match count {
+		0..128 => out.push(count as u8),
+		128..0x7F00 => out.extend_from_slice(&[(count >> 8) as u8 + 0x80, count as u8]),
+		_ => {
+			out.push(0xFF);
+			out.extend_from_slice(&((count - 0x7F00) as u16).to_le_bytes());
+		},
+	}
+	if count == 0 {
+		return out;
+	}
+	//predefined literal length, offset and match length tables
+	out.push(0);
+
+	let [ll_table, of_table, ml_table] = tables;
+	let codes: Vec<(usize, usize, usize)> = sequences.iter().map(|s| {
+		(code(&LL_CODES, s.literals), highbit(s.offset + 3) as usize, code(&ML_CODES, s.length))
+	}).collect();
+	let extra_bits = |bits: &mut BitWriter, s: &Sequence, (ll, of, ml): (usize, usize, usize)| {
+		bits.add(s.literals, LL_CODES[ll].1);
+		bits.add(s.length - 3, ML_CODES[ml].1);
+		bits.add(s.offset + 3, of as u32);
+	};
+
+	let mut bits = BitWriter::default();
+	let (ll, of, ml) = codes[count - 1];
+	let (mut ml_state, mut of_state, mut ll_state) = (ml_table.init(ml), of_table.init(of), ll_table.init(ll));
+	extra_bits(&mut bits, &sequences[count - 1], codes[count - 1]);
+	for n in (0..count - 1).rev() {
+		let (ll, of, ml) = codes[n];
+		of_table.encode(&mut bits, &mut of_state, of);
+		ml_table.encode(&mut bits, &mut ml_state, ml);
+		ll_table.encode(&mut bits, &mut ll_state, ll);
+		extra_bits(&mut bits, &sequences[n], codes[n]);
+	}
+	ml_table.flush(&mut bits, ml_state);
+	of_table.flush(&mut bits, of_state);
+	ll_table.flush(&mut bits, ll_state);
+	out.extend(bits.close());
+	out
+}
+
+fn hash(data: &[u8]) -> usize {
+	(u32::from_le_bytes([data[0], data[1], data[2], data[3]]).wrapping_mul(2654435761) >> 16) as usize
+}
+
+/// The positions of the history with each hash of their first bytes
+struct HashChains {
+	/// The last position of each hash
+	heads: Vec<usize>,
+	/// The position before with the same hash, of each position
+	previous: Vec<usize>,
+}
+
+impl HashChains {
+	fn new(size: usize) -> HashChains {
+		HashChains { heads: vec![usize::MAX; 1 << 16], previous: vec![usize::MAX; size] }
+	}
+
+	fn insert(&mut self, history: &[u8], i: usize) {
+		if i + MIN_MATCH <= history.len() {
+			let h = hash(&history[i..]);
+			self.previous[i] = self.heads[h];
+			self.heads[h] = i;
+		}
+	}
+}
+
+/// A zstd frame of `data`, referring to the content of `dictionary`
+pub fn compress(data: &[u8], dictionary: &Dictionary) -> Vec<u8> {
+	let tables = [FseTable::new(LL_DISTRIBUTION), FseTable::new(OF_DISTRIBUTION), FseTable::new(ML_DISTRIBUTION)];
+	let history: Vec<u8> = dictionary.content.iter().chain(data).copied().collect();
+	let start = dictionary.content.len();
+
+	let mut out = vec![];
+	out.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+	//4 bytes of content size and of dictionary id, with a window reaching the start of the dictionary
+	out.push(2 << 6 | 3);
+	let window_log = highbit(history.len().max(1 << 10).next_power_of_two() as u32);
+	out.push(((window_log - 10) << 3) as u8);
+	out.extend_from_slice(&dictionary.id.to_le_bytes());
+	out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+	let mut chains = HashChains::new(history.len());
+	for i in 0..start {
+		chains.insert(&history, i);
+	}
+
+	let mut block_start = start;
+	loop {
+		let block_end = usize::min(block_start + MAX_BLOCK_SIZE, history.len());
+		let last = block_end == history.len();
+		let mut sequences = vec![];
+		let mut literals = vec![];
+		let mut anchor = block_start;
+		let mut i = block_start;
+		while i + MIN_MATCH <= block_end {
+			let (mut best_length, mut best_offset) = (0, 0);
+			let mut candidate = chains.heads[hash(&history[i..])];
+			for _ in 0..MAX_CANDIDATES {
+				if candidate == usize::MAX {
+					break;
+				}
+				let max = usize::min(MAX_MATCH, block_end - i);
+				let length = history[candidate..].iter().zip(&history[i..i + max]).take_while(|(a, b)| a == b).count();
+				if length > best_length {
+					(best_length, best_offset) = (length, i - candidate);
+				}
+				candidate = chains.previous[candidate];
+			}
+			if best_length >= MIN_MATCH {
+				literals.extend_from_slice(&history[anchor..i]);
+				sequences.push(Sequence { literals: (i - anchor) as u32, length: best_length as u32, offset: best_offset as u32 });
+				for j in i..i + best_length {
+					chains.insert(&history, j);
+				}
+				i += best_length;
+				anchor = i;
+			}
+			else {
+				chains.insert(&history, i);
+				i += 1;
+			}
+		}
+		for j in i..block_end {
+			chains.insert(&history, j);
+		}
+		literals.extend_from_slice(&history[anchor..block_end]);
+
+		let block = encode_block(&literals, &sequences, &tables);
+		let raw = &history[block_start..block_end];
+		let (kind, content) = if block.len() < raw.len() { (2, &block[..]) } else { (0, raw) };
+		let header = last as u32 | kind << 1 | (content.len() as u32) << 3;
+		out.extend_from_slice(&header.to_le_bytes()[..3]);
+		out.extend_from_slice(content);
+		if last {
+			break;
+		}
+		block_start = block_end;
+	}
+	out
+}
+
+impl Dictionary {
+	/// The dictionary file, with the default entropy tables and repeat offsets
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = vec![];
+		out.extend_from_slice(&DICTIONARY_MAGIC.to_le_bytes());
+		out.extend_from_slice(&self.id.to_le_bytes());
+		//a Huffman tree of two 1 bit literals, which the raw literals never use
+		out.extend_from_slice(&[128, 0x10]);
+		write_distribution(&mut out, OF_DISTRIBUTION);
+		write_distribution(&mut out, ML_DISTRIBUTION);
+		write_distribution(&mut out, LL_DISTRIBUTION);
+		for offset in [1u32, 4, 8] {
+			out.extend_from_slice(&offset.to_le_bytes());
+		}
+		out.extend_from_slice(&self.content);
+		out
+	}
+
+	/// Picks the segments of `size` bytes in total whose substrings the most `samples` share, like `zstd --train`
+	/// does. The id comes from the content, in the range the format leaves to users
+	pub fn train(samples: &[&[u8]], size: usize) -> Dictionary {
+		//the samples each substring is in
+		let mut frequencies: HashMap<&[u8], u32> = HashMap::new();
+		for sample in samples {
+			let mut seen: Vec<&[u8]> = sample.windows(DMER).collect();
+			seen.sort_unstable();
+			seen.dedup();
+			for dmer in seen {
+				*frequencies.entry(dmer).or_default() += 1;
+			}
+		}
+		//the ones in a single sample won't be found again
+		frequencies.retain(|_, &mut count| count > 1);
+
+		//the best segment of each epoch of the samples
+		let all: Vec<u8> = samples.concat();
+		let mut segments = vec![];
+		let epochs = usize::max(size / SEGMENT, 1);
+		let epoch_size = usize::max(all.len() / epochs, SEGMENT);
+		for epoch in all.chunks(epoch_size) {
+			let mut best = (0, 0);
+			let mut in_window: HashMap<&[u8], u32> = HashMap::new();
+			let mut score = 0;
+			let dmers: Vec<&[u8]> = epoch.windows(DMER).collect();
+			//of the segment
+			let window = SEGMENT - DMER + 1;
+			for (i, &dmer) in dmers.iter().enumerate() {
+				let count = in_window.entry(dmer).or_default();
+				*count += 1;
+				if *count == 1 {
+					score += frequencies.get(dmer).copied().unwrap_or(0);
+				}
+				if i >= window {
+					let old = dmers[i - window];
+					let count = in_window.get_mut(old).unwrap();
+					*count -= 1;
+					if *count == 0 {
+						score -= frequencies.get(old).copied().unwrap_or(0);
+					}
+				}
+				if score > best.0 {
+					best = (score, (i + DMER).saturating_sub(SEGMENT));
+				}
+			}
+			if best.0 == 0 {
+				continue;
+			}
+			let segment = &epoch[best.1..usize::min(best.1 + SEGMENT, epoch.len())];
+			for dmer in segment.windows(DMER) {
+				frequencies.remove(dmer);
+			}
+			segments.push((best.0, segment));
+		}
+
+		//the most useful last, closest to the data
+		segments.sort_by_key(|&(score, _)| score);
+		let mut content: Vec<u8> = segments.iter().flat_map(|&(_, segment)| segment).copied().collect();
+		if content.len() > size {
+			content.drain(..content.len() - size);
+		}
+		//the repeat offsets have to be inside the content
+		if content.len() < 8 {
+			content.resize(8, 0);
+		}
+
+		let mut crc = Crc32::default();
+		crc.update(&content);
+		Dictionary { id: 32768 + crc.value() % ((1 << 31) - 32768), content }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Decodes with libzstd, which has the last word on the frames being valid
+	fn round_trip(data: &[u8], dictionary: &Dictionary) {
+		let frame = compress(data, dictionary);
+		let mut decompressor = ::zstd::bulk::Decompressor::with_dictionary(&dictionary.to_bytes()).unwrap();
+		let decoded = decompressor.decompress(&frame, data.len()).unwrap();
+		assert!(decoded == data, "{} bytes decoded as {} different ones", data.len(), decoded.len());
+	}
+
+	fn small_dictionary() -> Dictionary {
+		Dictionary { id: 32768, content: b"no repeats in the data of this dictionary".to_vec() }
+	}
+
+	/// Bytes without any 4 byte repeat, from a xorshift
+	fn noise(len: usize) -> Vec<u8> {
+		let mut state = 0x2545_F491u32;
+		(0..len).map(|_| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state as u8
+		}).collect()
+	}
+
+	#[test]
+	fn empty() {
+		round_trip(&[], &small_dictionary());
+	}
+
+	#[test]
+	fn under_the_block_size() {
+		round_trip(b"a short text, a short text, a short text", &small_dictionary());
+		round_trip(&noise(1000), &small_dictionary());
+	}
+
+	#[test]
+	fn over_the_block_size() {
+		let data: Vec<u8> = (0..MAX_BLOCK_SIZE * 2 + 1000).map(|i| (i / 7 % 251) as u8).collect();
+		round_trip(&data, &small_dictionary());
+		round_trip(&data[..MAX_BLOCK_SIZE], &small_dictionary());
+		round_trip(&data[..MAX_BLOCK_SIZE + 1], &small_dictionary());
+	}
+
+	#[test]
+	fn no_repeats() {
+		let data = noise(MAX_BLOCK_SIZE + 5000);
+		let frame = compress(&data, &small_dictionary());
+		//stored raw
+		assert!(frame.len() > data.len());
+		round_trip(&data, &small_dictionary());
+	}
+
+	#[test]
+	fn repetitive() {
+		let data = vec![42; MAX_BLOCK_SIZE * 3];
+		let frame = compress(&data, &small_dictionary());
+		assert!(frame.len() < data.len() / 100, "{} bytes", frame.len());
+		round_trip(&data, &small_dictionary());
+		round_trip(&b"abcd".repeat(100000), &small_dictionary());
+	}
+
+	#[test]
+	fn trained_dictionary() {
+		let samples: Vec<Vec<u8>> = (0..50).map(|i| {
+			let mut sample = b"MESH header shared by every file, with the same chunk ids\0\0\0".to_vec();
+			sample.extend_from_slice(&noise(200 + i * 10)[i..]);
+			sample.extend_from_slice(format!("{{\"name\": \"mesh_{}\", \"generator\": \"obj_to_mesh\"}}", i).as_bytes());
+			sample
+		}).collect();
+		let sample_refs: Vec<&[u8]> = samples.iter().map(|sample| &sample[..]).collect();
+		let dictionary = Dictionary::train(&sample_refs, 4096);
+		assert!(!dictionary.content.is_empty() && dictionary.content.len() <= 4096);
+		assert!(dictionary.id >= 32768);
+		for sample in &samples {
+			round_trip(sample, &dictionary);
+		}
+		//the shared header comes from the dictionary
+		let frame = compress(&samples[0], &dictionary);
+		assert!(frame.len() < compress(&samples[0], &small_dictionary()).len());
+	}
+}